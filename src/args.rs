@@ -1,5 +1,10 @@
+use std::path::PathBuf;
+
 use clap::{Parser, Subcommand, ValueEnum};
 
+use crate::color::Color;
+use crate::format::OutputFormat;
+
 #[derive(Parser)]
 #[command(author, 
     version, 
@@ -17,18 +22,41 @@ pub struct Args {
 
 #[derive(Subcommand)]
 pub enum Commands {
-    /// Start the window manager
+    /// Start the window manager.
+    /// TONIOWM_CONFIG, TONIOWM_SOCKET and TONIOWM_LOG (error, warn, info or debug)
+    /// override the path of the config file, the path of the socket and the log level
     Start{
-        ///Sets the path of the rc file
+        /// Sets the path of the rc file, run at startup if it exists
         #[clap(short, long, default_value = "~/.config/toniowm/toniorc")]
         autostart: String,
+        /// Serve a subset of the i3 IPC protocol, for tools written for i3
+        #[clap(long)]
+        i3_ipc: bool,
+        /// Manage another X display than DISPLAY, e.g. ":1" for Xephyr.
+        /// The socket is named after it, unless TONIOWM_SOCKET is set
+        #[clap(long, value_name = "DISPLAY")]
+        display: Option<String>,
+        /// Run in the background once it is known that no other instance is running
+        #[clap(long)]
+        daemonize: bool,
+        /// Put the windows left by a crashed instance back on their workspace and geometry
+        #[clap(long)]
+        recover: bool,
     },
-    /// Send a command to the window manager
+    /// Send a command to the window manager, through the socket set by TONIOWM_SOCKET if any
     #[command(subcommand)]
     Client(Command),
 }
 
 
+/// A single command of a batch.
+#[derive(Parser)]
+#[command(no_binary_name = true)]
+pub struct BatchCommand {
+    #[command(subcommand)]
+    pub command: Command,
+}
+
 #[derive(Subcommand)]
 pub enum Command {
     Quit,
@@ -36,26 +64,282 @@ pub enum Command {
         #[clap(flatten)]
         selector: WindowSelector,
     },
+    /// Cycle through the windows in most recently used order
+    CycleMru {
+        #[clap(value_name = "DIRECTION")]
+        direction: CycleDirection,
+        /// Include the windows of all the workspaces
+        #[clap(long, short)]
+        all_workspaces: bool,
+    },
+    /// End the current most recently used cycle
+    EndCycleMru,
     Close {
         #[clap(flatten)]
         selector: WindowSelector,
     },
+    Kill {
+        #[clap(flatten)]
+        selector: WindowSelector,
+    },
+    Mark {
+        #[clap(flatten)]
+        selector: WindowSelector,
+        #[clap(value_name = "NAME")]
+        name: String,
+    },
+    Snap {
+        #[clap(flatten)]
+        selector: WindowSelector,
+        #[clap(value_name = "POSITION")]
+        position: SnapPosition,
+    },
+    /// Restore the geometry a window had before it was snapped
+    Unsnap {
+        #[clap(flatten)]
+        selector: WindowSelector,
+    },
+    Undo {
+        #[clap(flatten)]
+        selector: WindowSelector,
+    },
+    Resize {
+        #[clap(flatten)]
+        selector: WindowSelector,
+        #[clap(value_name = "WIDTH", allow_negative_numbers = true)]
+        width: i32,
+        #[clap(value_name = "HEIGHT", allow_negative_numbers = true)]
+        height: i32,
+        /// Grow or shrink the window by the given amount
+        #[clap(long, short)]
+        relative: bool,
+        /// Keep the center of the window fixed
+        #[clap(long)]
+        center: bool,
+    },
     AddWorkspace {
         #[clap(short, long)]
         name: Option<String>,
+        #[clap(flatten)]
+        monitor: MonitorSelector,
     },
+    #[command(group = WorkspaceSelector::required())]
     RenameWorkspace {
         #[clap(flatten)]
         selector: WorkspaceSelector,
         #[clap( value_name = "NEW_NAME" )]
         new_name: String,
     },
+    #[command(group = WorkspaceSelector::required())]
     ActivateWorkspace {
         #[clap(flatten)]
         selector: WorkspaceSelector,
     },
+    #[command(group = WorkspaceSelector::required())]
+    MoveWorkspaceToMonitor {
+        #[clap(flatten)]
+        workspace_selector: WorkspaceSelector,
+        #[clap(flatten)]
+        monitor_selector: MonitorSelector,
+    },
+    /// Focus the most recently focused window of a monitor
+    FocusMonitor {
+        #[clap(flatten)]
+        selector: MonitorSelector,
+    },
+    /// Exchange the workspaces shown on the focused monitor and another one
+    SwapMonitors {
+        #[clap(flatten)]
+        selector: MonitorSelector,
+    },
     #[command(subcommand)]
     Config(Config),
+    /// Query the state of the window manager
+    Query {
+        /// The output format: json, plain, or a template such as "{id} {title}"
+        #[clap(long, global = true, default_value = "json", value_parser = parse_format)]
+        format: OutputFormat,
+        #[command(subcommand)]
+        query: Query,
+    },
+    /// Assign the windows of an application to a workspace
+    Assign {
+        #[clap(value_name = "class=CLASS", value_parser = parse_class)]
+        class: String,
+        #[clap(value_name = "workspace=WORKSPACE", value_parser = parse_workspace)]
+        workspace: String,
+        /// Activate the workspace when a window is assigned to it
+        #[clap(long, short)]
+        switch: bool,
+    },
+    /// Run several commands, separated by ";", in order as a single request.
+    /// The screen is updated once they have all run. A failing command stops the batch,
+    /// the commands before it stay applied.
+    /// With "-", read one command per line from stdin instead and send each of them as it is read
+    Batch {
+        #[clap(
+            value_name = "COMMAND [ARGS]... [; COMMAND [ARGS]...]",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        args: Vec<String>,
+    },
+    /// Run a shell command from the window manager, inheriting its environment.
+    /// Bind it to keys to launch programs, e.g. bind super+Return exec alacritty
+    Exec {
+        /// Place the first window of the program on a workspace
+        #[clap(long, short, value_name = "WORKSPACE_NAME")]
+        workspace: Option<String>,
+        #[clap(
+            value_name = "COMMAND",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        cmd: Vec<String>,
+    },
+    /// Run a command when a key combination such as "super+shift+Return" is pressed,
+    /// or a chord of combinations separated by commas such as "super+w, f"
+    Bind {
+        /// The binding mode the keys are bound in
+        #[clap(long, short, default_value = "default")]
+        mode: String,
+        /// Run the command when the keys are released instead of pressed, e.g. Alt_L
+        #[clap(long, short)]
+        release: bool,
+        #[clap(value_name = "KEYS")]
+        keys: String,
+        #[clap(
+            value_name = "COMMAND [ARGS]...",
+            required = true,
+            trailing_var_arg = true,
+            allow_hyphen_values = true
+        )]
+        command: Vec<String>,
+    },
+    /// Remove the binding of a key combination
+    Unbind {
+        /// The binding mode the keys are bound in
+        #[clap(long, short, default_value = "default")]
+        mode: String,
+        /// Remove the binding run when the keys are released
+        #[clap(long, short)]
+        release: bool,
+        #[clap(value_name = "KEYS")]
+        keys: String,
+    },
+    /// Switch to a binding mode, where only the keys bound in that mode are active.
+    /// Escape goes back to the default mode, unless it is bound
+    Mode {
+        #[clap(value_name = "NAME")]
+        name: String,
+    },
+    /// Manage the rules applied to new windows
+    #[command(subcommand)]
+    Rule(Rule),
+    /// Read the config file again, resetting the settings changed at runtime.
+    /// The rules added at runtime are kept
+    Reload,
+    /// Record the commands run by the window manager to a file
+    #[command(subcommand)]
+    Record(Record),
+    /// Run the commands recorded in a file, in order
+    Replay {
+        #[clap(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Print the events of the window manager as JSON lines, until interrupted
+    Subscribe {
+        /// The kinds of events to print, all of them if omitted
+        #[clap(value_name = "EVENT")]
+        events: Vec<EventType>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum Rule {
+    /// Assign the new windows matching all the given properties to a workspace
+    #[clap(group(clap::ArgGroup::new("properties").required(true).multiple(true)))]
+    Add {
+        /// The class of the application
+        #[clap(long, group = "properties")]
+        class: Option<String>,
+        /// A part of the title of the window
+        #[clap(long, group = "properties")]
+        title: Option<String>,
+        /// The type of the window, e.g. dialog or utility
+        #[clap(long = "type", group = "properties", value_name = "TYPE")]
+        window_type: Option<String>,
+        #[clap(value_name = "WORKSPACE_NAME")]
+        workspace: String,
+        /// Activate the workspace when a window is assigned to it
+        #[clap(long, short)]
+        switch: bool,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum Record {
+    /// Start recording, overwriting the file
+    Start {
+        #[clap(value_name = "FILE")]
+        file: PathBuf,
+    },
+    /// Stop recording
+    Stop,
+}
+
+fn parse_class(arg: &str) -> Result<String, String> {
+    parse_property(arg, "class")
+}
+
+fn parse_workspace(arg: &str) -> Result<String, String> {
+    parse_property(arg, "workspace")
+}
+
+/// Parse the output format of a query, see `OutputFormat`.
+fn parse_format(arg: &str) -> Result<OutputFormat, String> {
+    match arg {
+        "json" => Ok(OutputFormat::Json),
+        "plain" => Ok(OutputFormat::Plain),
+        template if template.contains('{') => Ok(OutputFormat::Custom(template.to_owned())),
+        _ => Err("expected json, plain or a template such as \"{id} {title}\"".to_owned()),
+    }
+}
+
+/// Parse a `key=value` argument, expecting the given key.
+fn parse_property(arg: &str, key: &str) -> Result<String, String> {
+    match arg.split_once('=') {
+        Some((k, value)) if k == key && !value.is_empty() => Ok(value.to_owned()),
+        _ => Err(format!("expected {}=VALUE", key)),
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Query {
+    #[clap(about = "Print the workspaces")]
+    Workspaces,
+    #[clap(about = "Print the windows, optionally only those of a workspace")]
+    Windows {
+        #[clap(flatten)]
+        workspace: Option<WorkspaceSelector>,
+    },
+    #[clap(about = "Print the focused window, or null if nothing is focused")]
+    Focused,
+    #[clap(about = "Print the monitors with their workspaces and windows, from the topmost window")]
+    Tree,
+    #[clap(about = "Print counters about the activity of the window manager")]
+    Metrics,
+    #[clap(about = "Print the key bindings of every mode and the mouse bindings")]
+    Bindings,
+    #[clap(about = "Print the whole state of the window manager, for debugging")]
+    State,
+    #[clap(about = "Print a monitor")]
+    Monitor {
+        #[clap(flatten)]
+        selector: MonitorSelector,
+    },
 }
 
 #[derive(ValueEnum, Clone)]
@@ -66,12 +350,43 @@ pub enum CardinalDirection {
     South,
 }
 
+#[derive(ValueEnum, Clone)]
+pub enum EventType {
+    WindowMapped,
+    WindowClosed,
+    FocusChanged,
+    TitleChanged,
+    WorkspaceSwitched,
+    LayoutChanged,
+    ModeChanged,
+}
+
 #[derive(ValueEnum, Clone)]
 pub enum CycleDirection {
     Next,
     Prev,
 }
 
+#[derive(ValueEnum, Clone)]
+pub enum SnapPosition {
+    Maximized,
+    LeftHalf,
+    RightHalf,
+    TopHalf,
+    BottomHalf,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+#[derive(ValueEnum, Clone)]
+pub enum FocusModel {
+    Click,
+    Sloppy,
+    Strict,
+}
+
 #[derive(clap::Args, Clone)]
 #[group(multiple = false)]
 pub struct WindowSelector {
@@ -86,10 +401,50 @@ pub struct WindowSelector {
 
     #[clap(long, short)]
     pub cycle: Option<CycleDirection>,
+
+    #[clap(long, short)]
+    pub mark: Option<String>,
+
+    #[clap(long, short)]
+    pub last: bool,
+
+    #[clap(long, short)]
+    pub urgent: bool,
+
+    #[clap(long, short = 'p')]
+    pub under_pointer: bool,
+
+    /// The window of the active workspace with the largest area
+    #[clap(long)]
+    pub biggest: bool,
+
+    /// The window of the active workspace with the smallest area
+    #[clap(long)]
+    pub smallest: bool,
+
+    /// The window of the active workspace managed first
+    #[clap(long)]
+    pub oldest: bool,
+
+    /// The window of the active workspace managed last
+    #[clap(long)]
+    pub newest: bool,
+
+    /// The class of the window, where * and ? are wildcards
+    #[clap(long, value_name = "PATTERN")]
+    pub class: Option<String>,
+
+    /// The title of the window, where * and ? are wildcards
+    #[clap(long, value_name = "PATTERN")]
+    pub title: Option<String>,
+
+    /// Every window of the active workspace
+    #[clap(long, short)]
+    pub all: bool,
 }
 
 #[derive(clap::Args, Clone)]
-#[group(multiple = false, required = true)]
+#[group(multiple = false)]
 pub struct WorkspaceSelector {
     #[clap(long, short)]
     pub index: Option<usize>,
@@ -99,6 +454,41 @@ pub struct WorkspaceSelector {
 
     #[clap(long, short)]
     pub cycle: Option<CycleDirection>,
+
+    #[clap(long, short)]
+    pub empty: bool,
+
+    #[clap(long)]
+    pub next_empty: bool,
+}
+
+impl WorkspaceSelector {
+    /// The group making one of the selectors mandatory, for the commands that
+    /// have no workspace to fall back to.
+    fn required() -> clap::ArgGroup {
+        clap::ArgGroup::new("workspace-required")
+            .args(["index", "name", "cycle", "empty", "next_empty"])
+            .required(true)
+    }
+}
+
+#[derive(clap::Args, Clone)]
+#[group(multiple = false)]
+pub struct MonitorSelector {
+    #[clap(id = "monitor-focused", long = "monitor-focused", default_value = "true")]
+    pub focused: bool,
+
+    #[clap(id = "monitor-index", long = "monitor-index")]
+    pub index: Option<usize>,
+
+    #[clap(id = "monitor-name", long = "monitor-name")]
+    pub name: Option<String>,
+
+    #[clap(id = "monitor-closest", long = "monitor-closest")]
+    pub closest: Option<CardinalDirection>,
+
+    #[clap(id = "monitor-primary", long = "monitor-primary")]
+    pub primary: bool,
 }
 
 #[derive(Subcommand)]
@@ -108,17 +498,181 @@ pub enum Config {
         #[clap(value_name = "BORDER_WIDTH")]
         width: u32,
     },
-    #[clap(about = "Set the border color")]
+    #[clap(about = "Set the border color, as #RRGGBB, 0xRRGGBB or a name such as \"steel blue\"")]
     BorderColor{
         #[clap(value_name = "BORDER_COLOR")]
-        color: u32,
+        color: Color,
     },
-    #[clap(about = "Set the focused border color")]
+    #[clap(about = "Set the focused border color, as #RRGGBB, 0xRRGGBB or a name such as \"steel blue\"")]
     FocusedBorderColor{
         #[clap(value_name = "FOCUSED_BORDER_COLOR")]
-        color: u32
+        color: Color,
+    },
+    #[clap(about = "Set the width of the outer part of the border, 0 for a single border")]
+    OuterBorderWidth{
+        #[clap(value_name = "OUTER_BORDER_WIDTH")]
+        width: u32,
+    },
+    #[clap(about = "Set the outer border color, as #RRGGBB, 0xRRGGBB or a name such as \"steel blue\"")]
+    OuterBorderColor{
+        #[clap(value_name = "OUTER_BORDER_COLOR")]
+        color: Color,
+    },
+    #[clap(about = "Switch to a theme of the config")]
+    Theme{
+        #[clap(value_name = "THEME")]
+        name: String,
+    },
+    #[clap(about = "Set the focus model")]
+    FocusModel{
+        #[clap(value_name = "FOCUS_MODEL")]
+        focus_model: FocusModel,
+    },
+    #[clap(about = "Warp the pointer to the focused window on keyboard navigation")]
+    WarpPointer{
+        #[clap(value_name = "WARP_POINTER", action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    #[clap(about = "Snap windows dragged to the edges of the screen")]
+    EdgeSnap{
+        #[clap(value_name = "EDGE_SNAP", action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    #[clap(about = "Set the distance from an edge at which dragged windows are snapped")]
+    EdgeSnapThreshold{
+        #[clap(value_name = "EDGE_SNAP_THRESHOLD")]
+        threshold: u32,
+    },
+    #[clap(about = "Wrap around when cycling past the last workspace or window")]
+    Wrap{
+        #[clap(value_name = "WRAP", action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    #[clap(about = "Wrap around to the opposite edge when focusing in a direction with no window")]
+    DirectionalWrap{
+        #[clap(value_name = "DIRECTIONAL_WRAP", action = clap::ArgAction::Set)]
+        enabled: bool,
+    },
+    #[clap(about = "Set the monitor where new windows are placed")]
+    PlacementMonitor{
+        #[clap(flatten)]
+        selector: MonitorSelector,
+    },
+    #[clap(about = "Set the space kept free at the edges of a monitor, in addition to the docks")]
+    Padding{
+        #[clap(flatten)]
+        selector: MonitorSelector,
+        #[clap(long, default_value = "0")]
+        top: i32,
+        #[clap(long, default_value = "0")]
+        right: i32,
+        #[clap(long, default_value = "0")]
+        bottom: i32,
+        #[clap(long, default_value = "0")]
+        left: i32,
+    },
+    #[clap(about = "Set a shell command run before switching workspace, or unset it if omitted")]
+    PreWorkspaceSwitchHook{
+        #[clap(value_name = "COMMAND")]
+        command: Option<String>,
+    },
+    #[clap(about = "Set a shell command run after switching workspace, or unset it if omitted")]
+    PostWorkspaceSwitchHook{
+        #[clap(value_name = "COMMAND")]
+        command: Option<String>,
+    },
+    #[clap(about = "Set the modifier held to focus, move and resize windows with the mouse, e.g. super")]
+    ModKey{
+        #[clap(value_name = "MODIFIER")]
+        modifier: String,
+    },
+    #[clap(about = "Set the mouse button focusing a window, from 1 to 5")]
+    SelectButton{
+        #[clap(value_name = "BUTTON", value_parser = clap::value_parser!(u8).range(1..=5))]
+        button: u8,
+    },
+    #[clap(about = "Set the mouse button moving a window, from 1 to 5")]
+    DragButton{
+        #[clap(value_name = "BUTTON", value_parser = clap::value_parser!(u8).range(1..=5))]
+        button: u8,
+    },
+    #[clap(about = "Set the mouse button resizing a window, from 1 to 5")]
+    ResizeButton{
+        #[clap(value_name = "BUTTON", value_parser = clap::value_parser!(u8).range(1..=5))]
+        button: u8,
+    },
+    #[clap(about = "Set how long to wait for the next key of a chord, in milliseconds")]
+    ChordTimeout{
+        #[clap(value_name = "MILLISECONDS")]
+        timeout: u64,
     },
 }
 
+impl Command {
+    /// Whether the commands should be read from stdin.
+    pub fn reads_stdin(&self) -> bool {
+        matches!(self, Command::Batch { args } if args == &["-"])
+    }
 
+    /// Get the format used to print the response of the command.
+    pub fn output_format(&self) -> OutputFormat {
+        match self {
+            Command::Query { format, .. } => format.clone(),
+            _ => OutputFormat::Json,
+        }
+    }
+}
+
+/// Split a command line into words, the way a shell would for simple quoting.
+///
+/// Words can be quoted with single or double quotes, and a backslash escapes the next character.
+pub fn split_words(line: &str) -> Result<Vec<String>, String> {
+    let mut words = Vec::new();
+    let mut word: Option<String> = None;
+    let mut quote = None;
+    let mut chars = line.chars();
+
+    while let Some(c) = chars.next() {
+        match (c, quote) {
+            ('\\', Some('\'')) => word.get_or_insert_with(String::new).push(c),
+            ('\\', _) => match chars.next() {
+                Some(escaped) => word.get_or_insert_with(String::new).push(escaped),
+                None => return Err("trailing backslash".to_owned()),
+            },
+            (c, Some(q)) if c == q => quote = None,
+            (c, Some(_)) => word.get_or_insert_with(String::new).push(c),
+            ('\'' | '"', None) => {
+                word.get_or_insert_with(String::new);
+                quote = Some(c);
+            }
+            (c, None) if c.is_whitespace() => words.extend(word.take()),
+            (c, None) => word.get_or_insert_with(String::new).push(c),
+        }
+    }
+
+    if quote.is_some() {
+        return Err("unterminated quote".to_owned());
+    }
+    words.extend(word);
 
+    Ok(words)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_split_words() {
+        assert_eq!(
+            vec!["rename-workspace", "--name", "1", "web browsing", "it's", ""],
+            split_words(r#"  rename-workspace --name 1 "web browsing" 'it'\''s' "" "#).unwrap()
+        );
+        assert_eq!(
+            vec!["a\\b", "c d"],
+            split_words(r#"'a\b' c\ d"#).unwrap()
+        );
+        assert!(split_words("focus \"unterminated").is_err());
+        assert!(split_words("focus \\").is_err());
+    }
+}
@@ -1,24 +1,58 @@
 use xcb::atoms_struct;
 
-atoms_struct! {
-    pub struct Atoms {
-        // For some reason xcb::x::ATOM_STRING works for some requests but not others.
-        // For instance, it works for _NET_WM_NAME but not for _NET_DESKTOP_NAMES.
-        // Using UTF8_STRING as a type works for both.
-        pub utf8_string => b"UTF8_STRING" only_if_exists = false,
-        // ICCCM hints
-        pub wm_protocols  => b"WM_PROTOCOLS" only_if_exists = false,
-        pub wm_delete_window  => b"WM_DELETE_WINDOW" only_if_exists = false,
-        // Supported EWMH hints
-        pub net_supported  => b"_NET_SUPPORTED" only_if_exists = false,
-        pub net_active_window  => b"_NET_ACTIVE_WINDOW" only_if_exists = false,
-        pub net_supporting_wm_check  => b"_NET_SUPPORTING_WM_CHECK" only_if_exists = false,
-        pub net_wm_name  => b"_NET_WM_NAME" only_if_exists = false,
-        pub net_number_of_desktops  => b"_NET_NUMBER_OF_DESKTOPS" only_if_exists = false,
-        pub net_desktop_names => b"_NET_DESKTOP_NAMES" only_if_exists = false,
-        pub net_current_desktop => b"_NET_CURRENT_DESKTOP" only_if_exists = false,
-        // EWMH window types
-        pub net_wm_window_type => b"_NET_WM_WINDOW_TYPE" only_if_exists = false,
-        pub net_wm_window_type_dock => b"_NET_WM_WINDOW_TYPE_DOCK" only_if_exists = false,
-    }
+/// Declare the atoms of the window manager, interned when it starts,
+/// and numbered in the tests which run without an X server.
+macro_rules! atoms {
+    ($($field:ident => $name:tt,)*) => {
+        atoms_struct! {
+            pub struct Atoms {
+                $(pub $field => $name only_if_exists = false,)*
+            }
+        }
+
+        #[cfg(test)]
+        impl Atoms {
+        /// Distinct atoms, numbered after the predefined ones.
+            pub fn numbered() -> Atoms {
+                let mut id = 0x100;
+                $(
+                    id += 1;
+                    let $field = unsafe { xcb::XidNew::new(id) };
+                )*
+                Atoms { $($field,)* }
+            }
+        }
+    };
+}
+
+atoms! {
+    // For some reason xcb::x::ATOM_STRING works for some requests but not others.
+    // For instance, it works for _NET_WM_NAME but not for _NET_DESKTOP_NAMES.
+    // Using UTF8_STRING as a type works for both.
+    utf8_string => b"UTF8_STRING",
+    // ICCCM hints
+    wm_protocols => b"WM_PROTOCOLS",
+    wm_delete_window => b"WM_DELETE_WINDOW",
+    wm_state => b"WM_STATE",
+    // Supported EWMH hints
+    net_supported => b"_NET_SUPPORTED",
+    net_active_window => b"_NET_ACTIVE_WINDOW",
+    net_supporting_wm_check => b"_NET_SUPPORTING_WM_CHECK",
+    net_wm_name => b"_NET_WM_NAME",
+    net_number_of_desktops => b"_NET_NUMBER_OF_DESKTOPS",
+    net_desktop_names => b"_NET_DESKTOP_NAMES",
+    net_current_desktop => b"_NET_CURRENT_DESKTOP",
+    net_client_list_stacking => b"_NET_CLIENT_LIST_STACKING",
+    // EWMH window types
+    net_wm_window_type => b"_NET_WM_WINDOW_TYPE",
+    net_wm_window_type_dock => b"_NET_WM_WINDOW_TYPE_DOCK",
+    // EWMH process id of the client owning a window
+    net_wm_pid => b"_NET_WM_PID",
+    // EWMH struts
+    net_wm_strut => b"_NET_WM_STRUT",
+    net_wm_strut_partial => b"_NET_WM_STRUT_PARTIAL",
+    // Invisible shadow of the GTK windows drawing their own decorations
+    gtk_frame_extents => b"_GTK_FRAME_EXTENTS",
+    // Where i3 tools look for the i3 IPC socket when I3SOCK is not set
+    i3_socket_path => b"I3_SOCKET_PATH",
 }
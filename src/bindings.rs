@@ -0,0 +1,406 @@
+//! Key bindings, which run a command when a key combination is pressed.
+//!
+//! Bindings belong to a mode, and only the bindings of the active mode are grabbed,
+//! e.g. a "resize" mode can bind the arrows to resize the focused window.
+//!
+//! A binding can be a chord, a sequence of key combinations such as "super+w, f".
+//! Only the first combination is grabbed: the following ones are read while
+//! the keyboard is grabbed, until the chord is complete, broken or timed out.
+//!
+//! A binding can also run its command when the keys are released instead of pressed,
+//! e.g. to end cycling through the windows when Alt_L is released.
+
+use serde::Serialize;
+
+use crate::commands::Command;
+use crate::config::Config;
+use crate::keys::{self, KeyCombo};
+
+/// The mode the window manager starts in.
+pub const DEFAULT_MODE: &str = "default";
+
+/// The keysym of Escape, which goes back to the default mode unless it is bound.
+pub const ESCAPE: u32 = 0xff1b;
+
+#[derive(Clone)]
+pub struct Binding {
+    /// The mode the binding belongs to.
+    pub mode: String,
+    /// The key combinations to press in order, a single one unless the binding is a chord.
+    pub keys: Vec<KeyCombo>,
+    /// Whether the command runs when the keys are released. Chords cannot be released.
+    pub release: bool,
+    /// The command run when the keys are pressed.
+    pub command: Command,
+}
+
+/// What a key press did.
+pub enum Press {
+    /// The keys pressed so far are bound to a command.
+    Command(Command),
+    /// The keys pressed so far start a chord, more keys are expected.
+    Chord,
+    /// The keys pressed so far are not bound.
+    Unbound,
+}
+
+/// A key binding, as reported to the clients.
+#[derive(Serialize)]
+pub struct KeyBindingInfo {
+    pub mode: String,
+    /// The key combinations, separated by commas for chords.
+    pub keys: String,
+    pub release: bool,
+    /// Whether the binding belongs to the active mode, so its keys are grabbed.
+    pub active: bool,
+    pub command: Command,
+}
+
+/// A mouse binding, as reported to the clients.
+#[derive(Serialize)]
+pub struct ButtonBindingInfo {
+    /// The modifiers and the button, e.g. "super+button1".
+    pub buttons: String,
+    /// What the button does on a window: focus, move or resize.
+    pub action: &'static str,
+}
+
+/// The key and mouse bindings, as reported to the clients.
+#[derive(Serialize)]
+pub struct BindingsReport {
+    pub keys: Vec<KeyBindingInfo>,
+    pub buttons: Vec<ButtonBindingInfo>,
+}
+
+/// The key bindings, in the order they were added.
+pub struct Bindings {
+    bindings: Vec<Binding>,
+    /// The active mode.
+    mode: String,
+    /// The keys of the chord being pressed.
+    pending: Vec<KeyCombo>,
+}
+
+impl Default for Bindings {
+    fn default() -> Self {
+        Self {
+            bindings: Vec::new(),
+            mode: DEFAULT_MODE.to_owned(),
+            pending: Vec::new(),
+        }
+    }
+}
+
+impl Bindings {
+    /// Add a binding, replacing the previous binding of the same keys and trigger in its mode.
+    pub fn bind(&mut self, binding: Binding) {
+        self.unbind(&binding.mode, &binding.keys, binding.release);
+        self.bindings.push(binding);
+    }
+
+    /// Remove the binding of some keys in a mode, either pressed or released, and return it.
+    pub fn unbind(&mut self, mode: &str, keys: &[KeyCombo], release: bool) -> Option<Binding> {
+        let index = self.bindings.iter().position(|binding| {
+            binding.mode == mode && binding.keys == keys && binding.release == release
+        })?;
+
+        Some(self.bindings.remove(index))
+    }
+
+    pub fn mode(&self) -> &str {
+        &self.mode
+    }
+
+    /// Switch mode, abandoning the chord in progress.
+    pub fn set_mode(&mut self, mode: String) {
+        self.mode = mode;
+        self.pending.clear();
+    }
+
+    /// Get the key combinations to grab in the active mode, which start its bindings.
+    ///
+    /// Outside of the default mode, Escape is grabbed as well to go back to it.
+    pub fn active_combos(&self) -> Vec<KeyCombo> {
+        let mut combos = Vec::new();
+        for binding in self.active_bindings() {
+            if !combos.contains(&binding.keys[0]) {
+                combos.push(binding.keys[0]);
+            }
+        }
+
+        let escape = Self::escape();
+        if self.mode != DEFAULT_MODE && !combos.contains(&escape) {
+            combos.push(escape);
+        }
+
+        combos
+    }
+
+    /// Whether a key combination leaves the active mode, as it is an unbound Escape.
+    pub fn is_escape(&self, combo: KeyCombo) -> bool {
+        self.mode != DEFAULT_MODE
+            && self.pending.is_empty()
+            && combo == Self::escape()
+            && !self.active_combos_bound(combo)
+    }
+
+    /// Whether a chord is in progress.
+    pub fn is_chord_pending(&self) -> bool {
+        !self.pending.is_empty()
+    }
+
+    /// Abandon the chord in progress.
+    pub fn cancel_chord(&mut self) {
+        self.pending.clear();
+    }
+
+    /// Whether a key combination is expected next in the active mode:
+    /// it continues the chord in progress, or it starts a binding.
+    pub fn expects(&self, combo: KeyCombo) -> bool {
+        self.active_bindings().any(|binding| {
+            binding.keys.starts_with(&self.pending)
+                && binding.keys.get(self.pending.len()) == Some(&combo)
+        })
+    }
+
+    /// Handle a key press, following the chord in progress if any.
+    ///
+    /// A binding matching the keys pressed so far wins over the chords it starts.
+    pub fn press(&mut self, combo: KeyCombo) -> Press {
+        self.pending.push(combo);
+
+        let bound = self
+            .active_bindings()
+            .find(|binding| !binding.release && binding.keys == self.pending)
+            .map(|binding| binding.command.clone());
+        if let Some(command) = bound {
+            self.pending.clear();
+            return Press::Command(command);
+        }
+
+        if self
+            .active_bindings()
+            .any(|binding| !binding.release && binding.keys.starts_with(&self.pending))
+        {
+            Press::Chord
+        } else {
+            self.pending.clear();
+            Press::Unbound
+        }
+    }
+
+    /// Handle a key release, getting the command bound to it if any.
+    /// Releases are ignored during a chord.
+    pub fn release(&self, combo: KeyCombo) -> Option<Command> {
+        if self.is_chord_pending() {
+            return None;
+        }
+
+        self.active_bindings()
+            .find(|binding| binding.release && binding.keys == [combo])
+            .map(|binding| binding.command.clone())
+    }
+
+    /// Describe the key bindings of every mode, and the mouse bindings set by the config.
+    pub fn report(&self, config: &Config) -> BindingsReport {
+        let keys = self
+            .bindings
+            .iter()
+            .map(|binding| KeyBindingInfo {
+                mode: binding.mode.clone(),
+                keys: binding
+                    .keys
+                    .iter()
+                    .map(KeyCombo::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                release: binding.release,
+                active: binding.mode == self.mode,
+                command: binding.command.clone(),
+            })
+            .collect();
+
+        let modifiers = keys::modifier_names(config.mod_key).join("+");
+        let buttons = [
+            (config.select_button, "focus"),
+            (config.drag_button, "move"),
+            (config.resize_button, "resize"),
+        ]
+        .into_iter()
+        .map(|(button, action)| ButtonBindingInfo {
+            buttons: format!("{}+button{}", modifiers, button.number()),
+            action,
+        })
+        .collect();
+
+        BindingsReport { keys, buttons }
+    }
+
+    fn active_bindings(&self) -> impl Iterator<Item = &Binding> {
+        self.bindings
+            .iter()
+            .filter(|binding| binding.mode == self.mode)
+    }
+
+    fn active_combos_bound(&self, combo: KeyCombo) -> bool {
+        self.active_bindings()
+            .any(|binding| binding.keys[0] == combo)
+    }
+
+    fn escape() -> KeyCombo {
+        KeyCombo {
+            modifiers: xcb::x::ModMask::empty(),
+            keysym: ESCAPE,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::keys::{parse_key_combo, parse_key_sequence};
+
+    fn binding(mode: &str, keys: &str, command: Command) -> Binding {
+        Binding {
+            mode: mode.to_owned(),
+            keys: parse_key_sequence(keys).unwrap(),
+            release: false,
+            command,
+        }
+    }
+
+    fn press(bindings: &mut Bindings, keys: &str) -> Press {
+        bindings.press(parse_key_combo(keys).unwrap())
+    }
+
+    #[test]
+    fn test_bind() {
+        let mut bindings = Bindings::default();
+        bindings.bind(binding(DEFAULT_MODE, "super+q", Command::Quit));
+        bindings.bind(binding(DEFAULT_MODE, "super+Q", Command::EndCycleMru));
+
+        let keys = parse_key_sequence("super+q").unwrap();
+        assert_eq!(1, bindings.bindings.len());
+        assert!(matches!(
+            press(&mut bindings, "super+q"),
+            Press::Command(Command::EndCycleMru)
+        ));
+
+        assert!(bindings.unbind(DEFAULT_MODE, &keys, true).is_none());
+        assert!(bindings.unbind(DEFAULT_MODE, &keys, false).is_some());
+        assert!(bindings.unbind(DEFAULT_MODE, &keys, false).is_none());
+        assert!(matches!(press(&mut bindings, "super+q"), Press::Unbound));
+    }
+
+    #[test]
+    fn test_modes() {
+        let mut bindings = Bindings::default();
+        bindings.bind(binding(DEFAULT_MODE, "super+r", Command::EndCycleMru));
+        bindings.bind(binding("resize", "Left", Command::EndCycleMru));
+
+        let left = parse_key_combo("Left").unwrap();
+        let escape = parse_key_combo("Escape").unwrap();
+        assert!(matches!(press(&mut bindings, "Left"), Press::Unbound));
+        assert!(!bindings.is_escape(escape));
+        assert_eq!(
+            vec![parse_key_combo("super+r").unwrap()],
+            bindings.active_combos()
+        );
+
+        bindings.set_mode("resize".to_owned());
+
+        assert!(matches!(press(&mut bindings, "Left"), Press::Command(_)));
+        assert!(bindings.is_escape(escape));
+        assert_eq!(vec![left, escape], bindings.active_combos());
+    }
+
+    #[test]
+    fn test_chords() {
+        let mut bindings = Bindings::default();
+        bindings.bind(binding(DEFAULT_MODE, "super+w, f", Command::Quit));
+        bindings.bind(binding(DEFAULT_MODE, "super+w, g, g", Command::EndCycleMru));
+
+        assert_eq!(
+            vec![parse_key_combo("super+w").unwrap()],
+            bindings.active_combos()
+        );
+
+        assert!(bindings.expects(parse_key_combo("super+w").unwrap()));
+        assert!(!bindings.expects(parse_key_combo("f").unwrap()));
+        assert!(matches!(press(&mut bindings, "super+w"), Press::Chord));
+        assert!(bindings.is_chord_pending());
+        assert!(bindings.expects(parse_key_combo("f").unwrap()));
+        assert!(!bindings.expects(parse_key_combo("super+w").unwrap()));
+        assert!(matches!(
+            press(&mut bindings, "f"),
+            Press::Command(Command::Quit)
+        ));
+        assert!(!bindings.is_chord_pending());
+
+        assert!(matches!(press(&mut bindings, "super+w"), Press::Chord));
+        assert!(matches!(press(&mut bindings, "g"), Press::Chord));
+        assert!(matches!(
+            press(&mut bindings, "g"),
+            Press::Command(Command::EndCycleMru)
+        ));
+
+        // A key which is not part of the chord breaks it
+        assert!(matches!(press(&mut bindings, "super+w"), Press::Chord));
+        assert!(matches!(press(&mut bindings, "x"), Press::Unbound));
+        assert!(!bindings.is_chord_pending());
+        assert!(matches!(press(&mut bindings, "f"), Press::Unbound));
+    }
+
+    #[test]
+    fn test_release() {
+        let mut bindings = Bindings::default();
+        bindings.bind(binding(DEFAULT_MODE, "alt+Tab", Command::Quit));
+        bindings.bind(Binding {
+            release: true,
+            ..binding(DEFAULT_MODE, "Alt_L", Command::EndCycleMru)
+        });
+        bindings.bind(binding(DEFAULT_MODE, "super+w, f", Command::Quit));
+
+        let alt = parse_key_combo("Alt_L").unwrap();
+        assert_eq!(3, bindings.active_combos().len());
+        assert!(matches!(press(&mut bindings, "Alt_L"), Press::Unbound));
+        assert!(matches!(bindings.release(alt), Some(Command::EndCycleMru)));
+        assert!(bindings
+            .release(parse_key_combo("alt+Tab").unwrap())
+            .is_none());
+
+        assert!(matches!(press(&mut bindings, "super+w"), Press::Chord));
+        assert!(bindings.release(alt).is_none());
+    }
+
+    #[test]
+    fn test_report() {
+        let mut bindings = Bindings::default();
+        bindings.bind(binding(DEFAULT_MODE, "super+w, F", Command::Quit));
+        bindings.bind(binding("resize", "Left", Command::EndCycleMru));
+
+        let report = bindings.report(&Config::default());
+
+        assert_eq!(
+            vec![("default", "super+w, f", true), ("resize", "Left", false)],
+            report
+                .keys
+                .iter()
+                .map(|info| (info.mode.as_str(), info.keys.as_str(), info.active))
+                .collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![
+                ("super+button1", "focus"),
+                ("super+button1", "move"),
+                ("super+button3", "resize")
+            ],
+            report
+                .buttons
+                .iter()
+                .map(|info| (info.buttons.as_str(), info.action))
+                .collect::<Vec<_>>()
+        );
+    }
+}
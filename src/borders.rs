@@ -0,0 +1,115 @@
+//! Double borders, drawn in a pixmap tiled around the windows.
+
+use xcb::x;
+
+use crate::vector::Vector2D;
+
+/// Get the rectangles of the inner part of a double border, in the border pixmap.
+///
+/// The pixmap is as large as the window with its border. Its origin is the inside
+/// of the window, so the left and top parts of the border, at negative coordinates,
+/// wrap around to its right and bottom ends. The rest of the pixmap is the outer part.
+pub fn inner_border_rectangles(
+    size: Vector2D,
+    border_width: u32,
+    outer_width: u32,
+) -> Vec<x::Rectangle> {
+    let border_width = border_width as i32;
+    let inner_width = border_width - (outer_width as i32).min(border_width);
+    let period = size + Vector2D::new(2 * border_width, 2 * border_width);
+
+    // The inner border around the window, in window coordinates
+    let ring = [
+        (
+            -inner_width,
+            -inner_width,
+            size.x + 2 * inner_width,
+            inner_width,
+        ),
+        (-inner_width, size.y, size.x + 2 * inner_width, inner_width),
+        (-inner_width, 0, inner_width, size.y),
+        (size.x, 0, inner_width, size.y),
+    ];
+
+    let mut rectangles = Vec::new();
+    for (x, y, width, height) in ring {
+        for (x, width) in wrap(x, width, period.x) {
+            for (y, height) in wrap(y, height, period.y) {
+                rectangles.push(x::Rectangle {
+                    x: x as i16,
+                    y: y as i16,
+                    width: width as u16,
+                    height: height as u16,
+                });
+            }
+        }
+    }
+
+    rectangles
+}
+
+/// Split a range starting at a negative coordinate,
+/// so that it fits in a pixmap tiled every `period` pixels.
+fn wrap(start: i32, length: i32, period: i32) -> Vec<(i32, i32)> {
+    let end = start + length;
+    let ranges = if start >= 0 {
+        vec![(start, length)]
+    } else if end <= 0 {
+        vec![(start + period, length)]
+    } else {
+        vec![(start + period, -start), (0, end)]
+    };
+
+    ranges
+        .into_iter()
+        .filter(|(_, length)| *length > 0)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn inner(size: Vector2D, border_width: u32, outer_width: u32) -> Vec<(i16, i16, u16, u16)> {
+        inner_border_rectangles(size, border_width, outer_width)
+            .iter()
+            .map(|r| (r.x, r.y, r.width, r.height))
+            .collect()
+    }
+
+    #[test]
+    fn test_wrap() {
+        assert_eq!(vec![(2, 3)], wrap(2, 3, 10));
+        assert_eq!(vec![(7, 2)], wrap(-3, 2, 10));
+        assert_eq!(vec![(8, 2), (0, 3)], wrap(-2, 5, 10));
+        assert!(wrap(0, 0, 10).is_empty());
+    }
+
+    #[test]
+    fn test_inner_border_rectangles() {
+        // A 10x6 window with a 3 pixels border, 1 of which is the outer part.
+        // The pixmap is 16x12: the right border is at x 10..13, the left one at 13..16.
+        assert_eq!(
+            vec![
+                // Top
+                (14, 10, 2, 2),
+                (0, 10, 12, 2),
+                // Bottom
+                (14, 6, 2, 2),
+                (0, 6, 12, 2),
+                // Left
+                (14, 0, 2, 6),
+                // Right
+                (10, 0, 2, 6),
+            ],
+            inner(Vector2D::new(10, 6), 3, 1)
+        );
+        // Without an outer part, the whole border is inner
+        assert_eq!(
+            vec![(12, 8, 2, 2), (0, 8, 12, 2)],
+            inner(Vector2D::new(10, 6), 2, 0)[0..2]
+        );
+        // Without an inner part, there is nothing to draw
+        assert!(inner(Vector2D::new(10, 6), 2, 5).is_empty());
+    }
+}
@@ -1,57 +1,451 @@
 use std::{
-    io::{BufReader, Read, Write},
-    os::unix::net::{UnixListener, UnixStream},
+    fs::Permissions,
+    io::{BufRead, BufReader, Write},
+    os::unix::{
+        fs::PermissionsExt,
+        io::AsRawFd,
+        net::{UnixListener, UnixStream},
+    },
+    path::{Path, PathBuf},
     thread,
 };
 
+use anyhow::{anyhow, bail, Context, Result};
+use clap::Parser;
 use crossbeam::channel;
+use serde::{Deserialize, Serialize};
 
-use crate::commands::{self, Command};
+use crate::args;
+use crate::commands::Command;
+use crate::events::Event;
+use crate::format::{self, OutputFormat};
 
-pub fn handle_ipc(client_sender: channel::Sender<commands::Command>) {
-    std::fs::remove_file("/tmp/toniowm.socket").unwrap_or_default();
-    let listener = UnixListener::bind("/tmp/toniowm.socket").unwrap();
+/// A command received from a client.
+pub struct Request {
+    pub command: Command,
+    /// Used to send a response back to the client.
+    /// The client gets no response if this is dropped without sending.
+    pub response_sender: channel::Sender<Response>,
+    /// Used to stream events to the client, only set for subscriptions.
+    pub event_sender: Option<channel::Sender<Event>>,
+}
+
+/// The response written back to a client once its command has been handled,
+/// serialized as `{"ok": payload}` or `{"error": message}`.
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Response {
+    /// The command succeeded. The payload is null unless the command queries something.
+    Ok(serde_json::Value),
+    /// The command failed.
+    Error(String),
+}
+
+/// Get the path of the socket the window manager listens on.
+///
+/// It can be set with TONIOWM_SOCKET, e.g. to run a nested window manager for testing.
+pub fn socket_path() -> PathBuf {
+    std::env::var_os("TONIOWM_SOCKET")
+        .map(PathBuf::from)
+        .unwrap_or_else(|| PathBuf::from("/tmp/toniowm.socket"))
+}
+
+/// Get the path of the socket of a window manager started on another display,
+/// so that it does not take over the socket of the main one.
+pub fn display_socket_path(display: &str) -> PathBuf {
+    PathBuf::from(format!("/tmp/toniowm-{}.socket", display.replace('/', "_")))
+}
 
-    // accept connections and process them, spawning a new thread for each one
-    for stream in listener.incoming() {
-        match stream {
-            Ok(stream) => {
-                /* connection succeeded */
-                let client_sender = client_sender.clone();
-                thread::spawn(|| handle_client(stream, client_sender));
+/// Serve the IPC on the given socket.
+///
+/// Return an error if the socket cannot be created.
+pub fn handle_ipc(socket: &Path, client_sender: channel::Sender<Request>) -> Result<()> {
+    listen(socket, move |stream| {
+        handle_client(stream, client_sender.clone())
+    })
+}
+
+/// Create a socket and handle each connection to it in its own thread,
+/// from a thread accepting them in the background.
+///
+/// Return an error if the socket cannot be created.
+pub fn listen<F>(socket: &Path, handler: F) -> Result<()>
+where
+    F: Fn(UnixStream) + Clone + Send + 'static,
+{
+    std::fs::remove_file(socket).unwrap_or_default();
+    let listener = UnixListener::bind(socket)
+        .with_context(|| format!("Failed to bind {}", socket.display()))?;
+    // Keep other users from connecting, peer credentials are checked anyway
+    std::fs::set_permissions(socket, Permissions::from_mode(0o600))
+        .with_context(|| format!("Failed to set the permissions of {}", socket.display()))?;
+
+    thread::spawn(move || {
+        for stream in listener.incoming() {
+            match stream {
+                Ok(stream) => {
+                    let handler = handler.clone();
+                    thread::spawn(move || handler(stream));
+                }
+                Err(err) => {
+                    println!("Error: {}", err);
+                    break;
+                }
             }
+        }
+    });
+
+    Ok(())
+}
+
+/// Get the user id of the process on the other end of a stream.
+fn peer_uid(stream: &UnixStream) -> std::io::Result<libc::uid_t> {
+    let mut cred = libc::ucred {
+        pid: 0,
+        uid: 0,
+        gid: 0,
+    };
+    let mut len = std::mem::size_of::<libc::ucred>() as libc::socklen_t;
+
+    // SAFETY: cred and len are valid for writes and len is the size of cred
+    let ret = unsafe {
+        libc::getsockopt(
+            stream.as_raw_fd(),
+            libc::SOL_SOCKET,
+            libc::SO_PEERCRED,
+            &mut cred as *mut libc::ucred as *mut libc::c_void,
+            &mut len,
+        )
+    };
+    if ret != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+
+    Ok(cred.uid)
+}
+
+/// Check whether the process on the other end of a stream belongs to
+/// the user running the window manager, who is the only one allowed to send commands.
+pub fn is_same_user(stream: &UnixStream) -> bool {
+    // SAFETY: getuid cannot fail
+    let uid = unsafe { libc::getuid() };
+    match peer_uid(stream) {
+        Ok(peer_uid) if peer_uid == uid => true,
+        Ok(peer_uid) => {
+            println!("Error: Rejected a connection from user {}", peer_uid);
+            false
+        }
+        Err(err) => {
+            println!("Error: {}", err);
+            false
+        }
+    }
+}
+
+fn handle_client(mut stream: UnixStream, client_sender: channel::Sender<Request>) {
+    if !is_same_user(&stream) {
+        let response = Response::Error("Permission denied".to_owned());
+        let _ = writeln!(stream, "{}", serde_json::to_string(&response).unwrap());
+        return;
+    }
+
+    let reader = match stream.try_clone() {
+        Ok(stream) => BufReader::new(stream),
+        Err(err) => {
+            println!("Error: {}", err);
+            return;
+        }
+    };
+
+    // Each line holds a command, which is answered with a response line
+    for line in reader.lines() {
+        let line = match line {
+            Ok(line) if line.trim().is_empty() => continue,
+            Ok(line) => line,
             Err(err) => {
-                /* connection failed */
                 println!("Error: {}", err);
-                break;
+                return;
+            }
+        };
+
+        let (event_sender, event_receiver) = channel::unbounded();
+        let mut is_subscription = false;
+        let response = match parse_command(&line) {
+            Ok(command) => {
+                let (response_sender, response_receiver) = channel::bounded(1);
+                is_subscription = matches!(command, Command::Subscribe { .. });
+                client_sender
+                    .send(Request {
+                        command,
+                        response_sender,
+                        event_sender: is_subscription.then_some(event_sender),
+                    })
+                    .unwrap();
+
+                match response_receiver.recv() {
+                    Ok(response) => response,
+                    // The window manager is gone
+                    Err(_) => return,
+                }
+            }
+            Err(err) => Response::Error(format!("Invalid command: {}", err)),
+        };
+
+        let serialized_response = serde_json::to_string(&response).unwrap();
+        if let Err(err) = writeln!(stream, "{}", serialized_response) {
+            println!("Error: {}", err);
+            return;
+        }
+
+        // A subscription turns the connection into a stream of events, one per line,
+        // until the client disconnects
+        if is_subscription {
+            for event in event_receiver {
+                let serialized_event = serde_json::to_string(&event).unwrap();
+                if writeln!(stream, "{}", serialized_event).is_err() {
+                    return;
+                }
             }
+            return;
         }
     }
 }
 
-fn handle_client(stream: UnixStream, client_sender: channel::Sender<commands::Command>) {
-    let mut buf = BufReader::new(stream);
+/// Parse a command. A plain array of commands is run as a batch.
+fn parse_command(data: &str) -> serde_json::Result<Command> {
+    serde_json::from_str(data).or_else(|err| {
+        serde_json::from_str(data)
+            .map(|commands| Command::Batch { commands })
+            .map_err(|_| err)
+    })
+}
+
+/// A connection to the window manager.
+///
+/// Commands and responses are exchanged as JSON lines,
+/// so many commands can be sent over the same connection.
+pub struct Connection {
+    reader: BufReader<UnixStream>,
+    writer: UnixStream,
+}
 
-    let mut data = String::new();
-    if let Err(err) = buf.read_to_string(&mut data) {
-        eprintln!("Error: {}", err);
-        return;
+impl Connection {
+    /// Connect to the window manager socket.
+    pub fn open() -> Result<Self> {
+        let socket = socket_path();
+        let stream = UnixStream::connect(&socket)
+            .with_context(|| format!("Failed to connect to {}", socket.display()))?;
+
+        Self::new(stream)
     }
 
-    let command = match serde_json::from_str(&data) {
-        Ok(command) => command,
-        Err(_) => {
-            eprintln!("Error: Invalid command");
-            return;
+    fn new(stream: UnixStream) -> Result<Self> {
+        Ok(Self {
+            reader: BufReader::new(stream.try_clone()?),
+            writer: stream,
+        })
+    }
+
+    /// Send a command and wait for its response.
+    pub fn send(&mut self, command: &Command) -> Result<Response> {
+        writeln!(self.writer, "{}", serde_json::to_string(command)?)?;
+
+        let mut response = String::new();
+        if self.reader.read_line(&mut response)? == 0 {
+            bail!("The window manager closed the connection");
         }
+
+        serde_json::from_str(&response).context("Invalid response from the window manager")
+    }
+
+    /// Get the events streamed after subscribing, one JSON object per line.
+    pub fn events(self) -> impl Iterator<Item = std::io::Result<String>> {
+        self.reader.lines()
+    }
+}
+
+/// Send a command to the window manager and print its response.
+///
+/// Return an error if the window manager is unreachable or reports an error,
+/// so that the client exits with a nonzero code.
+pub fn dispatch_command(command: Command, format: &OutputFormat) -> Result<()> {
+    let is_subscription = matches!(command, Command::Subscribe { .. });
+
+    let mut connection = Connection::open()?;
+    let response = connection.send(&command)?;
+    print_response(&command, response, format)?;
+
+    if is_subscription {
+        for event in connection.events() {
+            println!("{}", event?);
+        }
+        bail!("The window manager closed the connection");
+    }
+
+    Ok(())
+}
+
+/// Send the commands read from the input, one per line, over a single connection.
+///
+/// Empty lines and lines starting with "#" are skipped.
+/// A failing command is reported with its line number and does not stop the following ones.
+pub fn dispatch_lines(input: impl BufRead) -> Result<()> {
+    let mut connection = Connection::open()?;
+    let mut failed = 0;
+
+    for (index, line) in input.lines().enumerate() {
+        let line = line.context("Failed to read the commands")?;
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let result = parse_line(line).and_then(|(command, format)| {
+            let response = connection.send(&command)?;
+            print_response(&command, response, &format)
+        });
+        if let Err(err) = result {
+            eprintln!("Line {}: {:#}", index + 1, err);
+            failed += 1;
+        }
+    }
+
+    if failed > 0 {
+        bail!("{} command(s) failed", failed);
+    }
+
+    Ok(())
+}
+
+/// Parse a line of the input of `dispatch_lines` as the arguments of `toniowm client`.
+fn parse_line(line: &str) -> Result<(Command, OutputFormat)> {
+    let words = args::split_words(line).map_err(|err| anyhow!(err))?;
+    let command = match args::BatchCommand::try_parse_from(words) {
+        Ok(batch_command) => batch_command.command,
+        // Only keep the message, without the usage
+        Err(err) => bail!(err
+            .to_string()
+            .lines()
+            .next()
+            .unwrap_or_default()
+            .trim_start_matches("error: ")
+            .to_owned()),
     };
-    client_sender.send(command).unwrap();
+
+    if command.reads_stdin() || matches!(command, args::Command::Subscribe { .. }) {
+        bail!("This command cannot be read from stdin");
+    }
+
+    let format = command.output_format();
+    Ok((command.into(), format))
+}
+
+fn print_response(command: &Command, response: Response, format: &OutputFormat) -> Result<()> {
+    match response {
+        // Queries print their payload even if it is null
+        Response::Ok(serde_json::Value::Null) if !matches!(command, Command::Query { .. }) => {}
+        Response::Ok(payload) => println!("{}", format::format_payload(&payload, format)),
+        Response::Error(message) => bail!(message),
+    }
+
+    Ok(())
 }
-// TODO: handle errors
-pub fn dispatch_command(command: Command) {
-    let socket = std::path::Path::new("/tmp/toniowm.socket");
-    let mut stream = std::os::unix::net::UnixStream::connect(socket).unwrap();
-    let serialized_command = serde_json::to_string(&command).unwrap();
 
-    stream.write_all(serialized_command.as_bytes()).unwrap();
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::commands::{Query, WorkspaceSelector};
+
+    #[test]
+    fn test_response_serialization() {
+        assert_eq!(
+            r#"{"ok":null}"#,
+            serde_json::to_string(&Response::Ok(serde_json::Value::Null)).unwrap()
+        );
+        assert_eq!(
+            r#"{"error":"Workspace not found."}"#,
+            serde_json::to_string(&Response::Error("Workspace not found.".to_owned())).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_display_socket_path() {
+        assert_eq!(
+            PathBuf::from("/tmp/toniowm-:1.socket"),
+            display_socket_path(":1")
+        );
+        assert_eq!(
+            PathBuf::from("/tmp/toniowm-_tmp_.X11-unix_X1.socket"),
+            display_socket_path("/tmp/.X11-unix/X1")
+        );
+    }
+
+    #[test]
+    fn test_peer_uid() {
+        let (stream, _) = UnixStream::pair().unwrap();
+
+        assert_eq!(unsafe { libc::getuid() }, peer_uid(&stream).unwrap());
+    }
+
+    #[test]
+    fn test_persistent_connection() {
+        let (client_stream, server_stream) = UnixStream::pair().unwrap();
+        let (client_sender, client_receiver) = channel::unbounded::<Request>();
+        thread::spawn(move || handle_client(server_stream, client_sender));
+        // Answer the commands like the window manager would
+        thread::spawn(move || {
+            for request in client_receiver {
+                let payload = match request.command {
+                    Command::Query { .. } => serde_json::json!([]),
+                    _ => serde_json::Value::Null,
+                };
+                request.response_sender.send(Response::Ok(payload)).unwrap();
+            }
+        });
+
+        let mut connection = Connection::new(client_stream).unwrap();
+        assert!(matches!(
+            connection.send(&Command::EndCycleMru).unwrap(),
+            Response::Ok(serde_json::Value::Null)
+        ));
+        assert!(matches!(
+            connection
+                .send(&Command::Query {
+                    query: Query::Workspaces
+                })
+                .unwrap(),
+            Response::Ok(serde_json::Value::Array(_))
+        ));
+    }
+
+    #[test]
+    fn test_parse_line() {
+        let (command, format) =
+            parse_line("query --format plain windows --name 'web browsing'").unwrap();
+
+        assert!(matches!(
+            command,
+            Command::Query {
+                query: Query::Windows(Some(WorkspaceSelector::Name(name)))
+            } if name == "web browsing"
+        ));
+        assert_eq!(OutputFormat::Plain, format);
+        assert!(matches!(
+            parse_line("query windows --index 1").unwrap().0,
+            Command::Query {
+                query: Query::Windows(Some(WorkspaceSelector::Index(1)))
+            }
+        ));
+        assert!(matches!(
+            parse_line("query windows").unwrap().0,
+            Command::Query {
+                query: Query::Windows(None)
+            }
+        ));
+        assert!(parse_line("activate-workspace").is_err());
+        assert!(parse_line("subscribe").is_err());
+        assert!(parse_line("batch -").is_err());
+        assert!(parse_line("unknown-command").is_err());
+    }
 }
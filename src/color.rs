@@ -0,0 +1,137 @@
+//! Colors of the window decorations, as written in the config and on the command line.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+
+/// A color given by its RGB components, or by a name known to the X server, e.g. "steel blue".
+///
+/// The X pixel value depends on the visual of the screen,
+/// so colors are allocated in its colormap before being used.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "ColorValue", into = "String")]
+pub enum Color {
+    Rgb(u8, u8, u8),
+    Named(String),
+}
+
+/// A color in the config file: a string, or a number such as 0xcccccc for older configs.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ColorValue {
+    Number(u32),
+    String(String),
+}
+
+impl TryFrom<ColorValue> for Color {
+    type Error = String;
+
+    fn try_from(value: ColorValue) -> Result<Self, Self::Error> {
+        match value {
+            ColorValue::Number(rgb) => Self::from_rgb(rgb),
+            ColorValue::String(color) => color.parse(),
+        }
+    }
+}
+
+impl From<Color> for String {
+    fn from(color: Color) -> Self {
+        color.to_string()
+    }
+}
+
+impl Color {
+    fn from_rgb(rgb: u32) -> Result<Self, String> {
+        if rgb > 0xffffff {
+            return Err(format!("Invalid color {:#x}, expected 0xRRGGBB", rgb));
+        }
+        let [_, r, g, b] = rgb.to_be_bytes();
+
+        Ok(Self::Rgb(r, g, b))
+    }
+}
+
+/// Parse a color written as #RRGGBB, #RGB, 0xRRGGBB, a decimal number or a name.
+impl FromStr for Color {
+    type Err = String;
+
+    fn from_str(color: &str) -> Result<Self, Self::Err> {
+        let color = color.trim();
+        let invalid = || {
+            format!(
+                "Invalid color {}, expected #RRGGBB, 0xRRGGBB or a name",
+                color
+            )
+        };
+
+        if let Some(hex) = color.strip_prefix('#').or_else(|| color.strip_prefix("0x")) {
+            let rgb = u32::from_str_radix(hex, 16).map_err(|_| invalid())?;
+            return match hex.len() {
+                6 => Self::from_rgb(rgb),
+                // #RGB is short for #RRGGBB
+                3 if color.starts_with('#') => {
+                    let expand = |shift: u32| ((rgb >> shift) & 0xf) as u8 * 0x11;
+                    Ok(Self::Rgb(expand(8), expand(4), expand(0)))
+                }
+                _ => Err(invalid()),
+            };
+        }
+        if !color.is_empty() && color.chars().all(|c| c.is_ascii_digit()) {
+            return Self::from_rgb(color.parse().map_err(|_| invalid())?);
+        }
+        // The names are looked up by the X server, which ignores case and spaces
+        if !color.is_empty() && color.chars().all(|c| c.is_ascii_alphanumeric() || c == ' ') {
+            return Ok(Self::Named(color.to_owned()));
+        }
+
+        Err(invalid())
+    }
+}
+
+impl fmt::Display for Color {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Color::Rgb(r, g, b) => write!(f, "#{:02x}{:02x}{:02x}", r, g, b),
+            Color::Named(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_color() {
+        assert_eq!(Ok(Color::Rgb(0xff, 0x88, 0x00)), "#ff8800".parse());
+        assert_eq!(Ok(Color::Rgb(0xff, 0x88, 0x00)), "0xFF8800".parse());
+        assert_eq!(Ok(Color::Rgb(0xff, 0x88, 0x00)), "#f80".parse());
+        assert_eq!(Ok(Color::Rgb(0xcc, 0xcc, 0xcc)), "13421772".parse());
+        assert_eq!(
+            Ok(Color::Named("steel blue".to_owned())),
+            "steel blue".parse::<Color>()
+        );
+        assert!("#ff88".parse::<Color>().is_err());
+        assert!("0xf80".parse::<Color>().is_err());
+        assert!("#gg8800".parse::<Color>().is_err());
+        assert!("16777216".parse::<Color>().is_err());
+        assert!("".parse::<Color>().is_err());
+    }
+
+    #[test]
+    fn test_color_serialization() {
+        assert_eq!(
+            Color::Rgb(0, 0xcc, 0xff),
+            serde_json::from_str("\"#00ccff\"").unwrap()
+        );
+        assert_eq!(
+            Color::Rgb(0, 0xcc, 0xff),
+            serde_json::from_str("52479").unwrap()
+        );
+        assert_eq!(
+            "\"#00ccff\"",
+            serde_json::to_string(&Color::Rgb(0, 0xcc, 0xff)).unwrap()
+        );
+    }
+}
@@ -2,21 +2,58 @@
 //! A command represents the intent of the user to change the state of the wm.
 //! From traits are implemented to convert from clap arguments to commands.
 
+use std::path::{self, PathBuf};
+
+use clap::Parser;
 use serde::{Deserialize, Serialize};
 
 use crate::args;
+use crate::color::Color;
+use crate::config::FocusModel;
+use crate::events::EventType;
+use crate::rules::Rule;
+use crate::vector::Vector2D;
 
-#[derive(Serialize, Deserialize)]
+#[derive(Clone, Serialize, Deserialize)]
 pub enum Command {
     Quit,
     Focus {
         selector: WindowSelector,
     },
+    CycleMru {
+        direction: CycleDirection,
+        all_workspaces: bool,
+    },
+    EndCycleMru,
     Close {
         selector: WindowSelector,
     },
+    Kill {
+        selector: WindowSelector,
+    },
+    Mark {
+        selector: WindowSelector,
+        name: String,
+    },
+    Snap {
+        selector: WindowSelector,
+        position: SnapPosition,
+    },
+    Unsnap {
+        selector: WindowSelector,
+    },
+    Undo {
+        selector: WindowSelector,
+    },
+    Resize {
+        selector: WindowSelector,
+        size: Vector2D,
+        relative: bool,
+        center: bool,
+    },
     AddWorkspace {
         name: Option<String>,
+        monitor: MonitorSelector,
     },
     RenameWorkspace {
         selector: WorkspaceSelector,
@@ -25,18 +62,134 @@ pub enum Command {
     ActivateWorkspace {
         selector: WorkspaceSelector,
     },
+    MoveWorkspaceToMonitor {
+        workspace_selector: WorkspaceSelector,
+        monitor_selector: MonitorSelector,
+    },
     SetBorderWidth {
         width: u32,
     },
     SetBorderColor {
-        color: u32,
+        color: Color,
     },
     SetFocusedBorderColor {
-        color: u32,
+        color: Color,
+    },
+    SetOuterBorderWidth {
+        width: u32,
+    },
+    SetOuterBorderColor {
+        color: Color,
+    },
+    SetTheme {
+        name: String,
+    },
+    SetFocusModel {
+        focus_model: FocusModel,
+    },
+    SetWarpPointer {
+        enabled: bool,
+    },
+    SetEdgeSnap {
+        enabled: bool,
+    },
+    SetEdgeSnapThreshold {
+        threshold: u32,
+    },
+    SetWrap {
+        enabled: bool,
+    },
+    SetDirectionalWrap {
+        enabled: bool,
+    },
+    SetPlacementMonitor {
+        selector: MonitorSelector,
+    },
+    SetPadding {
+        selector: MonitorSelector,
+        padding: Padding,
+    },
+    SetPreWorkspaceSwitchHook {
+        command: Option<String>,
+    },
+    SetPostWorkspaceSwitchHook {
+        command: Option<String>,
+    },
+    SetModKey {
+        modifier: String,
+    },
+    SetSelectButton {
+        button: u8,
+    },
+    SetDragButton {
+        button: u8,
+    },
+    SetResizeButton {
+        button: u8,
+    },
+    SetChordTimeout {
+        timeout: u64,
+    },
+    Query {
+        query: Query,
+    },
+    Assign {
+        rule: Rule,
+    },
+    Subscribe {
+        events: Vec<EventType>,
+    },
+    Batch {
+        commands: Vec<Command>,
+    },
+    Exec {
+        cmd: String,
+        workspace: Option<String>,
+    },
+    Bind {
+        mode: String,
+        keys: String,
+        release: bool,
+        command: Box<Command>,
+    },
+    Unbind {
+        mode: String,
+        keys: String,
+        release: bool,
+    },
+    Mode {
+        name: String,
+    },
+    Reload,
+    RecordStart {
+        file: PathBuf,
+    },
+    RecordStop,
+    Replay {
+        file: PathBuf,
+    },
+    SwapMonitors {
+        first: MonitorSelector,
+        second: MonitorSelector,
+    },
+    FocusMonitor {
+        selector: MonitorSelector,
     },
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum Query {
+    Workspaces,
+    Windows(Option<WorkspaceSelector>),
+    Focused,
+    Monitor(MonitorSelector),
+    Tree,
+    Metrics,
+    Bindings,
+    State,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CardinalDirection {
     East,
     West,
@@ -55,12 +208,26 @@ impl From<args::CardinalDirection> for CardinalDirection {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum CycleDirection {
     Next,
     Prev,
 }
 
+impl From<args::EventType> for EventType {
+    fn from(event_type: args::EventType) -> Self {
+        match event_type {
+            args::EventType::WindowMapped => Self::WindowMapped,
+            args::EventType::WindowClosed => Self::WindowClosed,
+            args::EventType::FocusChanged => Self::FocusChanged,
+            args::EventType::TitleChanged => Self::TitleChanged,
+            args::EventType::WorkspaceSwitched => Self::WorkspaceSwitched,
+            args::EventType::LayoutChanged => Self::LayoutChanged,
+            args::EventType::ModeChanged => Self::ModeChanged,
+        }
+    }
+}
+
 impl From<args::CycleDirection> for CycleDirection {
     fn from(direction: args::CycleDirection) -> Self {
         match direction {
@@ -70,19 +237,102 @@ impl From<args::CycleDirection> for CycleDirection {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+/// The space kept free at each edge of a monitor.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+pub struct Padding {
+    pub top: i32,
+    pub right: i32,
+    pub bottom: i32,
+    pub left: i32,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+pub enum SnapPosition {
+    Maximized,
+    LeftHalf,
+    RightHalf,
+    TopHalf,
+    BottomHalf,
+    TopLeft,
+    TopRight,
+    BottomLeft,
+    BottomRight,
+}
+
+impl From<args::SnapPosition> for SnapPosition {
+    fn from(position: args::SnapPosition) -> Self {
+        match position {
+            args::SnapPosition::Maximized => Self::Maximized,
+            args::SnapPosition::LeftHalf => Self::LeftHalf,
+            args::SnapPosition::RightHalf => Self::RightHalf,
+            args::SnapPosition::TopHalf => Self::TopHalf,
+            args::SnapPosition::BottomHalf => Self::BottomHalf,
+            args::SnapPosition::TopLeft => Self::TopLeft,
+            args::SnapPosition::TopRight => Self::TopRight,
+            args::SnapPosition::BottomLeft => Self::BottomLeft,
+            args::SnapPosition::BottomRight => Self::BottomRight,
+        }
+    }
+}
+
+impl From<args::FocusModel> for FocusModel {
+    fn from(focus_model: args::FocusModel) -> Self {
+        match focus_model {
+            args::FocusModel::Click => Self::Click,
+            args::FocusModel::Sloppy => Self::Sloppy,
+            args::FocusModel::Strict => Self::Strict,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WindowSelector {
     Focused,
     Window(u32),
     Closest(CardinalDirection),
     Cycle(CycleDirection),
+    Mark(String),
+    LastFocused,
+    Urgent,
+    UnderPointer,
+    Biggest,
+    Smallest,
+    Oldest,
+    Newest,
+    /// The class of the window, as a pattern where `*` and `?` are wildcards.
+    Class(String),
+    /// The title of the window, as a pattern where `*` and `?` are wildcards.
+    Title(String),
+    /// Every window of the active workspace.
+    All,
+}
+
+impl WindowSelector {
+    /// Whether the selector can match several windows.
+    /// Commands selecting a single window use the first match.
+    pub fn matches_many(&self) -> bool {
+        matches!(self, Self::Class(_) | Self::Title(_) | Self::All)
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub enum WorkspaceSelector {
     Index(usize),
     Name(String),
     Cycle(CycleDirection),
+    /// The first workspace without clients
+    Empty,
+    /// The first workspace without clients after the active one
+    NextEmpty,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub enum MonitorSelector {
+    Focused,
+    Index(usize),
+    Name(String),
+    Closest(CardinalDirection),
+    Primary,
 }
 
 impl From<args::Command> for Command {
@@ -92,10 +342,50 @@ impl From<args::Command> for Command {
             args::Command::Focus { selector } => Self::Focus {
                 selector: selector.into(),
             },
+            args::Command::CycleMru {
+                direction,
+                all_workspaces,
+            } => Self::CycleMru {
+                direction: direction.into(),
+                all_workspaces,
+            },
+            args::Command::EndCycleMru => Self::EndCycleMru,
             args::Command::Close { selector } => Self::Close {
                 selector: selector.into(),
             },
-            args::Command::AddWorkspace { name } => Self::AddWorkspace { name },
+            args::Command::Kill { selector } => Self::Kill {
+                selector: selector.into(),
+            },
+            args::Command::Mark { selector, name } => Self::Mark {
+                selector: selector.into(),
+                name,
+            },
+            args::Command::Snap { selector, position } => Self::Snap {
+                selector: selector.into(),
+                position: position.into(),
+            },
+            args::Command::Unsnap { selector } => Self::Unsnap {
+                selector: selector.into(),
+            },
+            args::Command::Undo { selector } => Self::Undo {
+                selector: selector.into(),
+            },
+            args::Command::Resize {
+                selector,
+                width,
+                height,
+                relative,
+                center,
+            } => Self::Resize {
+                selector: selector.into(),
+                size: Vector2D::new(width, height),
+                relative,
+                center,
+            },
+            args::Command::AddWorkspace { name, monitor } => Self::AddWorkspace {
+                name,
+                monitor: monitor.into(),
+            },
             args::Command::RenameWorkspace {
                 selector,
                 new_name: name,
@@ -106,6 +396,13 @@ impl From<args::Command> for Command {
             args::Command::ActivateWorkspace { selector } => Self::ActivateWorkspace {
                 selector: selector.into(),
             },
+            args::Command::MoveWorkspaceToMonitor {
+                workspace_selector,
+                monitor_selector,
+            } => Self::MoveWorkspaceToMonitor {
+                workspace_selector: workspace_selector.into(),
+                monitor_selector: monitor_selector.into(),
+            },
             args::Command::Config(args::Config::BorderWidth { width }) => {
                 Self::SetBorderWidth { width }
             }
@@ -115,6 +412,208 @@ impl From<args::Command> for Command {
             args::Command::Config(args::Config::FocusedBorderColor { color }) => {
                 Self::SetFocusedBorderColor { color }
             }
+            args::Command::Config(args::Config::OuterBorderWidth { width }) => {
+                Self::SetOuterBorderWidth { width }
+            }
+            args::Command::Config(args::Config::OuterBorderColor { color }) => {
+                Self::SetOuterBorderColor { color }
+            }
+            args::Command::Config(args::Config::Theme { name }) => Self::SetTheme { name },
+            args::Command::Config(args::Config::FocusModel { focus_model }) => {
+                Self::SetFocusModel {
+                    focus_model: focus_model.into(),
+                }
+            }
+            args::Command::Config(args::Config::WarpPointer { enabled }) => {
+                Self::SetWarpPointer { enabled }
+            }
+            args::Command::Config(args::Config::EdgeSnap { enabled }) => {
+                Self::SetEdgeSnap { enabled }
+            }
+            args::Command::Config(args::Config::EdgeSnapThreshold { threshold }) => {
+                Self::SetEdgeSnapThreshold { threshold }
+            }
+            args::Command::Config(args::Config::Wrap { enabled }) => Self::SetWrap { enabled },
+            args::Command::Config(args::Config::DirectionalWrap { enabled }) => {
+                Self::SetDirectionalWrap { enabled }
+            }
+            args::Command::Config(args::Config::ModKey { modifier }) => {
+                Self::SetModKey { modifier }
+            }
+            args::Command::Config(args::Config::SelectButton { button }) => {
+                Self::SetSelectButton { button }
+            }
+            args::Command::Config(args::Config::DragButton { button }) => {
+                Self::SetDragButton { button }
+            }
+            args::Command::Config(args::Config::ResizeButton { button }) => {
+                Self::SetResizeButton { button }
+            }
+            args::Command::Config(args::Config::ChordTimeout { timeout }) => {
+                Self::SetChordTimeout { timeout }
+            }
+            args::Command::Config(args::Config::PlacementMonitor { selector }) => {
+                Self::SetPlacementMonitor {
+                    selector: selector.into(),
+                }
+            }
+            args::Command::Config(args::Config::Padding {
+                selector,
+                top,
+                right,
+                bottom,
+                left,
+            }) => Self::SetPadding {
+                selector: selector.into(),
+                padding: Padding {
+                    top,
+                    right,
+                    bottom,
+                    left,
+                },
+            },
+            args::Command::Config(args::Config::PreWorkspaceSwitchHook { command }) => {
+                Self::SetPreWorkspaceSwitchHook { command }
+            }
+            args::Command::Config(args::Config::PostWorkspaceSwitchHook { command }) => {
+                Self::SetPostWorkspaceSwitchHook { command }
+            }
+            args::Command::Query {
+                query: args::Query::Workspaces,
+                ..
+            } => Self::Query {
+                query: Query::Workspaces,
+            },
+            args::Command::Query {
+                query: args::Query::Windows { workspace },
+                ..
+            } => Self::Query {
+                query: Query::Windows(workspace.map(Into::into)),
+            },
+            args::Command::Query {
+                query: args::Query::Tree,
+                ..
+            } => Self::Query { query: Query::Tree },
+            args::Command::Query {
+                query: args::Query::Metrics,
+                ..
+            } => Self::Query {
+                query: Query::Metrics,
+            },
+            args::Command::Query {
+                query: args::Query::Bindings,
+                ..
+            } => Self::Query {
+                query: Query::Bindings,
+            },
+            args::Command::Query {
+                query: args::Query::State,
+                ..
+            } => Self::Query {
+                query: Query::State,
+            },
+            args::Command::Query {
+                query: args::Query::Focused,
+                ..
+            } => Self::Query {
+                query: Query::Focused,
+            },
+            args::Command::Query {
+                query: args::Query::Monitor { selector },
+                ..
+            } => Self::Query {
+                query: Query::Monitor(selector.into()),
+            },
+            args::Command::FocusMonitor { selector } => Self::FocusMonitor {
+                selector: selector.into(),
+            },
+            args::Command::SwapMonitors { selector } => Self::SwapMonitors {
+                first: MonitorSelector::Focused,
+                second: selector.into(),
+            },
+            args::Command::Assign {
+                class,
+                workspace,
+                switch,
+            } => Self::Assign {
+                rule: Rule {
+                    class: Some(class),
+                    title: None,
+                    window_type: None,
+                    workspace,
+                    switch,
+                },
+            },
+            args::Command::Rule(args::Rule::Add {
+                class,
+                title,
+                window_type,
+                workspace,
+                switch,
+            }) => Self::Assign {
+                rule: Rule {
+                    class,
+                    title,
+                    window_type,
+                    workspace,
+                    switch,
+                },
+            },
+            args::Command::Reload => Self::Reload,
+            args::Command::Batch { args } => Self::Batch {
+                commands: args
+                    .split(|arg| arg == ";")
+                    .filter(|args| !args.is_empty())
+                    .map(|args| {
+                        args::BatchCommand::try_parse_from(args)
+                            .unwrap_or_else(|err| err.exit())
+                            .command
+                            .into()
+                    })
+                    .collect(),
+            },
+            args::Command::Exec { workspace, cmd } => Self::Exec {
+                cmd: cmd.join(" "),
+                workspace,
+            },
+            args::Command::Bind {
+                mode,
+                release,
+                keys,
+                command,
+            } => Self::Bind {
+                mode,
+                keys,
+                release,
+                command: Box::new(
+                    args::BatchCommand::try_parse_from(command)
+                        .unwrap_or_else(|err| err.exit())
+                        .command
+                        .into(),
+                ),
+            },
+            args::Command::Unbind {
+                mode,
+                release,
+                keys,
+            } => Self::Unbind {
+                mode,
+                keys,
+                release,
+            },
+            args::Command::Mode { name } => Self::Mode { name },
+            // The paths are resolved by the client, since the window manager
+            // runs in another directory
+            args::Command::Record(args::Record::Start { file }) => Self::RecordStart {
+                file: path::absolute(&file).unwrap_or(file),
+            },
+            args::Command::Record(args::Record::Stop) => Self::RecordStop,
+            args::Command::Replay { file } => Self::Replay {
+                file: path::absolute(&file).unwrap_or(file),
+            },
+            args::Command::Subscribe { events } => Self::Subscribe {
+                events: events.into_iter().map(Into::into).collect(),
+            },
         }
     }
 }
@@ -127,6 +626,17 @@ impl From<args::WindowSelector> for WindowSelector {
                 window: None,
                 closest: None,
                 cycle: None,
+                mark: None,
+                last: false,
+                urgent: false,
+                under_pointer: false,
+                biggest: false,
+                smallest: false,
+                oldest: false,
+                newest: false,
+                class: None,
+                title: None,
+                all: false,
             } => Self::Focused,
             args::WindowSelector {
                 window: Some(window),
@@ -140,6 +650,28 @@ impl From<args::WindowSelector> for WindowSelector {
                 cycle: Some(direction),
                 ..
             } => Self::Cycle(direction.into()),
+            args::WindowSelector {
+                mark: Some(name), ..
+            } => Self::Mark(name),
+            args::WindowSelector { last: true, .. } => Self::LastFocused,
+            args::WindowSelector { urgent: true, .. } => Self::Urgent,
+            args::WindowSelector {
+                under_pointer: true,
+                ..
+            } => Self::UnderPointer,
+            args::WindowSelector { biggest: true, .. } => Self::Biggest,
+            args::WindowSelector { smallest: true, .. } => Self::Smallest,
+            args::WindowSelector { oldest: true, .. } => Self::Oldest,
+            args::WindowSelector { newest: true, .. } => Self::Newest,
+            args::WindowSelector {
+                class: Some(pattern),
+                ..
+            } => Self::Class(pattern),
+            args::WindowSelector {
+                title: Some(pattern),
+                ..
+            } => Self::Title(pattern),
+            args::WindowSelector { all: true, .. } => Self::All,
             // This is unreachable because the clap parser
             // will always return either a focused or a window.
             _ => unreachable!(),
@@ -154,19 +686,63 @@ impl From<args::WorkspaceSelector> for WorkspaceSelector {
                 index: Some(index),
                 name: None,
                 cycle: None,
+                empty: false,
+                next_empty: false,
             } => Self::Index(index),
             args::WorkspaceSelector {
                 name: Some(name),
                 index: None,
                 cycle: None,
+                empty: false,
+                next_empty: false,
             } => Self::Name(name),
             args::WorkspaceSelector {
                 cycle: Some(direction),
                 ..
             } => Self::Cycle(direction.into()),
+            args::WorkspaceSelector { empty: true, .. } => Self::Empty,
+            args::WorkspaceSelector {
+                next_empty: true, ..
+            } => Self::NextEmpty,
             // This is unreachable because the clap parser
             // will always return either a focused or a window.
             _ => unreachable!(),
         }
     }
 }
+
+impl From<args::MonitorSelector> for MonitorSelector {
+    fn from(selector: args::MonitorSelector) -> Self {
+        match selector {
+            args::MonitorSelector {
+                focused: true,
+                index: None,
+                name: None,
+                closest: None,
+                primary: false,
+            } => Self::Focused,
+            args::MonitorSelector {
+                index: Some(index),
+                name: None,
+                closest: None,
+                primary: false,
+                ..
+            } => Self::Index(index),
+            args::MonitorSelector {
+                name: Some(name),
+                index: None,
+                closest: None,
+                primary: false,
+                ..
+            } => Self::Name(name),
+            args::MonitorSelector {
+                closest: Some(direction),
+                ..
+            } => Self::Closest(direction.into()),
+            args::MonitorSelector { primary: true, .. } => Self::Primary,
+            // This is unreachable because the clap parser
+            // will always return either a focused, an index, a name, a direction or the primary.
+            _ => unreachable!(),
+        }
+    }
+}
@@ -1,28 +1,446 @@
+use std::{
+    collections::HashMap,
+    fs,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use serde::{de, Deserialize, Deserializer, Serialize};
+use serde_json::Value;
 use xcb::x;
 
-pub static MOD_KEY: x::ModMask = x::ModMask::N4; // Mod
-pub static MOD_KEY_BUT: x::KeyButMask = x::KeyButMask::MOD4;
+use crate::color::Color;
+use crate::commands::MonitorSelector;
+use crate::keys;
+use crate::launcher::AutostartProgram;
+use crate::rules::Rule;
+use crate::state::WorkspaceSettings;
+
+/// A mouse button, from 1 (left) to 5.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(try_from = "u8")]
+pub struct Button(u8);
 
-pub static DRAG_BUTTON: x::ButtonIndex = x::ButtonIndex::N1; // Left Mouse Button
-pub static DRAG_BUTTON_MASK: x::KeyButMask = x::KeyButMask::BUTTON1;
+impl TryFrom<u8> for Button {
+    type Error = String;
 
-pub static SELECT_BUTTON: x::ButtonIndex = x::ButtonIndex::N1; // Left Mouse Button
+    fn try_from(number: u8) -> Result<Self, Self::Error> {
+        if (1..=5).contains(&number) {
+            Ok(Self(number))
+        } else {
+            Err(format!("Invalid mouse button {}, expected 1 to 5", number))
+        }
+    }
+}
 
-pub static RESIZE_BUTTON: x::ButtonIndex = x::ButtonIndex::N3; // Right Mouse Button
-pub static RESIZE_BUTTON_MASK: x::KeyButMask = x::KeyButMask::BUTTON3;
+impl Button {
+    pub fn number(self) -> u8 {
+        self.0
+    }
+
+    pub fn index(self) -> x::ButtonIndex {
+        match self.0 {
+            1 => x::ButtonIndex::N1,
+            2 => x::ButtonIndex::N2,
+            3 => x::ButtonIndex::N3,
+            4 => x::ButtonIndex::N4,
+            _ => x::ButtonIndex::N5,
+        }
+    }
 
+    /// Get the mask of the button in the state of pointer events.
+    pub fn mask(self) -> x::KeyButMask {
+        x::KeyButMask::from_bits_truncate(x::KeyButMask::BUTTON1.bits() << (self.0 - 1))
+    }
+}
+
+/// How the focus follows the pointer.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum FocusModel {
+    /// Focus a window when it is clicked.
+    Click,
+    /// Focus a window when the pointer enters it.
+    /// The focus is kept when the pointer leaves to the root window.
+    Sloppy,
+    /// Focus a window when the pointer enters it.
+    /// The focus is dropped when the pointer leaves to the root window.
+    Strict,
+}
+
+/// Visual settings switched together, by name.
+///
+/// The settings a theme leaves out keep their current values.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    pub border_width: Option<u32>,
+    pub border_color: Option<Color>,
+    pub focused_border_color: Option<Color>,
+    pub outer_border_width: Option<u32>,
+    pub outer_border_color: Option<Color>,
+    pub corner_radius: Option<u32>,
+}
+
+/// The settings of the window manager.
+///
+/// They are read from the config file, where every setting is optional,
+/// and can be changed at runtime by the clients.
+#[derive(Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct Config {
     pub border_width: u32,
-    pub border_color: u32,
-    pub focused_border_color: u32,
+    pub border_color: Color,
+    pub focused_border_color: Color,
+    /// The width of the outer part of the border, drawn with its own color
+    /// around the inner part, which has the border colors. 0 for a single border.
+    pub outer_border_width: u32,
+    pub outer_border_color: Color,
+    /// The radius in pixels of the rounded corners of the windows, 0 for square corners.
+    /// The corners are cut with the Shape extension, the windows shaped by
+    /// their application keep their own shape.
+    pub corner_radius: u32,
+    /// The themes which can be switched to at runtime.
+    pub themes: HashMap<String, Theme>,
+    /// The theme applied over the settings above when the config is loaded.
+    pub theme: Option<String>,
+    pub focus_model: FocusModel,
+    /// Warp the pointer to the center of the focused window
+    /// when navigating with the keyboard.
+    pub warp_pointer: bool,
+    /// Snap windows dragged to the edges of the work area.
+    pub edge_snap: bool,
+    /// The distance in pixels from an edge at which a dragged window is snapped.
+    pub edge_snap_threshold: u32,
+    /// Wrap around when cycling past the last workspace or window.
+    pub wrap: bool,
+    /// Wrap around to the opposite edge when no window is in the direction of the focus.
+    pub directional_wrap: bool,
+    /// A shell command run before switching workspace.
+    pub pre_workspace_switch_hook: Option<String>,
+    /// A shell command run after switching workspace.
+    pub post_workspace_switch_hook: Option<String>,
+    /// The monitor where new windows are placed, unless a rule or their parent decides otherwise.
+    pub placement_monitor: MonitorSelector,
+    /// The rules applied to new windows.
+    pub rules: Vec<Rule>,
+    /// The modifier held to focus, move and resize windows with the mouse.
+    #[serde(deserialize_with = "deserialize_modifier")]
+    pub mod_key: x::ModMask,
+    /// The mouse button focusing a window.
+    pub select_button: Button,
+    /// The mouse button moving a window.
+    pub drag_button: Button,
+    /// The mouse button resizing a window.
+    pub resize_button: Button,
+    /// How long to wait for the next key of a chord, in milliseconds.
+    pub chord_timeout: u64,
+    /// The programs started with the window manager, after the autostart script.
+    pub autostart: Vec<AutostartProgram>,
+    /// The workspaces created at startup, in order.
+    /// When set, the workspaces of the previous session are not restored.
+    pub workspaces: Vec<WorkspaceSettings>,
 }
 
 impl Default for Config {
     fn default() -> Self {
         Self {
             border_width: 1,
-            border_color: 0xcccccc,
-            focused_border_color: 0x00ccff,
+            border_color: Color::Rgb(0xcc, 0xcc, 0xcc),
+            focused_border_color: Color::Rgb(0x00, 0xcc, 0xff),
+            outer_border_width: 0,
+            outer_border_color: Color::Rgb(0x22, 0x22, 0x22),
+            corner_radius: 0,
+            themes: HashMap::new(),
+            theme: None,
+            focus_model: FocusModel::Click,
+            warp_pointer: false,
+            edge_snap: true,
+            edge_snap_threshold: 8,
+            wrap: true,
+            directional_wrap: false,
+            pre_workspace_switch_hook: None,
+            post_workspace_switch_hook: None,
+            placement_monitor: MonitorSelector::Primary,
+            rules: Vec::new(),
+            mod_key: x::ModMask::N4,
+            select_button: Button(1),
+            drag_button: Button(1),
+            resize_button: Button(3),
+            chord_timeout: 1000,
+            autostart: Vec::new(),
+            workspaces: Vec::new(),
+        }
+    }
+}
+
+impl Config {
+    /// Read the config file, written in JSON.
+    ///
+    /// The default config is used if the file does not exist.
+    /// The file can list other files to include, see `read_config_file`.
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let value = read_config_file(path, &mut Vec::new())?;
+
+        let mut config: Self = serde_json::from_value(value)
+            .with_context(|| format!("Invalid config file {}", path.display()))?;
+        for rule in &config.rules {
+            rule.validate()
+                .map_err(anyhow::Error::msg)
+                .with_context(|| format!("Invalid config file {}", path.display()))?;
         }
+        if let Some(name) = config.theme.clone() {
+            config
+                .apply_theme(&name)
+                .with_context(|| format!("Invalid config file {}", path.display()))?;
+        }
+
+        Ok(config)
+    }
+
+    /// Apply the settings of a theme.
+    ///
+    /// Return an error if the theme is not found.
+    pub fn apply_theme(&mut self, name: &str) -> Result<()> {
+        let theme = self
+            .themes
+            .get(name)
+            .cloned()
+            .ok_or_else(|| anyhow!("Theme {} not found", name))?;
+
+        if let Some(width) = theme.border_width {
+            self.border_width = width;
+        }
+        if let Some(color) = theme.border_color {
+            self.border_color = color;
+        }
+        if let Some(color) = theme.focused_border_color {
+            self.focused_border_color = color;
+        }
+        if let Some(width) = theme.outer_border_width {
+            self.outer_border_width = width;
+        }
+        if let Some(color) = theme.outer_border_color {
+            self.outer_border_color = color;
+        }
+        if let Some(radius) = theme.corner_radius {
+            self.corner_radius = radius;
+        }
+        self.theme = Some(name.to_owned());
+
+        Ok(())
+    }
+
+    /// Get the modifier held to use the mouse, as found in the state of pointer events.
+    pub fn mod_key_mask(&self) -> x::KeyButMask {
+        x::KeyButMask::from_bits_truncate(self.mod_key.bits())
+    }
+}
+
+/// Read a config file and the files it includes, merged into a single JSON object.
+///
+/// The "include" setting lists the files to read before the including one,
+/// relative to its directory. The settings of a file override the ones of the files
+/// it includes, except for lists such as the rules, which are concatenated.
+///
+/// `including` holds the files being read, to detect include cycles.
+fn read_config_file(path: &Path, including: &mut Vec<PathBuf>) -> Result<Value> {
+    let data =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let mut value: Value = serde_json::from_str(&data)
+        .with_context(|| format!("Invalid config file {}", path.display()))?;
+    let Some(object) = value.as_object_mut() else {
+        return Err(anyhow!(
+            "Invalid config file {}, expected an object",
+            path.display()
+        ));
+    };
+    let includes: Vec<String> = match object.remove("include") {
+        Some(includes) => serde_json::from_value(includes)
+            .with_context(|| format!("Invalid include in {}", path.display()))?,
+        None => Vec::new(),
+    };
+
+    including.push(path.canonicalize()?);
+    let mut merged = Value::Object(Default::default());
+    for include in includes {
+        let include_path = path
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(expanduser::expanduser(&include)?);
+        if including.contains(&include_path.canonicalize().unwrap_or_default()) {
+            return Err(anyhow!("{} includes itself", include_path.display()));
+        }
+        let included = read_config_file(&include_path, including)
+            .with_context(|| format!("Failed to include {} from {}", include, path.display()))?;
+        merge(&mut merged, included);
+    }
+    including.pop();
+    merge(&mut merged, value);
+
+    Ok(merged)
+}
+
+/// Merge the settings of a config object into another one.
+/// Lists are concatenated, other settings are replaced.
+fn merge(base: &mut Value, overlay: Value) {
+    let (Value::Object(base), Value::Object(overlay)) = (base, overlay) else {
+        return;
+    };
+
+    for (key, value) in overlay {
+        match (base.get_mut(&key), value) {
+            (Some(Value::Array(items)), Value::Array(more_items)) => items.extend(more_items),
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn deserialize_modifier<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<x::ModMask, D::Error> {
+    let name = String::deserialize(deserializer)?;
+
+    keys::parse_modifier(&name)
+        .ok_or_else(|| de::Error::custom(format!("Unknown modifier: {}", name)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_load() {
+        let path =
+            std::env::temp_dir().join(format!("toniowm-config-test-{}.json", std::process::id()));
+        assert_eq!(x::ModMask::N4, Config::load(&path).unwrap().mod_key);
+
+        fs::write(
+            &path,
+            r##"{ "border_width": 3, "border_color": "#ff8800", "mod_key": "alt", "resize_button": 2 }"##,
+        )
+        .unwrap();
+        let config = Config::load(&path).unwrap();
+
+        assert_eq!(3, config.border_width);
+        assert_eq!(Color::Rgb(0xff, 0x88, 0x00), config.border_color);
+        assert_eq!(x::ModMask::N1, config.mod_key);
+        assert_eq!(x::KeyButMask::MOD1, config.mod_key_mask());
+        assert_eq!(Button(1), config.drag_button);
+        assert_eq!(x::KeyButMask::BUTTON2, config.resize_button.mask());
+
+        fs::write(&path, r#"{ "resize_button": 6 }"#).unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(&path, r##"{ "border_color": "#ff88" }"##).unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(&path, r#"{ "unknown": true }"#).unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(
+            &path,
+            r#"{ "workspaces": [{ "name": "web" }, { "name": "chat", "monitor": "HDMI-1" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            vec![
+                WorkspaceSettings {
+                    name: "web".to_owned(),
+                    monitor: String::new(),
+                },
+                WorkspaceSettings {
+                    name: "chat".to_owned(),
+                    monitor: "HDMI-1".to_owned(),
+                }
+            ],
+            Config::load(&path).unwrap().workspaces
+        );
+        fs::write(&path, r#"{ "rules": [{ "workspace": "web" }] }"#).unwrap();
+        assert!(Config::load(&path).is_err());
+        fs::write(
+            &path,
+            r#"{ "rules": [{ "type": "dialog", "workspace": "web" }] }"#,
+        )
+        .unwrap();
+        assert_eq!(
+            Some("dialog"),
+            Config::load(&path).unwrap().rules[0].window_type.as_deref()
+        );
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_apply_theme() {
+        let mut config: Config = serde_json::from_str(
+            r##"{
+                "border_width": 3,
+                "themes": {
+                    "dark": { "border_color": "#222222", "corner_radius": 6 },
+                    "light": { "border_color": "#eeeeee", "border_width": 1 }
+                },
+                "theme": "dark"
+            }"##,
+        )
+        .unwrap();
+
+        config.apply_theme("light").unwrap();
+        assert_eq!(1, config.border_width);
+        assert_eq!(Color::Rgb(0xee, 0xee, 0xee), config.border_color);
+        config.apply_theme("dark").unwrap();
+        assert_eq!(1, config.border_width);
+        assert_eq!(Color::Rgb(0x22, 0x22, 0x22), config.border_color);
+        assert_eq!(6, config.corner_radius);
+        assert_eq!(Some("dark"), config.theme.as_deref());
+
+        assert!(config.apply_theme("solarized").is_err());
+        assert_eq!(Some("dark"), config.theme.as_deref());
+    }
+
+    #[test]
+    fn test_load_includes() {
+        let dir = std::env::temp_dir().join(format!("toniowm-include-test-{}", std::process::id()));
+        fs::create_dir_all(dir.join("shared")).unwrap();
+        fs::write(
+            dir.join("config.json"),
+            r#"{ "include": ["shared/theme.json"], "border_width": 3,
+                 "rules": [{ "class": "Emacs", "workspace": "code" }] }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("shared/theme.json"),
+            r#"{ "border_width": 2, "edge_snap_threshold": 20, "include": ["rules.json"] }"#,
+        )
+        .unwrap();
+        fs::write(
+            dir.join("shared/rules.json"),
+            r#"{ "rules": [{ "class": "firefox", "workspace": "web" }] }"#,
+        )
+        .unwrap();
+
+        let config = Config::load(&dir.join("config.json")).unwrap();
+        assert_eq!(3, config.border_width);
+        assert_eq!(20, config.edge_snap_threshold);
+        assert_eq!(
+            vec![Some("firefox"), Some("Emacs")],
+            config
+                .rules
+                .iter()
+                .map(|rule| rule.class.as_deref())
+                .collect::<Vec<_>>()
+        );
+
+        fs::write(
+            dir.join("shared/rules.json"),
+            r#"{ "include": ["theme.json"] }"#,
+        )
+        .unwrap();
+        assert!(Config::load(&dir.join("config.json")).is_err());
+        fs::remove_file(dir.join("shared/rules.json")).unwrap();
+        assert!(Config::load(&dir.join("config.json")).is_err());
+
+        fs::remove_dir_all(dir).unwrap();
     }
 }
@@ -0,0 +1,87 @@
+//! Rounded window corners, cut out of the windows with the Shape extension.
+
+use xcb::x;
+
+use crate::vector::Vector2D;
+
+/// Get the rectangles covering a window with rounded corners, to set as its bounding shape.
+///
+/// The window includes its border, and the rectangles are relative to its inside,
+/// so the border starts at `-border_width`. There is a rectangle per row cut by the
+/// top corners, one for the rows in between, and one per row cut by the bottom corners.
+pub fn rounded_rectangles(size: Vector2D, border_width: u32, radius: u32) -> Vec<x::Rectangle> {
+    let border_width = border_width as i32;
+    let (width, height) = (size.x + 2 * border_width, size.y + 2 * border_width);
+    let radius = (radius as i32).min(width / 2).min(height / 2);
+
+    // How much each row of the top corners is cut, from the topmost row,
+    // measured at the middle of the row
+    let insets: Vec<i32> = (0..radius)
+        .map(|row| {
+            let dy = (radius - row) as f64 - 0.5;
+            let dx = ((radius * radius) as f64 - dy * dy).sqrt();
+            (radius as f64 - dx).round() as i32
+        })
+        .take_while(|inset| *inset > 0)
+        .collect();
+
+    let rectangle = |inset: i32, y: i32, rows: i32| x::Rectangle {
+        x: (inset - border_width) as i16,
+        y: (y - border_width) as i16,
+        width: (width - 2 * inset) as u16,
+        height: rows as u16,
+    };
+    let rows = insets.len() as i32;
+    let mut rectangles: Vec<_> = insets
+        .iter()
+        .enumerate()
+        .map(|(row, inset)| rectangle(*inset, row as i32, 1))
+        .collect();
+    rectangles.push(rectangle(0, rows, height - 2 * rows));
+    rectangles.extend(
+        insets
+            .iter()
+            .enumerate()
+            .rev()
+            .map(|(row, inset)| rectangle(*inset, height - 1 - row as i32, 1)),
+    );
+
+    rectangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rounded(size: Vector2D, border_width: u32, radius: u32) -> Vec<(i16, i16, u16, u16)> {
+        rounded_rectangles(size, border_width, radius)
+            .iter()
+            .map(|r| (r.x, r.y, r.width, r.height))
+            .collect()
+    }
+
+    #[test]
+    fn test_rounded_rectangles() {
+        assert_eq!(
+            vec![(-2, -2, 104, 54)],
+            rounded(Vector2D::new(100, 50), 2, 0)
+        );
+        assert_eq!(
+            vec![
+                (3, 0, 94, 1),
+                (1, 1, 98, 1),
+                (1, 2, 98, 1),
+                (0, 3, 100, 44),
+                (1, 47, 98, 1),
+                (1, 48, 98, 1),
+                (3, 49, 94, 1),
+            ],
+            rounded(Vector2D::new(100, 50), 0, 5)
+        );
+        // The radius is at most half the smallest side
+        assert_eq!(
+            rounded(Vector2D::new(10, 6), 1, 4),
+            rounded(Vector2D::new(10, 6), 1, 100)
+        );
+    }
+}
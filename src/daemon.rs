@@ -0,0 +1,130 @@
+//! Runs the window manager in the background, with a single instance per socket.
+
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, Read, Seek, Write},
+    os::fd::AsRawFd,
+    path::{Path, PathBuf},
+    process,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+/// A locked file holding the process id of the running window manager.
+///
+/// The lock is released when the process exits, even if it crashes,
+/// so a stale file does not prevent starting again.
+#[derive(Debug)]
+pub struct PidFile {
+    file: File,
+    path: PathBuf,
+}
+
+impl PidFile {
+    /// Lock the pid file and write the id of the current process in it.
+    ///
+    /// Return an error if another instance holds the lock.
+    pub fn lock(path: &Path) -> Result<Self> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open {}", path.display()))?;
+
+        if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) } == -1 {
+            let err = io::Error::last_os_error();
+            if err.kind() != io::ErrorKind::WouldBlock {
+                return Err(err).with_context(|| format!("Failed to lock {}", path.display()));
+            }
+            let mut pid = String::new();
+            file.read_to_string(&mut pid)?;
+            return Err(anyhow!(
+                "toniowm is already running with pid {}",
+                pid.trim()
+            ));
+        }
+
+        let mut pid_file = Self {
+            file,
+            path: path.to_owned(),
+        };
+        pid_file.write_pid()?;
+
+        Ok(pid_file)
+    }
+
+    /// Write the id of the current process, e.g. after daemonizing.
+    pub fn write_pid(&mut self) -> io::Result<()> {
+        self.file.set_len(0)?;
+        self.file.rewind()?;
+        writeln!(self.file, "{}", process::id())
+    }
+}
+
+impl Drop for PidFile {
+    fn drop(&mut self) {
+        fs::remove_file(&self.path).unwrap_or_default();
+    }
+}
+
+/// Get the path of the pid file of the window manager listening on a socket.
+pub fn pid_file_path(socket_path: &Path) -> PathBuf {
+    socket_path.with_extension("pid")
+}
+
+/// Keep running in the background, detached from the terminal.
+///
+/// The parent process exits, so this must be called before spawning any thread.
+/// The standard input is closed, the output is kept for the logs.
+pub fn daemonize() -> io::Result<()> {
+    match unsafe { libc::fork() } {
+        -1 => return Err(io::Error::last_os_error()),
+        0 => {}
+        _ => process::exit(0),
+    }
+    if unsafe { libc::setsid() } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    let null = File::open("/dev/null")?;
+    if unsafe { libc::dup2(null.as_raw_fd(), libc::STDIN_FILENO) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pid_file() {
+        let path = std::env::temp_dir().join(format!("toniowm-test-{}.pid", process::id()));
+
+        let pid_file = PidFile::lock(&path).unwrap();
+        assert_eq!(
+            format!("{}\n", process::id()),
+            fs::read_to_string(&path).unwrap()
+        );
+        let err = PidFile::lock(&path).unwrap_err();
+        assert_eq!(
+            format!("toniowm is already running with pid {}", process::id()),
+            err.to_string()
+        );
+
+        drop(pid_file);
+        assert!(!path.exists());
+        drop(PidFile::lock(&path).unwrap());
+    }
+
+    #[test]
+    fn test_pid_file_path() {
+        assert_eq!(
+            PathBuf::from("/tmp/toniowm-:1.pid"),
+            pid_file_path(Path::new("/tmp/toniowm-:1.socket"))
+        );
+    }
+}
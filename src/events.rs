@@ -0,0 +1,150 @@
+//! Events streamed to the clients subscribed to the window manager.
+
+use crossbeam::channel;
+use serde::{Deserialize, Serialize};
+
+/// The kinds of events a client can subscribe to.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum EventType {
+    WindowMapped,
+    WindowClosed,
+    FocusChanged,
+    TitleChanged,
+    WorkspaceSwitched,
+    LayoutChanged,
+    ModeChanged,
+}
+
+/// An event, serialized as a JSON object tagged by its kind.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    /// A window has been mapped and is now managed.
+    WindowMapped { window: u32 },
+    /// A managed window has been destroyed or withdrawn.
+    WindowClosed { window: u32 },
+    /// The focused window changed, or nothing is focused anymore.
+    FocusChanged {
+        window: Option<u32>,
+        title: Option<String>,
+    },
+    /// The title of a managed window changed.
+    TitleChanged { window: u32, title: String },
+    /// The active workspace changed.
+    WorkspaceSwitched { index: usize, name: String },
+    /// The workspaces were added, renamed or moved across monitors.
+    LayoutChanged,
+    /// The binding mode changed.
+    ModeChanged { mode: String },
+}
+
+impl Event {
+    pub fn event_type(&self) -> EventType {
+        match self {
+            Event::WindowMapped { .. } => EventType::WindowMapped,
+            Event::WindowClosed { .. } => EventType::WindowClosed,
+            Event::FocusChanged { .. } => EventType::FocusChanged,
+            Event::TitleChanged { .. } => EventType::TitleChanged,
+            Event::WorkspaceSwitched { .. } => EventType::WorkspaceSwitched,
+            Event::LayoutChanged => EventType::LayoutChanged,
+            Event::ModeChanged { .. } => EventType::ModeChanged,
+        }
+    }
+}
+
+/// A client waiting for events.
+struct Subscriber {
+    /// The kinds of events the client is interested in, or all if empty.
+    events: Vec<EventType>,
+    sender: channel::Sender<Event>,
+}
+
+/// The clients subscribed to the events.
+#[derive(Default)]
+pub struct Subscribers {
+    subscribers: Vec<Subscriber>,
+}
+
+impl Subscribers {
+    /// Subscribe a client to some kinds of events, or to all of them if none is given.
+    pub fn subscribe(&mut self, events: Vec<EventType>, sender: channel::Sender<Event>) {
+        self.subscribers.push(Subscriber { events, sender });
+    }
+
+    /// Send an event to the interested subscribers.
+    ///
+    /// Subscribers which disconnected are dropped.
+    pub fn publish(&mut self, event: Event) {
+        let event_type = event.event_type();
+
+        self.subscribers.retain(|subscriber| {
+            if !subscriber.events.is_empty() && !subscriber.events.contains(&event_type) {
+                return true;
+            }
+
+            subscriber.sender.send(event.clone()).is_ok()
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_publish() {
+        let mut subscribers = Subscribers::default();
+        let (all_sender, all_receiver) = channel::unbounded();
+        let (focus_sender, focus_receiver) = channel::unbounded();
+        let (gone_sender, gone_receiver) = channel::unbounded();
+        subscribers.subscribe(Vec::new(), all_sender);
+        subscribers.subscribe(vec![EventType::FocusChanged], focus_sender);
+        subscribers.subscribe(Vec::new(), gone_sender);
+        drop(gone_receiver);
+
+        subscribers.publish(Event::WindowMapped { window: 1 });
+        subscribers.publish(Event::FocusChanged {
+            window: Some(1),
+            title: None,
+        });
+
+        assert_eq!(
+            vec![
+                Event::WindowMapped { window: 1 },
+                Event::FocusChanged {
+                    window: Some(1),
+                    title: None,
+                }
+            ],
+            all_receiver.try_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(
+            vec![Event::FocusChanged {
+                window: Some(1),
+                title: None,
+            }],
+            focus_receiver.try_iter().collect::<Vec<_>>()
+        );
+        assert_eq!(2, subscribers.subscribers.len());
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        assert_eq!(
+            r#"{"event":"focus_changed","window":null,"title":null}"#,
+            serde_json::to_string(&Event::FocusChanged {
+                window: None,
+                title: None
+            })
+            .unwrap()
+        );
+        assert_eq!(
+            r#"{"event":"title_changed","window":1,"title":"Terminal"}"#,
+            serde_json::to_string(&Event::TitleChanged {
+                window: 1,
+                title: "Terminal".to_owned()
+            })
+            .unwrap()
+        );
+    }
+}
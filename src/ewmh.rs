@@ -3,28 +3,96 @@
 use xcb::x;
 
 use crate::atoms::Atoms;
+use crate::xconn::XConn;
 
 pub fn get_wm_window_type(
-    conn: &xcb::Connection,
+    conn: &impl XConn,
     atoms: &Atoms,
     window: x::Window,
 ) -> xcb::Result<Vec<x::Atom>> {
-    let cookie = conn.send_request(&x::GetProperty {
-        window,
-        delete: false,
-        property: atoms.net_wm_window_type,
-        r#type: x::ATOM_ATOM,
-        long_offset: 0,
-        long_length: 1024,
-    });
-    let reply = conn.wait_for_reply(cookie)?;
+    let property = conn.get_property(window, atoms.net_wm_window_type, x::ATOM_ATOM, 1024)?;
+
+    Ok(property.resources())
+}
+
+/// Get the name of a window type from the name of its atom,
+/// lowercase and without the _NET_WM_WINDOW_TYPE_ prefix, e.g. dialog.
+pub fn window_type_name(atom_name: &str) -> String {
+    atom_name
+        .strip_prefix("_NET_WM_WINDOW_TYPE_")
+        .unwrap_or(atom_name)
+        .to_lowercase()
+}
+
+/// Get the _NET_WM_NAME property from a window.
+///
+/// This is the UTF-8 title of the window, preferred over WM_NAME.
+/// Return None if the property is not set.
+pub fn get_wm_name(
+    conn: &impl XConn,
+    atoms: &Atoms,
+    window: x::Window,
+) -> xcb::Result<Option<String>> {
+    let property = conn.get_property(window, atoms.net_wm_name, atoms.utf8_string, 1024)?;
+    if property.r#type == x::ATOM_NONE {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8_lossy(property.bytes()).into_owned()))
+}
 
-    Ok(reply.value().into())
+/// Get the _NET_WM_PID property from a window.
+///
+/// This is the process id of the client owning the window.
+/// Return None if the property is not set.
+pub fn get_wm_pid(conn: &impl XConn, atoms: &Atoms, window: x::Window) -> xcb::Result<Option<u32>> {
+    let property = conn.get_property(window, atoms.net_wm_pid, x::ATOM_CARDINAL, 1)?;
+
+    Ok(property.cardinals().first().copied())
+}
+
+/// Get the space reserved by a window at the left, right, top and bottom edges of the screen.
+///
+/// _NET_WM_STRUT_PARTIAL is preferred over _NET_WM_STRUT,
+/// as the first four values of both properties have the same meaning.
+/// Return None if neither property is set.
+pub fn get_wm_strut(
+    conn: &impl XConn,
+    atoms: &Atoms,
+    window: x::Window,
+) -> xcb::Result<Option<[u32; 4]>> {
+    for property in [atoms.net_wm_strut_partial, atoms.net_wm_strut] {
+        let property = conn.get_property(window, property, x::ATOM_CARDINAL, 4)?;
+
+        if let [left, right, top, bottom] = property.cardinals() {
+            return Ok(Some([*left, *right, *top, *bottom]));
+        }
+    }
+
+    Ok(None)
+}
+
+/// Get the width of the invisible shadow at the left, right, top and bottom edges
+/// of a window drawing its own decorations, as GTK does.
+///
+/// This is the _GTK_FRAME_EXTENTS property, which is not part of EWMH.
+/// Return None if the property is not set.
+pub fn get_gtk_frame_extents(
+    conn: &impl XConn,
+    atoms: &Atoms,
+    window: x::Window,
+) -> xcb::Result<Option<[u32; 4]>> {
+    let property = conn.get_property(window, atoms.gtk_frame_extents, x::ATOM_CARDINAL, 4)?;
+
+    match property.cardinals() {
+        [left, right, top, bottom] => Ok(Some([*left, *right, *top, *bottom])),
+        _ => Ok(None),
+    }
 }
 
 // Set the _NET_SUPPORTED property on the root window.
 // This is needed to indicate which hints are supported by the window manager.
-pub fn set_supported(conn: &xcb::Connection, atoms: &Atoms, root: x::Window) {
+pub fn set_supported(conn: &impl XConn, atoms: &Atoms, root: x::Window) {
     conn.send_request(&x::ChangeProperty {
         mode: x::PropMode::Replace,
         window: root,
@@ -36,15 +104,38 @@ pub fn set_supported(conn: &xcb::Connection, atoms: &Atoms, root: x::Window) {
             atoms.net_number_of_desktops,
             atoms.net_desktop_names,
             atoms.net_current_desktop,
+            atoms.net_client_list_stacking,
             atoms.net_wm_window_type,
+            atoms.net_wm_strut,
+            atoms.net_wm_strut_partial,
         ],
     });
 }
 
+/// Delete the properties set on the root window, when the window manager exits.
+/// This tells the clients that no compliant window manager is active anymore.
+pub fn delete_root_properties(conn: &impl XConn, atoms: &Atoms, root: x::Window) {
+    for property in [
+        atoms.net_supported,
+        atoms.net_supporting_wm_check,
+        atoms.net_active_window,
+        atoms.net_number_of_desktops,
+        atoms.net_desktop_names,
+        atoms.net_current_desktop,
+        atoms.net_client_list_stacking,
+        atoms.i3_socket_path,
+    ] {
+        conn.send_request(&x::DeleteProperty {
+            window: root,
+            property,
+        });
+    }
+}
+
 /// Set the _NET_SUPPORTING_WM_CHECK property on the root and child windows.
 /// This is needed to indicate that a compliant window manager is active.
 pub fn set_supporting_wm_check(
-    conn: &xcb::Connection,
+    conn: &impl XConn,
     atoms: &Atoms,
     root: x::Window,
     child: x::Window,
@@ -68,7 +159,7 @@ pub fn set_supporting_wm_check(
 
 /// Set the _NET_WM_NAME property on the child window.
 /// This is needed to indicate the name of the window manager.
-pub fn set_wm_name(conn: &xcb::Connection, atoms: &Atoms, child: x::Window, wm_name: &str) {
+pub fn set_wm_name(conn: &impl XConn, atoms: &Atoms, child: x::Window, wm_name: &str) {
     conn.send_request(&x::ChangeProperty {
         mode: x::PropMode::Replace,
         window: child,
@@ -80,23 +171,35 @@ pub fn set_wm_name(conn: &xcb::Connection, atoms: &Atoms, child: x::Window, wm_n
 
 /// Set the _NET_ACTIVE_WINDOW property on the root window.
 /// This is needed to indicate the currently active window.
-pub fn set_active_window(
-    conn: &xcb::Connection,
+pub fn set_active_window(conn: &impl XConn, atoms: &Atoms, root: x::Window, window: x::Window) {
+    conn.send_request(&x::ChangeProperty {
+        mode: x::PropMode::Replace,
+        window: root,
+        property: atoms.net_active_window,
+        r#type: x::ATOM_WINDOW,
+        data: &[window],
+    });
+}
+
+/// Set the _NET_CLIENT_LIST_STACKING property on the root window.
+/// This lists the managed windows from the bottommost to the topmost.
+pub fn set_client_list_stacking(
+    conn: &impl XConn,
     atoms: &Atoms,
     root: x::Window,
-    window: x::Window,
+    windows: &[x::Window],
 ) {
     conn.send_request(&x::ChangeProperty {
         mode: x::PropMode::Replace,
         window: root,
-        property: atoms.net_active_window,
+        property: atoms.net_client_list_stacking,
         r#type: x::ATOM_WINDOW,
-        data: &[window],
+        data: windows,
     });
 }
 /// Set the _NET_NUMBER_OF_DESKTOPS property on the root window.
 /// This is needed to indicate the number of desktops.
-pub fn set_number_of_desktops(conn: &xcb::Connection, atoms: &Atoms, root: x::Window, num: u32) {
+pub fn set_number_of_desktops(conn: &impl XConn, atoms: &Atoms, root: x::Window, num: u32) {
     conn.send_request(&x::ChangeProperty {
         mode: x::PropMode::Replace,
         window: root,
@@ -108,12 +211,7 @@ pub fn set_number_of_desktops(conn: &xcb::Connection, atoms: &Atoms, root: x::Wi
 
 /// Set the _NET_DESKTOP_NAMES property on the root window.
 /// This is needed to indicate the names of the desktops.
-pub fn set_desktop_names(
-    conn: &xcb::Connection,
-    atoms: &Atoms,
-    root: x::Window,
-    names: Vec<String>,
-) {
+pub fn set_desktop_names(conn: &impl XConn, atoms: &Atoms, root: x::Window, names: Vec<String>) {
     let mut data = names.join("\0").as_bytes().to_vec();
     data.push(b'\0');
 
@@ -128,7 +226,7 @@ pub fn set_desktop_names(
 
 /// Set the _NET_CURRENT_DESKTOP property on the root window.
 /// This is needed to indicate the currently active desktop.
-pub fn set_current_desktop(conn: &xcb::Connection, atoms: &Atoms, root: x::Window, num: u32) {
+pub fn set_current_desktop(conn: &impl XConn, atoms: &Atoms, root: x::Window, num: u32) {
     conn.send_request(&x::ChangeProperty {
         mode: x::PropMode::Replace,
         window: root,
@@ -137,3 +235,21 @@ pub fn set_current_desktop(conn: &xcb::Connection, atoms: &Atoms, root: x::Windo
         data: &[num],
     });
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_type_name() {
+        assert_eq!("dialog", window_type_name("_NET_WM_WINDOW_TYPE_DIALOG"));
+        assert_eq!(
+            "dropdown_menu",
+            window_type_name("_NET_WM_WINDOW_TYPE_DROPDOWN_MENU")
+        );
+        assert_eq!(
+            "_kde_net_wm_window_type_override",
+            window_type_name("_KDE_NET_WM_WINDOW_TYPE_OVERRIDE")
+        );
+    }
+}
@@ -0,0 +1,146 @@
+//! Output formats of the query results printed by the client.
+
+use serde_json::Value;
+
+/// How the payload of a query is printed.
+#[derive(Debug, Clone, PartialEq)]
+pub enum OutputFormat {
+    /// The payload as a JSON document.
+    Json,
+    /// One line per item, with the fields as space separated key=value pairs.
+    Plain,
+    /// One line per item, replacing each {field} of the template with its value.
+    /// Nested fields are joined by dots, e.g. {pos.x}.
+    Custom(String),
+}
+
+/// Format the payload of a query.
+///
+/// Lists are printed one item per line, unless the format is JSON.
+pub fn format_payload(payload: &Value, format: &OutputFormat) -> String {
+    let format_item = |item: &Value| match format {
+        OutputFormat::Json => item.to_string(),
+        OutputFormat::Plain => format_plain(item),
+        OutputFormat::Custom(template) => format_template(item, template),
+    };
+
+    match (payload, format) {
+        (Value::Array(items), OutputFormat::Plain | OutputFormat::Custom(_)) => {
+            items.iter().map(format_item).collect::<Vec<_>>().join("\n")
+        }
+        (payload, _) => format_item(payload),
+    }
+}
+
+fn format_plain(item: &Value) -> String {
+    match item {
+        Value::Object(_) => {
+            let mut fields = Vec::new();
+            flatten_fields(item, String::new(), &mut fields);
+            fields
+                .iter()
+                .map(|(key, value)| format!("{}={}", key, value))
+                .collect::<Vec<_>>()
+                .join(" ")
+        }
+        item => format_scalar(item),
+    }
+}
+
+fn format_template(item: &Value, template: &str) -> String {
+    let mut output = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        let Some(end) = rest[start..].find('}') else {
+            break;
+        };
+        let path = &rest[start + 1..start + end];
+        output.push_str(&rest[..start]);
+        output.push_str(&lookup(item, path).map(format_scalar).unwrap_or_default());
+        rest = &rest[start + end + 1..];
+    }
+    output.push_str(rest);
+
+    output
+}
+
+/// Get a field of an object by its dotted path.
+fn lookup<'a>(item: &'a Value, path: &str) -> Option<&'a Value> {
+    path.split('.').try_fold(item, |value, key| value.get(key))
+}
+
+/// Collect the scalar fields of an object, naming nested fields by their dotted path.
+fn flatten_fields(value: &Value, path: String, fields: &mut Vec<(String, String)>) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map {
+                let path = if path.is_empty() {
+                    key.clone()
+                } else {
+                    format!("{}.{}", path, key)
+                };
+                flatten_fields(value, path, fields);
+            }
+        }
+        value => fields.push((path, format_scalar(value))),
+    }
+}
+
+/// Format a value without quoting strings. Null is printed as an empty string.
+fn format_scalar(value: &Value) -> String {
+    match value {
+        Value::Null => String::new(),
+        Value::String(string) => string.clone(),
+        value => value.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use serde_json::json;
+
+    fn windows() -> Value {
+        json!([
+            {"id": 1, "title": "Terminal", "pos": {"x": 0, "y": 10}},
+            {"id": 2, "title": null, "pos": {"x": 5, "y": 20}},
+        ])
+    }
+
+    #[test]
+    fn test_format_payload_json() {
+        assert_eq!(
+            r#"{"id":1,"title":"Terminal"}"#,
+            format_payload(&json!({"id": 1, "title": "Terminal"}), &OutputFormat::Json)
+        );
+    }
+
+    #[test]
+    fn test_format_payload_plain() {
+        assert_eq!(
+            "id=1 title=Terminal pos.x=0 pos.y=10\nid=2 title= pos.x=5 pos.y=20",
+            format_payload(&windows(), &OutputFormat::Plain)
+        );
+        assert_eq!("", format_payload(&Value::Null, &OutputFormat::Plain));
+    }
+
+    #[test]
+    fn test_format_payload_custom() {
+        assert_eq!(
+            "1 Terminal (0)\n2  (5)",
+            format_payload(
+                &windows(),
+                &OutputFormat::Custom("{id} {title} ({pos.x})".to_owned())
+            )
+        );
+        assert_eq!(
+            " {unclosed",
+            format_payload(
+                &json!({"id": 1}),
+                &OutputFormat::Custom("{missing} {unclosed".to_owned())
+            )
+        );
+    }
+}
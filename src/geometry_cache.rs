@@ -0,0 +1,44 @@
+//! A small on-disk cache of the last geometry of each application class.
+//! This is used to reopen windows where the user left them.
+
+use std::{collections::HashMap, path::PathBuf};
+
+use anyhow::Result;
+
+use crate::json_file::JsonFile;
+use crate::vector::Vector2D;
+
+#[derive(Debug)]
+pub struct GeometryCache {
+    /// The last position and size of the windows, indexed by class.
+    geometries: HashMap<String, (Vector2D, Vector2D)>,
+    /// The cache file.
+    file: JsonFile<HashMap<String, (Vector2D, Vector2D)>>,
+}
+
+impl GeometryCache {
+    /// Load the cache from a file.
+    ///
+    /// If the file does not exist or cannot be parsed, the cache starts empty.
+    pub fn load(path: PathBuf) -> Self {
+        let file = JsonFile::new(path);
+        let geometries = file.load().unwrap_or_default();
+
+        Self { geometries, file }
+    }
+
+    /// Write the cache to its file.
+    pub fn save(&self) -> Result<()> {
+        self.file.save(&self.geometries)
+    }
+
+    /// Get the last position and size of a class.
+    pub fn get(&self, class: &str) -> Option<(Vector2D, Vector2D)> {
+        self.geometries.get(class).copied()
+    }
+
+    /// Remember the position and size of a class.
+    pub fn insert(&mut self, class: String, pos: Vector2D, size: Vector2D) {
+        self.geometries.insert(class, (pos, size));
+    }
+}
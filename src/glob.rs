@@ -0,0 +1,56 @@
+//! Shell-like patterns matching the class and title of the windows.
+
+/// Check whether a text matches a pattern, where `*` matches any sequence of characters
+/// and `?` matches any single character. The whole text must match.
+pub fn matches(pattern: &str, text: &str) -> bool {
+    let pattern: Vec<char> = pattern.chars().collect();
+    let text: Vec<char> = text.chars().collect();
+    let (mut p, mut t) = (0, 0);
+    // The position of the last star in the pattern, and of the text it was tried at
+    let mut backtrack = None;
+
+    while t < text.len() {
+        match pattern.get(p) {
+            Some('*') => {
+                backtrack = Some((p, t));
+                p += 1;
+            }
+            Some(c) if *c == '?' || *c == text[t] => {
+                p += 1;
+                t += 1;
+            }
+            // Let the last star match one more character
+            _ => match backtrack {
+                Some((star, start)) => {
+                    backtrack = Some((star, start + 1));
+                    p = star + 1;
+                    t = start + 1;
+                }
+                None => return false,
+            },
+        }
+    }
+
+    pattern[p..].iter().all(|c| *c == '*')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("firefox", "firefox"));
+        assert!(!matches("firefox", "Firefox"));
+        assert!(!matches("fire", "firefox"));
+        assert!(matches("fire*", "firefox"));
+        assert!(matches("*Mozilla Firefox", "Home - Mozilla Firefox"));
+        assert!(matches("*-*-*", "a-b-c"));
+        assert!(!matches("*-*-*", "a-b"));
+        assert!(matches("xterm?", "xterm2"));
+        assert!(!matches("xterm?", "xterm"));
+        assert!(matches("*", ""));
+        assert!(matches("", ""));
+        assert!(!matches("", "a"));
+    }
+}
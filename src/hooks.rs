@@ -0,0 +1,23 @@
+//! User commands run by the window manager when something happens,
+//! e.g. when the active workspace changes.
+
+use std::process;
+
+use crate::launcher;
+use crate::log::warning;
+
+/// Run a shell command with some variables in its environment.
+///
+/// The command runs in the background, so it cannot block the window manager.
+pub fn run_hook(command: &str, env: &[(&str, String)]) {
+    let result = launcher::spawn(
+        process::Command::new("sh")
+            .arg("-c")
+            .arg(command)
+            .envs(env.iter().cloned()),
+    );
+
+    if let Err(err) = result {
+        warning!("Failed to run hook `{}`: {}", command, err);
+    }
+}
@@ -0,0 +1,624 @@
+//! A subset of the i3 IPC protocol, served on a second socket,
+//! so that tools written for i3 (status bars, i3-msg scripts) work with toniowm.
+//!
+//! Only RUN_COMMAND, GET_WORKSPACES, SUBSCRIBE and GET_TREE are supported.
+//! See: https://i3wm.org/docs/ipc.html
+
+use std::{
+    io::{self, Read, Write},
+    os::unix::net::UnixStream,
+    path::{Path, PathBuf},
+};
+
+use anyhow::Result;
+use crossbeam::channel;
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+use xcb::x;
+
+use crate::atoms::Atoms;
+use crate::client::{self, Request, Response};
+use crate::commands::{
+    CardinalDirection, Command, CycleDirection, Query, WindowSelector, WorkspaceSelector,
+};
+use crate::events::{Event, EventType};
+use crate::state::{MonitorNode, WorkspaceInfo};
+use crate::vector::Vector2D;
+use crate::xconn::XConn;
+
+const MAGIC: &[u8] = b"i3-ipc";
+
+// Message types
+const RUN_COMMAND: u32 = 0;
+const GET_WORKSPACES: u32 = 1;
+const SUBSCRIBE: u32 = 2;
+const GET_TREE: u32 = 4;
+
+// Event types, with the highest bit set
+const WORKSPACE_EVENT: u32 = 0x8000_0000;
+const MODE_EVENT: u32 = 0x8000_0002;
+const WINDOW_EVENT: u32 = 0x8000_0003;
+
+/// Get the path of the i3 IPC socket, next to the socket of the window manager,
+/// e.g. /tmp/toniowm-i3.socket for /tmp/toniowm.socket.
+pub fn socket_path() -> PathBuf {
+    i3_socket_path(&client::socket_path())
+}
+
+fn i3_socket_path(socket: &Path) -> PathBuf {
+    let mut name = socket.file_stem().unwrap_or_default().to_os_string();
+    name.push("-i3");
+    if let Some(extension) = socket.extension() {
+        name.push(".");
+        name.push(extension);
+    }
+    socket.with_file_name(name)
+}
+
+/// Set the I3_SOCKET_PATH property on the root window,
+/// which i3 tools read when I3SOCK is not set in their environment.
+pub fn set_socket_path_property(conn: &impl XConn, atoms: &Atoms, root: x::Window, path: &Path) {
+    conn.send_request(&x::ChangeProperty {
+        mode: x::PropMode::Replace,
+        window: root,
+        property: atoms.i3_socket_path,
+        r#type: atoms.utf8_string,
+        data: path.to_string_lossy().as_bytes(),
+    });
+}
+
+/// Serve the i3 IPC on the given socket.
+///
+/// Return an error if the socket cannot be created.
+pub fn handle_i3_ipc(socket: &Path, client_sender: channel::Sender<Request>) -> Result<()> {
+    client::listen(socket, move |stream| {
+        handle_i3_client(stream, client_sender.clone())
+    })
+}
+
+fn handle_i3_client(mut stream: UnixStream, client_sender: channel::Sender<Request>) {
+    if !client::is_same_user(&stream) {
+        return;
+    }
+
+    loop {
+        let (message_type, payload) = match read_message(&mut stream) {
+            Ok(message) => message,
+            // The client disconnected
+            Err(_) => return,
+        };
+        let payload = String::from_utf8_lossy(&payload);
+
+        let reply = match message_type {
+            RUN_COMMAND => {
+                let Some(workspaces) =
+                    query::<Vec<WorkspaceInfo>>(&client_sender, Query::Workspaces)
+                else {
+                    return;
+                };
+
+                Value::Array(
+                    parse_commands(&payload, &workspaces)
+                        .into_iter()
+                        .map(|command| match command {
+                            Ok(command) => match send(&client_sender, command, None) {
+                                Some(Response::Ok(_)) => json!({ "success": true }),
+                                Some(Response::Error(error)) => {
+                                    json!({ "success": false, "error": error })
+                                }
+                                None => json!({ "success": false }),
+                            },
+                            Err(error) => json!({ "success": false, "error": error }),
+                        })
+                        .collect(),
+                )
+            }
+            GET_WORKSPACES | GET_TREE => {
+                let (Some(workspaces), Some(monitors)) = (
+                    query::<Vec<WorkspaceInfo>>(&client_sender, Query::Workspaces),
+                    query::<Vec<MonitorNode>>(&client_sender, Query::Tree),
+                ) else {
+                    return;
+                };
+
+                if message_type == GET_WORKSPACES {
+                    i3_workspaces(&workspaces, &monitors)
+                } else {
+                    i3_tree(&workspaces, &monitors)
+                }
+            }
+            SUBSCRIBE => {
+                subscribe(stream, &client_sender, &payload);
+                return;
+            }
+            _ => json!({ "success": false, "error": "Unsupported message type" }),
+        };
+
+        if write_message(&mut stream, message_type, &reply).is_err() {
+            return;
+        }
+    }
+}
+
+/// Stream the events the client subscribed to, until it disconnects.
+fn subscribe(mut stream: UnixStream, client_sender: &channel::Sender<Request>, payload: &str) {
+    let events = serde_json::from_str::<Vec<String>>(payload)
+        .unwrap_or_default()
+        .iter()
+        .flat_map(|name| match name.as_str() {
+            "workspace" => vec![EventType::WorkspaceSwitched, EventType::LayoutChanged],
+            "mode" => vec![EventType::ModeChanged],
+            "window" => vec![
+                EventType::WindowMapped,
+                EventType::WindowClosed,
+                EventType::FocusChanged,
+                EventType::TitleChanged,
+            ],
+            _ => vec![],
+        })
+        .collect::<Vec<_>>();
+
+    let (event_sender, event_receiver) = channel::unbounded();
+    // Subscribing to no event would subscribe to all of them
+    let subscribed = !events.is_empty();
+    let command = Command::Subscribe { events };
+    let success = subscribed
+        && matches!(
+            send(client_sender, command, Some(event_sender)),
+            Some(Response::Ok(_))
+        );
+    let reply = json!({ "success": success });
+    if write_message(&mut stream, SUBSCRIBE, &reply).is_err() {
+        return;
+    }
+
+    for event in event_receiver {
+        if let Some((event_type, payload)) = i3_event(&event) {
+            if write_message(&mut stream, event_type, &payload).is_err() {
+                return;
+            }
+        }
+    }
+}
+
+/// Send a command to the window manager and wait for its response.
+/// Return None if the window manager is gone.
+fn send(
+    client_sender: &channel::Sender<Request>,
+    command: Command,
+    event_sender: Option<channel::Sender<Event>>,
+) -> Option<Response> {
+    let (response_sender, response_receiver) = channel::bounded(1);
+    client_sender
+        .send(Request {
+            command,
+            response_sender,
+            event_sender,
+        })
+        .ok()?;
+
+    response_receiver.recv().ok()
+}
+
+/// Query the window manager.
+/// Return None if the window manager is gone or the query failed.
+fn query<T: DeserializeOwned>(client_sender: &channel::Sender<Request>, query: Query) -> Option<T> {
+    match send(client_sender, Command::Query { query }, None)? {
+        Response::Ok(value) => serde_json::from_value(value).ok(),
+        Response::Error(_) => None,
+    }
+}
+
+fn read_message(stream: &mut UnixStream) -> io::Result<(u32, Vec<u8>)> {
+    let mut header = [0; 14];
+    stream.read_exact(&mut header)?;
+    if &header[..6] != MAGIC {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "Invalid magic"));
+    }
+
+    let length = u32::from_ne_bytes(header[6..10].try_into().unwrap());
+    let message_type = u32::from_ne_bytes(header[10..14].try_into().unwrap());
+    let mut payload = vec![0; length as usize];
+    stream.read_exact(&mut payload)?;
+
+    Ok((message_type, payload))
+}
+
+fn write_message(stream: &mut UnixStream, message_type: u32, payload: &Value) -> io::Result<()> {
+    let payload = payload.to_string();
+    let mut message = Vec::with_capacity(14 + payload.len());
+    message.extend_from_slice(MAGIC);
+    message.extend_from_slice(&(payload.len() as u32).to_ne_bytes());
+    message.extend_from_slice(&message_type.to_ne_bytes());
+    message.extend_from_slice(payload.as_bytes());
+
+    stream.write_all(&message)
+}
+
+/// Parse the i3 commands of a RUN_COMMAND message, separated by ";" or ",".
+/// The workspaces are needed to find a workspace by its number.
+fn parse_commands(payload: &str, workspaces: &[WorkspaceInfo]) -> Vec<Result<Command, String>> {
+    payload
+        .split([';', ','])
+        .map(str::trim)
+        .filter(|command| !command.is_empty())
+        .map(|command| parse_command(command, workspaces))
+        .collect()
+}
+
+fn parse_command(command: &str, workspaces: &[WorkspaceInfo]) -> Result<Command, String> {
+    let words = command.split_whitespace().collect::<Vec<_>>();
+
+    match words.as_slice() {
+        ["workspace", "next"] => Ok(activate_workspace(WorkspaceSelector::Cycle(
+            CycleDirection::Next,
+        ))),
+        ["workspace", "prev"] => Ok(activate_workspace(WorkspaceSelector::Cycle(
+            CycleDirection::Prev,
+        ))),
+        ["workspace", "number", number] => {
+            let num = number
+                .parse::<i64>()
+                .map_err(|_| format!("Invalid workspace number: {}", number))?;
+            // Fall back to a workspace named after the number, as i3 would create it
+            let name = workspaces
+                .iter()
+                .find(|workspace| workspace_num(&workspace.name) == num)
+                .map_or_else(|| number.to_string(), |workspace| workspace.name.clone());
+
+            Ok(activate_workspace(WorkspaceSelector::Name(name)))
+        }
+        ["workspace", ..] => Ok(activate_workspace(WorkspaceSelector::Name(
+            words[1..].join(" "),
+        ))),
+        ["focus", direction] => {
+            let direction = match *direction {
+                "left" => CardinalDirection::West,
+                "right" => CardinalDirection::East,
+                "up" => CardinalDirection::North,
+                "down" => CardinalDirection::South,
+                _ => return Err(format!("Unsupported command: {}", command)),
+            };
+            Ok(Command::Focus {
+                selector: WindowSelector::Closest(direction),
+            })
+        }
+        ["kill"] => Ok(Command::Close {
+            selector: WindowSelector::Focused,
+        }),
+        ["exit"] => Ok(Command::Quit),
+        ["reload"] => Ok(Command::Reload),
+        ["mode", ..] if words.len() > 1 => Ok(Command::Mode {
+            name: words[1..].join(" ").trim_matches('"').to_owned(),
+        }),
+        _ => Err(format!("Unsupported command: {}", command)),
+    }
+}
+
+fn activate_workspace(selector: WorkspaceSelector) -> Command {
+    Command::ActivateWorkspace { selector }
+}
+
+/// Convert an event to an i3 event, if it has an equivalent.
+fn i3_event(event: &Event) -> Option<(u32, Value)> {
+    let window_event = |change: &str, window: u32, title: Option<&String>| {
+        let container = json!({ "id": window, "window": window, "name": title });
+        (
+            WINDOW_EVENT,
+            json!({ "change": change, "container": container }),
+        )
+    };
+
+    match event {
+        Event::WorkspaceSwitched { name, .. } => {
+            let current = json!({ "name": name, "num": workspace_num(name) });
+            Some((
+                WORKSPACE_EVENT,
+                json!({ "change": "focus", "current": current, "old": null }),
+            ))
+        }
+        Event::LayoutChanged => Some((WORKSPACE_EVENT, json!({ "change": "reload" }))),
+        Event::WindowMapped { window } => Some(window_event("new", *window, None)),
+        Event::WindowClosed { window } => Some(window_event("close", *window, None)),
+        Event::FocusChanged {
+            window: Some(window),
+            title,
+        } => Some(window_event("focus", *window, title.as_ref())),
+        Event::FocusChanged { window: None, .. } => None,
+        Event::TitleChanged { window, title } => Some(window_event("title", *window, Some(title))),
+        Event::ModeChanged { mode } => {
+            Some((MODE_EVENT, json!({ "change": mode, "pango_markup": false })))
+        }
+    }
+}
+
+/// i3 numbers the workspaces whose name starts with a number, others get -1.
+fn workspace_num(name: &str) -> i64 {
+    let digits = name
+        .chars()
+        .take_while(|c| c.is_ascii_digit())
+        .collect::<String>();
+
+    digits.parse().unwrap_or(-1)
+}
+
+fn rect(pos: Vector2D, size: Vector2D) -> Value {
+    json!({ "x": pos.x, "y": pos.y, "width": size.x, "height": size.y })
+}
+
+/// Build the reply to GET_WORKSPACES, in workspace order.
+fn i3_workspaces(workspaces: &[WorkspaceInfo], monitors: &[MonitorNode]) -> Value {
+    workspaces
+        .iter()
+        .filter_map(|workspace| {
+            let (monitor, node) = monitors.iter().find_map(|monitor| {
+                monitor
+                    .workspaces
+                    .iter()
+                    .find(|node| node.index == workspace.index)
+                    .map(|node| (monitor, node))
+            })?;
+
+            Some(json!({
+                "id": workspace.index + 1,
+                "num": workspace_num(&workspace.name),
+                "name": workspace.name,
+                "visible": node.visible,
+                "focused": workspace.active,
+                "urgent": workspace.urgent,
+                "output": monitor.name,
+                "rect": rect(monitor.pos, monitor.size),
+            }))
+        })
+        .collect()
+}
+
+/// Build the reply to GET_TREE.
+///
+/// The tree goes from the root to the outputs, their workspaces,
+/// and the clients, which are all floating.
+fn i3_tree(workspaces: &[WorkspaceInfo], monitors: &[MonitorNode]) -> Value {
+    let focused = workspaces
+        .iter()
+        .find(|workspace| workspace.active)
+        .and_then(|workspace| workspace.focused);
+    // Containers without a window get ids which cannot clash with window ids
+    let workspace_id = |index: usize| 0x8000_0000_u64 + index as u64;
+    let output_id = |index: usize| 0x9000_0000_u64 + index as u64;
+    let root_id = 0xa000_0000_u64;
+
+    let outputs = monitors
+        .iter()
+        .enumerate()
+        .map(|(monitor_index, monitor)| {
+            let monitor_rect = rect(monitor.pos, monitor.size);
+            let workspace_nodes = monitor
+                .workspaces
+                .iter()
+                .map(|workspace| {
+                    let floating_nodes = workspace
+                        .windows
+                        .iter()
+                        .map(|window| {
+                            let window_rect = rect(window.pos, window.size);
+                            let con = json!({
+                                "id": window.id,
+                                "type": "con",
+                                "window": window.id,
+                                "name": window.title,
+                                "rect": window_rect,
+                                "focused": focused == Some(window.id),
+                                "urgent": window.urgent,
+                                "window_properties": {
+                                    "class": window.class,
+                                    "title": window.title,
+                                },
+                                "nodes": [],
+                                "floating_nodes": [],
+                            });
+                            json!({
+                                "id": window.id,
+                                "type": "floating_con",
+                                "rect": window_rect,
+                                "nodes": [con],
+                                "floating_nodes": [],
+                            })
+                        })
+                        .collect::<Vec<_>>();
+
+                    json!({
+                        "id": workspace_id(workspace.index),
+                        "type": "workspace",
+                        "name": workspace.name,
+                        "num": workspace_num(&workspace.name),
+                        "rect": monitor_rect,
+                        "nodes": [],
+                        "floating_nodes": floating_nodes,
+                    })
+                })
+                .collect::<Vec<_>>();
+
+            json!({
+                "id": output_id(monitor_index),
+                "type": "output",
+                "name": monitor.name,
+                "rect": monitor_rect,
+                "nodes": [{
+                    "type": "con",
+                    "name": "content",
+                    "nodes": workspace_nodes,
+                    "floating_nodes": [],
+                }],
+                "floating_nodes": [],
+            })
+        })
+        .collect::<Vec<_>>();
+
+    json!({
+        "id": root_id,
+        "type": "root",
+        "name": "root",
+        "nodes": outputs,
+        "floating_nodes": [],
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use xcb::{x, XidNew};
+
+    use crate::state::State;
+
+    #[test]
+    fn test_i3_socket_path() {
+        assert_eq!(
+            PathBuf::from("/tmp/toniowm-i3.socket"),
+            i3_socket_path(Path::new("/tmp/toniowm.socket"))
+        );
+        assert_eq!(
+            PathBuf::from("/tmp/toniowm-:1-i3.socket"),
+            i3_socket_path(Path::new("/tmp/toniowm-:1.socket"))
+        );
+        assert_eq!(
+            PathBuf::from("/run/user/1000/wm-i3"),
+            i3_socket_path(Path::new("/run/user/1000/wm"))
+        );
+    }
+
+    fn state() -> State {
+        let mut state = State::default();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1920, 1080))
+            .unwrap();
+        state.add_workspace(Some("2: web".to_owned())).unwrap();
+        let window = unsafe { x::Window::new(7) };
+        state
+            .add_client_to_workspace(1, window, Vector2D::new(10, 20), Vector2D::new(300, 200))
+            .unwrap();
+        state.set_client_urgent(window, true).unwrap();
+
+        state
+    }
+
+    #[test]
+    fn test_parse_commands() {
+        let workspaces = state().workspaces_info();
+        let commands = parse_commands(
+            "workspace number 2; focus left, kill; resize grow; mode \"resize\"; workspace number 3",
+            &workspaces,
+        );
+
+        assert!(matches!(
+            commands[0],
+            Ok(Command::ActivateWorkspace {
+                selector: WorkspaceSelector::Name(ref name)
+            }) if name == "2: web"
+        ));
+        assert!(matches!(
+            commands[1],
+            Ok(Command::Focus {
+                selector: WindowSelector::Closest(CardinalDirection::West)
+            })
+        ));
+        assert!(matches!(
+            commands[2],
+            Ok(Command::Close {
+                selector: WindowSelector::Focused
+            })
+        ));
+        assert!(commands[3].is_err());
+        assert!(matches!(
+            commands[4],
+            Ok(Command::Mode { ref name }) if name == "resize"
+        ));
+        assert!(matches!(
+            commands[5],
+            Ok(Command::ActivateWorkspace {
+                selector: WorkspaceSelector::Name(ref name)
+            }) if name == "3"
+        ));
+    }
+
+    #[test]
+    fn test_workspaces() {
+        let state = state();
+        let workspaces = i3_workspaces(&state.workspaces_info(), &state.tree());
+
+        assert_eq!(
+            json!({
+                "id": 2,
+                "num": 2,
+                "name": "2: web",
+                "visible": false,
+                "focused": false,
+                "urgent": true,
+                "output": "default",
+                "rect": { "x": 0, "y": 0, "width": 1920, "height": 1080 },
+            }),
+            workspaces[1]
+        );
+        assert_eq!(json!(true), workspaces[0]["focused"]);
+    }
+
+    #[test]
+    fn test_tree() {
+        let state = state();
+        // The tree goes through the client socket as JSON
+        let monitors = serde_json::to_value(state.tree()).unwrap();
+        let monitors = serde_json::from_value::<Vec<MonitorNode>>(monitors).unwrap();
+        assert_eq!(state.tree(), monitors);
+        let tree = i3_tree(&state.workspaces_info(), &monitors);
+        let workspace = &tree["nodes"][0]["nodes"][0]["nodes"][1];
+
+        assert_eq!(json!("2: web"), workspace["name"]);
+        assert_eq!(
+            json!(7),
+            workspace["floating_nodes"][0]["nodes"][0]["window"]
+        );
+    }
+
+    #[test]
+    fn test_i3_event() {
+        assert_eq!(
+            Some((
+                WORKSPACE_EVENT,
+                json!({ "change": "focus", "current": { "name": "3", "num": 3 }, "old": null })
+            )),
+            i3_event(&Event::WorkspaceSwitched {
+                index: 2,
+                name: "3".to_owned()
+            })
+        );
+        assert_eq!(
+            Some((
+                MODE_EVENT,
+                json!({ "change": "resize", "pango_markup": false })
+            )),
+            i3_event(&Event::ModeChanged {
+                mode: "resize".to_owned()
+            })
+        );
+        assert_eq!(
+            Some((
+                WINDOW_EVENT,
+                json!({
+                    "change": "title",
+                    "container": { "id": 1, "window": 1, "name": "Terminal" }
+                })
+            )),
+            i3_event(&Event::TitleChanged {
+                window: 1,
+                title: "Terminal".to_owned()
+            })
+        );
+        assert_eq!(
+            None,
+            i3_event(&Event::FocusChanged {
+                window: None,
+                title: None
+            })
+        );
+    }
+}
@@ -2,33 +2,137 @@
 
 use xcb::{x, Xid};
 
-use crate::atoms::Atoms;
+use serde::Serialize;
+
+use crate::{atoms::Atoms, vector::Vector2D, xconn::XConn};
 
 /// Get the WM_PROTOCOLS property from a window.
 ///
 /// The WM_PROTOCOLS property (of type ATOM) is a list of atoms.
 /// Each atom identifies a communication protocol between the client and the window manager in which the client is willing to participate.
 pub fn get_wm_protocols(
-    conn: &xcb::Connection,
+    conn: &impl XConn,
     atoms: &Atoms,
     window: x::Window,
 ) -> xcb::Result<Vec<x::Atom>> {
-    let cookie = conn.send_request(&x::GetProperty {
-        delete: false,
-        window,
-        property: atoms.wm_protocols,
-        r#type: x::ATOM_ATOM,
-        long_offset: 0,
-        long_length: 124,
-    });
+    let property = conn.get_property(window, atoms.wm_protocols, x::ATOM_ATOM, 124)?;
+
+    Ok(property.resources())
+}
+
+/// Get the WM_CLASS property from a window.
+///
+/// The WM_CLASS property (of type STRING) contains two consecutive null-terminated strings:
+/// the instance name and the class name of the application.
+/// Return None if the property is not set.
+pub fn get_wm_class(conn: &impl XConn, window: x::Window) -> xcb::Result<Option<(String, String)>> {
+    let property = conn.get_property(window, x::ATOM_WM_CLASS, x::ATOM_STRING, 1024)?;
+    let mut parts = property
+        .bytes()
+        .split(|byte| *byte == b'\0')
+        .map(|part| String::from_utf8_lossy(part).into_owned());
+
+    match (parts.next(), parts.next()) {
+        (Some(instance), Some(class)) => Ok(Some((instance, class))),
+        _ => Ok(None),
+    }
+}
+
+/// Get the WM_NAME property from a window.
+///
+/// The WM_NAME property (of type STRING or COMPOUND_TEXT) contains the title of the window.
+/// Return None if the property is not set.
+pub fn get_wm_name(conn: &impl XConn, window: x::Window) -> xcb::Result<Option<String>> {
+    let property = conn.get_property(window, x::ATOM_WM_NAME, x::ATOM_ANY, 1024)?;
+    if property.r#type == x::ATOM_NONE {
+        return Ok(None);
+    }
+
+    Ok(Some(String::from_utf8_lossy(property.bytes()).into_owned()))
+}
+
+/// The state of a window which is hidden, e.g. minimized, in the WM_STATE property.
+pub const ICONIC_STATE: u32 = 3;
+
+/// Get the state from the WM_STATE property of a window.
+///
+/// The WM_STATE property (of type WM_STATE) is set by the window manager on the windows it manages.
+/// Its first field is the state: 0 for withdrawn, 1 for normal and 3 for iconic.
+/// Return None if the property is not set, e.g. if no window manager was running.
+pub fn get_wm_state(
+    conn: &impl XConn,
+    atoms: &Atoms,
+    window: x::Window,
+) -> xcb::Result<Option<u32>> {
+    let property = conn.get_property(window, atoms.wm_state, atoms.wm_state, 2)?;
+
+    Ok(property.cardinals().first().copied())
+}
+
+/// Get the WM_TRANSIENT_FOR property from a window.
+///
+/// The WM_TRANSIENT_FOR property (of type WINDOW) contains the window
+/// for which a transient window, e.g. a dialog, is opened.
+/// Return None if the property is not set.
+pub fn get_wm_transient_for(
+    conn: &impl XConn,
+    window: x::Window,
+) -> xcb::Result<Option<x::Window>> {
+    let property = conn.get_property(window, x::ATOM_WM_TRANSIENT_FOR, x::ATOM_WINDOW, 1)?;
+
+    Ok(property.resources().first().copied())
+}
+
+/// Get the urgency hint from the WM_HINTS property of a window.
+///
+/// The WM_HINTS property (of type WM_HINTS) starts with a flags field.
+/// The UrgencyHint flag signals that the contents of the window demand the user's attention.
+/// Return false if the property is not set.
+pub fn get_wm_hints_urgency(conn: &impl XConn, window: x::Window) -> xcb::Result<bool> {
+    const URGENCY_HINT: u32 = 1 << 8;
 
-    let reply = conn.wait_for_reply(cookie)?;
+    let property = conn.get_property(window, x::ATOM_WM_HINTS, x::ATOM_WM_HINTS, 9)?;
+    let flags = property.cardinals().first().copied().unwrap_or(0);
 
-    Ok(reply.value().to_vec())
+    Ok(flags & URGENCY_HINT != 0)
+}
+
+/// The size constraints of a window, from the WM_NORMAL_HINTS property.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Serialize)]
+pub struct SizeHints {
+    pub min_size: Option<Vector2D>,
+    pub max_size: Option<Vector2D>,
+}
+
+/// Get the minimum and maximum sizes from the WM_NORMAL_HINTS property of a window.
+///
+/// The WM_NORMAL_HINTS property (of type WM_SIZE_HINTS) starts with a flags field,
+/// followed by four obsolete fields, the minimum size and the maximum size.
+/// The PMinSize and PMaxSize flags tell which sizes are set.
+pub fn get_wm_normal_hints(conn: &impl XConn, window: x::Window) -> xcb::Result<SizeHints> {
+    const P_MIN_SIZE: u32 = 1 << 4;
+    const P_MAX_SIZE: u32 = 1 << 5;
+
+    let property = conn.get_property(window, x::ATOM_WM_NORMAL_HINTS, x::ATOM_WM_SIZE_HINTS, 18)?;
+    let value = property.cardinals();
+    if value.len() < 9 {
+        return Ok(SizeHints::default());
+    }
+    let flags = value[0];
+    // The sizes are signed 32 bit integers
+    let size = |flag: u32, width: u32, height: u32| {
+        let (width, height) = (width as i32, height as i32);
+        (flags & flag != 0 && width > 0 && height > 0).then(|| Vector2D::new(width, height))
+    };
+
+    Ok(SizeHints {
+        min_size: size(P_MIN_SIZE, value[5], value[6]),
+        max_size: size(P_MAX_SIZE, value[7], value[8]),
+    })
 }
 
 pub fn send_wm_delete_window(
-    conn: &xcb::Connection,
+    conn: &impl XConn,
     atoms: &Atoms,
     window: x::Window,
 ) -> xcb::Result<()> {
@@ -44,14 +148,101 @@ pub fn send_wm_delete_window(
         ]),
     );
 
-    let cookie = conn.send_request_checked(&x::SendEvent {
+    conn.send_and_check_request(&x::SendEvent {
         propagate: false,
         destination: x::SendEventDest::Window(window),
         event_mask: x::EventMask::NO_EVENT,
         event: &event,
+    })?;
+
+    Ok(())
+}
+
+/// Send a synthetic ConfigureNotify event to a window with its geometry.
+///
+/// The X server sends no ConfigureNotify when a window is moved without being resized,
+/// so the window manager tells the client its new position (ICCCM 4.1.5).
+pub fn send_configure_notify(
+    conn: &impl XConn,
+    window: x::Window,
+    pos: Vector2D,
+    size: Vector2D,
+    border_width: u32,
+) {
+    let event = x::ConfigureNotifyEvent::new(
+        window,
+        window,
+        x::Window::none(),
+        pos.x as i16,
+        pos.y as i16,
+        size.x as u16,
+        size.y as u16,
+        border_width as u16,
+        false,
+    );
+
+    conn.send_request(&x::SendEvent {
+        propagate: false,
+        destination: x::SendEventDest::Window(window),
+        event_mask: x::EventMask::STRUCTURE_NOTIFY,
+        event: &event,
     });
+}
 
-    conn.check_request(cookie)?;
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::xconn::{MockConn, MockRequest, PropertyValue};
+    use xcb::XidNew;
 
-    Ok(())
+    #[test]
+    fn test_send_configure_notify() {
+        let conn = MockConn::default();
+        let window = unsafe { x::Window::new(42) };
+
+        send_configure_notify(
+            &conn,
+            window,
+            Vector2D::new(10, 20),
+            Vector2D::new(300, 200),
+            2,
+        );
+
+        assert_eq!(
+            vec![MockRequest::SendConfigureNotify {
+                window,
+                event_mask: x::EventMask::STRUCTURE_NOTIFY,
+                pos: Vector2D::new(10, 20),
+                size: Vector2D::new(300, 200),
+                border_width: 2,
+            }],
+            conn.take_requests()
+        );
+    }
+
+    #[test]
+    fn test_get_wm_normal_hints() {
+        let mut conn = MockConn::default();
+        let window = unsafe { x::Window::new(42) };
+        conn.add_window(window, Vector2D::new(0, 0), Vector2D::new(300, 200));
+        assert_eq!(
+            SizeHints::default(),
+            get_wm_normal_hints(&conn, window).unwrap()
+        );
+
+        // Only the maximum size is set
+        conn.set_property(
+            window,
+            x::ATOM_WM_NORMAL_HINTS,
+            x::ATOM_WM_SIZE_HINTS,
+            PropertyValue::Cardinals(vec![1 << 5, 0, 0, 0, 0, 100, 50, 800, 600]),
+        );
+        assert_eq!(
+            SizeHints {
+                min_size: None,
+                max_size: Some(Vector2D::new(800, 600)),
+            },
+            get_wm_normal_hints(&conn, window).unwrap()
+        );
+    }
 }
@@ -0,0 +1,73 @@
+//! A value kept in a JSON file, e.g. a cache which survives a restart
+//! of the window manager.
+
+use std::{fs, marker::PhantomData, path::PathBuf};
+
+use anyhow::Result;
+use serde::{de::DeserializeOwned, Serialize};
+
+#[derive(Debug)]
+pub struct JsonFile<T> {
+    /// The path of the file.
+    path: PathBuf,
+    value: PhantomData<T>,
+}
+
+impl<T: Serialize + DeserializeOwned> JsonFile<T> {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            path,
+            value: PhantomData,
+        }
+    }
+
+    /// Read the value from the file.
+    ///
+    /// Return None if the file does not exist or cannot be parsed.
+    pub fn load(&self) -> Option<T> {
+        fs::read_to_string(&self.path)
+            .ok()
+            .and_then(|data| serde_json::from_str(&data).ok())
+    }
+
+    /// Write the value to the file, creating its directory if needed.
+    pub fn save(&self, value: &T) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        fs::write(&self.path, serde_json::to_string(value)?)?;
+
+        Ok(())
+    }
+
+    /// Remove the file, if it exists.
+    pub fn remove(&self) {
+        fs::remove_file(&self.path).unwrap_or_default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_save_and_load() {
+        let path = std::env::temp_dir()
+            .join(format!("toniowm-json-file-test-{}", std::process::id()))
+            .join("file.json");
+        let file = JsonFile::<Vec<String>>::new(path.clone());
+        assert_eq!(file.load(), None);
+
+        // The directory is created as needed
+        let value = vec!["web".to_owned(), "mail".to_owned()];
+        file.save(&value).unwrap();
+        assert_eq!(file.load(), Some(value));
+
+        fs::write(&path, "not json").unwrap();
+        assert_eq!(file.load(), None);
+
+        file.remove();
+        assert!(!path.exists());
+        fs::remove_dir(path.parent().unwrap()).unwrap();
+    }
+}
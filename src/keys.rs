@@ -0,0 +1,532 @@
+//! Key combinations such as "super+shift+Return",
+//! and the translation between keysyms and the keycodes of the keyboard.
+//!
+//! This module does not talk to the X server: the keyboard mapping is given to a `Keymap`,
+//! so that the translation can be tested without a keyboard.
+
+use std::fmt;
+
+use xcb::x;
+
+/// The keysym of Num_Lock, used to find the modifier it is mapped to.
+const NUM_LOCK: u32 = 0xff7f;
+
+/// The bits of the state of a key event holding the active group, when XKB is in use.
+const GROUP_SHIFT: u32 = 13;
+const GROUP_MASK: u32 = 0b11;
+
+/// The names of the keysyms which are not a single character,
+/// as found in X11/keysymdef.h and X11/XF86keysym.h.
+const KEYSYM_NAMES: &[(&str, u32)] = &[
+    ("space", 0x0020),
+    ("exclam", 0x0021),
+    ("quotedbl", 0x0022),
+    ("numbersign", 0x0023),
+    ("dollar", 0x0024),
+    ("percent", 0x0025),
+    ("ampersand", 0x0026),
+    ("apostrophe", 0x0027),
+    ("parenleft", 0x0028),
+    ("parenright", 0x0029),
+    ("asterisk", 0x002a),
+    ("plus", 0x002b),
+    ("comma", 0x002c),
+    ("minus", 0x002d),
+    ("period", 0x002e),
+    ("slash", 0x002f),
+    ("colon", 0x003a),
+    ("semicolon", 0x003b),
+    ("less", 0x003c),
+    ("equal", 0x003d),
+    ("greater", 0x003e),
+    ("question", 0x003f),
+    ("at", 0x0040),
+    ("bracketleft", 0x005b),
+    ("backslash", 0x005c),
+    ("bracketright", 0x005d),
+    ("asciicircum", 0x005e),
+    ("underscore", 0x005f),
+    ("grave", 0x0060),
+    ("braceleft", 0x007b),
+    ("bar", 0x007c),
+    ("braceright", 0x007d),
+    ("asciitilde", 0x007e),
+    ("BackSpace", 0xff08),
+    ("Tab", 0xff09),
+    ("Return", 0xff0d),
+    ("Pause", 0xff13),
+    ("Scroll_Lock", 0xff14),
+    ("Escape", 0xff1b),
+    ("Home", 0xff50),
+    ("Left", 0xff51),
+    ("Up", 0xff52),
+    ("Right", 0xff53),
+    ("Down", 0xff54),
+    ("Prior", 0xff55),
+    ("Page_Up", 0xff55),
+    ("Next", 0xff56),
+    ("Page_Down", 0xff56),
+    ("End", 0xff57),
+    ("Print", 0xff61),
+    ("Insert", 0xff63),
+    ("Menu", 0xff67),
+    ("Num_Lock", NUM_LOCK),
+    ("KP_Enter", 0xff8d),
+    ("KP_Add", 0xffab),
+    ("KP_Subtract", 0xffad),
+    ("KP_Multiply", 0xffaa),
+    ("KP_Divide", 0xffaf),
+    ("Shift_L", 0xffe1),
+    ("Shift_R", 0xffe2),
+    ("Control_L", 0xffe3),
+    ("Control_R", 0xffe4),
+    ("Caps_Lock", 0xffe5),
+    ("Meta_L", 0xffe7),
+    ("Meta_R", 0xffe8),
+    ("Alt_L", 0xffe9),
+    ("Alt_R", 0xffea),
+    ("Super_L", 0xffeb),
+    ("Super_R", 0xffec),
+    ("Hyper_L", 0xffed),
+    ("Hyper_R", 0xffee),
+    ("Delete", 0xffff),
+    ("XF86MonBrightnessUp", 0x1008ff02),
+    ("XF86MonBrightnessDown", 0x1008ff03),
+    ("XF86AudioLowerVolume", 0x1008ff11),
+    ("XF86AudioMute", 0x1008ff12),
+    ("XF86AudioRaiseVolume", 0x1008ff13),
+    ("XF86AudioPlay", 0x1008ff14),
+    ("XF86AudioStop", 0x1008ff15),
+    ("XF86AudioPrev", 0x1008ff16),
+    ("XF86AudioNext", 0x1008ff17),
+    ("XF86AudioMicMute", 0x1008ffb2),
+];
+
+/// A key pressed together with some modifiers.
+/// The key is identified by its keysym, which does not depend on the keyboard.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct KeyCombo {
+    pub modifiers: x::ModMask,
+    pub keysym: u32,
+}
+
+/// The modifiers by their canonical name, in the order they are formatted.
+const MODIFIER_NAMES: &[(&str, x::ModMask)] = &[
+    ("super", x::ModMask::N4),
+    ("ctrl", x::ModMask::CONTROL),
+    ("alt", x::ModMask::N1),
+    ("shift", x::ModMask::SHIFT),
+    ("mod2", x::ModMask::N2),
+    ("mod3", x::ModMask::N3),
+    ("mod5", x::ModMask::N5),
+];
+
+/// Format a key combination the way it is parsed, e.g. "super+shift+Return".
+impl fmt::Display for KeyCombo {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for name in modifier_names(self.modifiers) {
+            write!(f, "{}+", name)?;
+        }
+
+        write!(f, "{}", keysym_name(self.keysym))
+    }
+}
+
+/// Get the names of the modifiers of a mask.
+pub fn modifier_names(modifiers: x::ModMask) -> Vec<&'static str> {
+    MODIFIER_NAMES
+        .iter()
+        .filter(|(_, modifier)| modifiers.contains(*modifier))
+        .map(|(name, _)| *name)
+        .collect()
+}
+
+/// Parse a key combination, made of modifiers and a key joined by "+".
+///
+/// The modifiers are shift, ctrl, alt, super and mod1 to mod5, in any case.
+/// The key is a single character or the name of a keysym, e.g. Return or comma.
+pub fn parse_key_combo(keys: &str) -> Result<KeyCombo, String> {
+    let mut names: Vec<&str> = keys.split('+').map(str::trim).collect();
+    // We can unwrap here because split always yields at least one element
+    let key = names.pop().unwrap();
+
+    let modifiers = names
+        .iter()
+        .try_fold(x::ModMask::empty(), |modifiers, name| {
+            parse_modifier(name)
+                .map(|modifier| modifiers | modifier)
+                .ok_or_else(|| format!("Unknown modifier: {}", name))
+        })?;
+    let keysym = parse_keysym(key).ok_or_else(|| format!("Unknown key: {}", key))?;
+
+    Ok(KeyCombo { modifiers, keysym })
+}
+
+/// Parse a sequence of key combinations separated by ",", e.g. "super+w, f".
+pub fn parse_key_sequence(keys: &str) -> Result<Vec<KeyCombo>, String> {
+    keys.split(',').map(parse_key_combo).collect()
+}
+
+/// Whether a keysym is a modifier key, such as Shift_L.
+/// Pressing a modifier alone does not break a chord.
+pub fn is_modifier(keysym: u32) -> bool {
+    matches!(keysym, 0xffe1..=0xffee | 0xfe03 | 0xff7e | NUM_LOCK)
+}
+
+/// Parse the name of a modifier, e.g. super.
+pub fn parse_modifier(name: &str) -> Option<x::ModMask> {
+    match name.to_ascii_lowercase().as_str() {
+        "shift" => Some(x::ModMask::SHIFT),
+        "ctrl" | "control" => Some(x::ModMask::CONTROL),
+        "alt" | "mod1" => Some(x::ModMask::N1),
+        "mod2" => Some(x::ModMask::N2),
+        "mod3" => Some(x::ModMask::N3),
+        "super" | "mod4" => Some(x::ModMask::N4),
+        "mod5" => Some(x::ModMask::N5),
+        _ => None,
+    }
+}
+
+/// Get the name of a keysym, or its hexadecimal value if it has none.
+fn keysym_name(keysym: u32) -> String {
+    // Punctuation is named, as "," and "+" separate the keys
+    if let Some((name, _)) = KEYSYM_NAMES.iter().find(|(_, k)| *k == keysym) {
+        return (*name).to_owned();
+    }
+    if let Some(c) = char::from_u32(keysym).filter(char::is_ascii_graphic) {
+        return c.to_string();
+    }
+
+    match keysym {
+        0xffbe..=0xffe0 => format!("F{}", keysym - 0xffbe + 1),
+        0xffb0..=0xffb9 => format!("KP_{}", keysym - 0xffb0),
+        keysym => format!("{:#x}", keysym),
+    }
+}
+
+/// Parse the name of a keysym, or its hexadecimal value, e.g. 0x1008ff02.
+pub fn parse_keysym(name: &str) -> Option<u32> {
+    let mut chars = name.chars();
+    if let (Some(c), None) = (chars.next(), chars.next()) {
+        // The keysyms of the printable ASCII characters are their codes.
+        // Letters are bound by their lowercase keysym, the one produced without shift
+        return c.is_ascii_graphic().then(|| c.to_ascii_lowercase() as u32);
+    }
+
+    if let Some((_, keysym)) = KEYSYM_NAMES.iter().find(|(n, _)| *n == name) {
+        return Some(*keysym);
+    }
+
+    if let Some(hex) = name.strip_prefix("0x") {
+        return u32::from_str_radix(hex, 16).ok();
+    }
+
+    // The function keys and the keypad digits are consecutive
+    if let Some(number) = name.strip_prefix('F').and_then(|n| n.parse::<u32>().ok()) {
+        return (1..=35).contains(&number).then_some(0xffbe + number - 1);
+    }
+    if let Some(digit) = name.strip_prefix("KP_").and_then(|n| n.parse::<u32>().ok()) {
+        return (0..=9).contains(&digit).then_some(0xffb0 + digit);
+    }
+
+    None
+}
+
+/// The keysyms produced by each keycode of the keyboard without modifiers, in each of its groups,
+/// and the modifiers the keys are mapped to.
+///
+/// A group is a layout of the keyboard, e.g. us and ru when both are configured.
+#[derive(Debug)]
+pub struct Keymap {
+    min_keycode: u8,
+    /// The keysym of each group, for each keycode from the minimum one.
+    keysyms: Vec<Vec<u32>>,
+    num_lock: x::ModMask,
+    /// The keycodes of the modifier keys and the modifier each one sets.
+    modifier_keys: Vec<(u8, x::ModMask)>,
+}
+
+impl Keymap {
+    /// Build a keymap from the core keyboard mapping, as returned by GetKeyboardMapping.
+    /// Only the first group is known, the others need XKB.
+    pub fn new(min_keycode: u8, keysyms_per_keycode: u8, keysyms: Vec<u32>) -> Self {
+        let keysyms = keysyms
+            .chunks(keysyms_per_keycode.max(1) as usize)
+            .map(|keysyms| keysyms[..1].to_vec())
+            .collect();
+
+        Self::with_groups(min_keycode, keysyms)
+    }
+
+    /// Build a keymap from the keysyms of the groups of each keycode, as returned by the XKB GetMap.
+    pub fn with_groups(min_keycode: u8, keysyms: Vec<Vec<u32>>) -> Self {
+        Self {
+            min_keycode,
+            keysyms,
+            num_lock: x::ModMask::empty(),
+            modifier_keys: Vec::new(),
+        }
+    }
+
+    /// Find the modifiers the keys are mapped to, e.g. NumLock usually to mod2,
+    /// given the keycodes of each of the eight modifiers as returned by GetModifierMapping.
+    pub fn set_modifier_mapping(&mut self, keycodes_per_modifier: u8, keycodes: &[u8]) {
+        self.modifier_keys = keycodes
+            .chunks(keycodes_per_modifier.max(1) as usize)
+            .enumerate()
+            .flat_map(|(index, keycodes)| {
+                let modifier = x::ModMask::from_bits_truncate(1 << index);
+                keycodes
+                    .iter()
+                    .filter(|keycode| **keycode != 0)
+                    .map(move |keycode| (*keycode, modifier))
+            })
+            .collect();
+
+        let num_lock_keycodes = self.keycodes(NUM_LOCK);
+        self.num_lock = self
+            .modifier_keys
+            .iter()
+            .find(|(keycode, _)| num_lock_keycodes.contains(keycode))
+            .map(|(_, modifier)| *modifier)
+            .unwrap_or(x::ModMask::empty());
+    }
+
+    /// Get the keycodes producing a keysym without modifiers, in any group.
+    pub fn keycodes(&self, keysym: u32) -> Vec<u8> {
+        self.keysyms
+            .iter()
+            .enumerate()
+            .filter(|(_, keysyms)| keysyms.contains(&keysym))
+            .map(|(index, _)| self.min_keycode + index as u8)
+            .collect()
+    }
+
+    /// Get the keysym produced by a keycode without modifiers in a group.
+    ///
+    /// As the X server does, a group the key does not have wraps around to the ones it has.
+    pub fn keysym(&self, keycode: u8, group: usize) -> Option<u32> {
+        let index = keycode.checked_sub(self.min_keycode)? as usize;
+        let keysyms = self.keysyms.get(index)?;
+        if keysyms.is_empty() {
+            return None;
+        }
+
+        Some(keysyms[group % keysyms.len()])
+    }
+
+    /// Get the keycodes and modifiers to grab for a key combination.
+    ///
+    /// CapsLock and NumLock are modifiers too, so each key is grabbed with every combination
+    /// of them, otherwise the bindings would not work while they are on.
+    pub fn grabs(&self, combo: KeyCombo) -> Vec<(u8, x::ModMask)> {
+        let mut locks = vec![x::ModMask::empty(), x::ModMask::LOCK];
+        if !self.num_lock.is_empty() {
+            locks.extend([self.num_lock, self.num_lock | x::ModMask::LOCK]);
+        }
+
+        self.keycodes(combo.keysym)
+            .into_iter()
+            .flat_map(|keycode| {
+                locks
+                    .iter()
+                    .map(move |locks| (keycode, combo.modifiers | *locks))
+            })
+            .collect()
+    }
+
+    /// Get the key combinations a key event may stand for, ignoring CapsLock and NumLock.
+    ///
+    /// The first one has the keysym of the active group,
+    /// the next ones the keysyms of the other groups of the key,
+    /// so that the bindings of a layout also work while another one is active.
+    /// The state of the release of a modifier key includes the modifier itself,
+    /// which is ignored too, so that releasing Alt_L is reported as Alt_L rather than alt+Alt_L.
+    pub fn combos(&self, keycode: u8, state: x::ModMask, group: usize) -> Vec<KeyCombo> {
+        let locks = x::ModMask::LOCK | self.num_lock;
+        let own_modifier = self
+            .modifier_keys
+            .iter()
+            .filter(|(modifier_keycode, _)| *modifier_keycode == keycode)
+            .fold(x::ModMask::empty(), |mask, (_, modifier)| mask | *modifier);
+        let modifiers = state - locks - own_modifier - x::ModMask::ANY;
+
+        let groups = keycode
+            .checked_sub(self.min_keycode)
+            .and_then(|index| self.keysyms.get(index as usize))
+            .map_or(0, Vec::len);
+        let mut combos: Vec<KeyCombo> = Vec::new();
+        for keysym in (0..groups).filter_map(|offset| self.keysym(keycode, group + offset)) {
+            let combo = KeyCombo { modifiers, keysym };
+            if !combos.contains(&combo) {
+                combos.push(combo);
+            }
+        }
+
+        combos
+    }
+}
+
+/// Get the active group from the state of a key event, as set by the X server when XKB is in use.
+pub fn state_group(state: u32) -> usize {
+    ((state >> GROUP_SHIFT) & GROUP_MASK) as usize
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Keycode 8 produces a and A, keycode 9 Return, keycode 10 Num_Lock and keycode 11 Alt_L.
+    /// NumLock is mapped to mod2, the fifth modifier, and Alt_L to mod1, the fourth one.
+    fn keymap() -> Keymap {
+        let mut keymap = Keymap::new(
+            8,
+            2,
+            vec!['a' as u32, 'A' as u32, 0xff0d, 0, NUM_LOCK, 0, 0xffe9, 0],
+        );
+        keymap.set_modifier_mapping(1, &[0, 0, 0, 11, 10, 0, 0, 0]);
+
+        keymap
+    }
+
+    #[test]
+    fn test_parse_key_combo() {
+        assert_eq!(
+            Ok(KeyCombo {
+                modifiers: x::ModMask::N4 | x::ModMask::SHIFT,
+                keysym: 0xff0d,
+            }),
+            parse_key_combo("super + Shift + Return")
+        );
+        assert_eq!(
+            Ok(KeyCombo {
+                modifiers: x::ModMask::N1,
+                keysym: 'q' as u32,
+            }),
+            parse_key_combo("alt+Q")
+        );
+        assert_eq!(
+            Ok(KeyCombo {
+                modifiers: x::ModMask::CONTROL,
+                keysym: 0x2c,
+            }),
+            parse_key_combo("ctrl+comma")
+        );
+        assert!(parse_key_combo("hyper+a").is_err());
+        assert!(parse_key_combo("super+").is_err());
+    }
+
+    #[test]
+    fn test_format_key_combo() {
+        for keys in [
+            "super+shift+Return",
+            "ctrl+alt+F12",
+            "a",
+            "comma",
+            "KP_5",
+            "0x1008ffff",
+        ] {
+            assert_eq!(keys, parse_key_combo(keys).unwrap().to_string());
+        }
+        assert_eq!(
+            "super+shift+a",
+            parse_key_combo("Shift+mod4+A").unwrap().to_string()
+        );
+    }
+
+    #[test]
+    fn test_parse_key_sequence() {
+        assert_eq!(
+            Ok(vec![
+                parse_key_combo("super+w").unwrap(),
+                parse_key_combo("f").unwrap()
+            ]),
+            parse_key_sequence("super+w, f")
+        );
+        assert!(parse_key_sequence("super+w,").is_err());
+    }
+
+    #[test]
+    fn test_parse_keysym() {
+        assert_eq!(Some(0x2b), parse_keysym("plus"));
+        assert_eq!(Some(0xffc9), parse_keysym("F12"));
+        assert_eq!(Some(0xffb5), parse_keysym("KP_5"));
+        assert_eq!(Some(0x1008ff13), parse_keysym("XF86AudioRaiseVolume"));
+        assert_eq!(None, parse_keysym("F36"));
+        assert_eq!(None, parse_keysym("return"));
+    }
+
+    #[test]
+    fn test_keymap() {
+        let keymap = keymap();
+
+        assert_eq!(vec![8], keymap.keycodes('a' as u32));
+        assert_eq!(Vec::<u8>::new(), keymap.keycodes('A' as u32));
+        assert_eq!(Some(0xff0d), keymap.keysym(9, 0));
+        assert_eq!(None, keymap.keysym(7, 0));
+        assert_eq!(None, keymap.keysym(12, 0));
+        assert_eq!(x::ModMask::N2, keymap.num_lock);
+    }
+
+    #[test]
+    fn test_keymap_groups() {
+        // Keycode 8 produces a in the us group and Cyrillic ef in the ru one, keycode 9 Return in both
+        let keymap = Keymap::with_groups(8, vec![vec!['a' as u32, 0x6c6], vec![0xff0d]]);
+
+        assert_eq!(vec![8], keymap.keycodes('a' as u32));
+        assert_eq!(vec![8], keymap.keycodes(0x6c6));
+        assert_eq!(Some(0x6c6), keymap.keysym(8, 1));
+        // The keys with a single group produce it in every group
+        assert_eq!(Some(0xff0d), keymap.keysym(9, 1));
+        assert_eq!(Some('a' as u32), keymap.keysym(8, 2));
+
+        let super_a = parse_key_combo("super+a").unwrap();
+        let super_ef = KeyCombo {
+            keysym: 0x6c6,
+            ..super_a
+        };
+        assert_eq!(vec![super_a, super_ef], keymap.combos(8, x::ModMask::N4, 0));
+        assert_eq!(vec![super_ef, super_a], keymap.combos(8, x::ModMask::N4, 1));
+        assert_eq!(
+            vec![parse_key_combo("Return").unwrap()],
+            keymap.combos(9, x::ModMask::empty(), 1)
+        );
+    }
+
+    #[test]
+    fn test_state_group() {
+        assert_eq!(0, state_group(x::ModMask::N4.bits()));
+        assert_eq!(1, state_group(0x2000 | x::ModMask::N4.bits()));
+        assert_eq!(3, state_group(0x6000));
+    }
+
+    #[test]
+    fn test_grabs() {
+        let combo = parse_key_combo("super+a").unwrap();
+
+        assert_eq!(
+            vec![
+                (8, x::ModMask::N4),
+                (8, x::ModMask::N4 | x::ModMask::LOCK),
+                (8, x::ModMask::N4 | x::ModMask::N2),
+                (8, x::ModMask::N4 | x::ModMask::N2 | x::ModMask::LOCK),
+            ],
+            keymap().grabs(combo)
+        );
+        assert_eq!(2, Keymap::new(8, 1, vec!['a' as u32]).grabs(combo).len());
+    }
+
+    #[test]
+    fn test_combos() {
+        assert_eq!(
+            vec![parse_key_combo("super+a").unwrap()],
+            keymap().combos(8, x::ModMask::N4 | x::ModMask::N2 | x::ModMask::LOCK, 0)
+        );
+        assert!(keymap().combos(20, x::ModMask::empty(), 0).is_empty());
+        // Releasing Alt_L
+        assert_eq!(
+            vec![parse_key_combo("Alt_L").unwrap()],
+            keymap().combos(11, x::ModMask::N1, 0)
+        );
+    }
+}
@@ -0,0 +1,318 @@
+//! Programs spawned by the window manager on behalf of the clients,
+//! or started with it as listed in the config.
+//!
+//! A program can be started on a workspace: its first window is placed there
+//! instead of the usual placement, matching the window by its _NET_WM_PID.
+//!
+//! No program becomes a zombie when it exits: the programs are double forked with `spawn`,
+//! so init reaps them, except the restarted ones, which a thread waits for.
+//! Anything spawning a process must use one of the two.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::fd::{AsRawFd, FromRawFd};
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{self, Stdio};
+use std::sync::{Arc, Mutex};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde::Deserialize;
+
+use crate::log::warning;
+
+/// How many times in a row a crashing program is restarted before giving up.
+const MAX_RESTARTS: u32 = 5;
+/// A program running for longer than this before crashing is not crashing in a loop.
+const RESTART_RESET: Duration = Duration::from_secs(60);
+const RESTART_DELAY: Duration = Duration::from_secs(1);
+
+/// A program started with the window manager.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AutostartProgram {
+    /// The shell command running the program.
+    pub command: String,
+    /// The variables added to the environment of the program.
+    #[serde(default)]
+    pub env: BTreeMap<String, String>,
+    /// The working directory of the program, "~" is expanded.
+    pub cwd: Option<PathBuf>,
+    /// Start the program again when it exits with a failure or is killed.
+    #[serde(default)]
+    pub restart: bool,
+}
+
+impl AutostartProgram {
+    fn command(&self) -> io::Result<process::Command> {
+        let mut command = process::Command::new("sh");
+        command.arg("-c").arg(&self.command).envs(&self.env);
+        if let Some(cwd) = &self.cwd {
+            command.current_dir(expanduser::expanduser(cwd.to_string_lossy())?);
+        }
+        // Like the detached programs, it is not attached to the terminal of the window manager
+        command.stdin(Stdio::null()).process_group(0);
+
+        Ok(command)
+    }
+
+    /// Wait for the program to exit, and start it again if it failed.
+    fn supervise(&self, mut child: process::Child, supervised: Arc<Mutex<Supervised>>) {
+        let mut restarts = 0;
+        loop {
+            let started = Instant::now();
+            let status = child.wait();
+            supervised.lock().unwrap().pids.remove(&child.id());
+            let status = match status {
+                Ok(status) if status.success() => return,
+                Ok(status) => status,
+                Err(err) => return warning!("Failed to wait for `{}`: {}", self.command, err),
+            };
+            if started.elapsed() > RESTART_RESET {
+                restarts = 0;
+            }
+            if restarts == MAX_RESTARTS {
+                return warning!("`{}` keeps failing, not restarting it", self.command);
+            }
+            restarts += 1;
+            warning!("`{}` exited with {}, restarting it", self.command, status);
+
+            thread::sleep(RESTART_DELAY);
+            // The window manager may have exited in the meantime
+            let mut supervised = supervised.lock().unwrap();
+            if supervised.stopped {
+                return;
+            }
+            child = match self.command().and_then(|mut command| command.spawn()) {
+                Ok(child) => child,
+                Err(err) => return warning!("Failed to restart `{}`: {}", self.command, err),
+            };
+            supervised.pids.insert(child.id());
+        }
+    }
+}
+
+/// The autostart programs which are restarted, shared with the threads waiting for them.
+#[derive(Debug, Default)]
+struct Supervised {
+    /// The process ids of the running programs, which lead their process group.
+    pids: HashSet<u32>,
+    /// Whether the programs were stopped, so they must not be restarted.
+    stopped: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Launcher {
+    /// The names of the workspaces the programs were started on, indexed by process id.
+    startup_workspaces: HashMap<u32, String>,
+    supervised: Arc<Mutex<Supervised>>,
+}
+
+impl Launcher {
+    /// Run a shell command, optionally associating its first window with a workspace.
+    ///
+    /// The program inherits the environment of the window manager,
+    /// and runs in the background so it cannot block the window manager.
+    pub fn exec(&mut self, command: &str, workspace: Option<String>) -> io::Result<()> {
+        let pid = spawn(process::Command::new("sh").arg("-c").arg(command))?;
+
+        // Forget the programs which exited without opening a window
+        self.startup_workspaces.retain(|pid, _| is_running(*pid));
+        if let Some(workspace) = workspace {
+            self.startup_workspaces.insert(pid, workspace);
+        }
+
+        Ok(())
+    }
+
+    /// Start a program of the config in the background.
+    ///
+    /// A program which is not restarted is detached like the ones run by exec,
+    /// otherwise a thread waits for it to exit.
+    pub fn autostart(&mut self, program: &AutostartProgram) -> io::Result<()> {
+        let mut command = program.command()?;
+        if !program.restart {
+            return spawn(&mut command).map(|_| ());
+        }
+
+        let child = command.spawn()?;
+        self.supervised.lock().unwrap().pids.insert(child.id());
+        let program = program.clone();
+        let supervised = Arc::clone(&self.supervised);
+        thread::spawn(move || program.supervise(child, supervised));
+
+        Ok(())
+    }
+
+    /// Terminate the autostart programs which are restarted, as they belong to the window manager.
+    /// The detached programs keep running.
+    pub fn stop_autostart(&mut self) {
+        let mut supervised = self.supervised.lock().unwrap();
+        supervised.stopped = true;
+        for pid in &supervised.pids {
+            // Terminate the shell and the program it started
+            unsafe { libc::kill(-(*pid as libc::pid_t), libc::SIGTERM) };
+        }
+    }
+
+    /// Take the workspace a window should be placed on, given the process id of its owner.
+    ///
+    /// The process may be a descendant of the spawned one, e.g. when the shell forks.
+    pub fn take_startup_workspace(&mut self, pid: u32) -> Option<String> {
+        let mut pid = Some(pid);
+        while let Some(current) = pid.filter(|pid| *pid > 1) {
+            if let Some(workspace) = self.startup_workspaces.remove(&current) {
+                return Some(workspace);
+            }
+            pid = parent_pid(current);
+        }
+
+        None
+    }
+}
+
+/// Spawn a program detached from the window manager and return its process id.
+///
+/// The program is double forked: the intermediate process exits right away,
+/// so the program is adopted and reaped by init instead of becoming a zombie,
+/// and it keeps running if the window manager exits.
+/// It runs in a new session, with no standard input.
+pub fn spawn(command: &mut process::Command) -> io::Result<u32> {
+    let mut fds = [0; 2];
+    if unsafe { libc::pipe2(fds.as_mut_ptr(), libc::O_CLOEXEC) } == -1 {
+        return Err(io::Error::last_os_error());
+    }
+    // The intermediate process sends the id of the program through the pipe
+    let (mut reader, writer) = unsafe { (File::from_raw_fd(fds[0]), File::from_raw_fd(fds[1])) };
+    let writer_fd = writer.as_raw_fd();
+
+    // Only async-signal-safe functions can be called between fork and exec
+    let mut child = unsafe {
+        command
+            .stdin(Stdio::null())
+            .pre_exec(move || match libc::fork() {
+                -1 => Err(io::Error::last_os_error()),
+                0 => {
+                    libc::setsid();
+                    Ok(())
+                }
+                pid => {
+                    libc::write(
+                        writer_fd,
+                        &pid as *const libc::pid_t as *const libc::c_void,
+                        std::mem::size_of::<libc::pid_t>(),
+                    );
+                    libc::_exit(0)
+                }
+            })
+    }
+    .spawn()?;
+    drop(writer);
+    child.wait()?;
+
+    let mut pid = [0; std::mem::size_of::<libc::pid_t>()];
+    reader.read_exact(&mut pid)?;
+
+    Ok(libc::pid_t::from_ne_bytes(pid) as u32)
+}
+
+fn is_running(pid: u32) -> bool {
+    Path::new(&format!("/proc/{}", pid)).exists()
+}
+
+/// Get the parent of a process from /proc/<pid>/stat.
+fn parent_pid(pid: u32) -> Option<u32> {
+    let stat = fs::read_to_string(format!("/proc/{}/stat", pid)).ok()?;
+    // The name of the process is in parentheses and may contain spaces,
+    // the state and the parent id follow it
+    let (_, rest) = stat.rsplit_once(')')?;
+
+    rest.split_whitespace().nth(1)?.parse().ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_take_startup_workspace() {
+        let mut launcher = Launcher::default();
+        launcher
+            .startup_workspaces
+            .insert(process::id(), "web".to_owned());
+        let mut child = process::Command::new("sleep").arg("1").spawn().unwrap();
+
+        assert_eq!(Some(process::id()), parent_pid(child.id()));
+        assert_eq!(
+            Some("web".to_owned()),
+            launcher.take_startup_workspace(child.id())
+        );
+        assert_eq!(None, launcher.take_startup_workspace(child.id()));
+
+        child.kill().unwrap();
+        child.wait().unwrap();
+    }
+
+    #[test]
+    fn test_autostart_program() {
+        let file = format!("toniowm-autostart-test-{}", process::id());
+        let program: AutostartProgram = serde_json::from_value(serde_json::json!({
+            "command": "echo $GREETING > $FILE",
+            "env": { "GREETING": "hi", "FILE": file },
+            "cwd": std::env::temp_dir(),
+        }))
+        .unwrap();
+        let output = program.command().unwrap().output().unwrap();
+
+        let path = std::env::temp_dir().join(file);
+        assert!(output.status.success());
+        assert_eq!("hi\n", fs::read_to_string(&path).unwrap());
+        assert!(!program.restart);
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_stop_autostart() {
+        let mut launcher = Launcher::default();
+        let program: AutostartProgram = serde_json::from_value(serde_json::json!({
+            "command": "sleep 10",
+            "restart": true,
+        }))
+        .unwrap();
+        launcher.autostart(&program).unwrap();
+        let pid = *launcher
+            .supervised
+            .lock()
+            .unwrap()
+            .pids
+            .iter()
+            .next()
+            .unwrap();
+        assert!(is_running(pid));
+
+        launcher.stop_autostart();
+
+        // The program is reaped by the thread waiting for it, and not restarted
+        let start = Instant::now();
+        while is_running(pid) && start.elapsed() < Duration::from_secs(5) {
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(!is_running(pid));
+        thread::sleep(RESTART_DELAY + Duration::from_millis(200));
+        assert!(launcher.supervised.lock().unwrap().pids.is_empty());
+    }
+
+    #[test]
+    fn test_spawn() {
+        let pid = spawn(process::Command::new("sleep").arg("1")).unwrap();
+
+        assert!(is_running(pid));
+        assert_ne!(Some(process::id()), parent_pid(pid));
+        assert!(spawn(&mut process::Command::new("/nonexistent")).is_err());
+
+        unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) };
+    }
+}
@@ -0,0 +1,90 @@
+//! Messages printed by the window manager, filtered by their level.
+//!
+//! Errors are always printed. The level of the other messages is read from TONIOWM_LOG.
+
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU8, Ordering};
+
+/// How detailed the printed messages are, from the least to the most detailed.
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Level {
+    Error,
+    Warn,
+    Info,
+    Debug,
+}
+
+static LEVEL: AtomicU8 = AtomicU8::new(Level::Info as u8);
+
+impl FromStr for Level {
+    type Err = String;
+
+    fn from_str(level: &str) -> Result<Self, Self::Err> {
+        match level.to_ascii_lowercase().as_str() {
+            "error" => Ok(Level::Error),
+            "warn" | "warning" => Ok(Level::Warn),
+            "info" => Ok(Level::Info),
+            "debug" => Ok(Level::Debug),
+            _ => Err(format!(
+                "Invalid log level {}, expected error, warn, info or debug",
+                level
+            )),
+        }
+    }
+}
+
+/// Set the level from TONIOWM_LOG, if it is set. The default level is info.
+pub fn init_from_env() -> Result<(), String> {
+    if let Ok(level) = std::env::var("TONIOWM_LOG") {
+        LEVEL.store(level.parse::<Level>()? as u8, Ordering::Relaxed);
+    }
+
+    Ok(())
+}
+
+/// Whether the messages of a level are printed.
+pub fn enabled(level: Level) -> bool {
+    level as u8 <= LEVEL.load(Ordering::Relaxed)
+}
+
+/// Print a warning, unless the level is error.
+macro_rules! warning {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Warn) {
+            println!("Warning: {}", format_args!($($arg)*));
+        }
+    };
+}
+
+/// Print a message about the normal operation of the window manager.
+macro_rules! info {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Info) {
+            println!($($arg)*);
+        }
+    };
+}
+
+/// Print a message only useful to debug the window manager, e.g. each X event.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if $crate::log::enabled($crate::log::Level::Debug) {
+            println!($($arg)*);
+        }
+    };
+}
+
+pub(crate) use {debug, info, warning};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_level() {
+        assert_eq!(Ok(Level::Warn), "WARN".parse());
+        assert_eq!(Ok(Level::Debug), "debug".parse());
+        assert!("verbose".parse::<Level>().is_err());
+        assert!(Level::Error < Level::Debug);
+    }
+}
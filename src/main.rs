@@ -2,48 +2,180 @@ use anyhow::Result;
 use clap::Parser;
 use crossbeam::channel;
 use expanduser::expanduser;
-use std::{path::PathBuf, thread};
+use std::{path::PathBuf, process};
 use window_manager::WindowManager;
 
 mod args;
 mod atoms;
+mod bindings;
+mod borders;
 mod client;
+mod color;
 mod commands;
 mod config;
+mod corners;
+mod daemon;
+mod events;
 mod ewmh;
+mod format;
+mod geometry_cache;
+mod glob;
+mod hooks;
+mod i3_ipc;
 mod icccm;
+mod json_file;
+mod keys;
+mod launcher;
+mod log;
+mod metrics;
+mod monitor;
+mod recording;
+mod rules;
+mod session_store;
+mod signals;
 mod state;
 mod vector;
 mod window_manager;
+mod workspace_store;
+mod xconn;
+
+/// The exit code when the connection to the X server is lost, e.g. because it exited,
+/// so that a session script can tell it apart from a failure of the window manager.
+const EXIT_CONNECTION_LOST: i32 = 2;
 
 fn main() -> Result<()> {
     let cli = args::Args::parse();
     match cli.command {
         Some(args::Commands::Start {
             autostart: autostart_file_path,
-        }) => start(expanduser(autostart_file_path)?),
+            i3_ipc,
+            display,
+            daemonize,
+            recover,
+        }) => start(
+            expanduser(autostart_file_path)?,
+            i3_ipc,
+            display,
+            daemonize,
+            recover,
+        ),
+        Some(args::Commands::Client(command)) if command.reads_stdin() => {
+            client::dispatch_lines(std::io::stdin().lock())
+        }
         Some(args::Commands::Client(command)) => {
-            client::dispatch_command(command.into());
-
-            Ok(())
+            let format = command.output_format();
+            client::dispatch_command(command.into(), &format)
         }
         _ => Ok(()),
     }
 }
 
-fn start(autostart_file_path: PathBuf) -> Result<()> {
+fn start(
+    autostart_file_path: PathBuf,
+    i3_ipc: bool,
+    display: Option<String>,
+    daemonize: bool,
+    recover: bool,
+) -> Result<()> {
+    if let Some(display) = &display {
+        // The programs started by the window manager open their windows on the same display,
+        // and the clients they run find the socket
+        std::env::set_var("DISPLAY", display);
+        if std::env::var_os("TONIOWM_SOCKET").is_none() {
+            std::env::set_var("TONIOWM_SOCKET", client::display_socket_path(display));
+        }
+    }
+
+    // Fail before daemonizing if another instance is running, so the error is seen
+    let mut pid_file = daemon::PidFile::lock(&daemon::pid_file_path(&client::socket_path()))?;
+    if daemonize {
+        daemon::daemonize()?;
+        pid_file.write_pid()?;
+    }
+
     // Initialize the XCB connection
-    let (conn, screen_num) = xcb::Connection::connect(None)?;
+    let (conn, screen_num) = xcb::Connection::connect_with_extensions(
+        display.as_deref(),
+        &[],
+        &[
+            xcb::Extension::RandR,
+            xcb::Extension::Shape,
+            xcb::Extension::Xinerama,
+            xcb::Extension::Xkb,
+        ],
+    )?;
     // Initialize the client channel
     let (client_sender, client_receiver) = channel::unbounded();
 
-    let config = config::Config::default();
+    if let Err(e) = log::init_from_env() {
+        println!("Error: {}", e);
+    }
+    // Before spawning any thread, see `signals::handle_signals`
+    if let Err(e) = signals::handle_signals(client_sender.clone()) {
+        println!("Error: Failed to handle the termination signals: {}", e);
+    }
 
-    // Spawn the IPC thread
-    thread::spawn(move || {
-        client::handle_ipc(client_sender);
+    // A broken config file should not prevent the window manager from starting
+    let config_path = match std::env::var_os("TONIOWM_CONFIG") {
+        Some(path) => PathBuf::from(path),
+        None => expanduser("~/.config/toniowm/config.json")?,
+    };
+    let config = config::Config::load(&config_path).unwrap_or_else(|e| {
+        println!("Error: {:?}", e);
+        config::Config::default()
     });
+    let geometry_cache =
+        geometry_cache::GeometryCache::load(expanduser("~/.cache/toniowm/geometry.json")?);
+    let workspace_store =
+        workspace_store::WorkspaceStore::new(expanduser("~/.cache/toniowm/workspaces.json")?);
+    let session_store =
+        session_store::SessionStore::new(expanduser("~/.cache/toniowm/session.json")?);
+
+    // Serve the IPC, the window manager cannot be controlled without its socket
+    client::handle_ipc(&client::socket_path(), client_sender.clone())?;
+    // Serve the i3 IPC, which i3 tools find through I3SOCK or the root window.
+    // The i3 tools do not work if its socket cannot be created, but the window manager still does.
+    let i3_socket = if i3_ipc {
+        let socket = i3_ipc::socket_path();
+        match i3_ipc::handle_i3_ipc(&socket, client_sender) {
+            Ok(()) => {
+                std::env::set_var("I3SOCK", &socket);
+                Some(socket)
+            }
+            Err(e) => {
+                println!("Error: {:?}", e);
+                None
+            }
+        }
+    } else {
+        None
+    };
     // Start the window manager
-    let mut wm = WindowManager::new(conn, screen_num, client_receiver, config);
-    wm.run(autostart_file_path)
+    let mut wm = WindowManager::new(
+        conn,
+        screen_num,
+        client_receiver,
+        config,
+        config_path,
+        geometry_cache,
+        workspace_store,
+        session_store,
+        i3_socket.clone(),
+    );
+    let result = wm.run(autostart_file_path, recover);
+
+    // Do not leave stale sockets behind, clients would try to connect to them
+    std::fs::remove_file(client::socket_path()).unwrap_or_default();
+    if let Some(socket) = i3_socket {
+        std::fs::remove_file(socket).unwrap_or_default();
+    }
+    drop(pid_file);
+
+    match result {
+        Err(e) if window_manager::is_connection_error(&e) => {
+            println!("Error: {:?}", e);
+            process::exit(EXIT_CONNECTION_LOST);
+        }
+        result => result,
+    }
 }
@@ -0,0 +1,120 @@
+//! Counters about the activity of the window manager,
+//! useful to diagnose performance problems and leaks.
+
+use std::{collections::BTreeMap, time::Instant};
+
+use serde::Serialize;
+use xcb::x;
+
+#[derive(Debug)]
+pub struct Metrics {
+    /// When the window manager started.
+    started: Instant,
+    /// The number of X events handled, indexed by type.
+    events: BTreeMap<&'static str, u64>,
+    /// The number of commands received from the clients.
+    commands: u64,
+    /// The number of errors reported by the X server.
+    x_errors: u64,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+/// A snapshot of the metrics, as printed by `query metrics`.
+pub struct MetricsReport {
+    /// The number of seconds since the window manager started
+    pub uptime: u64,
+    /// The number of X events handled, indexed by type
+    pub events: BTreeMap<&'static str, u64>,
+    /// The number of commands received from the clients
+    pub commands: u64,
+    /// The number of clients currently managed
+    pub clients: usize,
+    /// The number of errors reported by the X server
+    pub x_errors: u64,
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            started: Instant::now(),
+            events: Default::default(),
+            commands: 0,
+            x_errors: 0,
+        }
+    }
+}
+
+impl Metrics {
+    pub fn count_event(&mut self, event: &x::Event) {
+        *self.events.entry(event_name(event)).or_default() += 1;
+    }
+
+    pub fn count_command(&mut self) {
+        self.commands += 1;
+    }
+
+    pub fn count_x_error(&mut self) {
+        self.x_errors += 1;
+    }
+
+    /// Get a snapshot of the metrics, given the number of clients currently managed.
+    pub fn report(&self, clients: usize) -> MetricsReport {
+        MetricsReport {
+            uptime: self.started.elapsed().as_secs(),
+            events: self.events.clone(),
+            commands: self.commands,
+            clients,
+            x_errors: self.x_errors,
+        }
+    }
+}
+
+/// Get the name of the type of an event.
+/// The events the window manager does not select are counted together.
+fn event_name(event: &x::Event) -> &'static str {
+    match event {
+        x::Event::ButtonPress(_) => "ButtonPress",
+        x::Event::ButtonRelease(_) => "ButtonRelease",
+        x::Event::MotionNotify(_) => "MotionNotify",
+        x::Event::EnterNotify(_) => "EnterNotify",
+        x::Event::LeaveNotify(_) => "LeaveNotify",
+        x::Event::KeyPress(_) => "KeyPress",
+        x::Event::KeyRelease(_) => "KeyRelease",
+        x::Event::ConfigureRequest(_) => "ConfigureRequest",
+        x::Event::ConfigureNotify(_) => "ConfigureNotify",
+        x::Event::ResizeRequest(_) => "ResizeRequest",
+        x::Event::MapRequest(_) => "MapRequest",
+        x::Event::MapNotify(_) => "MapNotify",
+        x::Event::UnmapNotify(_) => "UnmapNotify",
+        x::Event::CreateNotify(_) => "CreateNotify",
+        x::Event::DestroyNotify(_) => "DestroyNotify",
+        x::Event::ReparentNotify(_) => "ReparentNotify",
+        x::Event::PropertyNotify(_) => "PropertyNotify",
+        x::Event::ClientMessage(_) => "ClientMessage",
+        _ => "Other",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_report() {
+        let mut metrics = Metrics::default();
+        metrics.count_command();
+        metrics.count_command();
+        metrics.count_x_error();
+
+        assert_eq!(
+            MetricsReport {
+                uptime: 0,
+                events: BTreeMap::new(),
+                commands: 2,
+                clients: 3,
+                x_errors: 1,
+            },
+            metrics.report(3)
+        );
+    }
+}
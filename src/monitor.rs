@@ -0,0 +1,107 @@
+//! Functions to detect the monitors using the RandR extension,
+//! or the Xinerama extension on servers without RandR 1.5.
+
+use xcb::{randr, x, xinerama};
+
+use crate::vector::Vector2D;
+
+/// A monitor as reported by the X server.
+#[derive(Debug)]
+pub struct MonitorInfo {
+    /// The name of the monitor, e.g. HDMI-1
+    pub name: String,
+    /// The position of the monitor
+    pub pos: Vector2D,
+    /// The size of the monitor
+    pub size: Vector2D,
+    /// Whether the monitor is the primary one
+    pub primary: bool,
+}
+
+/// Query the active monitors of a screen.
+///
+/// The primary monitor comes first.
+/// Fall back to Xinerama if RandR reports no monitors,
+/// and return an empty list if neither extension is usable.
+pub fn query_monitors(conn: &xcb::Connection, root: x::Window) -> xcb::Result<Vec<MonitorInfo>> {
+    let monitors = query_randr_monitors(conn, root)?;
+    if !monitors.is_empty() {
+        return Ok(monitors);
+    }
+
+    query_xinerama_monitors(conn)
+}
+
+fn is_extension_active(conn: &xcb::Connection, extension: xcb::Extension) -> bool {
+    conn.active_extensions().any(|active| active == extension)
+}
+
+/// Query the monitors using RandR, which must be at least version 1.5.
+fn query_randr_monitors(conn: &xcb::Connection, root: x::Window) -> xcb::Result<Vec<MonitorInfo>> {
+    if !is_extension_active(conn, xcb::Extension::RandR) {
+        return Ok(Vec::new());
+    }
+
+    let cookie = conn.send_request(&randr::QueryVersion {
+        major_version: 1,
+        minor_version: 5,
+    });
+    let reply = conn.wait_for_reply(cookie)?;
+    if (reply.major_version(), reply.minor_version()) < (1, 5) {
+        return Ok(Vec::new());
+    }
+
+    let cookie = conn.send_request(&randr::GetMonitors {
+        window: root,
+        get_active: true,
+    });
+    let reply = conn.wait_for_reply(cookie)?;
+
+    let mut monitors = Vec::new();
+    for monitor in reply.monitors() {
+        let cookie = conn.send_request(&x::GetAtomName {
+            atom: monitor.name(),
+        });
+        let name = conn.wait_for_reply(cookie)?.name().to_utf8().into_owned();
+
+        monitors.push(MonitorInfo {
+            name,
+            pos: Vector2D::new(monitor.x().into(), monitor.y().into()),
+            size: Vector2D::new(monitor.width().into(), monitor.height().into()),
+            primary: monitor.primary(),
+        });
+    }
+    monitors.sort_by_key(|monitor| !monitor.primary);
+
+    Ok(monitors)
+}
+
+/// Query the monitors using Xinerama.
+///
+/// Xinerama screens have no name nor primary flag,
+/// so they are named after their index and the first one is the primary.
+fn query_xinerama_monitors(conn: &xcb::Connection) -> xcb::Result<Vec<MonitorInfo>> {
+    if !is_extension_active(conn, xcb::Extension::Xinerama) {
+        return Ok(Vec::new());
+    }
+
+    let cookie = conn.send_request(&xinerama::IsActive {});
+    if conn.wait_for_reply(cookie)?.state() == 0 {
+        return Ok(Vec::new());
+    }
+
+    let cookie = conn.send_request(&xinerama::QueryScreens {});
+    let reply = conn.wait_for_reply(cookie)?;
+
+    Ok(reply
+        .screen_info()
+        .iter()
+        .enumerate()
+        .map(|(index, screen)| MonitorInfo {
+            name: format!("xinerama-{}", index),
+            pos: Vector2D::new(screen.x_org.into(), screen.y_org.into()),
+            size: Vector2D::new(screen.width.into(), screen.height.into()),
+            primary: index == 0,
+        })
+        .collect())
+}
@@ -0,0 +1,164 @@
+//! Records the commands run by the window manager to a file,
+//! so that they can be replayed later, e.g. to reproduce a bug.
+//! The file holds one command per line, as JSON.
+
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::Path,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+use crate::commands::Command;
+
+#[derive(Debug)]
+pub struct Recording {
+    /// The file the commands are appended to.
+    file: File,
+}
+
+impl Recording {
+    /// Start recording to a file, overwriting it.
+    pub fn start(path: &Path) -> Result<Self> {
+        if let Some(parent) = path.parent() {
+            fs::create_dir_all(parent)?;
+        }
+        let file =
+            File::create(path).with_context(|| format!("Failed to create {}", path.display()))?;
+
+        Ok(Self { file })
+    }
+
+    /// Append a command to the recording.
+    pub fn record(&mut self, command: &Command) -> Result<()> {
+        writeln!(self.file, "{}", serde_json::to_string(command)?)?;
+
+        Ok(())
+    }
+}
+
+/// Whether a command is part of a recording.
+/// Queries do not change the state, the recording commands control the recording itself
+/// and a replay is recorded as the commands it runs.
+pub fn is_recorded(command: &Command) -> bool {
+    !matches!(
+        command,
+        Command::Query { .. }
+            | Command::RecordStart { .. }
+            | Command::RecordStop
+            | Command::Replay { .. }
+    )
+}
+
+/// Check the commands of a batch or a replay before any of them runs.
+///
+/// Quitting, subscribing and the recording commands cannot be run from there,
+/// nor from a batch nested in there: a recorded batch replaying its own recording would never end.
+pub fn check_nested_commands(commands: &[Command], context: &str) -> Result<()> {
+    for (index, command) in commands.iter().enumerate() {
+        match command {
+            Command::Quit
+            | Command::Subscribe { .. }
+            | Command::Replay { .. }
+            | Command::RecordStart { .. }
+            | Command::RecordStop => {
+                return Err(anyhow!(
+                    "Command {} cannot be run in a {}",
+                    index + 1,
+                    context
+                ));
+            }
+            Command::Batch { commands } => check_nested_commands(commands, "batch")
+                .with_context(|| format!("Command {} of the {} is invalid", index + 1, context))?,
+            _ => {}
+        }
+    }
+
+    Ok(())
+}
+
+/// Read the commands of a recording, skipping empty lines.
+pub fn read_commands(path: &Path) -> Result<Vec<Command>> {
+    let data =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+
+    data.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(index, line)| {
+            serde_json::from_str(line).with_context(|| {
+                format!(
+                    "Invalid command at line {} of {}",
+                    index + 1,
+                    path.display()
+                )
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::commands::{MonitorSelector, Query, WorkspaceSelector};
+
+    #[test]
+    fn test_record_and_read() {
+        let path = std::env::temp_dir().join(format!(
+            "toniowm-recording-test-{}.jsonl",
+            std::process::id()
+        ));
+        let commands = [
+            Command::AddWorkspace {
+                name: Some("web".to_owned()),
+                monitor: MonitorSelector::Focused,
+            },
+            Command::ActivateWorkspace {
+                selector: WorkspaceSelector::Name("web".to_owned()),
+            },
+        ];
+        let mut recording = Recording::start(&path).unwrap();
+        for command in &commands {
+            recording.record(command).unwrap();
+        }
+
+        let recorded = read_commands(&path).unwrap();
+
+        assert_eq!(2, recorded.len());
+        assert!(matches!(
+            &recorded[1],
+            Command::ActivateWorkspace {
+                selector: WorkspaceSelector::Name(name)
+            } if name == "web"
+        ));
+        assert!(!is_recorded(&Command::Query {
+            query: Query::Workspaces
+        }));
+
+        fs::write(&path, "{\"Focus\":{}}\n").unwrap();
+        assert!(read_commands(&path).is_err());
+
+        fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    fn test_check_nested_commands() {
+        let replay = Command::Replay {
+            file: "recording.jsonl".into(),
+        };
+        let activate = Command::ActivateWorkspace {
+            selector: WorkspaceSelector::Index(0),
+        };
+
+        assert!(check_nested_commands(std::slice::from_ref(&activate), "batch").is_ok());
+        assert!(check_nested_commands(&[activate.clone(), replay.clone()], "batch").is_err());
+        assert!(check_nested_commands(&[Command::RecordStop], "replay").is_err());
+        // A batch in a replay cannot replay either
+        let batch = Command::Batch {
+            commands: vec![activate, replay],
+        };
+        assert!(check_nested_commands(&[batch], "replay").is_err());
+    }
+}
@@ -0,0 +1,149 @@
+//! Rules applied to new windows when they are mapped.
+
+use serde::{Deserialize, Serialize};
+
+/// Assign the windows matching some properties to a workspace.
+///
+/// Every property given must match. Rules come from the config file or are added at runtime.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Rule {
+    /// The class of the application, from WM_CLASS.
+    pub class: Option<String>,
+    /// A part of the title of the window.
+    pub title: Option<String>,
+    /// The type of the window from _NET_WM_WINDOW_TYPE, e.g. dialog or utility.
+    #[serde(rename = "type")]
+    pub window_type: Option<String>,
+    /// The name of the workspace the windows are assigned to.
+    pub workspace: String,
+    /// Activate the workspace when a window is assigned to it.
+    #[serde(default)]
+    pub switch: bool,
+}
+
+/// The properties of a new window the rules match against.
+#[derive(Debug, Default)]
+pub struct WindowProperties<'a> {
+    pub class: Option<&'a str>,
+    pub title: Option<&'a str>,
+    /// The types of the window, lowercase and without the _NET_WM_WINDOW_TYPE_ prefix.
+    pub types: &'a [String],
+}
+
+impl Rule {
+    /// Check that the rule matches at least one property, otherwise it would match every window.
+    pub fn validate(&self) -> Result<(), String> {
+        if self.class.is_none() && self.title.is_none() && self.window_type.is_none() {
+            return Err(format!(
+                "The rule for the workspace {} must match a class, a title or a type",
+                self.workspace
+            ));
+        }
+
+        Ok(())
+    }
+
+    fn matches(&self, window: &WindowProperties) -> bool {
+        let class = self
+            .class
+            .as_ref()
+            .is_none_or(|class| window.class == Some(class.as_str()));
+        let title = self.title.as_ref().is_none_or(|part| {
+            window
+                .title
+                .is_some_and(|title| title.contains(part.as_str()))
+        });
+        let window_type = self.window_type.as_ref().is_none_or(|window_type| {
+            window
+                .types
+                .iter()
+                .any(|t| t.eq_ignore_ascii_case(window_type))
+        });
+
+        class && title && window_type
+    }
+}
+
+/// Find the rule matching a window.
+///
+/// The most recently added rule takes precedence.
+pub fn matching_rule<'a>(rules: &'a [Rule], window: &WindowProperties) -> Option<&'a Rule> {
+    rules.iter().rev().find(|rule| rule.matches(window))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(class: &str, workspace: &str) -> Rule {
+        Rule {
+            class: Some(class.to_owned()),
+            title: None,
+            window_type: None,
+            workspace: workspace.to_owned(),
+            switch: false,
+        }
+    }
+
+    fn class(class: &str) -> WindowProperties<'_> {
+        WindowProperties {
+            class: Some(class),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_matching_rule() {
+        let rules = vec![
+            rule("firefox", "web"),
+            rule("Emacs", "code"),
+            rule("firefox", "browser"),
+        ];
+
+        assert_eq!(matching_rule(&rules, &class("firefox")), Some(&rules[2]));
+        assert_eq!(matching_rule(&rules, &class("Emacs")), Some(&rules[1]));
+        assert_eq!(matching_rule(&rules, &class("emacs")), None);
+    }
+
+    #[test]
+    fn test_matching_rule_properties() {
+        let rules = vec![
+            Rule {
+                title: Some("Picture-in-Picture".to_owned()),
+                ..rule("firefox", "video")
+            },
+            Rule {
+                class: None,
+                window_type: Some("dialog".to_owned()),
+                ..rule("", "dialogs")
+            },
+        ];
+        let types = ["dialog".to_owned()];
+
+        let window = WindowProperties {
+            class: Some("firefox"),
+            title: Some("Picture-in-Picture - Mozilla Firefox"),
+            types: &[],
+        };
+        assert_eq!(matching_rule(&rules, &window), Some(&rules[0]));
+        assert_eq!(matching_rule(&rules, &class("firefox")), None);
+        let window = WindowProperties {
+            class: Some("gimp"),
+            types: &types,
+            ..Default::default()
+        };
+        assert_eq!(matching_rule(&rules, &window), Some(&rules[1]));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(rule("firefox", "web").validate().is_ok());
+        assert!(Rule {
+            class: None,
+            ..rule("", "web")
+        }
+        .validate()
+        .is_err());
+    }
+}
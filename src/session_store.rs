@@ -0,0 +1,50 @@
+//! Keeps the clients on disk while the window manager runs,
+//! so that they can be recovered after a crash.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::json_file::JsonFile;
+use crate::state::Session;
+
+#[derive(Debug)]
+pub struct SessionStore {
+    /// The session file.
+    file: JsonFile<Session>,
+    /// The session written last, to skip writing it again when nothing changed.
+    saved: Option<Session>,
+}
+
+impl SessionStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            file: JsonFile::new(path),
+            saved: None,
+        }
+    }
+
+    /// Read the session from the file.
+    ///
+    /// Return None if the file does not exist or cannot be parsed.
+    pub fn load(&self) -> Option<Session> {
+        self.file.load()
+    }
+
+    /// Write the session to the file, unless it did not change since it was last written.
+    pub fn save(&mut self, session: Session) -> Result<()> {
+        if self.saved.as_ref() == Some(&session) {
+            return Ok(());
+        }
+        self.file.save(&session)?;
+        self.saved = Some(session);
+
+        Ok(())
+    }
+
+    /// Remove the file, as there is nothing to recover after a clean exit.
+    pub fn remove(&mut self) {
+        self.file.remove();
+        self.saved = None;
+    }
+}
@@ -0,0 +1,53 @@
+//! Termination signals, handled like the quit command so that the window manager cleans up.
+
+use std::{io, mem, ptr, thread};
+
+use crossbeam::channel;
+
+use crate::client::Request;
+use crate::commands::Command;
+use crate::log::info;
+
+/// The signals asking the window manager to exit, e.g. from `systemctl stop` or Ctrl-C.
+const SIGNALS: [libc::c_int; 2] = [libc::SIGTERM, libc::SIGINT];
+
+/// Quit the window manager when it receives a termination signal.
+///
+/// The signals are blocked and waited for by a dedicated thread, which sends the quit command.
+/// This must be called before spawning the other threads, as they inherit the blocked signals.
+/// The programs spawned later start with no blocked signals.
+pub fn handle_signals(client_sender: channel::Sender<Request>) -> io::Result<()> {
+    let set = unsafe {
+        let mut set = mem::zeroed();
+        libc::sigemptyset(&mut set);
+        for signal in SIGNALS {
+            libc::sigaddset(&mut set, signal);
+        }
+        set
+    };
+    let err = unsafe { libc::pthread_sigmask(libc::SIG_BLOCK, &set, ptr::null_mut()) };
+    if err != 0 {
+        return Err(io::Error::from_raw_os_error(err));
+    }
+
+    thread::spawn(move || loop {
+        let mut signal = 0;
+        if unsafe { libc::sigwait(&set, &mut signal) } != 0 {
+            continue;
+        }
+        info!("Received signal {}", signal);
+
+        let (response_sender, _) = channel::bounded(1);
+        let request = Request {
+            command: Command::Quit,
+            response_sender,
+            event_sender: None,
+        };
+        // The window manager is gone already
+        if client_sender.send(request).is_err() {
+            break;
+        }
+    });
+
+    Ok(())
+}
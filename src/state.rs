@@ -1,13 +1,28 @@
+use std::collections::{HashMap, VecDeque};
+
 use indexmap::{map::MutableKeys, IndexMap};
+use serde::{Deserialize, Serialize};
+use serde_json::json;
 use thiserror::Error;
 use xcb::{x, Xid, XidNew};
 
 use crate::{
-    commands::{CardinalDirection, CycleDirection, WindowSelector, WorkspaceSelector},
+    commands::{
+        CardinalDirection, CycleDirection, MonitorSelector, Padding, SnapPosition, WindowSelector,
+        WorkspaceSelector,
+    },
+    config::FocusModel,
+    glob,
+    icccm::SizeHints,
+    monitor::MonitorInfo,
     vector::Vector2D,
 };
 
 const MIN_CLIENT_SIZE: Vector2D = Vector2D { x: 32, y: 32 };
+/// The maximum number of windows remembered in the focus history of a workspace.
+const FOCUS_HISTORY_SIZE: usize = 32;
+/// The maximum number of geometries remembered for each client.
+const GEOMETRY_HISTORY_SIZE: usize = 8;
 
 #[derive(Error, Debug)]
 pub enum Error {
@@ -19,12 +34,215 @@ pub enum Error {
     WorkspaceAlreadyExists,
     #[error("Workspace not found.")]
     WorkspaceNotFound,
+    #[error("Mark not found.")]
+    MarkNotFound,
+    #[error("Nothing to undo.")]
+    NothingToUndo,
+    #[error("The window is not snapped.")]
+    NotSnapped,
+    #[error("Monitor not found.")]
+    MonitorNotFound,
+    #[error("Cannot move the only workspace of a monitor.")]
+    LastWorkspaceOnMonitor,
 }
 
 #[derive(Debug, PartialEq, Default)]
 pub struct Workspace {
     /// The list of clients managed by the workspace
     clients: IndexMap<x::Window, Client>,
+    /// The windows of the workspace, from the most to the least recently focused.
+    focus_history: VecDeque<x::Window>,
+    /// The windows of the workspace, from the bottommost to the topmost.
+    stack: Vec<x::Window>,
+    /// The index of the monitor the workspace belongs to.
+    monitor: usize,
+}
+
+#[derive(Debug, PartialEq, Serialize)]
+/// A monitor shows one of its workspaces at a time.
+pub struct Monitor {
+    /// The name of the monitor
+    name: String,
+    /// The position of the monitor
+    pos: Vector2D,
+    /// The size of the monitor
+    size: Vector2D,
+    /// The index of the workspace shown on the monitor
+    workspace: usize,
+    /// Whether the monitor is the primary one
+    primary: bool,
+    /// The space kept free at the edges of the monitor
+    padding: Padding,
+}
+
+impl Monitor {
+    pub fn pos(&self) -> Vector2D {
+        self.pos
+    }
+
+    pub fn workspace(&self) -> usize {
+        self.workspace
+    }
+}
+
+/// Where a client is moved in the stacking order of its workspace.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum StackPosition {
+    /// Above every other window
+    Top,
+    /// Below every other window
+    Bottom,
+    /// Directly above a sibling
+    Above(x::Window),
+    /// Directly below a sibling
+    Below(x::Window),
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+/// The space reserved by a dock at the edges of the screen,
+/// as set in the _NET_WM_STRUT property.
+///
+/// This is also the invisible shadow around a client drawing its own decorations.
+pub struct Strut {
+    pub left: i32,
+    pub right: i32,
+    pub top: i32,
+    pub bottom: i32,
+}
+
+#[derive(Debug, PartialEq)]
+/// A dock window, e.g. a panel, which is not managed.
+struct Dock {
+    /// The index of the monitor the dock overlaps
+    monitor: usize,
+    /// The space reserved by the dock
+    strut: Strut,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The settings of a workspace that are kept across restarts.
+pub struct WorkspaceSettings {
+    /// The name of the workspace
+    pub name: String,
+    /// The name of the monitor the workspace belongs to.
+    /// If it is empty or not connected, the workspace belongs to the focused monitor.
+    #[serde(default)]
+    pub monitor: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// The clients written to disk while running, to recover them after a crash.
+/// Windows are represented by their ids.
+pub struct Session {
+    /// The clients from the bottommost to the topmost of each workspace
+    pub clients: Vec<SessionClient>,
+    /// The focused window
+    pub focused: Option<u32>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+/// A client of a saved session.
+pub struct SessionClient {
+    /// The id of the window
+    pub window: u32,
+    /// The name of the workspace of the window
+    pub workspace: String,
+    /// The position of the window
+    pub pos: Vector2D,
+    /// The size of the window
+    pub size: Vector2D,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// A summary of a workspace, meant to be rendered by bars.
+pub struct WorkspaceInfo {
+    /// The name of the workspace
+    pub name: String,
+    /// The index of the workspace
+    pub index: usize,
+    /// Whether the workspace is the active one
+    pub active: bool,
+    /// The number of clients on the workspace
+    pub clients: usize,
+    /// The id of the focused window of the workspace
+    pub focused: Option<u32>,
+    /// Whether a client of the workspace demands attention
+    pub urgent: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// A summary of a window, meant to be consumed by scripts.
+pub struct WindowInfo {
+    /// The id of the window
+    pub id: u32,
+    /// The title of the window
+    pub title: Option<String>,
+    /// The class of the application owning the window
+    pub class: Option<String>,
+    /// The instance name of the application owning the window
+    pub instance: Option<String>,
+    /// The process id of the client owning the window
+    pub pid: Option<u32>,
+    /// The types of the window, e.g. dialog
+    pub types: Vec<String>,
+    /// The id of the window this one is a dialog of
+    pub transient_for: Option<u32>,
+    /// The position of the window
+    pub pos: Vector2D,
+    /// The size of the window
+    pub size: Vector2D,
+    /// The index of the workspace of the window
+    pub workspace: usize,
+    /// Whether the window is floating, which is always the case for now
+    pub floating: bool,
+    /// Whether the window covers the whole monitor of its workspace
+    pub fullscreen: bool,
+    /// Whether the window demands attention
+    pub urgent: bool,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// A monitor with its workspaces, meant to be rendered by taskbars and overview tools.
+pub struct MonitorNode {
+    /// The name of the monitor
+    pub name: String,
+    /// The position of the monitor
+    pub pos: Vector2D,
+    /// The size of the monitor
+    pub size: Vector2D,
+    /// Whether the monitor is the primary one
+    pub primary: bool,
+    /// Whether the monitor shows the active workspace
+    pub focused: bool,
+    /// The workspaces belonging to the monitor, in order
+    pub workspaces: Vec<WorkspaceNode>,
+}
+
+#[derive(Debug, PartialEq, Serialize, Deserialize)]
+/// A workspace with its windows, as part of a monitor node.
+pub struct WorkspaceNode {
+    /// The name of the workspace
+    pub name: String,
+    /// The index of the workspace
+    pub index: usize,
+    /// Whether the workspace is shown on its monitor
+    pub visible: bool,
+    /// Whether the workspace is the active one
+    pub active: bool,
+    /// How the windows are arranged, which is always "floating" for now
+    pub layout: String,
+    /// The windows of the workspace, from the topmost to the bottommost
+    pub windows: Vec<WindowInfo>,
+}
+
+/// A state a client enters by changing its geometry, which is restored when it leaves it.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum GeometrySnapshot {
+    /// Snapped to a portion of the work area
+    Snapped,
+    /// Covering the whole work area
+    Maximized,
 }
 
 #[derive(Clone, Debug, PartialEq)]
@@ -36,12 +254,203 @@ pub struct Client {
     pos: Vector2D,
     /// The size of the window
     size: Vector2D,
+    /// The previous geometries (position and size) of the window, from the most recent.
+    geometry_history: VecDeque<(Vector2D, Vector2D)>,
+    /// The geometries the window had when it entered each of its states, from the oldest.
+    snapshots: Vec<(GeometrySnapshot, Vector2D, Vector2D)>,
+    /// The class of the application owning the window, from WM_CLASS.
+    class: Option<String>,
+    /// The title of the window, from _NET_WM_NAME or WM_NAME.
+    title: Option<String>,
+    /// The instance name of the application owning the window, from WM_CLASS.
+    instance: Option<String>,
+    /// The process id of the client owning the window, from _NET_WM_PID.
+    pid: Option<u32>,
+    /// The types of the window from _NET_WM_WINDOW_TYPE, lowercase and without prefix.
+    types: Vec<String>,
+    /// The protocols the client takes part in, from WM_PROTOCOLS.
+    protocols: Vec<x::Atom>,
+    /// The window this one is a dialog of, from WM_TRANSIENT_FOR.
+    transient_for: Option<x::Window>,
+    /// When the window was managed, as a serial of the state.
+    managed_since: u64,
+    /// When the window started demanding attention, from the urgency hint of WM_HINTS,
+    /// as a serial of the state. None if it does not demand attention.
+    urgent_since: Option<u64>,
+    /// The number of unmaps requested by the window manager and not yet notified.
+    /// Any other unmap is the client withdrawing its window.
+    pending_unmaps: u32,
+    /// The minimum and maximum sizes requested by the client.
+    size_hints: SizeHints,
+    /// Whether the window is not rectangular, from the Shape extension.
+    /// A border would be drawn around the rectangle instead of the shape.
+    shaped: bool,
+    /// The depth of the window, which the pixmap of a double border must match.
+    depth: u8,
+    /// The invisible shadow around a window drawing its own decorations, from _GTK_FRAME_EXTENTS.
+    /// It is left out when the window is snapped, and the window has no border.
+    frame_extents: Strut,
 }
 
 impl Client {
+    fn new(window: x::Window, pos: Vector2D, size: Vector2D, serial: u64) -> Self {
+        Self {
+            window,
+            pos,
+            size,
+            managed_since: serial,
+            geometry_history: VecDeque::new(),
+            snapshots: Vec::new(),
+            class: None,
+            title: None,
+            instance: None,
+            pid: None,
+            types: Vec::new(),
+            protocols: Vec::new(),
+            transient_for: None,
+            urgent_since: None,
+            pending_unmaps: 0,
+            size_hints: SizeHints::default(),
+            shaped: false,
+            depth: 0,
+            frame_extents: Strut::default(),
+        }
+    }
+
     pub fn window(&self) -> x::Window {
         self.window
     }
+
+    /// Get the position of the window.
+    pub fn pos(&self) -> Vector2D {
+        self.pos
+    }
+
+    /// Get the size of the window.
+    pub fn size(&self) -> Vector2D {
+        self.size
+    }
+
+    /// Get the class of the application owning the window.
+    pub fn class(&self) -> Option<&str> {
+        self.class.as_deref()
+    }
+
+    /// Whether the window demands attention.
+    pub fn urgent(&self) -> bool {
+        self.urgent_since.is_some()
+    }
+
+    /// Get the title of the window.
+    pub fn title(&self) -> Option<&str> {
+        self.title.as_deref()
+    }
+
+    /// Whether the client takes part in a protocol, e.g. WM_DELETE_WINDOW.
+    pub fn supports_protocol(&self, protocol: x::Atom) -> bool {
+        self.protocols.contains(&protocol)
+    }
+
+    /// Whether the window is not rectangular, so it has no border.
+    pub fn shaped(&self) -> bool {
+        self.shaped
+    }
+
+    pub fn depth(&self) -> u8 {
+        self.depth
+    }
+
+    /// Whether the window draws its own decorations with a shadow around them.
+    pub fn has_frame_extents(&self) -> bool {
+        self.frame_extents != Strut::default()
+    }
+
+    /// Get the area of the window, without its border.
+    fn area(&self) -> i64 {
+        self.size.x as i64 * self.size.y as i64
+    }
+
+    /// Get the center of the window.
+    pub fn center(&self) -> Vector2D {
+        self.pos + Vector2D::new(self.size.x / 2, self.size.y / 2)
+    }
+
+    /// Get the smallest size the window can be resized to.
+    fn min_size(&self) -> Vector2D {
+        self.size_hints
+            .min_size
+            .map_or(MIN_CLIENT_SIZE, |size| size.max(MIN_CLIENT_SIZE))
+    }
+
+    /// Get the largest size the window can be resized to.
+    fn max_size(&self) -> Vector2D {
+        self.size_hints
+            .max_size
+            .map_or(Vector2D::new(i32::MAX, i32::MAX), |size| {
+                size.max(self.min_size())
+            })
+    }
+
+    /// Constrain a size to the size hints of the window.
+    fn constrain_size(&self, size: Vector2D) -> Vector2D {
+        size.max(self.min_size()).min(self.max_size())
+    }
+
+    /// Change the geometry of the window, saving the current one in the history.
+    fn set_geometry(&mut self, pos: Vector2D, size: Vector2D) {
+        if (pos, size) != (self.pos, self.size) {
+            self.push_geometry(self.pos, self.size);
+        }
+        self.pos = pos;
+        self.size = size;
+    }
+
+    /// Save the current geometry when the window enters a state.
+    /// A window already in that state keeps the geometry it had when it first entered it,
+    /// and leaves the states it entered afterwards.
+    fn save_snapshot(&mut self, snapshot: GeometrySnapshot) {
+        match self
+            .snapshots
+            .iter()
+            .position(|(saved, ..)| *saved == snapshot)
+        {
+            Some(index) => self.snapshots.truncate(index + 1),
+            None => self.snapshots.push((snapshot, self.pos, self.size)),
+        }
+    }
+
+    /// Take the geometry the window had when it entered its most recent state,
+    /// which it leaves.
+    fn take_snapshot(&mut self) -> Option<(Vector2D, Vector2D)> {
+        let (_, pos, size) = self.snapshots.pop()?;
+
+        Some((pos, size))
+    }
+
+    /// Save a geometry in the history, unless it is already the most recent one.
+    fn push_geometry(&mut self, pos: Vector2D, size: Vector2D) {
+        if self.geometry_history.front() != Some(&(pos, size)) {
+            self.geometry_history.push_front((pos, size));
+            self.geometry_history.truncate(GEOMETRY_HISTORY_SIZE);
+        }
+    }
+}
+
+/// A cycle through the windows in most recently used order.
+///
+/// The order is captured when the cycle starts, so that focusing windows
+/// while cycling does not reorder the windows still to be visited.
+#[derive(Debug, PartialEq)]
+struct MruCycle {
+    /// The windows to cycle through, from the most to the least recently used.
+    windows: Vec<x::Window>,
+    /// The focus histories of the workspaces when the cycle started.
+    /// Only the window selected last is pushed on top of them.
+    histories: Vec<VecDeque<x::Window>>,
+    /// The index of the currently selected window.
+    index: usize,
+    /// Whether the windows of all the workspaces are included.
+    all_workspaces: bool,
 }
 
 pub struct State {
@@ -51,20 +460,43 @@ pub struct State {
     pub child: x::Window,
     /// The list of workspaces managed by the window manager
     workspaces: IndexMap<String, Workspace>,
-    /// The currently active workspace.
-    active_workspace: usize,
+    /// The list of monitors.
+    monitors: Vec<Monitor>,
+    /// The monitor showing the active workspace.
+    focused_monitor: usize,
     /// The currently focused window.
     focused: Option<x::Window>,
-    /// The last focused window.
-    last_focused: Option<x::Window>,
+    /// The client under the pointer, tracked with the crossing events.
+    hovered: Option<x::Window>,
+    /// The dock windows, indexed by window.
+    docks: HashMap<x::Window, Dock>,
+    /// The marks set by the user, indexed by name.
+    /// A mark points to a window regardless of its workspace.
+    marks: HashMap<String, x::Window>,
+    /// The geometry of the top-level windows which are not clients, e.g. not mapped yet
+    /// or withdrawn, followed from the events of the X server so that it is not asked for it.
+    window_geometries: HashMap<x::Window, (Vector2D, Vector2D)>,
+    /// The most recently used cycle in progress, if any.
+    mru_cycle: Option<MruCycle>,
     /// The start position of the cursor when dragging a window.
     /// This is used to calculate the new position of the window.
     pub drag_start_pos: Vector2D,
     /// The start position of the frame when dragging a window
     /// This is used to calculate the new position of the window.
     pub drag_start_frame_pos: Vector2D,
-    /// The size of the monitor.
-    pub monitor_size: Vector2D,
+    /// The start size of the frame when dragging a window.
+    /// This is used to calculate the new size of the window.
+    pub drag_start_frame_size: Vector2D,
+    /// The client being moved or resized with the pointer, until the button is released.
+    dragging: Option<x::Window>,
+    /// Whether cycling past the last workspace or window wraps around to the first one.
+    /// When disabled, cycling past the last element does nothing.
+    pub wrap: bool,
+    /// Whether moving the focus in a direction with no window wraps around to the opposite edge,
+    /// e.g. to the west-most window when moving east.
+    pub directional_wrap: bool,
+    /// A counter ordering what happens to the clients, e.g. when they were managed.
+    serial: u64,
 }
 
 impl Default for State {
@@ -73,12 +505,28 @@ impl Default for State {
             root: x::Window::none(),
             child: x::Window::none(),
             workspaces: Default::default(),
-            active_workspace: 0,
+            monitors: vec![Monitor {
+                name: "default".to_owned(),
+                pos: Default::default(),
+                size: Default::default(),
+                workspace: 0,
+                primary: false,
+                padding: Default::default(),
+            }],
+            focused_monitor: 0,
             focused: Default::default(),
-            last_focused: Default::default(),
+            hovered: None,
+            docks: Default::default(),
+            marks: Default::default(),
+            window_geometries: Default::default(),
+            mru_cycle: None,
             drag_start_pos: Default::default(),
             drag_start_frame_pos: Default::default(),
-            monitor_size: Default::default(),
+            drag_start_frame_size: Default::default(),
+            dragging: None,
+            wrap: true,
+            directional_wrap: false,
+            serial: 0,
         };
 
         state.add_workspace(None).unwrap();
@@ -90,26 +538,341 @@ impl Default for State {
 impl State {
     /// Add a workspace to the state.
     ///
-    /// If no name is provided, the workspace will be named after the index + 1.
+    /// If no name is provided, the workspace will be named after the index + 1,
+    /// or the next free number.
     /// The name of the workspace must be unique.
+    /// The workspace belongs to the focused monitor.
     pub fn add_workspace(&mut self, name: Option<String>) -> Result<(), Error> {
+        self.add_workspace_to_monitor(name, MonitorSelector::Focused)
+    }
+
+    /// Add a workspace belonging to a monitor.
+    ///
+    /// Accepts a monitor selector.
+    /// Return an error if no matching monitor is found or the name is already taken.
+    pub fn add_workspace_to_monitor(
+        &mut self,
+        name: Option<String>,
+        selector: MonitorSelector,
+    ) -> Result<(), Error> {
+        let monitor = self.select_monitor(selector)?;
+
+        self.insert_workspace(name, monitor)
+    }
+
+    fn insert_workspace(&mut self, name: Option<String>, monitor: usize) -> Result<(), Error> {
         let name = if let Some(name) = name {
             name
         } else {
-            (self.workspaces.len() + 1).to_string()
+            self.next_workspace_name()
         };
 
         if self.workspaces.contains_key(&name) {
             Err(Error::WorkspaceAlreadyExists)
         } else {
             let workspace = Workspace {
-                clients: IndexMap::new(),
+                monitor,
+                ..Default::default()
             };
 
             self.workspaces.insert(name, workspace);
             Ok(())
         }
     }
+
+    /// Get a summary of the workspaces, in order.
+    ///
+    /// The focused window of an inactive workspace is the one
+    /// that gets focused when the workspace is activated.
+    pub fn workspaces_info(&self) -> Vec<WorkspaceInfo> {
+        let active_workspace = self.active_workspace();
+
+        self.workspaces
+            .iter()
+            .enumerate()
+            .map(|(index, (name, workspace))| {
+                let focused = if index == active_workspace {
+                    self.focused
+                } else {
+                    workspace.focus_history.front().copied()
+                };
+
+                WorkspaceInfo {
+                    name: name.clone(),
+                    index,
+                    active: index == active_workspace,
+                    clients: workspace.clients.len(),
+                    focused: focused.map(|window| window.resource_id()),
+                    urgent: workspace.clients.values().any(Client::urgent),
+                }
+            })
+            .collect()
+    }
+
+    /// Get a summary of the windows, in workspace order.
+    /// Only the windows of the selected workspace are included, if any.
+    ///
+    /// Return an error if the workspace is not found.
+    pub fn windows_info(
+        &self,
+        workspace: Option<WorkspaceSelector>,
+    ) -> Result<Vec<WindowInfo>, Error> {
+        let selected = workspace
+            .map(|selector| self.select_workspace(selector))
+            .transpose()?;
+
+        Ok(self
+            .workspaces
+            .values()
+            .enumerate()
+            .filter(|(index, _)| selected.map_or(true, |selected| selected == *index))
+            .flat_map(|(index, workspace)| {
+                workspace
+                    .clients
+                    .values()
+                    .map(move |client| self.window_info(index, client))
+            })
+            .collect())
+    }
+
+    /// Get a summary of the focused window, if any.
+    pub fn focused_window_info(&self) -> Option<WindowInfo> {
+        let window = self.focused?;
+        let index = self.client_workspace(window)?;
+
+        Some(self.window_info(index, &self.workspaces[index].clients[&window]))
+    }
+
+    /// Get the monitors with their workspaces and windows, in order.
+    pub fn tree(&self) -> Vec<MonitorNode> {
+        let active_workspace = self.active_workspace();
+
+        self.monitors
+            .iter()
+            .enumerate()
+            .map(|(monitor_index, monitor)| MonitorNode {
+                name: monitor.name.clone(),
+                pos: monitor.pos,
+                size: monitor.size,
+                primary: monitor.primary,
+                focused: monitor_index == self.focused_monitor,
+                workspaces: self
+                    .workspaces
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, (_, workspace))| workspace.monitor == monitor_index)
+                    .map(|(index, (name, workspace))| WorkspaceNode {
+                        name: name.clone(),
+                        index,
+                        visible: monitor.workspace == index,
+                        active: index == active_workspace,
+                        layout: "floating".to_owned(),
+                        windows: Self::stacking_order(workspace)
+                            .into_iter()
+                            .map(|client| self.window_info(index, client))
+                            .collect(),
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    /// Get the clients of a workspace from the topmost to the bottommost.
+    fn stacking_order(workspace: &Workspace) -> Vec<&Client> {
+        workspace
+            .stack
+            .iter()
+            .rev()
+            .filter_map(|window| workspace.clients.get(window))
+            .collect()
+    }
+
+    /// Get the clients of every workspace from the bottommost to the topmost,
+    /// as in _NET_CLIENT_LIST_STACKING.
+    ///
+    /// The workspaces are stacked in order, since only one of them is shown on a monitor.
+    pub fn client_list_stacking(&self) -> Vec<x::Window> {
+        self.workspaces
+            .values()
+            .flat_map(|workspace| workspace.stack.iter().copied())
+            .collect()
+    }
+
+    fn window_info(&self, index: usize, client: &Client) -> WindowInfo {
+        let monitor = &self.monitors[self.workspaces[index].monitor];
+
+        WindowInfo {
+            id: client.window.resource_id(),
+            title: client.title.clone(),
+            class: client.class.clone(),
+            instance: client.instance.clone(),
+            pid: client.pid,
+            types: client.types.clone(),
+            transient_for: client.transient_for.map(|window| window.resource_id()),
+            pos: client.pos,
+            size: client.size,
+            workspace: index,
+            floating: true,
+            fullscreen: client.pos == monitor.pos && client.size == monitor.size,
+            urgent: client.urgent(),
+        }
+    }
+
+    /// Dump the whole state as JSON, for debugging.
+    /// Windows are represented by their ids.
+    pub fn dump(&self) -> serde_json::Value {
+        let ids = |windows: &mut dyn Iterator<Item = &x::Window>| {
+            windows
+                .map(|window| window.resource_id())
+                .collect::<Vec<_>>()
+        };
+
+        json!({
+            "root": self.root.resource_id(),
+            "child": self.child.resource_id(),
+            "workspaces": self.workspaces.iter().map(|(name, workspace)| json!({
+                "name": name,
+                "monitor": workspace.monitor,
+                "focus_history": ids(&mut workspace.focus_history.iter()),
+                "stack": ids(&mut workspace.stack.iter()),
+                "clients": workspace.clients.values().map(|client| json!({
+                    "window": client.window.resource_id(),
+                    "pos": client.pos,
+                    "size": client.size,
+                    "geometry_history": client.geometry_history,
+                    "snapshots": client.snapshots,
+                    "class": client.class,
+                    "title": client.title,
+                    "instance": client.instance,
+                    "pid": client.pid,
+                    "types": client.types,
+                    "protocols": client.protocols.iter().map(|atom| atom.resource_id())
+                        .collect::<Vec<_>>(),
+                    "transient_for": client.transient_for.map(|window| window.resource_id()),
+                    "urgent": client.urgent(),
+                })).collect::<Vec<_>>(),
+            })).collect::<Vec<_>>(),
+            "monitors": self.monitors,
+            "focused_monitor": self.focused_monitor,
+            "focused": self.focused.map(|window| window.resource_id()),
+            "hovered": self.hovered.map(|window| window.resource_id()),
+            "docks": self.docks.iter().map(|(window, dock)| json!({
+                "window": window.resource_id(),
+                "monitor": dock.monitor,
+                "strut": [dock.strut.left, dock.strut.right, dock.strut.top, dock.strut.bottom],
+            })).collect::<Vec<_>>(),
+            "marks": self.marks.iter().map(|(name, window)| (name.clone(), window.resource_id()))
+                .collect::<HashMap<_, _>>(),
+            "mru_cycle": self.mru_cycle.as_ref().map(|cycle| json!({
+                "windows": ids(&mut cycle.windows.iter()),
+                "index": cycle.index,
+                "all_workspaces": cycle.all_workspaces,
+            })),
+            "drag_start_pos": self.drag_start_pos,
+            "drag_start_frame_pos": self.drag_start_frame_pos,
+            "drag_start_frame_size": self.drag_start_frame_size,
+            "dragging": self.dragging.map(|window| window.resource_id()),
+            "wrap": self.wrap,
+            "directional_wrap": self.directional_wrap,
+        })
+    }
+
+    /// Get the settings of the workspaces, in order.
+    pub fn workspaces_settings(&self) -> Vec<WorkspaceSettings> {
+        self.workspaces
+            .iter()
+            .map(|(name, workspace)| WorkspaceSettings {
+                name: name.clone(),
+                monitor: self.monitors[workspace.monitor].name.clone(),
+            })
+            .collect()
+    }
+
+    /// Get the clients with their workspace and geometry, to recover them after a crash.
+    pub fn session(&self) -> Session {
+        let clients = self
+            .workspaces
+            .iter()
+            .flat_map(|(name, workspace)| {
+                Self::stacking_order(workspace)
+                    .into_iter()
+                    .rev()
+                    .map(|client| SessionClient {
+                        window: client.window.resource_id(),
+                        workspace: name.clone(),
+                        pos: client.pos,
+                        size: client.size,
+                    })
+            })
+            .collect();
+
+        Session {
+            clients,
+            focused: self.focused.map(|window| window.resource_id()),
+        }
+    }
+
+    /// Replace the workspaces with the ones described by the settings.
+    ///
+    /// This is meant to be called on startup, before any client is managed.
+    /// Workspaces of a missing monitor belong to the focused monitor,
+    /// and each monitor shows the first of its workspaces.
+    /// Duplicated names are ignored.
+    pub fn restore_workspaces(&mut self, settings: Vec<WorkspaceSettings>) {
+        let mut workspaces = IndexMap::new();
+        for WorkspaceSettings { name, monitor } in settings {
+            let monitor = self
+                .monitors
+                .iter()
+                .position(|m| m.name == monitor)
+                .unwrap_or(self.focused_monitor);
+
+            workspaces.entry(name).or_insert(Workspace {
+                monitor,
+                ..Default::default()
+            });
+        }
+        if workspaces.is_empty() {
+            return;
+        }
+
+        self.workspaces = workspaces;
+        self.marks.clear();
+        self.mru_cycle = None;
+        self.focused = None;
+
+        for index in 0..self.monitors.len() {
+            let workspace = self
+                .workspaces
+                .values()
+                .position(|workspace| workspace.monitor == index);
+
+            self.monitors[index].workspace = match workspace {
+                Some(workspace) => workspace,
+                None => {
+                    // Every monitor needs a workspace to show
+                    self.workspaces.insert(
+                        self.next_workspace_name(),
+                        Workspace {
+                            monitor: index,
+                            ..Default::default()
+                        },
+                    );
+                    self.workspaces.len() - 1
+                }
+            };
+        }
+    }
+
+    /// Get the first free numeric name for a new workspace,
+    /// starting from the index + 1.
+    fn next_workspace_name(&self) -> String {
+        (self.workspaces.len() + 1..)
+            .map(|n| n.to_string())
+            .find(|name| !self.workspaces.contains_key(name))
+            .expect("Unexpected: no free workspace name")
+    }
+
     /// Rename a workspace.
     ///
     /// Accepts a selector.
@@ -120,29 +883,11 @@ impl State {
         selector: WorkspaceSelector,
         name: String,
     ) -> Result<(), Error> {
-        let (old_name, _) = match selector {
-            WorkspaceSelector::Index(index) => {
-                if let Some((old_name, workspace)) = self.workspaces.get_index_mut2(index) {
-                    (old_name, workspace)
-                } else {
-                    return Err(Error::WorkspaceNotFound);
-                }
-            }
-            WorkspaceSelector::Name(name) => {
-                if let Some((_, old_name, workspace)) = self.workspaces.get_full_mut2(&name) {
-                    (old_name, workspace)
-                } else {
-                    return Err(Error::WorkspaceNotFound);
-                }
-            }
-            WorkspaceSelector::Cycle(direction) => {
-                let index = self.select_workspace_cycle(direction);
-
-                self.workspaces
-                    .get_index_mut2(index)
-                    .expect("Unexpected: no workspace")
-            }
-        };
+        let index = self.select_workspace(selector)?;
+        let (old_name, _) = self
+            .workspaces
+            .get_index_mut2(index)
+            .expect("Unexpected: no workspace");
 
         *old_name = name;
 
@@ -151,645 +896,4106 @@ impl State {
 
     ///  Active a workspace as active and return its index.
     ///
+    /// The workspace is shown on its monitor, which becomes the focused one.
+    /// The most recently focused window of the workspace is focused.
     /// Accepts a selector.
     /// Return an error if no matching workspace is not found.
     pub fn activate_workspace(&mut self, selector: WorkspaceSelector) -> Result<usize, Error> {
-        let index = match selector {
-            WorkspaceSelector::Index(index) => Some(index),
-            WorkspaceSelector::Name(name) => self.workspaces.get_index_of(&name),
-            WorkspaceSelector::Cycle(direction) => Some(self.select_workspace_cycle(direction)),
-        };
-        if let Some(index) = index {
-            self.active_workspace = index;
+        let index = self.select_workspace(selector)?;
+        let monitor = self.workspaces[index].monitor;
 
-            Ok(index)
-        } else {
-            Err(Error::WorkspaceNotFound)
-        }
+        self.monitors[monitor].workspace = index;
+        self.focused_monitor = monitor;
+        self.focused = self.active_focus_history().front().copied();
+
+        Ok(index)
     }
 
-    fn select_workspace_cycle(&self, direction: CycleDirection) -> usize {
-        match direction {
-            CycleDirection::Next => (self.active_workspace + 1) % self.workspaces.len(),
-            CycleDirection::Prev => {
-                (self.active_workspace + self.workspaces.len() - 1) % self.workspaces.len()
+    /// Select a workspace using a selector and return its index.
+    ///
+    /// Return an error if no matching workspace is found.
+    pub fn select_workspace(&self, selector: WorkspaceSelector) -> Result<usize, Error> {
+        match selector {
+            WorkspaceSelector::Index(index) if index < self.workspaces.len() => Ok(index),
+            WorkspaceSelector::Index(_) => Err(Error::WorkspaceNotFound),
+            WorkspaceSelector::Name(name) => self
+                .workspaces
+                .get_index_of(&name)
+                .ok_or(Error::WorkspaceNotFound),
+            WorkspaceSelector::Cycle(direction) => Ok(self.select_workspace_cycle(direction)),
+            WorkspaceSelector::Empty => self
+                .workspaces
+                .values()
+                .position(|workspace| workspace.clients.is_empty())
+                .ok_or(Error::WorkspaceNotFound),
+            WorkspaceSelector::NextEmpty => {
+                let len = self.workspaces.len();
+                // Start after the active workspace and wrap around
+                (1..=len)
+                    .map(|offset| (self.active_workspace() + offset) % len)
+                    .find(|index| self.workspaces[*index].clients.is_empty())
+                    .ok_or(Error::WorkspaceNotFound)
             }
         }
     }
 
-    /// Return a list of the workspaces names.
-    pub fn workspaces_names(&self) -> Vec<String> {
-        self.workspaces.keys().cloned().collect()
+    fn select_workspace_cycle(&self, direction: CycleDirection) -> usize {
+        cycle_index(
+            self.active_workspace(),
+            self.workspaces.len(),
+            direction,
+            self.wrap,
+        )
     }
 
-    /// Add a client to the state.
+    /// Get the index of the active workspace.
+    /// This is the workspace shown on the focused monitor.
+    pub fn active_workspace(&self) -> usize {
+        self.monitors[self.focused_monitor].workspace
+    }
+
+    /// Get the indexes of the workspaces shown on the monitors.
+    pub fn visible_workspaces(&self) -> Vec<usize> {
+        self.monitors
+            .iter()
+            .map(|monitor| monitor.workspace)
+            .collect()
+    }
+
+    /// Get the clients of a workspace.
+    pub fn workspace_clients(&self, index: usize) -> Option<&IndexMap<x::Window, Client>> {
+        self.workspaces
+            .get_index(index)
+            .map(|(_, workspace)| &workspace.clients)
+    }
+
+    /// Get the windows of the clients, in workspace order.
+    pub fn windows(&self) -> Vec<x::Window> {
+        self.workspaces
+            .values()
+            .flat_map(|workspace| workspace.clients.keys().copied())
+            .collect()
+    }
+
+    /// Get the number of clients, across all the workspaces.
+    pub fn clients_count(&self) -> usize {
+        self.workspaces
+            .values()
+            .map(|workspace| workspace.clients.len())
+            .sum()
+    }
+
+    /// Set the position and size of a monitor.
     ///
-    /// Return an error if the client already exists.
-    pub fn add_client(
+    /// Return an error if the monitor is not found.
+    pub fn set_monitor_geometry(
         &mut self,
-        window: x::Window,
+        index: usize,
         pos: Vector2D,
         size: Vector2D,
     ) -> Result<(), Error> {
-        if self.active_workspace_clients().contains_key(&window) {
-            Err(Error::ClientAlreadyExists)
-        } else {
-            let client = Client { window, pos, size };
-            self.active_workspace_clients_mut().insert(window, client);
+        let monitor = self.monitors.get_mut(index).ok_or(Error::MonitorNotFound)?;
+        monitor.pos = pos;
+        monitor.size = size;
 
-            Ok(())
-        }
+        Ok(())
     }
 
-    /// Remove a client from the state.
+    /// Set the space kept free at the edges of a monitor.
     ///
-    /// Return an error if the client is not found.
-    pub fn remove_client(&mut self, window: x::Window) -> Result<(), Error> {
-        if self
-            .active_workspace_clients_mut()
-            .shift_remove(&window)
-            .is_none()
-        {
-            Err(Error::ClientNotFound)
-        } else {
-            if self.focused == Some(window) {
-                self.focused = None;
-            }
-            Ok(())
-        }
+    /// Return an error if the monitor is not found.
+    pub fn set_monitor_padding(
+        &mut self,
+        selector: MonitorSelector,
+        padding: Padding,
+    ) -> Result<(), Error> {
+        let index = self.select_monitor(selector)?;
+        self.monitors[index].padding = padding;
+
+        Ok(())
     }
 
-    /// Drag a client and return its new position.
+    /// Replace the monitors with the ones detected on startup.
     ///
-    /// Return an error if the client is not found.
-    pub fn drag_client(
-        &mut self,
-        window: x::Window,
-        mouse_pos: Vector2D,
-    ) -> Result<Vector2D, Error> {
-        let new_pos = self.drag_start_frame_pos + mouse_pos - self.drag_start_pos;
-        if let Some(client) = self.active_workspace_clients_mut().get_mut(&window) {
-            client.pos = new_pos;
+    /// The first monitor keeps the existing workspaces,
+    /// while each of the others shows a new workspace.
+    /// Nothing changes if no monitor is given.
+    pub fn set_monitors(&mut self, monitors: Vec<MonitorInfo>) -> Result<(), Error> {
+        if monitors.is_empty() {
+            return Ok(());
+        }
 
-            Ok(new_pos)
-        } else {
-            Err(Error::ClientNotFound)
+        self.monitors.truncate(1);
+        for (index, monitor) in monitors.into_iter().enumerate() {
+            if index == 0 {
+                self.monitors[0].name = monitor.name;
+                self.set_monitor_geometry(0, monitor.pos, monitor.size)?;
+            } else {
+                self.add_monitor(monitor.name, monitor.pos, monitor.size)?;
+            }
+            self.monitors[index].primary = monitor.primary;
         }
+
+        Ok(())
     }
 
-    /// Resize a client by dragging it and return its new size.
+    /// Add a monitor showing a new workspace and return its index.
     ///
-    /// Return an error if the client is not found.
-    pub fn drag_resize_client(
+    /// Return an error if the name of the new workspace is already taken.
+    pub fn add_monitor(
         &mut self,
-        window: x::Window,
-        mouse_pos: Vector2D,
-    ) -> Result<Vector2D, Error> {
-        if let Some(client) = self.active_workspace_clients_mut().get_mut(&window) {
-            let new_size = (mouse_pos - client.pos).max(MIN_CLIENT_SIZE);
-            client.size = new_size;
-
-            Ok(new_size)
-        } else {
-            Err(Error::ClientNotFound)
-        }
+        name: String,
+        pos: Vector2D,
+        size: Vector2D,
+    ) -> Result<usize, Error> {
+        let index = self.monitors.len();
+        let workspace = self.workspaces.len();
+        self.insert_workspace(None, index)?;
+
+        self.monitors.push(Monitor {
+            name,
+            pos,
+            size,
+            workspace,
+            primary: false,
+            padding: Default::default(),
+        });
+
+        Ok(index)
     }
 
-    /// Teleport a client to a new position.
+    /// Get a monitor using a selector.
     ///
-    /// Return an error if the client is not found.
-    pub fn teleport_client(&mut self, window: x::Window, pos: Vector2D) -> Result<(), Error> {
-        if let Some(client) = self.active_workspace_clients_mut().get_mut(&window) {
-            client.pos = pos;
+    /// Return an error if no matching monitor is found.
+    pub fn monitor(&self, selector: MonitorSelector) -> Result<&Monitor, Error> {
+        let index = self.select_monitor(selector)?;
 
-            Ok(())
-        } else {
-            Err(Error::ClientNotFound)
-        }
+        Ok(&self.monitors[index])
     }
 
-    /// Focus a client, saving the last focused client.
+    /// Select a monitor using a selector and return its index.
     ///
-    /// Return an error if the client is not found.
-    pub fn focus_client(&mut self, selector: WindowSelector) -> Result<Option<x::Window>, Error> {
-        // Root window focus is used to unfocus the current window.
-        if let WindowSelector::Window(window) = selector {
-            if self.root.resource_id() == window {
-                self.set_focused(None);
-                return Ok(None);
-            }
+    /// Return an error if no matching monitor is found.
+    fn select_monitor(&self, selector: MonitorSelector) -> Result<usize, Error> {
+        match selector {
+            MonitorSelector::Focused => Ok(self.focused_monitor),
+            MonitorSelector::Closest(direction) => self.select_monitor_closest(direction),
+            // Fall back to the first monitor if none is marked as primary
+            MonitorSelector::Primary => Ok(self
+                .monitors
+                .iter()
+                .position(|monitor| monitor.primary)
+                .unwrap_or(0)),
+            MonitorSelector::Index(index) if index < self.monitors.len() => Ok(index),
+            MonitorSelector::Index(_) => Err(Error::MonitorNotFound),
+            MonitorSelector::Name(name) => self
+                .monitors
+                .iter()
+                .position(|monitor| monitor.name == name)
+                .ok_or(Error::MonitorNotFound),
         }
-
-        let client = self.select_client(selector)?.clone();
-
-        self.set_focused(Some(client.window));
-        Ok(Some(client.window))
     }
 
-    /// Get the active workspace clients.
-    pub fn active_workspace_clients(&self) -> &IndexMap<x::Window, Client> {
-        // We can unwrap here because we know the workspace exists.
-        let (_, workspace) = self.workspaces.get_index(self.active_workspace).unwrap();
-
-        &workspace.clients
+    fn select_monitor_closest(&self, direction: CardinalDirection) -> Result<usize, Error> {
+        let center = |monitor: &Monitor| {
+            Vector2D::new(
+                monitor.pos.x + monitor.size.x / 2,
+                monitor.pos.y + monitor.size.y / 2,
+            )
+        };
+        let focused = center(&self.monitors[self.focused_monitor]);
+
+        self.monitors
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| *index != self.focused_monitor)
+            .map(|(index, monitor)| (index, center(monitor) - focused))
+            .filter(|(_, delta)| match direction {
+                CardinalDirection::East => delta.x > 0,
+                CardinalDirection::West => delta.x < 0,
+                CardinalDirection::North => delta.y < 0,
+                CardinalDirection::South => delta.y > 0,
+            })
+            // We do not need to calculate the square root to compare distances.
+            .min_by_key(|(_, delta)| delta.x.pow(2) + delta.y.pow(2))
+            .map(|(index, _)| index)
+            .ok_or(Error::MonitorNotFound)
     }
 
-    /// Get the active workspace clients.
-    fn active_workspace_clients_mut(&mut self) -> &mut IndexMap<x::Window, Client> {
-        // We can unwrap here because we know the workspace exists.
-        let (_, workspace) = self
-            .workspaces
-            .get_index_mut(self.active_workspace)
-            .unwrap();
+    /// Focus a monitor and return the window to focus.
+    ///
+    /// The most recently focused window of the workspace shown on the monitor is focused.
+    /// Return an error if no matching monitor is found.
+    pub fn focus_monitor(&mut self, selector: MonitorSelector) -> Result<Option<x::Window>, Error> {
+        self.focused_monitor = self.select_monitor(selector)?;
+        self.focused = self.active_focus_history().front().copied();
 
-        &mut workspace.clients
+        Ok(self.focused)
     }
 
-    /// Select a client using a selector.
+    /// Move a workspace to another monitor and return its index.
     ///
-    /// Return an error if no matching client has been found.
-    pub fn select_client(&self, selector: WindowSelector) -> Result<&Client, Error> {
-        match selector {
-            WindowSelector::Focused => {
-                if let Some(window) = self.focused {
-                    self.active_workspace_clients()
-                        .get(&window)
-                        .ok_or(Error::ClientNotFound)
-                } else {
-                    Err(Error::ClientNotFound)
-                }
+    /// The clients are translated to the area of the new monitor.
+    /// If the workspace was shown, it is shown on the new monitor,
+    /// while the old monitor shows another of its workspaces.
+    /// Return an error if the workspace or the monitor is not found,
+    /// or if the workspace is the only one of its monitor.
+    pub fn move_workspace_to_monitor(
+        &mut self,
+        workspace_selector: WorkspaceSelector,
+        monitor_selector: MonitorSelector,
+    ) -> Result<usize, Error> {
+        let index = self.select_workspace(workspace_selector)?;
+        let target = self.select_monitor(monitor_selector)?;
+        let source = self.workspaces[index].monitor;
+        if source == target {
+            return Ok(index);
+        }
+
+        let replacement = self
+            .workspaces
+            .values()
+            .enumerate()
+            .position(|(i, workspace)| i != index && workspace.monitor == source)
+            .ok_or(Error::LastWorkspaceOnMonitor)?;
+        let active_workspace = self.active_workspace();
+        self.rehome_workspace(index, target);
+
+        if self.monitors[source].workspace == index {
+            self.monitors[source].workspace = replacement;
+            self.monitors[target].workspace = index;
+            if self.focused_monitor == source {
+                self.focused_monitor = target;
             }
-            WindowSelector::Window(window) => unsafe {
-                self.active_workspace_clients()
-                    .get(&x::Window::new(window))
-                    .ok_or(Error::ClientNotFound)
-            },
-            WindowSelector::Closest(direction) => self.select_client_closest(direction),
-            WindowSelector::Cycle(direction) => self.select_client_cycle(direction),
         }
+
+        if self.active_workspace() != active_workspace {
+            self.focused = self.active_focus_history().front().copied();
+        }
+
+        Ok(index)
+    }
+
+    /// Exchange the workspaces shown on two monitors and return their indexes.
+    ///
+    /// The clients are translated to the area of their new monitor.
+    /// The active workspace stays active, so the focus follows it to its new monitor.
+    /// Return an error if a monitor is not found.
+    pub fn swap_monitors(
+        &mut self,
+        first: MonitorSelector,
+        second: MonitorSelector,
+    ) -> Result<(usize, usize), Error> {
+        let first = self.select_monitor(first)?;
+        let second = self.select_monitor(second)?;
+        let first_workspace = self.monitors[first].workspace;
+        let second_workspace = self.monitors[second].workspace;
+        if first == second {
+            return Ok((first_workspace, second_workspace));
+        }
+
+        self.rehome_workspace(first_workspace, second);
+        self.rehome_workspace(second_workspace, first);
+        self.monitors[first].workspace = second_workspace;
+        self.monitors[second].workspace = first_workspace;
+        if self.focused_monitor == first {
+            self.focused_monitor = second;
+        } else if self.focused_monitor == second {
+            self.focused_monitor = first;
+        }
+
+        Ok((first_workspace, second_workspace))
+    }
+
+    /// Make a workspace belong to another monitor,
+    /// translating its clients to the area of the monitor.
+    fn rehome_workspace(&mut self, index: usize, monitor: usize) {
+        let from_pos = self.monitors[self.workspaces[index].monitor].pos;
+        let (to_pos, to_size) = (self.monitors[monitor].pos, self.monitors[monitor].size);
+
+        let workspace = &mut self.workspaces[index];
+        for client in workspace.clients.values_mut() {
+            client.pos = translate_pos(client.pos, client.size, from_pos, to_pos, to_size);
+        }
+        workspace.monitor = monitor;
+    }
+
+    /// Return a list of the workspaces names.
+    pub fn workspaces_names(&self) -> Vec<String> {
+        self.workspaces.keys().cloned().collect()
+    }
+
+    /// Add a client to the state.
+    ///
+    /// Return an error if the client already exists.
+    pub fn add_client(
+        &mut self,
+        window: x::Window,
+        pos: Vector2D,
+        size: Vector2D,
+    ) -> Result<(), Error> {
+        self.add_client_to_workspace(self.active_workspace(), window, pos, size)
+    }
+
+    /// Add a client to a workspace.
+    ///
+    /// Return an error if the workspace is not found or the client already exists.
+    pub fn add_client_to_workspace(
+        &mut self,
+        index: usize,
+        window: x::Window,
+        pos: Vector2D,
+        size: Vector2D,
+    ) -> Result<(), Error> {
+        if self.client_workspace(window).is_some() {
+            return Err(Error::ClientAlreadyExists);
+        }
+
+        let serial = self.next_serial();
+        let (_, workspace) = self
+            .workspaces
+            .get_index_mut(index)
+            .ok_or(Error::WorkspaceNotFound)?;
+        workspace
+            .clients
+            .insert(window, Client::new(window, pos, size, serial));
+        // New windows are mapped on top of the others
+        workspace.stack.push(window);
+        // From now on, the geometry of the window is the one of the client
+        self.window_geometries.remove(&window);
+
+        Ok(())
+    }
+
+    /// Set the class of a client.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_class(&mut self, window: x::Window, class: String) -> Result<(), Error> {
+        let index = self.client_workspace(window).ok_or(Error::ClientNotFound)?;
+        let client = self.workspaces[index]
+            .clients
+            .get_mut(&window)
+            .ok_or(Error::ClientNotFound)?;
+        client.class = Some(class);
+
+        Ok(())
+    }
+
+    /// Set the title of a client.
+    /// The client can be on any workspace.
+    ///
+    /// Return whether the title changed, or an error if the client is not found.
+    pub fn set_client_title(&mut self, window: x::Window, title: String) -> Result<bool, Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        let changed = client.title.as_ref() != Some(&title);
+        client.title = Some(title);
+
+        Ok(changed)
+    }
+
+    /// Set the size hints of a client, which constrain its size when it is resized.
+    /// The client can be on any workspace.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_size_hints(
+        &mut self,
+        window: x::Window,
+        size_hints: SizeHints,
+    ) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.size_hints = size_hints;
+
+        Ok(())
+    }
+
+    /// Set whether a client is not rectangular.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_shaped(&mut self, window: x::Window, shaped: bool) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.shaped = shaped;
+
+        Ok(())
+    }
+
+    /// Set the instance name of the application owning a client.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_instance(
+        &mut self,
+        window: x::Window,
+        instance: Option<String>,
+    ) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.instance = instance;
+
+        Ok(())
+    }
+
+    /// Set the process id of the client owning a window.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_pid(&mut self, window: x::Window, pid: Option<u32>) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.pid = pid;
+
+        Ok(())
+    }
+
+    /// Set the types of a client, lowercase and without the _NET_WM_WINDOW_TYPE_ prefix.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_types(&mut self, window: x::Window, types: Vec<String>) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.types = types;
+
+        Ok(())
+    }
+
+    /// Set the protocols a client takes part in.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_protocols(
+        &mut self,
+        window: x::Window,
+        protocols: Vec<x::Atom>,
+    ) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.protocols = protocols;
+
+        Ok(())
+    }
+
+    /// Set the window a client is a dialog of.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_transient_for(
+        &mut self,
+        window: x::Window,
+        transient_for: Option<x::Window>,
+    ) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.transient_for = transient_for;
+
+        Ok(())
+    }
+
+    /// Set the depth of a client, as reported by the X server.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_depth(&mut self, window: x::Window, depth: u8) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.depth = depth;
+
+        Ok(())
+    }
+
+    /// Set the invisible shadow around a client drawing its own decorations.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_frame_extents(
+        &mut self,
+        window: x::Window,
+        frame_extents: Strut,
+    ) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.frame_extents = frame_extents;
+
+        Ok(())
+    }
+
+    /// Set whether a client demands attention.
+    /// The client can be on any workspace.
+    ///
+    /// Return an error if the client is not found.
+    pub fn set_client_urgent(&mut self, window: x::Window, urgent: bool) -> Result<(), Error> {
+        let serial = self.next_serial();
+        let index = self.client_workspace(window).ok_or(Error::ClientNotFound)?;
+        let client = self.workspaces[index]
+            .clients
+            .get_mut(&window)
+            .ok_or(Error::ClientNotFound)?;
+        client.urgent_since = match client.urgent_since {
+            // The hint can be set again while the window is already urgent
+            Some(since) if urgent => Some(since),
+            None if urgent => Some(serial),
+            _ => None,
+        };
+
+        Ok(())
+    }
+
+    /// Get a new serial, greater than the previous ones.
+    fn next_serial(&mut self) -> u64 {
+        self.serial += 1;
+        self.serial
+    }
+
+    /// Record that the window manager is unmapping a client, e.g. to hide its workspace.
+    ///
+    /// Return an error if the client is not found.
+    pub fn expect_unmap(&mut self, window: x::Window) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.pending_unmaps += 1;
+
+        Ok(())
+    }
+
+    /// Record that a client has been unmapped.
+    ///
+    /// Return true if the client withdrew its window, i.e. the window manager did not unmap it,
+    /// in which case the client should be removed.
+    /// Return false if the window is not a client.
+    pub fn client_unmapped(&mut self, window: x::Window) -> bool {
+        match self.client_mut(window) {
+            Some(client) if client.pending_unmaps > 0 => {
+                client.pending_unmaps -= 1;
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// Remove a client from the state.
+    ///
+    /// If the client was focused, the focus falls back to the most recently focused window.
+    /// Return an error if the client is not found.
+    pub fn remove_client(&mut self, window: x::Window) -> Result<(), Error> {
+        let index = self.client_workspace(window).ok_or(Error::ClientNotFound)?;
+        let workspace = &mut self.workspaces[index];
+
+        // A withdrawn window may be mapped again, where it was left
+        if let Some(client) = workspace.clients.shift_remove(&window) {
+            self.window_geometries
+                .insert(window, (client.pos, client.size));
+        }
+        workspace.focus_history.retain(|w| *w != window);
+        workspace.stack.retain(|w| *w != window);
+        if self.focused == Some(window) {
+            self.focused = workspace.focus_history.front().copied();
+        }
+        if self.hovered == Some(window) {
+            self.hovered = None;
+        }
+        if self.dragging == Some(window) {
+            self.dragging = None;
+        }
+        self.marks.retain(|_, marked| *marked != window);
+        self.mru_cycle = None;
+
+        Ok(())
+    }
+
+    /// Move a client to another workspace.
+    ///
+    /// The client keeps its geometry and, if focused, its focus.
+    /// Return an error if the client or the workspace is not found.
+    pub fn move_client_to_workspace(
+        &mut self,
+        window: x::Window,
+        index: usize,
+    ) -> Result<(), Error> {
+        let from = self.client_workspace(window).ok_or(Error::ClientNotFound)?;
+        if index >= self.workspaces.len() {
+            return Err(Error::WorkspaceNotFound);
+        }
+        if from == index {
+            return Ok(());
+        }
+
+        let workspace = &mut self.workspaces[from];
+        let client = workspace
+            .clients
+            .shift_remove(&window)
+            .expect("Unexpected: no client");
+        workspace.focus_history.retain(|w| *w != window);
+        workspace.stack.retain(|w| *w != window);
+        self.workspaces[index].clients.insert(window, client);
+        self.workspaces[index].stack.push(window);
+        self.mru_cycle = None;
+
+        if self.focused == Some(window) {
+            self.set_focused(Some(window));
+        }
+
+        Ok(())
+    }
+
+    /// Start moving or resizing a client with the pointer.
+    ///
+    /// The position of the pointer and the geometry of the frame are the starting point of the drag.
+    /// Nothing is dragged if the window is not a client.
+    pub fn start_drag(
+        &mut self,
+        window: x::Window,
+        pointer: Vector2D,
+        frame_pos: Vector2D,
+        frame_size: Vector2D,
+    ) {
+        self.drag_start_pos = pointer;
+        self.drag_start_frame_pos = frame_pos;
+        self.drag_start_frame_size = frame_size;
+        self.dragging = self.client_workspace(window).map(|_| window);
+    }
+
+    /// Get the client being dragged, if any.
+    pub fn dragging(&self) -> Option<x::Window> {
+        self.dragging
+    }
+
+    /// End the drag in progress and return the client which was dragged, if any.
+    pub fn end_drag(&mut self) -> Option<x::Window> {
+        self.dragging.take()
+    }
+
+    /// Drag a client and return its new position.
+    ///
+    /// Return an error if the client is not found.
+    pub fn drag_client(
+        &mut self,
+        window: x::Window,
+        mouse_pos: Vector2D,
+    ) -> Result<Vector2D, Error> {
+        let start_pos = self.drag_start_frame_pos;
+        let new_pos = start_pos + mouse_pos - self.drag_start_pos;
+        if let Some(client) = self.client_mut(window) {
+            // The whole drag is undone at once
+            client.push_geometry(start_pos, client.size);
+            client.pos = new_pos;
+
+            Ok(new_pos)
+        } else {
+            Err(Error::ClientNotFound)
+        }
+    }
+
+    /// Resize a client by dragging it and return its new position and size.
+    ///
+    /// The corner closest to where the drag started is moved, while the opposite one stays fixed.
+    /// Return an error if the client is not found.
+    pub fn drag_resize_client(
+        &mut self,
+        window: x::Window,
+        mouse_pos: Vector2D,
+    ) -> Result<(Vector2D, Vector2D), Error> {
+        let start_pos = self.drag_start_frame_pos;
+        let start_size = self.drag_start_frame_size;
+        let delta = mouse_pos - self.drag_start_pos;
+        // The quadrant of the window where the drag started
+        let grab = self.drag_start_pos - start_pos;
+        let left = grab.x < start_size.x / 2;
+        let top = grab.y < start_size.y / 2;
+
+        if let Some(client) = self.client_mut(window) {
+            let (min, max) = (client.min_size(), client.max_size());
+            let (x, width) = resize_edge(start_pos.x, start_size.x, delta.x, (min.x, max.x), left);
+            let (y, height) = resize_edge(start_pos.y, start_size.y, delta.y, (min.y, max.y), top);
+            // The whole drag is undone at once
+            client.push_geometry(start_pos, start_size);
+            client.pos = Vector2D::new(x, y);
+            client.size = Vector2D::new(width, height);
+
+            Ok((client.pos, client.size))
+        } else {
+            Err(Error::ClientNotFound)
+        }
+    }
+
+    /// Teleport a client to a new position.
+    ///
+    /// Return an error if the client is not found.
+    pub fn teleport_client(&mut self, window: x::Window, pos: Vector2D) -> Result<(), Error> {
+        if let Some(client) = self.client_mut(window) {
+            client.set_geometry(pos, client.size);
+
+            Ok(())
+        } else {
+            Err(Error::ClientNotFound)
+        }
+    }
+
+    /// Resize a client and return it.
+    ///
+    /// If relative is true, the size is added to the current one.
+    /// If center is true, the center of the client stays fixed, otherwise its top left corner.
+    /// Return an error if the client is not found.
+    pub fn resize_client(
+        &mut self,
+        selector: WindowSelector,
+        size: Vector2D,
+        relative: bool,
+        center: bool,
+    ) -> Result<&Client, Error> {
+        let window = self.select_client(selector)?.window;
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+
+        let new_size = client.constrain_size(if relative { client.size + size } else { size });
+        let new_pos = if center {
+            let growth = new_size - client.size;
+            client.pos - Vector2D::new(growth.x / 2, growth.y / 2)
+        } else {
+            client.pos
+        };
+        client.set_geometry(new_pos, new_size);
+
+        Ok(client)
+    }
+
+    /// Decide the geometry of a client which asked to be moved or resized, apply it and return the client.
+    ///
+    /// Only the values in the mask are requested, the others are kept.
+    /// Every workspace is floating, so the client gets the requested geometry,
+    /// with the size constrained by its size hints.
+    /// The geometry is not saved in the history, as it was not changed by the user.
+    /// Return an error if the client is not found.
+    pub fn configure_request_geometry(
+        &mut self,
+        window: x::Window,
+        mask: x::ConfigWindowMask,
+        pos: Vector2D,
+        size: Vector2D,
+    ) -> Result<&Client, Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        let value = |flag, requested, current| {
+            if mask.contains(flag) {
+                requested
+            } else {
+                current
+            }
+        };
+        let pos = Vector2D::new(
+            value(x::ConfigWindowMask::X, pos.x, client.pos.x),
+            value(x::ConfigWindowMask::Y, pos.y, client.pos.y),
+        );
+        let size = Vector2D::new(
+            value(x::ConfigWindowMask::WIDTH, size.x, client.size.x),
+            value(x::ConfigWindowMask::HEIGHT, size.y, client.size.y),
+        );
+        client.pos = pos;
+        client.size = client.constrain_size(size);
+
+        Ok(client)
+    }
+
+    /// Record the geometry of a client reported by the X server.
+    ///
+    /// It is normally the one set by the window manager already,
+    /// but the state follows the server when they differ.
+    /// The geometry is not saved in the history.
+    /// Return an error if the client is not found.
+    pub fn client_configured(
+        &mut self,
+        window: x::Window,
+        pos: Vector2D,
+        size: Vector2D,
+    ) -> Result<(), Error> {
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        client.pos = pos;
+        client.size = size;
+
+        Ok(())
+    }
+
+    /// Record the geometry of a top-level window which is not a client,
+    /// as reported by the X server when it is created or configured.
+    pub fn window_configured(&mut self, window: x::Window, pos: Vector2D, size: Vector2D) {
+        if self.client_workspace(window).is_none() {
+            self.window_geometries.insert(window, (pos, size));
+        }
+    }
+
+    /// Get the last known geometry of a top-level window which is not a client, if any.
+    pub fn window_geometry(&self, window: x::Window) -> Option<(Vector2D, Vector2D)> {
+        self.window_geometries.get(&window).copied()
+    }
+
+    /// Forget the geometry of a destroyed window.
+    pub fn window_destroyed(&mut self, window: x::Window) {
+        self.window_geometries.remove(&window);
+    }
+
+    /// Snap a client to a portion of the work area and return it.
+    ///
+    /// The border width is taken into account so that the whole window fits the portion.
+    /// Return an error if the client is not found.
+    pub fn snap_client(
+        &mut self,
+        selector: WindowSelector,
+        position: SnapPosition,
+        border_width: u32,
+    ) -> Result<&Client, Error> {
+        let window = self.select_client(selector)?.window;
+        let workspace = self.client_workspace(window).ok_or(Error::ClientNotFound)?;
+        let (area_pos, area_size) = self.workspace_area(workspace);
+        let (pos, size) = snap_geometry(area_pos, area_size, position);
+        let borders = Vector2D::new(2 * border_width as i32, 2 * border_width as i32);
+
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+        // The shadow of client-side decorations may go past the work area
+        let extents = client.frame_extents;
+        let pos = pos - Vector2D::new(extents.left, extents.top);
+        let size = size + Vector2D::new(extents.left + extents.right, extents.top + extents.bottom);
+        client.save_snapshot(match position {
+            SnapPosition::Maximized => GeometrySnapshot::Maximized,
+            _ => GeometrySnapshot::Snapped,
+        });
+        client.set_geometry(pos, (size - borders).max(MIN_CLIENT_SIZE));
+
+        Ok(client)
+    }
+
+    /// Restore the geometry a client had before it was last snapped or maximized,
+    /// however many times it was.
+    /// A window snapped while maximized gets maximized again, and the other way around.
+    ///
+    /// Return an error if the client is not found or is not snapped.
+    pub fn unsnap_client(&mut self, selector: WindowSelector) -> Result<&Client, Error> {
+        let window = self.select_client(selector)?.window;
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+
+        let (pos, size) = client.take_snapshot().ok_or(Error::NotSnapped)?;
+        client.set_geometry(pos, size);
+
+        Ok(client)
+    }
+
+    /// Revert the most recent geometry change of a client and return it.
+    ///
+    /// Return an error if the client is not found or has no previous geometry.
+    pub fn undo_client_geometry(&mut self, selector: WindowSelector) -> Result<&Client, Error> {
+        let window = self.select_client(selector)?.window;
+        let client = self.client_mut(window).ok_or(Error::ClientNotFound)?;
+
+        let (pos, size) = client
+            .geometry_history
+            .pop_front()
+            .ok_or(Error::NothingToUndo)?;
+        client.pos = pos;
+        client.size = size;
+
+        Ok(client)
+    }
+
+    /// Decide where a client dragged to the pointer position should be snapped.
+    ///
+    /// The top edge of the work area maximizes, the left and right edges snap to that half.
+    /// The work area is the one of the monitor under the pointer.
+    /// Return None if the pointer is not within the threshold of an edge.
+    pub fn edge_snap_position(&self, pointer: Vector2D, threshold: i32) -> Option<SnapPosition> {
+        let (area_pos, area_size) = match self.workspace_at(pointer) {
+            Some(index) => self.workspace_area(index),
+            None => self.work_area(),
+        };
+        let area_end = area_pos + area_size - Vector2D::new(1, 1);
+
+        if pointer.y <= area_pos.y + threshold {
+            Some(SnapPosition::Maximized)
+        } else if pointer.x <= area_pos.x + threshold {
+            Some(SnapPosition::LeftHalf)
+        } else if pointer.x >= area_end.x - threshold {
+            Some(SnapPosition::RightHalf)
+        } else {
+            None
+        }
+    }
+
+    /// Get the position and size of the area available to the clients.
+    pub fn work_area(&self) -> (Vector2D, Vector2D) {
+        self.workspace_area(self.active_workspace())
+    }
+
+    /// Get the index of the workspace shown on the monitor containing a point.
+    ///
+    /// Return None if the point is outside of the monitors.
+    pub fn workspace_at(&self, point: Vector2D) -> Option<usize> {
+        self.monitor_at(point)
+            .map(|index| self.monitors[index].workspace)
+    }
+
+    /// Get the index of the monitor containing a point.
+    fn monitor_at(&self, point: Vector2D) -> Option<usize> {
+        self.monitors.iter().position(|monitor| {
+            let end = monitor.pos + monitor.size;
+            point.x >= monitor.pos.x
+                && point.y >= monitor.pos.y
+                && point.x < end.x
+                && point.y < end.y
+        })
+    }
+
+    /// Add or update a dock window reserving space at the edges of the screen.
+    ///
+    /// The dock belongs to the monitor containing its center,
+    /// or the primary monitor if it lies outside of all monitors.
+    pub fn set_dock(&mut self, window: x::Window, pos: Vector2D, size: Vector2D, strut: Strut) {
+        let center = Vector2D::new(pos.x + size.x / 2, pos.y + size.y / 2);
+        let monitor = self.monitor_at(center).unwrap_or_else(|| {
+            self.select_monitor(MonitorSelector::Primary)
+                .unwrap_or_default()
+        });
+
+        self.docks.insert(window, Dock { monitor, strut });
+    }
+
+    /// Check whether a window is a dock.
+    pub fn is_dock(&self, window: x::Window) -> bool {
+        self.docks.contains_key(&window)
+    }
+
+    /// Remove a dock window.
+    ///
+    /// Return whether the window was a dock.
+    pub fn remove_dock(&mut self, window: x::Window) -> bool {
+        self.docks.remove(&window).is_some()
+    }
+
+    /// Get the space reserved by the docks on a monitor.
+    ///
+    /// Struts are relative to the edges of the screen,
+    /// so only the part that reaches into the monitor is reserved.
+    fn monitor_strut(&self, index: usize) -> Strut {
+        let monitor = &self.monitors[index];
+        let monitor_end = monitor.pos + monitor.size;
+        let screen_end = self
+            .monitors
+            .iter()
+            .fold(Vector2D::new(0, 0), |end, monitor| {
+                let monitor_end = monitor.pos + monitor.size;
+                Vector2D::new(end.x.max(monitor_end.x), end.y.max(monitor_end.y))
+            });
+
+        self.docks
+            .values()
+            .filter(|dock| dock.monitor == index)
+            .fold(Strut::default(), |reserved, dock| Strut {
+                left: reserved.left.max(dock.strut.left - monitor.pos.x),
+                right: reserved
+                    .right
+                    .max(dock.strut.right - (screen_end.x - monitor_end.x)),
+                top: reserved.top.max(dock.strut.top - monitor.pos.y),
+                bottom: reserved
+                    .bottom
+                    .max(dock.strut.bottom - (screen_end.y - monitor_end.y)),
+            })
+    }
+
+    /// Check whether a client is on a workspace shown on a monitor.
+    fn is_client_visible(&self, window: x::Window) -> bool {
+        self.client_workspace(window)
+            .is_some_and(|index| self.monitors.iter().any(|m| m.workspace == index))
+    }
+
+    /// Get the position and size of the area available to the clients of a workspace.
+    /// This is the area of the monitor the workspace belongs to,
+    /// minus the space reserved by the docks on that monitor and its padding.
+    pub fn workspace_area(&self, index: usize) -> (Vector2D, Vector2D) {
+        let monitor_index = self.workspaces[index].monitor;
+        let monitor = &self.monitors[monitor_index];
+        let strut = self.monitor_strut(monitor_index);
+        let padding = monitor.padding;
+
+        let left = strut.left + padding.left;
+        let right = strut.right + padding.right;
+        let top = strut.top + padding.top;
+        let bottom = strut.bottom + padding.bottom;
+
+        (
+            Vector2D::new(monitor.pos.x + left, monitor.pos.y + top),
+            Vector2D::new(monitor.size.x - left - right, monitor.size.y - top - bottom),
+        )
+    }
+
+    /// Focus a client, saving the last focused client.
+    ///
+    /// Return an error if the client is not found.
+    pub fn focus_client(&mut self, selector: WindowSelector) -> Result<Option<x::Window>, Error> {
+        // Root window focus is used to unfocus the current window.
+        if let WindowSelector::Window(window) = selector {
+            if self.root.resource_id() == window {
+                self.set_focused(None);
+                return Ok(None);
+            }
+        }
+
+        let client = self.select_client(selector)?.clone();
+
+        self.set_focused(Some(client.window));
+        Ok(Some(client.window))
+    }
+
+    /// Mark a client with a name.
+    ///
+    /// If the name is already used by another client, the mark is moved.
+    /// Return an error if the client is not found.
+    pub fn mark_client(&mut self, selector: WindowSelector, name: String) -> Result<(), Error> {
+        let window = self.select_client(selector)?.window;
+        self.marks.insert(name, window);
+
+        Ok(())
+    }
+
+    /// Get the index of the workspace containing the client.
+    pub fn client_workspace(&self, window: x::Window) -> Option<usize> {
+        self.workspaces
+            .values()
+            .position(|workspace| workspace.clients.contains_key(&window))
+    }
+
+    /// Focus the next or previous window in most recently used order and return it.
+    ///
+    /// Consecutive calls continue the same cycle, as long as the focus is not changed otherwise
+    /// or the cycle is ended with `end_cycle_mru`.
+    /// Return an error if there are no clients to cycle through.
+    pub fn cycle_mru(
+        &mut self,
+        direction: CycleDirection,
+        all_workspaces: bool,
+    ) -> Result<x::Window, Error> {
+        let cycle = match self.mru_cycle.take() {
+            Some(cycle)
+                if cycle.all_workspaces == all_workspaces
+                    && Some(cycle.windows[cycle.index]) == self.focused =>
+            {
+                let len = cycle.windows.len();
+                let index = match direction {
+                    CycleDirection::Next => (cycle.index + 1) % len,
+                    CycleDirection::Prev => (cycle.index + len - 1) % len,
+                };
+
+                MruCycle { index, ..cycle }
+            }
+            _ => {
+                let windows = self.mru_windows(all_workspaces);
+                if windows.is_empty() {
+                    return Err(Error::ClientNotFound);
+                }
+
+                let index = match direction {
+                    CycleDirection::Next if windows.first() == self.focused.as_ref() => {
+                        1 % windows.len()
+                    }
+                    CycleDirection::Next => 0,
+                    CycleDirection::Prev => windows.len() - 1,
+                };
+
+                MruCycle {
+                    windows,
+                    histories: self
+                        .workspaces
+                        .values()
+                        .map(|workspace| workspace.focus_history.clone())
+                        .collect(),
+                    index,
+                    all_workspaces,
+                }
+            }
+        };
+
+        // Windows visited while cycling are not recorded in the focus history
+        for (workspace, history) in self.workspaces.values_mut().zip(&cycle.histories) {
+            workspace.focus_history = history.clone();
+        }
+
+        let window = cycle.windows[cycle.index];
+        self.set_focused(Some(window));
+        self.mru_cycle = Some(cycle);
+
+        Ok(window)
+    }
+
+    /// End the most recently used cycle in progress, if any.
+    pub fn end_cycle_mru(&mut self) {
+        self.mru_cycle = None;
+    }
+
+    /// Get the windows from the most to the least recently used.
+    ///
+    /// The active workspace comes first, followed by the other workspaces if requested.
+    /// Windows that were never focused come after the ones in the focus history.
+    fn mru_windows(&self, all_workspaces: bool) -> Vec<x::Window> {
+        let active_workspace = self.workspaces.get_index(self.active_workspace());
+        let other_workspaces = self
+            .workspaces
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| all_workspaces && *index != self.active_workspace())
+            .map(|(_, workspace)| workspace);
+
+        active_workspace
+            .into_iter()
+            .chain(other_workspaces)
+            .flat_map(|(_, workspace)| {
+                let never_focused = workspace
+                    .clients
+                    .keys()
+                    .filter(|window| !workspace.focus_history.contains(window));
+
+                workspace
+                    .focus_history
+                    .iter()
+                    .chain(never_focused)
+                    .copied()
+                    .collect::<Vec<_>>()
+            })
+            .collect()
+    }
+
+    /// Decide how the focus should change when the pointer enters a window,
+    /// according to the focus model.
+    ///
+    /// Return a selector to be passed to `focus_client`, or None if the focus should not change.
+    /// Entering the root window is reported as a selector of the root window, which unfocuses.
+    pub fn pointer_enter_focus(
+        &self,
+        focus_model: FocusModel,
+        window: x::Window,
+    ) -> Option<WindowSelector> {
+        match focus_model {
+            FocusModel::Click => None,
+            FocusModel::Sloppy | FocusModel::Strict if window == self.root => {
+                if focus_model == FocusModel::Strict && self.focused.is_some() {
+                    Some(WindowSelector::Window(self.root.resource_id()))
+                } else {
+                    None
+                }
+            }
+            FocusModel::Sloppy | FocusModel::Strict => {
+                if self.focused != Some(window) && self.is_client_visible(window) {
+                    Some(WindowSelector::Window(window.resource_id()))
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Track the client under the pointer when the pointer enters a window.
+    ///
+    /// Entering the root or an unmanaged window means that no client is under the pointer.
+    pub fn pointer_enter(&mut self, window: x::Window) {
+        self.hovered = self.client_workspace(window).map(|_| window);
+    }
+
+    /// Forget the client under the pointer when the pointer leaves it.
+    pub fn pointer_leave(&mut self, window: x::Window) {
+        if self.hovered == Some(window) {
+            self.hovered = None;
+        }
+    }
+
+    /// Get the active workspace clients.
+    pub fn active_workspace_clients(&self) -> &IndexMap<x::Window, Client> {
+        // We can unwrap here because we know the workspace exists.
+        let (_, workspace) = self.workspaces.get_index(self.active_workspace()).unwrap();
+
+        &workspace.clients
+    }
+
+    /// Get a client, whatever its workspace.
+    fn client(&self, window: x::Window) -> Option<&Client> {
+        self.workspaces
+            .values()
+            .find_map(|workspace| workspace.clients.get(&window))
+    }
+
+    /// Get a mutable client, whatever its workspace.
+    fn client_mut(&mut self, window: x::Window) -> Option<&mut Client> {
+        self.workspaces
+            .values_mut()
+            .find_map(|workspace| workspace.clients.get_mut(&window))
+    }
+
+    /// Select a client using a selector.
+    ///
+    /// Return an error if no matching client has been found.
+    pub fn select_client(&self, selector: WindowSelector) -> Result<&Client, Error> {
+        match selector {
+            WindowSelector::Focused => {
+                if let Some(window) = self.focused {
+                    self.client(window).ok_or(Error::ClientNotFound)
+                } else {
+                    Err(Error::ClientNotFound)
+                }
+            }
+            WindowSelector::Window(window) => unsafe {
+                self.client(x::Window::new(window))
+                    .ok_or(Error::ClientNotFound)
+            },
+            WindowSelector::Closest(direction) => self.select_client_closest(direction),
+            WindowSelector::Cycle(direction) => self.select_client_cycle(direction),
+            WindowSelector::Mark(name) => self.select_client_mark(&name),
+            WindowSelector::LastFocused => self
+                .last_focused()
+                .and_then(|window| self.active_workspace_clients().get(&window))
+                .ok_or(Error::ClientNotFound),
+            WindowSelector::Urgent => self.select_client_urgent(),
+            WindowSelector::UnderPointer => self
+                .hovered
+                .and_then(|window| self.client(window))
+                .ok_or(Error::ClientNotFound),
+            WindowSelector::Biggest => self.select_client_by_key(|client| -client.area()),
+            WindowSelector::Smallest => self.select_client_by_key(Client::area),
+            WindowSelector::Oldest => {
+                self.select_client_by_key(|client| client.managed_since as i64)
+            }
+            WindowSelector::Newest => {
+                self.select_client_by_key(|client| -(client.managed_since as i64))
+            }
+            WindowSelector::Class(_) | WindowSelector::Title(_) | WindowSelector::All => {
+                let window = self.select_clients(selector)?[0];
+                self.client(window).ok_or(Error::ClientNotFound)
+            }
+        }
+    }
+
+    /// Select the client of the active workspace with the smallest key.
+    /// The least recently managed client wins a tie.
+    fn select_client_by_key(&self, key: impl Fn(&Client) -> i64) -> Result<&Client, Error> {
+        self.active_workspace_clients()
+            .values()
+            .min_by_key(|client| (key(client), client.managed_since))
+            .ok_or(Error::ClientNotFound)
+    }
+
+    /// Select the clients matching a selector which can match several windows,
+    /// in the same order as `select_client`, or the client of any other selector.
+    ///
+    /// Return an error if no matching client has been found.
+    pub fn select_clients(&self, selector: WindowSelector) -> Result<Vec<x::Window>, Error> {
+        let windows: Vec<_> = match &selector {
+            WindowSelector::All => self.active_workspace_clients().keys().copied().collect(),
+            WindowSelector::Class(pattern) => self.select_clients_matching(|client| {
+                client
+                    .class
+                    .as_deref()
+                    .is_some_and(|class| glob::matches(pattern, class))
+            }),
+            WindowSelector::Title(pattern) => self.select_clients_matching(|client| {
+                client
+                    .title
+                    .as_deref()
+                    .is_some_and(|title| glob::matches(pattern, title))
+            }),
+            _ => vec![self.select_client(selector)?.window],
+        };
+
+        if windows.is_empty() {
+            return Err(Error::ClientNotFound);
+        }
+        Ok(windows)
+    }
+
+    /// Select the clients matching a condition, on any workspace.
+    ///
+    /// The clients of the active workspace come first, most recently focused first.
+    fn select_clients_matching(&self, condition: impl Fn(&Client) -> bool) -> Vec<x::Window> {
+        let active_clients = self.active_workspace_clients();
+        let recently_focused = self
+            .active_focus_history()
+            .iter()
+            .filter_map(|window| active_clients.get(window));
+
+        let mut windows = Vec::new();
+        for client in recently_focused.chain(active_clients.values()).chain(
+            self.workspaces
+                .values()
+                .flat_map(|workspace| workspace.clients.values()),
+        ) {
+            if condition(client) && !windows.contains(&client.window) {
+                windows.push(client.window);
+            }
+        }
+
+        windows
+    }
+
+    /// Select the client demanding attention for the longest time, on any workspace.
+    fn select_client_urgent(&self) -> Result<&Client, Error> {
+        self.workspaces
+            .values()
+            .flat_map(|workspace| workspace.clients.values())
+            .filter_map(|client| client.urgent_since.map(|since| (since, client)))
+            .min_by_key(|(since, _)| *since)
+            .map(|(_, client)| client)
+            .ok_or(Error::ClientNotFound)
+    }
+
+    fn select_client_mark(&self, name: &str) -> Result<&Client, Error> {
+        let window = self.marks.get(name).ok_or(Error::MarkNotFound)?;
+
+        self.client(*window).ok_or(Error::ClientNotFound)
+    }
+
+    fn select_client_cycle(&self, direction: CycleDirection) -> Result<&Client, Error> {
+        let window = if let Some(window) = self.focused {
+            window
+        } else {
+            return Err(Error::ClientNotFound);
+        };
+
+        let index = self
+            .active_workspace_clients()
+            .get_index_of(&window)
+            .expect("Focused client not found");
+
+        let index = cycle_index(
+            index,
+            self.active_workspace_clients().len(),
+            direction,
+            self.wrap,
+        );
+        self.active_workspace_clients()
+            .get_index(index)
+            .map(|(_, client)| client)
+            .ok_or(Error::ClientNotFound)
+    }
+
+    fn select_client_closest(&self, direction: CardinalDirection) -> Result<&Client, Error> {
+        let client = if let Some(focused) = self.focused {
+            self.active_workspace_clients()
+                .get(&focused)
+                .expect("Focused client not found")
+        } else {
+            return Err(Error::ClientNotFound);
+        };
+
+        let closest_client = self
+            .active_workspace_clients()
+            .values()
+            .filter(|c| c.window != client.window)
+            .filter_map(|c| Some((Self::directional_score(client, c, &direction)?, c)))
+            .min_by_key(|(score, _)| *score)
+            .map(|(_, c)| c);
+
+        match closest_client {
+            None if self.directional_wrap => self.select_client_wrapped(client, direction),
+            None => Err(Error::ClientNotFound),
+            Some(closest_client) => Ok(closest_client),
+        }
+    }
+
+    /// Score a client as the target of moving the focus in a direction from another one,
+    /// the lower the better, or None if it is not in that direction.
+    ///
+    /// The center of the target must be past the center of the focused client in the direction,
+    /// and the target must either overlap the focused client across the direction,
+    /// e.g. share some rows when moving east, or lie within the 45° cone around the direction.
+    /// Overlapping targets come first, then the closest ones, their offset across the direction
+    /// counting twice so that the windows level with the focused one are preferred.
+    fn directional_score(
+        from: &Client,
+        to: &Client,
+        direction: &CardinalDirection,
+    ) -> Option<(bool, i64)> {
+        // The centers are doubled to stay integer
+        let center = |c: &Client| {
+            (
+                2 * c.pos.x as i64 + c.size.x as i64,
+                2 * c.pos.y as i64 + c.size.y as i64,
+            )
+        };
+        let overlaps = |start: i32, size: i32, other_start: i32, other_size: i32| {
+            start < other_start + other_size && other_start < start + size
+        };
+        let (from_center, to_center) = (center(from), center(to));
+
+        let (along, across, overlap) = match direction {
+            CardinalDirection::East | CardinalDirection::West => (
+                to_center.0 - from_center.0,
+                to_center.1 - from_center.1,
+                overlaps(from.pos.y, from.size.y, to.pos.y, to.size.y),
+            ),
+            CardinalDirection::North | CardinalDirection::South => (
+                to_center.1 - from_center.1,
+                to_center.0 - from_center.0,
+                overlaps(from.pos.x, from.size.x, to.pos.x, to.size.x),
+            ),
+        };
+        let along = match direction {
+            CardinalDirection::East | CardinalDirection::South => along,
+            CardinalDirection::West | CardinalDirection::North => -along,
+        };
+
+        if along <= 0 || (!overlap && across.abs() > along) {
+            return None;
+        }
+
+        Some((!overlap, along + 2 * across.abs()))
+    }
+
+    /// Select the client at the edge opposite to a direction, e.g. the west-most one for east.
+    /// The client the closest to the focused one across the direction wins a tie.
+    fn select_client_wrapped(
+        &self,
+        client: &Client,
+        direction: CardinalDirection,
+    ) -> Result<&Client, Error> {
+        self.active_workspace_clients()
+            .values()
+            .filter(|c| c.window != client.window)
+            .min_by_key(|c| match direction {
+                CardinalDirection::East => (c.pos.x, (c.pos.y - client.pos.y).abs()),
+                CardinalDirection::West => (-c.pos.x, (c.pos.y - client.pos.y).abs()),
+                CardinalDirection::South => (c.pos.y, (c.pos.x - client.pos.x).abs()),
+                CardinalDirection::North => (-c.pos.y, (c.pos.x - client.pos.x).abs()),
+            })
+            .ok_or(Error::ClientNotFound)
+    }
+
+    /// Set the focused window.
+    /// Push the window on top of the focus history of its workspace.
+    fn set_focused(&mut self, window: Option<x::Window>) {
+        if let Some(window) = window {
+            if let Some(index) = self.client_workspace(window) {
+                // We can unwrap here because we know the workspace exists.
+                let (_, workspace) = self.workspaces.get_index_mut(index).unwrap();
+                workspace.focus_history.retain(|w| *w != window);
+                workspace.focus_history.push_front(window);
+                workspace.focus_history.truncate(FOCUS_HISTORY_SIZE);
+                // Focused windows are raised
+                let top = workspace.stack.len();
+                Self::restack(workspace, window, top);
+
+                // Focusing a window shown on another monitor focuses that monitor
+                let monitor = workspace.monitor;
+                if self.monitors[monitor].workspace == index {
+                    self.focused_monitor = monitor;
+                }
+            }
+        }
+
+        self.focused = window;
+    }
+
+    /// Move a client in the stacking order of its workspace.
+    ///
+    /// A sibling which is not on the same workspace, e.g. a dock, leaves the order unchanged,
+    /// since the windows of other workspaces are not stacked with it.
+    /// Return an error if the client is not found.
+    pub fn restack_client(
+        &mut self,
+        window: x::Window,
+        position: StackPosition,
+    ) -> Result<(), Error> {
+        let index = self.client_workspace(window).ok_or(Error::ClientNotFound)?;
+        let workspace = &mut self.workspaces[index];
+        let sibling_index = |sibling| {
+            workspace
+                .stack
+                .iter()
+                .filter(|w| **w != window)
+                .position(|w| *w == sibling)
+        };
+
+        let to = match position {
+            StackPosition::Top => workspace.stack.len(),
+            StackPosition::Bottom => 0,
+            StackPosition::Above(sibling) => match sibling_index(sibling) {
+                Some(i) => i + 1,
+                None => return Ok(()),
+            },
+            StackPosition::Below(sibling) => match sibling_index(sibling) {
+                Some(i) => i,
+                None => return Ok(()),
+            },
+        };
+        Self::restack(workspace, window, to);
+
+        Ok(())
+    }
+
+    /// Move a window of a workspace to a position of its stack, counted without the window.
+    fn restack(workspace: &mut Workspace, window: x::Window, to: usize) {
+        workspace.stack.retain(|w| *w != window);
+        let to = to.min(workspace.stack.len());
+        workspace.stack.insert(to, window);
+    }
+
+    /// Get the focus history of the active workspace.
+    fn active_focus_history(&self) -> &VecDeque<x::Window> {
+        // We can unwrap here because we know the workspace exists.
+        let (_, workspace) = self.workspaces.get_index(self.active_workspace()).unwrap();
+
+        &workspace.focus_history
+    }
+
+    /// Get the focused window.
+    pub fn focused(&self) -> Option<x::Window> {
+        self.focused
+    }
+
+    /// Get the last focused window.
+    /// This is the most recently focused window of the active workspace, other than the focused one.
+    pub fn last_focused(&self) -> Option<x::Window> {
+        self.active_focus_history()
+            .iter()
+            .find(|window| Some(**window) != self.focused)
+            .copied()
+    }
+}
+
+/// Resize a window along one axis by moving one of its edges by delta.
+///
+/// If `start_edge` is true the edge at the start of the axis is moved, otherwise the end one.
+/// The opposite edge stays fixed, even when the minimum or maximum size is reached.
+/// Return the new start coordinate and length.
+fn resize_edge(
+    start: i32,
+    length: i32,
+    delta: i32,
+    (min_length, max_length): (i32, i32),
+    start_edge: bool,
+) -> (i32, i32) {
+    if start_edge {
+        let new_length = (length - delta).clamp(min_length, max_length);
+        (start + length - new_length, new_length)
+    } else {
+        (start, (length + delta).clamp(min_length, max_length))
+    }
+}
+
+/// Compute the index following the given one in a cycle of `len` elements.
+///
+/// Without wrapping, the first and last indexes are kept when moving past them.
+fn cycle_index(index: usize, len: usize, direction: CycleDirection, wrap: bool) -> usize {
+    match direction {
+        CycleDirection::Next if wrap => (index + 1) % len,
+        CycleDirection::Next => (index + 1).min(len - 1),
+        CycleDirection::Prev if wrap => (index + len - 1) % len,
+        CycleDirection::Prev => index.saturating_sub(1),
+    }
+}
+
+/// Translate a position from a monitor to another one, keeping the offset
+/// from the monitor origin and clamping the window inside the new area.
+fn translate_pos(
+    pos: Vector2D,
+    size: Vector2D,
+    from_pos: Vector2D,
+    to_pos: Vector2D,
+    to_size: Vector2D,
+) -> Vector2D {
+    let offset = pos - from_pos;
+    let max_offset = (to_size - size).max(Vector2D::new(0, 0));
+
+    to_pos
+        + Vector2D::new(
+            offset.x.clamp(0, max_offset.x),
+            offset.y.clamp(0, max_offset.y),
+        )
+}
+
+/// Compute the position and size of a snap position within an area.
+fn snap_geometry(
+    area_pos: Vector2D,
+    area_size: Vector2D,
+    position: SnapPosition,
+) -> (Vector2D, Vector2D) {
+    let half = Vector2D::new(area_size.x / 2, area_size.y / 2);
+    // The second half gets the remaining pixel of odd sizes
+    let rest = area_size - half;
+
+    let (offset, size) = match position {
+        SnapPosition::Maximized => (Vector2D::new(0, 0), area_size),
+        SnapPosition::LeftHalf => (Vector2D::new(0, 0), Vector2D::new(half.x, area_size.y)),
+        SnapPosition::RightHalf => (Vector2D::new(half.x, 0), Vector2D::new(rest.x, area_size.y)),
+        SnapPosition::TopHalf => (Vector2D::new(0, 0), Vector2D::new(area_size.x, half.y)),
+        SnapPosition::BottomHalf => (Vector2D::new(0, half.y), Vector2D::new(area_size.x, rest.y)),
+        SnapPosition::TopLeft => (Vector2D::new(0, 0), half),
+        SnapPosition::TopRight => (Vector2D::new(half.x, 0), Vector2D::new(rest.x, half.y)),
+        SnapPosition::BottomLeft => (Vector2D::new(0, half.y), Vector2D::new(half.x, rest.y)),
+        SnapPosition::BottomRight => (half, rest),
+    };
+
+    (area_pos + offset, size)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use xcb::XidNew;
+
+    #[test]
+    fn test_add_workspace() {
+        let mut state = State::default();
+        state.add_workspace(Some("test".to_owned())).unwrap();
+
+        assert_eq!(state.workspaces.len(), 2);
+        assert!(state.workspaces.contains_key("test"));
+    }
+
+    #[test]
+    fn test_add_workspace_no_name() {
+        let mut state = State::default();
+        state.add_workspace(None).unwrap();
+
+        assert_eq!(state.workspaces.len(), 2);
+        assert!(state.workspaces.contains_key("1"));
+    }
+
+    #[test]
+    fn test_add_workspace_already_exists() {
+        let mut state = State::default();
+        state.add_workspace(Some("test".to_owned())).unwrap();
+
+        assert!(matches!(
+            state.add_workspace(Some("test".to_owned())),
+            Err(Error::WorkspaceAlreadyExists)
+        ));
+    }
+
+    #[test]
+    fn workspaces_names() {
+        let mut state = State::default();
+        state.add_workspace(Some("2".to_owned())).unwrap();
+        state.add_workspace(Some("3".to_owned())).unwrap();
+
+        let workspaces_names = state.workspaces_names();
+
+        assert_eq!(workspaces_names, vec!["1", "2", "3"]);
+    }
+
+    #[test]
+    fn test_activate_workspace() {
+        let mut state = State::default();
+        state.add_workspace(Some("test".to_owned())).unwrap();
+
+        let index = state
+            .activate_workspace(WorkspaceSelector::Name("test".to_string()))
+            .unwrap();
+
+        assert_eq!(1, index);
+        assert_eq!(1, state.active_workspace());
+    }
+
+    #[test]
+    fn test_activate_workspace_not_found() {
+        let mut state = State::default();
+        let result = state.activate_workspace(WorkspaceSelector::Name("test".to_string()));
+
+        assert!(matches!(result, Err(Error::WorkspaceNotFound)));
+        assert_eq!(0, state.active_workspace());
+    }
+
+    #[test]
+    fn select_workspace_cycle() {
+        let mut state = State::default();
+        state.add_workspace(Some("second".to_owned())).unwrap();
+        state.add_workspace(Some("third".to_owned())).unwrap();
+
+        let index = state.select_workspace_cycle(CycleDirection::Next);
+        assert_eq!(1, index);
+
+        let index = state.select_workspace_cycle(CycleDirection::Prev);
+        assert_eq!(2, index);
+    }
+
+    #[test]
+    fn test_add_client() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(0, 0);
+        let size = Vector2D::new(100, 100);
+
+        state.add_client(window, pos, size).unwrap();
+
+        let expected_client = Client::new(window, pos, size, 1);
+
+        assert_eq!(
+            &expected_client,
+            state.active_workspace_clients().get(&window).unwrap(),
+        );
+    }
+
+    #[test]
+    fn test_add_client_already_exists() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(0, 0);
+        let size = Vector2D::new(100, 100);
+
+        state.add_client(window, pos, size).unwrap();
+
+        let result = state.add_client(window, pos, size);
+
+        assert!(matches!(result, Err(Error::ClientAlreadyExists)));
+    }
+
+    #[test]
+    fn test_remove_client() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(0, 0);
+        let size = Vector2D::new(100, 100);
+
+        state.add_client(window, pos, size).unwrap();
+        state.set_focused(Some(window));
+
+        let result = state.remove_client(window);
+
+        assert!(matches!(result, Ok(())));
+        assert_eq!(state.active_workspace_clients().len(), 0);
+        assert_eq!(state.focused, None);
+    }
+
+    #[test]
+    fn test_remove_client_not_found() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+
+        let result = state.remove_client(window);
+
+        assert!(matches!(result, Err(Error::ClientNotFound)));
+    }
+
+    #[test]
+    fn test_drag_client() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(0, 0);
+        let size = Vector2D::new(100, 100);
+
+        state.add_client(window, pos, size).unwrap();
+
+        let new_pos = Vector2D::new(10, 10);
+        let pos = state.drag_client(window, new_pos).unwrap();
+
+        assert_eq!(
+            new_pos,
+            state.active_workspace_clients().get(&window).unwrap().pos
+        );
+        assert_eq!(new_pos, pos);
+    }
+
+    #[test]
+    fn test_drag_client_not_found() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+
+        let result = state.drag_client(window, Vector2D::new(10, 10));
+
+        assert!(matches!(result, Err(Error::ClientNotFound)));
+    }
+
+    #[test]
+    fn test_drag_resize_client() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(0, 0);
+        let size = Vector2D::new(100, 100);
+
+        state.add_client(window, pos, size).unwrap();
+
+        let new_size = Vector2D::new(50, 50);
+        let (_, size) = state.drag_resize_client(window, new_size).unwrap();
+
+        assert_eq!(
+            new_size,
+            state.active_workspace_clients().get(&window).unwrap().size
+        );
+        assert_eq!(new_size, size);
+    }
+
+    #[test]
+    fn test_drag_resize_client_min_value() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(0, 0);
+        let size = Vector2D::new(100, 100);
+
+        state.add_client(window, pos, size).unwrap();
+
+        let (_, size) = state
+            .drag_resize_client(window, Vector2D::new(0, 0))
+            .unwrap();
+
+        assert_eq!(size, MIN_CLIENT_SIZE);
+    }
+
+    #[test]
+    fn test_drag_resize_client_from_top_left() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(100, 100);
+        let size = Vector2D::new(200, 200);
+
+        state.add_client(window, pos, size).unwrap();
+        state.drag_start_pos = Vector2D::new(110, 120);
+        state.drag_start_frame_pos = pos;
+        state.drag_start_frame_size = size;
+
+        // The bottom right corner stays fixed
+        let (pos, size) = state
+            .drag_resize_client(window, Vector2D::new(60, 170))
+            .unwrap();
+        assert_eq!(pos, Vector2D::new(50, 150));
+        assert_eq!(size, Vector2D::new(250, 150));
+
+        // The minimum size does not move the fixed corner
+        let (pos, size) = state
+            .drag_resize_client(window, Vector2D::new(500, 500))
+            .unwrap();
+        assert_eq!(pos, Vector2D::new(268, 268));
+        assert_eq!(size, MIN_CLIENT_SIZE);
+    }
+
+    #[test]
+    fn test_drag_resize_client_from_bottom_left() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(100, 100);
+        let size = Vector2D::new(200, 200);
+
+        state.add_client(window, pos, size).unwrap();
+        state.drag_start_pos = Vector2D::new(110, 280);
+        state.drag_start_frame_pos = pos;
+        state.drag_start_frame_size = size;
+
+        // The top right corner stays fixed
+        let (pos, size) = state
+            .drag_resize_client(window, Vector2D::new(90, 300))
+            .unwrap();
+        assert_eq!(pos, Vector2D::new(80, 100));
+        assert_eq!(size, Vector2D::new(220, 220));
+    }
+
+    #[test]
+    fn test_resize_client_size_hints() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(100, 100);
+        let size = Vector2D::new(200, 200);
+
+        state.add_client(window, pos, size).unwrap();
+        state
+            .set_client_size_hints(
+                window,
+                SizeHints {
+                    min_size: Some(Vector2D::new(150, 10)),
+                    max_size: Some(Vector2D::new(300, 250)),
+                },
+            )
+            .unwrap();
+
+        let selector = || WindowSelector::Window(123);
+        let client = state
+            .resize_client(selector(), Vector2D::new(50, 20), false, false)
+            .unwrap();
+        assert_eq!(client.size, Vector2D::new(150, MIN_CLIENT_SIZE.y));
+        let client = state
+            .resize_client(selector(), Vector2D::new(400, 400), false, false)
+            .unwrap();
+        assert_eq!(client.size, Vector2D::new(300, 250));
+
+        // The maximum size does not move the fixed corner
+        state.drag_start_pos = Vector2D::new(110, 120);
+        state.drag_start_frame_pos = pos;
+        state.drag_start_frame_size = size;
+        let (pos, size) = state
+            .drag_resize_client(window, Vector2D::new(0, 0))
+            .unwrap();
+        assert_eq!(pos, Vector2D::new(0, 50));
+        assert_eq!(size, Vector2D::new(300, 250));
+    }
+
+    #[test]
+    fn test_configure_request_geometry() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .set_client_size_hints(
+                window,
+                SizeHints {
+                    min_size: None,
+                    max_size: Some(Vector2D::new(300, 200)),
+                },
+            )
+            .unwrap();
+
+        let client = state
+            .configure_request_geometry(
+                window,
+                x::ConfigWindowMask::all(),
+                Vector2D::new(10, 20),
+                Vector2D::new(400, 150),
+            )
+            .unwrap();
+        assert_eq!(Vector2D::new(10, 20), client.pos);
+        assert_eq!(Vector2D::new(300, 150), client.size);
+        assert!(client.geometry_history.is_empty());
+
+        // The values not in the mask are kept
+        let client = state
+            .configure_request_geometry(
+                window,
+                x::ConfigWindowMask::Y | x::ConfigWindowMask::WIDTH,
+                Vector2D::new(50, 60),
+                Vector2D::new(120, 80),
+            )
+            .unwrap();
+        assert_eq!(Vector2D::new(10, 60), client.pos);
+        assert_eq!(Vector2D::new(120, 150), client.size);
+
+        assert!(matches!(
+            state.configure_request_geometry(
+                unsafe { x::Window::new(456) },
+                x::ConfigWindowMask::all(),
+                Vector2D::new(0, 0),
+                Vector2D::new(100, 100)
+            ),
+            Err(Error::ClientNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_client_configured() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        state
+            .client_configured(window, Vector2D::new(10, 20), Vector2D::new(300, 200))
+            .unwrap();
+        let client = state
+            .select_client(WindowSelector::Window(window.resource_id()))
+            .unwrap();
+        assert_eq!(Vector2D::new(10, 20), client.pos);
+        assert_eq!(Vector2D::new(300, 200), client.size);
+        assert!(client.geometry_history.is_empty());
+
+        assert!(matches!(
+            state.client_configured(
+                unsafe { x::Window::new(456) },
+                Vector2D::new(0, 0),
+                Vector2D::new(100, 100)
+            ),
+            Err(Error::ClientNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_window_geometry() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+
+        state.window_configured(window, Vector2D::new(0, 0), Vector2D::new(100, 100));
+        state.window_configured(window, Vector2D::new(10, 20), Vector2D::new(200, 100));
+        assert_eq!(
+            Some((Vector2D::new(10, 20), Vector2D::new(200, 100))),
+            state.window_geometry(window)
+        );
+
+        // The geometry of a client is the one of the client
+        state
+            .add_client(window, Vector2D::new(50, 50), Vector2D::new(200, 100))
+            .unwrap();
+        assert_eq!(None, state.window_geometry(window));
+        state.window_configured(window, Vector2D::new(0, 0), Vector2D::new(1, 1));
+        assert_eq!(None, state.window_geometry(window));
+        state
+            .client_configured(window, Vector2D::new(60, 70), Vector2D::new(300, 200))
+            .unwrap();
+
+        // A withdrawn client is mapped again where it was left
+        state.remove_client(window).unwrap();
+        assert_eq!(
+            Some((Vector2D::new(60, 70), Vector2D::new(300, 200))),
+            state.window_geometry(window)
+        );
+
+        state.window_destroyed(window);
+        assert_eq!(None, state.window_geometry(window));
+    }
+
+    #[test]
+    fn test_drag() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let unmanaged = unsafe { x::Window::new(456) };
+        let pos = Vector2D::new(100, 100);
+        let size = Vector2D::new(200, 200);
+        state.add_client(window, pos, size).unwrap();
+
+        state.start_drag(unmanaged, Vector2D::new(110, 120), pos, size);
+        assert_eq!(None, state.dragging());
+
+        state.start_drag(window, Vector2D::new(110, 120), pos, size);
+        assert_eq!(Some(window), state.dragging());
+        assert_eq!(
+            Vector2D::new(120, 130),
+            state.drag_client(window, Vector2D::new(130, 150)).unwrap()
+        );
+        assert_eq!(Some(window), state.end_drag());
+        assert_eq!(None, state.end_drag());
+
+        // A client which goes away ends the drag
+        state.start_drag(window, Vector2D::new(110, 120), pos, size);
+        state.remove_client(window).unwrap();
+        assert_eq!(None, state.dragging());
+    }
+
+    #[test]
+    fn test_drag_resize_client_not_found() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+
+        let result = state.drag_resize_client(window, Vector2D::new(50, 50));
+
+        assert!(matches!(result, Err(Error::ClientNotFound)));
+    }
+
+    #[test]
+    fn test_teleport_client() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(0, 0);
+        let size = Vector2D::new(100, 100);
+
+        state.add_client(window, pos, size).unwrap();
+
+        let new_pos = Vector2D::new(10, 10);
+        state.teleport_client(window, new_pos).unwrap();
+
+        assert_eq!(
+            new_pos,
+            state.active_workspace_clients().get(&window).unwrap().pos
+        );
+    }
+
+    #[test]
+    fn test_teleport_client_not_found() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+
+        let result = state.teleport_client(window, Vector2D::new(10, 10));
+
+        assert!(matches!(result, Err(Error::ClientNotFound)));
+    }
+
+    #[test]
+    fn test_focus_client() {
+        let mut state = State {
+            root: unsafe { x::Window::new(0) },
+            ..Default::default()
+        };
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(0, 0);
+        let size = Vector2D::new(100, 100);
+
+        state.add_client(window, pos, size).unwrap();
+
+        state
+            .focus_client(WindowSelector::Window(window.resource_id()))
+            .unwrap();
+
+        assert_eq!(state.focused, Some(window));
+
+        state
+            .focus_client(WindowSelector::Window(state.root.resource_id()))
+            .unwrap();
+
+        assert_eq!(state.focused, None);
+        assert_eq!(state.last_focused(), Some(window));
+    }
+
+    #[test]
+    fn test_select_client_window_selector_focused() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state.set_focused(Some(window));
+
+        let client = state.select_client(WindowSelector::Focused).unwrap();
+
+        assert_eq!(window, client.window);
+    }
+
+    #[test]
+    fn test_select_client_window_selector_closest() {
+        let mut state = State::default();
+        let window_ne = unsafe { x::Window::new(1) };
+        let window_nw = unsafe { x::Window::new(2) };
+        let window_sw = unsafe { x::Window::new(3) };
+        let window_se = unsafe { x::Window::new(4) };
+
+        state
+            .add_client(window_nw, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        state
+            .add_client(window_ne, Vector2D::new(150, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        state
+            .add_client(window_sw, Vector2D::new(0, 150), Vector2D::new(100, 100))
+            .unwrap();
+
+        state
+            .add_client(window_se, Vector2D::new(150, 150), Vector2D::new(100, 100))
+            .unwrap();
+
+        state.set_focused(Some(window_ne));
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::South))
+            .unwrap();
+        assert_eq!(window_se, client.window);
+
+        state.set_focused(Some(window_se));
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::West))
+            .unwrap();
+        assert_eq!(window_sw, client.window);
+
+        state.set_focused(Some(window_sw));
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::North))
+            .unwrap();
+        assert_eq!(window_nw, client.window);
+    }
+
+    #[test]
+    fn test_select_client_closest_by_center() {
+        let mut state = State::default();
+        let focused = unsafe { x::Window::new(1) };
+        let top = unsafe { x::Window::new(2) };
+        let level = unsafe { x::Window::new(3) };
+        state
+            .add_client(focused, Vector2D::new(0, 0), Vector2D::new(600, 600))
+            .unwrap();
+        state
+            .add_client(top, Vector2D::new(650, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client(level, Vector2D::new(670, 250), Vector2D::new(100, 100))
+            .unwrap();
+        state.set_focused(Some(focused));
+
+        // The top corner of the top window is the closest, but the other one is level with the center
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::East))
+            .unwrap();
+        assert_eq!(level, client.window);
+
+        // The corner of a window being to the west is not enough, its center must be
+        state.set_focused(Some(level));
+        state
+            .add_client(
+                unsafe { x::Window::new(4) },
+                Vector2D::new(660, 700),
+                Vector2D::new(600, 100),
+            )
+            .unwrap();
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::West))
+            .unwrap();
+        assert_eq!(focused, client.window);
+    }
+
+    #[test]
+    fn test_select_client_closest_cone_and_overlap() {
+        let mut state = State::default();
+        let focused = unsafe { x::Window::new(1) };
+        let far = unsafe { x::Window::new(2) };
+        let diagonal = unsafe { x::Window::new(3) };
+        let off = unsafe { x::Window::new(4) };
+        state
+            .add_client(focused, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client(far, Vector2D::new(400, 50), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client(diagonal, Vector2D::new(150, 150), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client(off, Vector2D::new(0, 400), Vector2D::new(100, 100))
+            .unwrap();
+        state.set_focused(Some(focused));
+
+        // The diagonal window is closer, but the far one shares some rows with the focused one
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::East))
+            .unwrap();
+        assert_eq!(far, client.window);
+
+        // The diagonal window is within the cone, but the overlapping one still wins
+        state.set_focused(Some(off));
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::North))
+            .unwrap();
+        assert_eq!(focused, client.window);
+        state.set_focused(Some(far));
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::West))
+            .unwrap();
+        assert_eq!(focused, client.window);
+
+        // The diagonal window is closer, but out of the cone
+        state.set_focused(Some(off));
+        let client = state
+            .select_client(WindowSelector::Closest(CardinalDirection::East))
+            .unwrap();
+        assert_eq!(far, client.window);
+
+        state.set_focused(Some(far));
+        assert!(state
+            .select_client(WindowSelector::Closest(CardinalDirection::East))
+            .is_err());
+    }
+
+    #[test]
+    fn test_select_client_closest_wrap() {
+        let mut state = State::default();
+        let windows: Vec<_> = (1..=3).map(|id| unsafe { x::Window::new(id) }).collect();
+        for (window, pos) in windows.iter().zip([(0, 0), (0, 150), (300, 150)]) {
+            state
+                .add_client(
+                    *window,
+                    Vector2D::new(pos.0, pos.1),
+                    Vector2D::new(100, 100),
+                )
+                .unwrap();
+        }
+        state.set_focused(Some(windows[2]));
+        let select = |state: &State, direction| {
+            state
+                .select_client(WindowSelector::Closest(direction))
+                .ok()
+                .map(|client| client.window)
+        };
+
+        assert_eq!(None, select(&state, CardinalDirection::East));
+
+        state.directional_wrap = true;
+        // The west-most windows are tied, the one on the same row wins
+        assert_eq!(Some(windows[1]), select(&state, CardinalDirection::East));
+        assert_eq!(Some(windows[0]), select(&state, CardinalDirection::South));
+        // There is a window in that direction, so there is no wrapping
+        assert_eq!(Some(windows[1]), select(&state, CardinalDirection::West));
+    }
+
+    #[test]
+    fn select_client_window_selector_cycle() {
+        let mut state = State::default();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        let window_3 = unsafe { x::Window::new(3) };
+
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        state
+            .add_client(window_2, Vector2D::new(150, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        state
+            .add_client(window_3, Vector2D::new(0, 150), Vector2D::new(100, 100))
+            .unwrap();
+
+        state.set_focused(Some(window_1));
+
+        let client = state
+            .select_client(WindowSelector::Cycle(CycleDirection::Next))
+            .unwrap();
+
+        assert_eq!(window_2, client.window);
+
+        let client = state
+            .select_client(WindowSelector::Cycle(CycleDirection::Prev))
+            .unwrap();
+
+        assert_eq!(window_3, client.window);
+    }
+
+    #[test]
+    fn test_mark_client() {
+        let mut state = State::default();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client(window_2, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        state
+            .mark_client(
+                WindowSelector::Window(window_1.resource_id()),
+                "a".to_owned(),
+            )
+            .unwrap();
+        assert_eq!(state.marks.get("a"), Some(&window_1));
+
+        // Marking another client with the same name moves the mark
+        state
+            .mark_client(
+                WindowSelector::Window(window_2.resource_id()),
+                "a".to_owned(),
+            )
+            .unwrap();
+        assert_eq!(state.marks.get("a"), Some(&window_2));
+    }
+
+    #[test]
+    fn test_select_client_window_selector_mark() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .mark_client(WindowSelector::Window(window.resource_id()), "a".to_owned())
+            .unwrap();
+
+        // The mark is resolved even if the client is on another workspace
+        state.add_workspace(Some("second".to_owned())).unwrap();
+        state
+            .activate_workspace(WorkspaceSelector::Index(1))
+            .unwrap();
+
+        let client = state
+            .select_client(WindowSelector::Mark("a".to_owned()))
+            .unwrap();
+        assert_eq!(window, client.window);
+        assert_eq!(Some(0), state.client_workspace(window));
+
+        let result = state.select_client(WindowSelector::Mark("b".to_owned()));
+        assert!(matches!(result, Err(Error::MarkNotFound)));
+    }
+
+    #[test]
+    fn test_remove_client_removes_marks() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .mark_client(WindowSelector::Window(window.resource_id()), "a".to_owned())
+            .unwrap();
+
+        state.remove_client(window).unwrap();
+
+        assert!(state.marks.is_empty());
+    }
+
+    #[test]
+    fn test_pointer_enter_focus() {
+        let mut state = State {
+            root: unsafe { x::Window::new(1) },
+            ..Default::default()
+        };
+        let window = unsafe { x::Window::new(123) };
+        let unmanaged = unsafe { x::Window::new(456) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        assert!(state
+            .pointer_enter_focus(FocusModel::Click, window)
+            .is_none());
+        assert!(state
+            .pointer_enter_focus(FocusModel::Sloppy, unmanaged)
+            .is_none());
+        assert!(matches!(
+            state.pointer_enter_focus(FocusModel::Sloppy, window),
+            Some(WindowSelector::Window(123))
+        ));
+
+        state.set_focused(Some(window));
+        assert!(state
+            .pointer_enter_focus(FocusModel::Sloppy, window)
+            .is_none());
+        assert!(state
+            .pointer_enter_focus(FocusModel::Sloppy, state.root)
+            .is_none());
+        assert!(matches!(
+            state.pointer_enter_focus(FocusModel::Strict, state.root),
+            Some(WindowSelector::Window(1))
+        ));
+    }
+
+    #[test]
+    fn test_select_client_urgent() {
+        let mut state = State::default();
+        state.add_workspace(None).unwrap();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        let window_3 = unsafe { x::Window::new(3) };
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client_to_workspace(1, window_2, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client(window_3, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        assert!(matches!(
+            state.select_client(WindowSelector::Urgent),
+            Err(Error::ClientNotFound)
+        ));
+
+        // The oldest urgent window is selected, whatever its workspace
+        state.set_client_urgent(window_2, true).unwrap();
+        state.set_client_urgent(window_3, true).unwrap();
+        state.set_client_urgent(window_2, true).unwrap();
+        let client = state.select_client(WindowSelector::Urgent).unwrap();
+        assert_eq!(window_2, client.window);
+
+        state.set_client_urgent(window_2, false).unwrap();
+        let client = state.select_client(WindowSelector::Urgent).unwrap();
+        assert_eq!(window_3, client.window);
+    }
+
+    #[test]
+    fn test_select_client_class_title() {
+        let mut state = State::default();
+        state.add_workspace(None).unwrap();
+        let windows: Vec<_> = (1..=3).map(|id| unsafe { x::Window::new(id) }).collect();
+        for (window, (class, title)) in windows.iter().zip([
+            ("firefox", "Home - Mozilla Firefox"),
+            ("XTerm", "vim"),
+            ("firefox", "News - Mozilla Firefox"),
+        ]) {
+            state
+                .add_client(*window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+                .unwrap();
+            state.set_client_class(*window, class.to_owned()).unwrap();
+            state.set_client_title(*window, title.to_owned()).unwrap();
+        }
+        state.move_client_to_workspace(windows[1], 1).unwrap();
+
+        let select = |state: &State, selector| {
+            state
+                .select_client(selector)
+                .ok()
+                .map(|client| client.window)
+        };
+        assert_eq!(
+            Some(windows[0]),
+            select(&state, WindowSelector::Class("fire*".to_owned()))
+        );
+        assert_eq!(
+            Some(windows[1]),
+            select(&state, WindowSelector::Class("XTerm".to_owned()))
+        );
+        assert_eq!(
+            Some(windows[2]),
+            select(&state, WindowSelector::Title("News*".to_owned()))
+        );
+        assert_eq!(
+            None,
+            select(&state, WindowSelector::Class("xterm".to_owned()))
+        );
+
+        // The most recently focused match comes first
+        state.set_focused(Some(windows[2]));
+        state.set_focused(Some(windows[0]));
+        state.set_focused(Some(windows[2]));
+        assert_eq!(
+            Some(windows[2]),
+            select(&state, WindowSelector::Class("firefox".to_owned()))
+        );
+    }
+
+    #[test]
+    fn test_select_clients() {
+        let mut state = State::default();
+        state.add_workspace(None).unwrap();
+        let windows: Vec<_> = (1..=3).map(|id| unsafe { x::Window::new(id) }).collect();
+        for (window, class) in windows.iter().zip(["firefox", "XTerm", "firefox"]) {
+            state
+                .add_client(*window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+                .unwrap();
+            state.set_client_class(*window, class.to_owned()).unwrap();
+        }
+        state.move_client_to_workspace(windows[2], 1).unwrap();
+
+        assert_eq!(
+            vec![windows[0], windows[1]],
+            state.select_clients(WindowSelector::All).unwrap()
+        );
+        // Matches on other workspaces come after the active one
+        assert_eq!(
+            vec![windows[0], windows[2]],
+            state
+                .select_clients(WindowSelector::Class("firefox".to_owned()))
+                .unwrap()
+        );
+        state.set_focused(Some(windows[1]));
+        assert_eq!(
+            vec![windows[1]],
+            state.select_clients(WindowSelector::Focused).unwrap()
+        );
+        assert!(state
+            .select_clients(WindowSelector::Title("*".to_owned()))
+            .is_err());
+    }
+
+    #[test]
+    fn test_select_client_superlatives() {
+        let mut state = State::default();
+        state.add_workspace(None).unwrap();
+        let windows: Vec<_> = (1..=4).map(|id| unsafe { x::Window::new(id) }).collect();
+        for (window, size) in windows
+            .iter()
+            .zip([(100, 100), (300, 200), (50, 50), (200, 300)])
+        {
+            state
+                .add_client(*window, Vector2D::new(0, 0), Vector2D::new(size.0, size.1))
+                .unwrap();
+        }
+        // The newest client is on another workspace
+        state.move_client_to_workspace(windows[3], 1).unwrap();
+
+        let select = |selector| {
+            state
+                .select_client(selector)
+                .ok()
+                .map(|client| client.window)
+        };
+        // The oldest of the two biggest clients wins
+        assert_eq!(Some(windows[1]), select(WindowSelector::Biggest));
+        assert_eq!(Some(windows[2]), select(WindowSelector::Smallest));
+        assert_eq!(Some(windows[0]), select(WindowSelector::Oldest));
+        assert_eq!(Some(windows[2]), select(WindowSelector::Newest));
+    }
+
+    #[test]
+    fn test_client_unmapped() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(1) };
+        let unmanaged = unsafe { x::Window::new(2) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        // Hiding the workspace twice before the notifications arrive
+        state.expect_unmap(window).unwrap();
+        state.expect_unmap(window).unwrap();
+        assert!(!state.client_unmapped(window));
+        assert!(!state.client_unmapped(window));
+        assert!(state.client_unmapped(window));
+
+        assert!(!state.client_unmapped(unmanaged));
+        assert!(matches!(
+            state.expect_unmap(unmanaged),
+            Err(Error::ClientNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_pointer_crossing() {
+        let mut state = State {
+            root: unsafe { x::Window::new(1) },
+            ..Default::default()
+        };
+        let window_1 = unsafe { x::Window::new(2) };
+        let window_2 = unsafe { x::Window::new(3) };
+        let unmanaged = unsafe { x::Window::new(456) };
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client(window_2, Vector2D::new(100, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        state.pointer_enter(window_1);
+        assert_eq!(state.hovered, Some(window_1));
+        // The leave event of the previous window may come after the enter event of the next one
+        state.pointer_enter(window_2);
+        state.pointer_leave(window_1);
+        assert_eq!(state.hovered, Some(window_2));
+        state.pointer_leave(window_2);
+        assert_eq!(state.hovered, None);
+
+        state.pointer_enter(unmanaged);
+        assert_eq!(state.hovered, None);
+        state.pointer_enter(window_1);
+        state.pointer_enter(state.root);
+        assert_eq!(state.hovered, None);
+
+        state.pointer_enter(window_2);
+        let client = state.select_client(WindowSelector::UnderPointer).unwrap();
+        assert_eq!(window_2, client.window);
+
+        state.pointer_enter(window_1);
+        state.remove_client(window_1).unwrap();
+        assert_eq!(state.hovered, None);
+        assert!(matches!(
+            state.select_client(WindowSelector::UnderPointer),
+            Err(Error::ClientNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_focus_history() {
+        let mut state = State::default();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        let window_3 = unsafe { x::Window::new(3) };
+
+        for window in [window_1, window_2, window_3] {
+            state
+                .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+                .unwrap();
+            state.set_focused(Some(window));
+        }
+
+        assert_eq!(state.last_focused(), Some(window_2));
+        let client = state.select_client(WindowSelector::LastFocused).unwrap();
+        assert_eq!(window_2, client.window);
+
+        // Focusing a window again moves it on top of the history
+        state.set_focused(Some(window_1));
+        assert_eq!(
+            state.active_focus_history(),
+            &VecDeque::from([window_1, window_3, window_2])
+        );
+    }
+
+    #[test]
+    fn test_focus_history_bounded() {
+        let mut state = State::default();
+
+        for id in 0..(FOCUS_HISTORY_SIZE as u32 + 8) {
+            let window = unsafe { x::Window::new(id + 1) };
+            state
+                .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+                .unwrap();
+            state.set_focused(Some(window));
+        }
+
+        assert_eq!(state.active_focus_history().len(), FOCUS_HISTORY_SIZE);
+    }
+
+    #[test]
+    fn test_stacking_order() {
+        let mut state = State::default();
+        state.add_workspace(None).unwrap();
+        let windows: Vec<_> = (1..=4).map(|id| unsafe { x::Window::new(id) }).collect();
+        for window in &windows {
+            state
+                .add_client(*window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+                .unwrap();
+        }
+        let stack = |state: &State| {
+            state
+                .client_list_stacking()
+                .iter()
+                .map(|window| window.resource_id())
+                .collect::<Vec<_>>()
+        };
+        // New windows are mapped on top
+        assert_eq!(vec![1, 2, 3, 4], stack(&state));
+
+        // Focused windows are raised
+        state.set_focused(Some(windows[1]));
+        assert_eq!(vec![1, 3, 4, 2], stack(&state));
+
+        state
+            .restack_client(windows[1], StackPosition::Bottom)
+            .unwrap();
+        assert_eq!(vec![2, 1, 3, 4], stack(&state));
+        state
+            .restack_client(windows[3], StackPosition::Above(windows[1]))
+            .unwrap();
+        assert_eq!(vec![2, 4, 1, 3], stack(&state));
+        state
+            .restack_client(windows[0], StackPosition::Below(windows[1]))
+            .unwrap();
+        assert_eq!(vec![1, 2, 4, 3], stack(&state));
+        state
+            .restack_client(windows[0], StackPosition::Top)
+            .unwrap();
+        assert_eq!(vec![2, 4, 3, 1], stack(&state));
+
+        // A sibling of another workspace leaves the order unchanged
+        state.move_client_to_workspace(windows[2], 1).unwrap();
+        state
+            .restack_client(windows[0], StackPosition::Above(windows[2]))
+            .unwrap();
+        assert_eq!(vec![2, 4, 1, 3], stack(&state));
+        let tree = state.tree();
+        let ids = |index: usize| {
+            tree[0].workspaces[index]
+                .windows
+                .iter()
+                .map(|window| window.id)
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(vec![1, 4, 2], ids(0));
+        assert_eq!(vec![3], ids(1));
+
+        state.remove_client(windows[3]).unwrap();
+        assert_eq!(vec![2, 1, 3], stack(&state));
+        assert!(state
+            .restack_client(windows[3], StackPosition::Top)
+            .is_err());
+    }
+
+    #[test]
+    fn test_remove_focused_client_falls_back_to_history() {
+        let mut state = State::default();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        let window_3 = unsafe { x::Window::new(3) };
+
+        for window in [window_1, window_2, window_3] {
+            state
+                .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+                .unwrap();
+        }
+        state.set_focused(Some(window_2));
+        state.set_focused(Some(window_3));
+        state.set_focused(Some(window_1));
+
+        // The previously focused window is not live anymore
+        state.remove_client(window_3).unwrap();
+        state.remove_client(window_1).unwrap();
+
+        assert_eq!(state.focused, Some(window_2));
+        assert_eq!(state.last_focused(), None);
+    }
+
+    #[test]
+    fn test_activate_workspace_restores_focus() {
+        let mut state = State::default();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state.set_focused(Some(window_1));
+
+        state.add_workspace(None).unwrap();
+        state
+            .activate_workspace(WorkspaceSelector::Index(1))
+            .unwrap();
+        assert_eq!(state.focused, None);
+
+        state
+            .add_client(window_2, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state.set_focused(Some(window_2));
+
+        state
+            .activate_workspace(WorkspaceSelector::Index(0))
+            .unwrap();
+        assert_eq!(state.focused, Some(window_1));
+    }
+
+    #[test]
+    fn test_cycle_mru() {
+        let mut state = State::default();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        let window_3 = unsafe { x::Window::new(3) };
+
+        for window in [window_1, window_2, window_3] {
+            state
+                .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+                .unwrap();
+        }
+        state.set_focused(Some(window_1));
+        state.set_focused(Some(window_3));
+        state.set_focused(Some(window_2));
+
+        // The cycle visits the windows in most recently used order
+        let window = state.cycle_mru(CycleDirection::Next, false).unwrap();
+        assert_eq!(window_3, window);
+        let window = state.cycle_mru(CycleDirection::Next, false).unwrap();
+        assert_eq!(window_1, window);
+        let window = state.cycle_mru(CycleDirection::Prev, false).unwrap();
+        assert_eq!(window_3, window);
+
+        // Ending the cycle commits the focus, so the next cycle toggles back
+        state.end_cycle_mru();
+        let window = state.cycle_mru(CycleDirection::Next, false).unwrap();
+        assert_eq!(window_2, window);
+    }
+
+    #[test]
+    fn test_cycle_mru_all_workspaces() {
+        let mut state = State::default();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state.add_workspace(None).unwrap();
+        state
+            .activate_workspace(WorkspaceSelector::Index(1))
+            .unwrap();
+        state
+            .add_client(window_2, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state.set_focused(Some(window_2));
+
+        let window = state.cycle_mru(CycleDirection::Next, false).unwrap();
+        assert_eq!(window_2, window);
+
+        let window = state.cycle_mru(CycleDirection::Next, true).unwrap();
+        assert_eq!(window_1, window);
+        assert_eq!(Some(0), state.client_workspace(window));
+    }
+
+    #[test]
+    fn test_cycle_mru_no_clients() {
+        let mut state = State::default();
+
+        let result = state.cycle_mru(CycleDirection::Next, true);
+
+        assert!(matches!(result, Err(Error::ClientNotFound)));
+    }
+
+    #[test]
+    fn test_snap_geometry() {
+        let area_pos = Vector2D::new(10, 20);
+        let area_size = Vector2D::new(101, 200);
+
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::Maximized),
+            (Vector2D::new(10, 20), Vector2D::new(101, 200))
+        );
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::LeftHalf),
+            (Vector2D::new(10, 20), Vector2D::new(50, 200))
+        );
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::RightHalf),
+            (Vector2D::new(60, 20), Vector2D::new(51, 200))
+        );
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::TopHalf),
+            (Vector2D::new(10, 20), Vector2D::new(101, 100))
+        );
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::BottomHalf),
+            (Vector2D::new(10, 120), Vector2D::new(101, 100))
+        );
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::TopLeft),
+            (Vector2D::new(10, 20), Vector2D::new(50, 100))
+        );
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::TopRight),
+            (Vector2D::new(60, 20), Vector2D::new(51, 100))
+        );
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::BottomLeft),
+            (Vector2D::new(10, 120), Vector2D::new(50, 100))
+        );
+        assert_eq!(
+            snap_geometry(area_pos, area_size, SnapPosition::BottomRight),
+            (Vector2D::new(60, 120), Vector2D::new(51, 100))
+        );
+    }
+
+    #[test]
+    fn test_snap_client() {
+        let mut state = State::default();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1000, 800))
+            .unwrap();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+
+        let client = state
+            .snap_client(
+                WindowSelector::Window(window.resource_id()),
+                SnapPosition::RightHalf,
+                2,
+            )
+            .unwrap();
+
+        assert_eq!(client.pos, Vector2D::new(500, 0));
+        assert_eq!(client.size, Vector2D::new(496, 796));
+    }
+
+    #[test]
+    fn test_unsnap_client() {
+        let mut state = State::default();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1000, 800))
+            .unwrap();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(10, 20), Vector2D::new(100, 100))
+            .unwrap();
+        let selector = || WindowSelector::Window(window.resource_id());
+
+        assert!(matches!(
+            state.unsnap_client(selector()),
+            Err(Error::NotSnapped)
+        ));
+
+        // Snapping again keeps the geometry from before the first snap
+        state
+            .snap_client(selector(), SnapPosition::LeftHalf, 0)
+            .unwrap();
+        state
+            .snap_client(selector(), SnapPosition::RightHalf, 0)
+            .unwrap();
+        let client = state.unsnap_client(selector()).unwrap();
+        assert_eq!(
+            (Vector2D::new(10, 20), Vector2D::new(100, 100)),
+            (client.pos, client.size)
+        );
+        assert!(state.unsnap_client(selector()).is_err());
+
+        // Unsnapping can be undone
+        let client = state.undo_client_geometry(selector()).unwrap();
+        assert_eq!(Vector2D::new(500, 800), client.size);
+    }
+
+    #[test]
+    fn test_unsnap_maximized_client() {
+        let mut state = State::default();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1000, 800))
+            .unwrap();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(10, 20), Vector2D::new(100, 100))
+            .unwrap();
+        let selector = || WindowSelector::Window(window.resource_id());
+
+        // A window snapped while maximized is maximized again, then restored
+        state
+            .snap_client(selector(), SnapPosition::Maximized, 0)
+            .unwrap();
+        state
+            .snap_client(selector(), SnapPosition::LeftHalf, 0)
+            .unwrap();
+        let client = state.unsnap_client(selector()).unwrap();
+        assert_eq!(
+            (Vector2D::new(0, 0), Vector2D::new(1000, 800)),
+            (client.pos, client.size)
+        );
+        let client = state.unsnap_client(selector()).unwrap();
+        assert_eq!(
+            (Vector2D::new(10, 20), Vector2D::new(100, 100)),
+            (client.pos, client.size)
+        );
+
+        // Maximizing again leaves the snapped state
+        state
+            .snap_client(selector(), SnapPosition::Maximized, 0)
+            .unwrap();
+        state
+            .snap_client(selector(), SnapPosition::RightHalf, 0)
+            .unwrap();
+        state
+            .snap_client(selector(), SnapPosition::Maximized, 0)
+            .unwrap();
+        let client = state.unsnap_client(selector()).unwrap();
+        assert_eq!(
+            (Vector2D::new(10, 20), Vector2D::new(100, 100)),
+            (client.pos, client.size)
+        );
+        assert!(matches!(
+            state.unsnap_client(selector()),
+            Err(Error::NotSnapped)
+        ));
+    }
+
+    #[test]
+    fn test_client_snapshots() {
+        let mut client = Client::new(
+            unsafe { x::Window::new(1) },
+            Vector2D::new(0, 0),
+            Vector2D::new(100, 100),
+            1,
+        );
+        client.save_snapshot(GeometrySnapshot::Snapped);
+        client.set_geometry(Vector2D::new(50, 50), Vector2D::new(200, 200));
+        client.save_snapshot(GeometrySnapshot::Snapped);
+        client.save_snapshot(GeometrySnapshot::Maximized);
+        client.set_geometry(Vector2D::new(0, 0), Vector2D::new(1000, 800));
+
+        assert_eq!(
+            Some((Vector2D::new(50, 50), Vector2D::new(200, 200))),
+            client.take_snapshot()
+        );
+        assert_eq!(
+            Some((Vector2D::new(0, 0), Vector2D::new(100, 100))),
+            client.take_snapshot()
+        );
+        assert_eq!(None, client.take_snapshot());
+    }
+
+    #[test]
+    fn test_snap_client_frame_extents() {
+        let mut state = State::default();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1000, 800))
+            .unwrap();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .set_client_frame_extents(
+                window,
+                Strut {
+                    left: 20,
+                    right: 20,
+                    top: 10,
+                    bottom: 30,
+                },
+            )
+            .unwrap();
+
+        let client = state
+            .snap_client(
+                WindowSelector::Window(window.resource_id()),
+                SnapPosition::LeftHalf,
+                0,
+            )
+            .unwrap();
+
+        // The visible part of the window fills the left half
+        assert_eq!(client.pos, Vector2D::new(-20, -10));
+        assert_eq!(client.size, Vector2D::new(540, 840));
+    }
+
+    #[test]
+    fn test_edge_snap_position() {
+        let mut state = State::default();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1000, 800))
+            .unwrap();
+
+        assert!(matches!(
+            state.edge_snap_position(Vector2D::new(500, 5), 8),
+            Some(SnapPosition::Maximized)
+        ));
+        assert!(matches!(
+            state.edge_snap_position(Vector2D::new(0, 400), 8),
+            Some(SnapPosition::LeftHalf)
+        ));
+        assert!(matches!(
+            state.edge_snap_position(Vector2D::new(999, 400), 8),
+            Some(SnapPosition::RightHalf)
+        ));
+        assert!(state
+            .edge_snap_position(Vector2D::new(500, 400), 8)
+            .is_none());
+        assert!(state
+            .edge_snap_position(Vector2D::new(10, 400), 8)
+            .is_none());
     }
 
-    fn select_client_cycle(&self, direction: CycleDirection) -> Result<&Client, Error> {
-        let window = if let Some(window) = self.focused {
-            window
-        } else {
-            return Err(Error::ClientNotFound);
-        };
+    #[test]
+    fn test_resize_client() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(100, 100), Vector2D::new(200, 200))
+            .unwrap();
+        let selector = || WindowSelector::Window(window.resource_id());
 
-        let index = self
-            .active_workspace_clients()
-            .get_index_of(&window)
-            .expect("Focused client not found");
+        let client = state
+            .resize_client(selector(), Vector2D::new(300, 250), false, false)
+            .unwrap();
+        assert_eq!(client.pos, Vector2D::new(100, 100));
+        assert_eq!(client.size, Vector2D::new(300, 250));
 
-        match direction {
-            CycleDirection::Next => {
-                let index = (index + 1) % self.active_workspace_clients().len();
-                self.active_workspace_clients()
-                    .get_index(index)
-                    .map(|(_, client)| client)
-                    .ok_or(Error::ClientNotFound)
-            }
-            CycleDirection::Prev => {
-                let index = (index + self.active_workspace_clients().len() - 1)
-                    % self.active_workspace_clients().len();
-                self.active_workspace_clients()
-                    .get_index(index)
-                    .map(|(_, client)| client)
-                    .ok_or(Error::ClientNotFound)
-            }
-        }
+        let client = state
+            .resize_client(selector(), Vector2D::new(-400, -400), true, false)
+            .unwrap();
+        assert_eq!(client.size, MIN_CLIENT_SIZE);
     }
 
-    fn select_client_closest(&self, direction: CardinalDirection) -> Result<&Client, Error> {
-        let client = if let Some(focused) = self.focused {
-            self.active_workspace_clients()
-                .get(&focused)
-                .expect("Focused client not found")
-        } else {
-            return Err(Error::ClientNotFound);
-        };
+    #[test]
+    fn test_resize_client_center() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(100, 100), Vector2D::new(200, 200))
+            .unwrap();
+        let selector = || WindowSelector::Window(window.resource_id());
 
-        let mut distance: i32;
-        let mut min_distance = std::i32::MAX;
-        let mut closest_client = None;
+        let client = state
+            .resize_client(selector(), Vector2D::new(40, -20), true, true)
+            .unwrap();
+        assert_eq!(client.pos, Vector2D::new(80, 110));
+        assert_eq!(client.size, Vector2D::new(240, 180));
+        assert_eq!(client.center(), Vector2D::new(200, 200));
+    }
 
-        for (_, c) in self.active_workspace_clients() {
-            if c.window == client.window {
-                continue; // Skip the focused window
-            }
-            let dx = c.pos.x - client.pos.x;
-            let dy = c.pos.y - client.pos.y;
-            // Euclidean distance approximation
-            // We do not need to calculate the square root to compare distances.
-            distance = dx.pow(2) + dy.pow(2);
+    #[test]
+    fn test_undo_client_geometry() {
+        let mut state = State::default();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1000, 800))
+            .unwrap();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(10, 10);
+        let size = Vector2D::new(100, 100);
+        let selector = || WindowSelector::Window(window.resource_id());
+        state.add_client(window, pos, size).unwrap();
 
-            match direction {
-                CardinalDirection::East => {
-                    if c.pos.x > client.pos.x && distance < min_distance {
-                        min_distance = distance;
-                        closest_client = Some(c);
-                    }
-                }
-                CardinalDirection::West => {
-                    if c.pos.x < client.pos.x && distance < min_distance {
-                        min_distance = distance;
-                        closest_client = Some(c);
-                    }
-                }
-                CardinalDirection::North => {
-                    if c.pos.y < client.pos.y && distance < min_distance {
-                        min_distance = distance;
-                        closest_client = Some(c);
-                    }
-                }
-                CardinalDirection::South => {
-                    if c.pos.y > client.pos.y && distance < min_distance {
-                        min_distance = distance;
-                        closest_client = Some(c);
-                    }
-                }
-            }
-        }
+        state
+            .snap_client(selector(), SnapPosition::LeftHalf, 0)
+            .unwrap();
+        state
+            .resize_client(selector(), Vector2D::new(10, 10), true, false)
+            .unwrap();
 
-        match closest_client {
-            None => Err(Error::ClientNotFound),
-            Some(closest_client) => Ok(closest_client),
-        }
-    }
+        let client = state.undo_client_geometry(selector()).unwrap();
+        assert_eq!(client.pos, Vector2D::new(0, 0));
+        assert_eq!(client.size, Vector2D::new(500, 800));
 
-    /// Set the focused window.
-    /// Save the last focused window.
-    fn set_focused(&mut self, window: Option<x::Window>) {
-        self.last_focused = self.focused;
-        self.focused = window;
-    }
+        let client = state.undo_client_geometry(selector()).unwrap();
+        assert_eq!(client.pos, pos);
+        assert_eq!(client.size, size);
 
-    /// Get the focused window.
-    pub fn focused(&self) -> Option<x::Window> {
-        self.focused
+        let result = state.undo_client_geometry(selector());
+        assert!(matches!(result, Err(Error::NothingToUndo)));
     }
 
-    /// Get the last focused window.
-    pub fn last_focused(&self) -> Option<x::Window> {
-        self.last_focused
+    #[test]
+    fn test_undo_client_drag() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(123) };
+        let pos = Vector2D::new(10, 10);
+        let size = Vector2D::new(100, 100);
+        state.add_client(window, pos, size).unwrap();
+        state.drag_start_pos = Vector2D::new(50, 50);
+        state.drag_start_frame_pos = pos;
+
+        // A drag is made of many motions, but it is undone at once
+        state.drag_client(window, Vector2D::new(60, 60)).unwrap();
+        state.drag_client(window, Vector2D::new(70, 70)).unwrap();
+
+        let client = state
+            .undo_client_geometry(WindowSelector::Window(window.resource_id()))
+            .unwrap();
+        assert_eq!(client.pos, pos);
+        assert!(client.geometry_history.is_empty());
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    #[test]
+    fn test_add_monitor() {
+        let mut state = State::default();
+        state.add_workspace(None).unwrap();
+        let index = state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
 
-    use xcb::XidNew;
+        assert_eq!(1, index);
+        assert_eq!(vec![0, 2], state.visible_workspaces());
+        assert_eq!(0, state.active_workspace());
+    }
 
     #[test]
-    fn test_add_workspace() {
+    fn test_activate_workspace_on_other_monitor() {
         let mut state = State::default();
-        state.add_workspace(Some("test".to_owned())).unwrap();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
 
-        assert_eq!(state.workspaces.len(), 2);
-        assert!(state.workspaces.contains_key("test"));
+        state
+            .activate_workspace(WorkspaceSelector::Index(1))
+            .unwrap();
+
+        assert_eq!(1, state.active_workspace());
+        assert_eq!(vec![0, 1], state.visible_workspaces());
+        assert_eq!(
+            (Vector2D::new(1000, 0), Vector2D::new(800, 600)),
+            state.work_area()
+        );
     }
 
     #[test]
-    fn test_add_workspace_no_name() {
+    fn test_move_workspace_to_monitor() {
         let mut state = State::default();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1000, 800))
+            .unwrap();
         state.add_workspace(None).unwrap();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
+        let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(100, 700), Vector2D::new(200, 100))
+            .unwrap();
+        state
+            .focus_client(WindowSelector::Window(window.resource_id()))
+            .unwrap();
 
-        assert_eq!(state.workspaces.len(), 2);
-        assert!(state.workspaces.contains_key("1"));
+        let index = state
+            .move_workspace_to_monitor(
+                WorkspaceSelector::Index(0),
+                MonitorSelector::Name("HDMI-1".to_owned()),
+            )
+            .unwrap();
+
+        assert_eq!(0, index);
+        assert_eq!(0, state.active_workspace());
+        assert_eq!(vec![1, 0], state.visible_workspaces());
+        assert_eq!(Some(window), state.focused());
+        let client = state.workspace_clients(0).unwrap().get(&window).unwrap();
+        assert_eq!(Vector2D::new(1100, 500), client.pos());
     }
 
     #[test]
-    fn test_add_workspace_already_exists() {
+    fn test_move_workspace_to_monitor_last_workspace() {
         let mut state = State::default();
-        state.add_workspace(Some("test".to_owned())).unwrap();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
 
-        assert!(matches!(
-            state.add_workspace(Some("test".to_owned())),
-            Err(Error::WorkspaceAlreadyExists)
-        ));
+        let result =
+            state.move_workspace_to_monitor(WorkspaceSelector::Index(0), MonitorSelector::Index(1));
+
+        assert!(matches!(result, Err(Error::LastWorkspaceOnMonitor)));
+        assert_eq!(vec![0, 1], state.visible_workspaces());
     }
 
     #[test]
-    fn workspaces_names() {
+    fn test_move_workspace_to_monitor_not_found() {
         let mut state = State::default();
-        state.add_workspace(Some("2".to_owned())).unwrap();
-        state.add_workspace(Some("3".to_owned())).unwrap();
 
-        let workspaces_names = state.workspaces_names();
+        let result =
+            state.move_workspace_to_monitor(WorkspaceSelector::Index(0), MonitorSelector::Index(1));
 
-        assert_eq!(workspaces_names, vec!["1", "2", "3"]);
+        assert!(matches!(result, Err(Error::MonitorNotFound)));
     }
 
     #[test]
-    fn test_activate_workspace() {
+    fn test_restore_workspaces() {
         let mut state = State::default();
-        state.add_workspace(Some("test".to_owned())).unwrap();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
+        state.add_workspace(Some("web".to_owned())).unwrap();
+        state
+            .move_workspace_to_monitor(
+                WorkspaceSelector::Name("web".to_owned()),
+                MonitorSelector::Index(1),
+            )
+            .unwrap();
+        let settings = state.workspaces_settings();
 
-        let index = state
-            .activate_workspace(WorkspaceSelector::Name("test".to_string()))
+        let mut state = State::default();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
             .unwrap();
+        state.restore_workspaces(settings.clone());
 
-        assert_eq!(1, index);
-        assert_eq!(1, state.active_workspace);
+        assert_eq!(settings, state.workspaces_settings());
+        assert_eq!(vec!["1", "2", "web"], state.workspaces_names());
+        assert_eq!(vec![0, 1], state.visible_workspaces());
     }
 
     #[test]
-    fn test_activate_workspace_not_found() {
+    fn test_session() {
         let mut state = State::default();
-        let result = state.activate_workspace(WorkspaceSelector::Name("test".to_string()));
+        state.add_workspace(Some("web".to_owned())).unwrap();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client_to_workspace(1, window_2, Vector2D::new(10, 20), Vector2D::new(300, 200))
+            .unwrap();
+        state.set_focused(Some(window_2));
 
-        assert!(matches!(result, Err(Error::WorkspaceNotFound)));
-        assert_eq!(0, state.active_workspace);
+        let session = state.session();
+        assert_eq!(Some(2), session.focused);
+        assert_eq!(
+            vec![
+                SessionClient {
+                    window: 1,
+                    workspace: "1".to_owned(),
+                    pos: Vector2D::new(0, 0),
+                    size: Vector2D::new(100, 100),
+                },
+                SessionClient {
+                    window: 2,
+                    workspace: "web".to_owned(),
+                    pos: Vector2D::new(10, 20),
+                    size: Vector2D::new(300, 200),
+                },
+            ],
+            session.clients
+        );
     }
 
     #[test]
-    fn select_workspace_cycle() {
+    fn test_restore_workspaces_missing_monitor() {
         let mut state = State::default();
-        state.add_workspace(Some("second".to_owned())).unwrap();
-        state.add_workspace(Some("third".to_owned())).unwrap();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
 
-        let index = state.select_workspace_cycle(CycleDirection::Next);
-        assert_eq!(1, index);
+        state.restore_workspaces(vec![
+            WorkspaceSettings {
+                name: "first".to_owned(),
+                monitor: "default".to_owned(),
+            },
+            WorkspaceSettings {
+                name: "second".to_owned(),
+                monitor: "DP-2".to_owned(),
+            },
+        ]);
 
-        let index = state.select_workspace_cycle(CycleDirection::Prev);
-        assert_eq!(2, index);
+        assert_eq!(vec!["first", "second", "3"], state.workspaces_names());
+        assert_eq!(vec![0, 2], state.visible_workspaces());
     }
 
     #[test]
-    fn test_add_client() {
+    fn test_workspaces_info() {
         let mut state = State::default();
-        let window = unsafe { x::Window::new(123) };
-        let pos = Vector2D::new(0, 0);
-        let size = Vector2D::new(100, 100);
-
-        state.add_client(window, pos, size).unwrap();
-
-        let expected_client = Client { window, pos, size };
+        state.add_workspace(Some("web".to_owned())).unwrap();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .focus_client(WindowSelector::Window(window_1.resource_id()))
+            .unwrap();
+        state
+            .activate_workspace(WorkspaceSelector::Index(1))
+            .unwrap();
+        state
+            .add_client(window_2, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state.set_client_urgent(window_1, true).unwrap();
 
         assert_eq!(
-            &expected_client,
-            state.active_workspace_clients().get(&window).unwrap(),
+            vec![
+                WorkspaceInfo {
+                    name: "1".to_owned(),
+                    index: 0,
+                    active: false,
+                    clients: 1,
+                    focused: Some(1),
+                    urgent: true,
+                },
+                WorkspaceInfo {
+                    name: "web".to_owned(),
+                    index: 1,
+                    active: true,
+                    clients: 1,
+                    focused: None,
+                    urgent: false,
+                },
+            ],
+            state.workspaces_info()
         );
     }
 
     #[test]
-    fn test_add_client_already_exists() {
+    fn test_select_workspace_empty() {
         let mut state = State::default();
-        let window = unsafe { x::Window::new(123) };
-        let pos = Vector2D::new(0, 0);
-        let size = Vector2D::new(100, 100);
-
-        state.add_client(window, pos, size).unwrap();
+        state.add_workspace(None).unwrap();
+        state.add_workspace(None).unwrap();
+        state.add_workspace(None).unwrap();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .activate_workspace(WorkspaceSelector::Index(2))
+            .unwrap();
+        state
+            .add_client(window_2, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
 
-        let result = state.add_client(window, pos, size);
+        assert!(matches!(
+            state.select_workspace(WorkspaceSelector::Empty),
+            Ok(1)
+        ));
+        assert!(matches!(
+            state.select_workspace(WorkspaceSelector::NextEmpty),
+            Ok(3)
+        ));
 
-        assert!(matches!(result, Err(Error::ClientAlreadyExists)));
+        state
+            .activate_workspace(WorkspaceSelector::Index(3))
+            .unwrap();
+        assert!(matches!(
+            state.select_workspace(WorkspaceSelector::NextEmpty),
+            Ok(1)
+        ));
     }
 
     #[test]
-    fn test_remove_client() {
+    fn test_select_workspace_empty_not_found() {
         let mut state = State::default();
-        let window = unsafe { x::Window::new(123) };
-        let pos = Vector2D::new(0, 0);
-        let size = Vector2D::new(100, 100);
-
-        state.add_client(window, pos, size).unwrap();
-        state.set_focused(Some(window));
-
-        let result = state.remove_client(window);
+        let window = unsafe { x::Window::new(1) };
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
 
-        assert!(matches!(result, Ok(())));
-        assert_eq!(state.active_workspace_clients().len(), 0);
-        assert_eq!(state.focused, None);
+        assert!(matches!(
+            state.select_workspace(WorkspaceSelector::Empty),
+            Err(Error::WorkspaceNotFound)
+        ));
+        assert!(matches!(
+            state.select_workspace(WorkspaceSelector::NextEmpty),
+            Err(Error::WorkspaceNotFound)
+        ));
     }
 
     #[test]
-    fn test_remove_client_not_found() {
+    fn test_add_client_to_workspace() {
         let mut state = State::default();
+        state.add_workspace(None).unwrap();
         let window = unsafe { x::Window::new(123) };
 
-        let result = state.remove_client(window);
+        state
+            .add_client_to_workspace(1, window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .set_client_class(window, "firefox".to_owned())
+            .unwrap();
 
-        assert!(matches!(result, Err(Error::ClientNotFound)));
+        assert_eq!(Some(1), state.client_workspace(window));
+        assert!(state.active_workspace_clients().is_empty());
+        assert!(matches!(
+            state.add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100)),
+            Err(Error::ClientAlreadyExists)
+        ));
+        assert!(matches!(
+            state.add_client_to_workspace(
+                2,
+                unsafe { x::Window::new(456) },
+                Vector2D::new(0, 0),
+                Vector2D::new(100, 100)
+            ),
+            Err(Error::WorkspaceNotFound)
+        ));
     }
 
     #[test]
-    fn test_drag_client() {
+    fn test_swap_monitors() {
         let mut state = State::default();
-        let window = unsafe { x::Window::new(123) };
-        let pos = Vector2D::new(0, 0);
-        let size = Vector2D::new(100, 100);
-
-        state.add_client(window, pos, size).unwrap();
+        state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1000, 800))
+            .unwrap();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        state
+            .add_client(window_1, Vector2D::new(100, 100), Vector2D::new(200, 200))
+            .unwrap();
+        state
+            .add_client_to_workspace(
+                1,
+                window_2,
+                Vector2D::new(1100, 100),
+                Vector2D::new(200, 200),
+            )
+            .unwrap();
 
-        let new_pos = Vector2D::new(10, 10);
-        let pos = state.drag_client(window, new_pos).unwrap();
+        let workspaces = state
+            .swap_monitors(
+                MonitorSelector::Focused,
+                MonitorSelector::Name("HDMI-1".to_owned()),
+            )
+            .unwrap();
 
+        assert_eq!((0, 1), workspaces);
+        assert_eq!(vec![1, 0], state.visible_workspaces());
+        assert_eq!(0, state.active_workspace());
         assert_eq!(
-            new_pos,
-            state.active_workspace_clients().get(&window).unwrap().pos
+            (Vector2D::new(1000, 0), Vector2D::new(800, 600)),
+            state.work_area()
         );
-        assert_eq!(new_pos, pos);
+        let client_1 = state.workspace_clients(0).unwrap().get(&window_1).unwrap();
+        assert_eq!(Vector2D::new(1100, 100), client_1.pos());
+        let client_2 = state.workspace_clients(1).unwrap().get(&window_2).unwrap();
+        assert_eq!(Vector2D::new(100, 100), client_2.pos());
     }
 
     #[test]
-    fn test_drag_client_not_found() {
-        let mut state = State::default();
-        let window = unsafe { x::Window::new(123) };
+    fn test_cycle_without_wrap() {
+        let mut state = State {
+            wrap: false,
+            ..Default::default()
+        };
+        state.add_workspace(None).unwrap();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
+        state
+            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .add_client(window_2, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .focus_client(WindowSelector::Window(window_2.resource_id()))
+            .unwrap();
 
-        let result = state.drag_client(window, Vector2D::new(10, 10));
+        let client = state
+            .select_client(WindowSelector::Cycle(CycleDirection::Next))
+            .unwrap();
+        assert_eq!(window_2, client.window());
+        let client = state
+            .select_client(WindowSelector::Cycle(CycleDirection::Prev))
+            .unwrap();
+        assert_eq!(window_1, client.window());
 
-        assert!(matches!(result, Err(Error::ClientNotFound)));
+        assert_eq!(0, state.select_workspace_cycle(CycleDirection::Prev));
+        state
+            .activate_workspace(WorkspaceSelector::Index(1))
+            .unwrap();
+        assert_eq!(1, state.select_workspace_cycle(CycleDirection::Next));
     }
 
     #[test]
-    fn test_drag_resize_client() {
+    fn test_set_monitors() {
         let mut state = State::default();
-        let window = unsafe { x::Window::new(123) };
-        let pos = Vector2D::new(0, 0);
-        let size = Vector2D::new(100, 100);
-
-        state.add_client(window, pos, size).unwrap();
-
-        let new_size = Vector2D::new(50, 50);
-        let size = state.drag_resize_client(window, new_size).unwrap();
+        state
+            .set_monitors(vec![
+                MonitorInfo {
+                    name: "DP-1".to_owned(),
+                    pos: Vector2D::new(0, 0),
+                    size: Vector2D::new(1920, 1080),
+                    primary: false,
+                },
+                MonitorInfo {
+                    name: "HDMI-1".to_owned(),
+                    pos: Vector2D::new(1920, 0),
+                    size: Vector2D::new(1280, 1024),
+                    primary: false,
+                },
+            ])
+            .unwrap();
 
+        assert_eq!(vec![0, 1], state.visible_workspaces());
+        assert_eq!(Some(0), state.workspace_at(Vector2D::new(1919, 500)));
+        assert_eq!(Some(1), state.workspace_at(Vector2D::new(1920, 500)));
+        assert_eq!(None, state.workspace_at(Vector2D::new(2000, 1050)));
         assert_eq!(
-            new_size,
-            state.active_workspace_clients().get(&window).unwrap().size
+            vec!["DP-1", "HDMI-1"],
+            state
+                .workspaces_settings()
+                .into_iter()
+                .map(|settings| settings.monitor)
+                .collect::<Vec<_>>()
         );
-        assert_eq!(new_size, size);
     }
 
     #[test]
-    fn test_drag_resize_client_min_value() {
+    fn test_focus_client_on_other_monitor() {
         let mut state = State::default();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
         let window = unsafe { x::Window::new(123) };
-        let pos = Vector2D::new(0, 0);
-        let size = Vector2D::new(100, 100);
-
-        state.add_client(window, pos, size).unwrap();
+        state
+            .add_client_to_workspace(1, window, Vector2D::new(1100, 100), Vector2D::new(100, 100))
+            .unwrap();
 
-        let size = state
-            .drag_resize_client(window, Vector2D::new(0, 0))
+        assert!(matches!(
+            state.pointer_enter_focus(FocusModel::Sloppy, window),
+            Some(WindowSelector::Window(123))
+        ));
+        state
+            .focus_client(WindowSelector::Window(window.resource_id()))
             .unwrap();
 
-        assert_eq!(size, MIN_CLIENT_SIZE);
+        assert_eq!(1, state.active_workspace());
+        assert_eq!(Some(window), state.focused());
     }
 
     #[test]
-    fn test_drag_resize_client_not_found() {
+    fn test_move_client_to_workspace() {
         let mut state = State::default();
+        state
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
+            .unwrap();
         let window = unsafe { x::Window::new(123) };
+        state
+            .add_client(window, Vector2D::new(900, 100), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .focus_client(WindowSelector::Window(window.resource_id()))
+            .unwrap();
 
-        let result = state.drag_resize_client(window, Vector2D::new(50, 50));
+        state.move_client_to_workspace(window, 1).unwrap();
 
-        assert!(matches!(result, Err(Error::ClientNotFound)));
+        assert_eq!(Some(1), state.client_workspace(window));
+        assert_eq!(1, state.active_workspace());
+        assert_eq!(Some(window), state.focused());
+        assert!(state.workspace_clients(0).unwrap().is_empty());
+
+        state.remove_client(window).unwrap();
+        assert_eq!(None, state.focused());
     }
 
     #[test]
-    fn test_teleport_client() {
+    fn test_focus_monitor() {
         let mut state = State::default();
+        state
+            .set_monitors(vec![
+                MonitorInfo {
+                    name: "DP-1".to_owned(),
+                    pos: Vector2D::new(0, 0),
+                    size: Vector2D::new(1920, 1080),
+                    primary: false,
+                },
+                MonitorInfo {
+                    name: "HDMI-1".to_owned(),
+                    pos: Vector2D::new(1920, 0),
+                    size: Vector2D::new(1280, 1024),
+                    primary: false,
+                },
+                MonitorInfo {
+                    name: "HDMI-2".to_owned(),
+                    pos: Vector2D::new(3200, 0),
+                    size: Vector2D::new(1280, 1024),
+                    primary: false,
+                },
+            ])
+            .unwrap();
         let window = unsafe { x::Window::new(123) };
-        let pos = Vector2D::new(0, 0);
-        let size = Vector2D::new(100, 100);
+        state
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .unwrap();
+        state
+            .focus_client(WindowSelector::Window(window.resource_id()))
+            .unwrap();
 
-        state.add_client(window, pos, size).unwrap();
+        let focused = state
+            .focus_monitor(MonitorSelector::Closest(CardinalDirection::East))
+            .unwrap();
+        assert_eq!(None, focused);
+        assert_eq!(1, state.active_workspace());
 
-        let new_pos = Vector2D::new(10, 10);
-        state.teleport_client(window, new_pos).unwrap();
+        assert!(matches!(
+            state.focus_monitor(MonitorSelector::Closest(CardinalDirection::North)),
+            Err(Error::MonitorNotFound)
+        ));
 
-        assert_eq!(
-            new_pos,
-            state.active_workspace_clients().get(&window).unwrap().pos
-        );
+        let focused = state
+            .focus_monitor(MonitorSelector::Name("DP-1".to_owned()))
+            .unwrap();
+        assert_eq!(Some(window), focused);
+        assert_eq!(0, state.active_workspace());
     }
 
     #[test]
-    fn test_teleport_client_not_found() {
+    fn test_select_monitor() {
         let mut state = State::default();
-        let window = unsafe { x::Window::new(123) };
-
-        let result = state.teleport_client(window, Vector2D::new(10, 10));
+        state
+            .set_monitors(vec![
+                MonitorInfo {
+                    name: "DP-1".to_owned(),
+                    pos: Vector2D::new(0, 0),
+                    size: Vector2D::new(1920, 1080),
+                    primary: false,
+                },
+                MonitorInfo {
+                    name: "HDMI-1".to_owned(),
+                    pos: Vector2D::new(0, 1080),
+                    size: Vector2D::new(1920, 1080),
+                    primary: true,
+                },
+            ])
+            .unwrap();
 
-        assert!(matches!(result, Err(Error::ClientNotFound)));
+        assert!(matches!(
+            state.select_monitor(MonitorSelector::Focused),
+            Ok(0)
+        ));
+        assert!(matches!(
+            state.select_monitor(MonitorSelector::Index(1)),
+            Ok(1)
+        ));
+        assert!(matches!(
+            state.select_monitor(MonitorSelector::Name("HDMI-1".to_owned())),
+            Ok(1)
+        ));
+        assert!(matches!(
+            state.select_monitor(MonitorSelector::Closest(CardinalDirection::South)),
+            Ok(1)
+        ));
+        assert!(matches!(
+            state.select_monitor(MonitorSelector::Primary),
+            Ok(1)
+        ));
+        assert!(matches!(
+            state.select_monitor(MonitorSelector::Index(2)),
+            Err(Error::MonitorNotFound)
+        ));
+        assert!(matches!(
+            state.select_monitor(MonitorSelector::Name("DP-2".to_owned())),
+            Err(Error::MonitorNotFound)
+        ));
     }
 
     #[test]
-    fn test_focus_client() {
-        let mut state = State {
-            root: unsafe { x::Window::new(0) },
-            ..Default::default()
-        };
-        let window = unsafe { x::Window::new(123) };
-        let pos = Vector2D::new(0, 0);
-        let size = Vector2D::new(100, 100);
-
-        state.add_client(window, pos, size).unwrap();
-
+    fn test_add_workspace_to_monitor() {
+        let mut state = State::default();
         state
-            .focus_client(WindowSelector::Window(window.resource_id()))
+            .add_monitor(
+                "HDMI-1".to_owned(),
+                Vector2D::new(1000, 0),
+                Vector2D::new(800, 600),
+            )
             .unwrap();
 
-        assert_eq!(state.focused, Some(window));
-
         state
-            .focus_client(WindowSelector::Window(state.root.resource_id()))
+            .add_workspace_to_monitor(Some("web".to_owned()), MonitorSelector::Index(1))
             .unwrap();
 
-        assert_eq!(state.focused, None);
-        assert_eq!(state.last_focused, Some(window));
+        let settings = state.workspaces_settings();
+        assert_eq!("HDMI-1", settings[2].monitor);
+        assert!(matches!(
+            state.add_workspace_to_monitor(None, MonitorSelector::Index(2)),
+            Err(Error::MonitorNotFound)
+        ));
     }
 
     #[test]
-    fn test_select_client_window_selector_focused() {
+    fn test_workspace_area_docks() {
         let mut state = State::default();
-        let window = unsafe { x::Window::new(123) };
         state
-            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .set_monitors(vec![
+                MonitorInfo {
+                    name: "DP-1".to_owned(),
+                    pos: Vector2D::new(0, 0),
+                    size: Vector2D::new(1920, 1080),
+                    primary: true,
+                },
+                MonitorInfo {
+                    name: "HDMI-1".to_owned(),
+                    pos: Vector2D::new(1920, 0),
+                    size: Vector2D::new(1280, 1024),
+                    primary: false,
+                },
+            ])
             .unwrap();
-        state.set_focused(Some(window));
+        let panel = unsafe { x::Window::new(10) };
+        let bar = unsafe { x::Window::new(11) };
+
+        // A panel on the left edge of the left monitor
+        state.set_dock(
+            panel,
+            Vector2D::new(0, 0),
+            Vector2D::new(30, 1080),
+            Strut {
+                left: 30,
+                ..Default::default()
+            },
+        );
+        // A bar on the bottom edge of the right monitor, which is shorter than the screen
+        state.set_dock(
+            bar,
+            Vector2D::new(1920, 1000),
+            Vector2D::new(1280, 24),
+            Strut {
+                bottom: 80,
+                ..Default::default()
+            },
+        );
 
-        let client = state.select_client(WindowSelector::Focused).unwrap();
+        assert_eq!(
+            (Vector2D::new(30, 0), Vector2D::new(1890, 1080)),
+            state.workspace_area(0)
+        );
+        assert_eq!(
+            (Vector2D::new(1920, 0), Vector2D::new(1280, 1000)),
+            state.workspace_area(1)
+        );
 
-        assert_eq!(window, client.window);
+        assert!(state.remove_dock(panel));
+        assert!(!state.is_dock(panel));
+        assert!(!state.remove_dock(panel));
+        assert_eq!(
+            (Vector2D::new(0, 0), Vector2D::new(1920, 1080)),
+            state.workspace_area(0)
+        );
     }
 
     #[test]
-    fn test_select_client_window_selector_closest() {
+    fn test_workspace_area_padding() {
         let mut state = State::default();
-        let window_ne = unsafe { x::Window::new(1) };
-        let window_nw = unsafe { x::Window::new(2) };
-        let window_sw = unsafe { x::Window::new(3) };
-        let window_se = unsafe { x::Window::new(4) };
-
         state
-            .add_client(window_nw, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1920, 1080))
             .unwrap();
+        state.set_dock(
+            unsafe { x::Window::new(10) },
+            Vector2D::new(0, 0),
+            Vector2D::new(1920, 30),
+            Strut {
+                top: 30,
+                ..Default::default()
+            },
+        );
 
         state
-            .add_client(window_ne, Vector2D::new(150, 0), Vector2D::new(100, 100))
+            .set_monitor_padding(
+                MonitorSelector::Focused,
+                Padding {
+                    top: 10,
+                    right: 20,
+                    bottom: 30,
+                    left: 40,
+                },
+            )
             .unwrap();
 
+        assert_eq!(
+            (Vector2D::new(40, 40), Vector2D::new(1860, 1010)),
+            state.workspace_area(0)
+        );
+        assert!(matches!(
+            state.set_monitor_padding(MonitorSelector::Index(1), Padding::default()),
+            Err(Error::MonitorNotFound)
+        ));
+    }
+
+    #[test]
+    fn test_windows_info() {
+        let mut state = State::default();
         state
-            .add_client(window_sw, Vector2D::new(0, 150), Vector2D::new(100, 100))
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1920, 1080))
             .unwrap();
-
+        state.add_workspace(Some("web".to_owned())).unwrap();
+        let window_1 = unsafe { x::Window::new(1) };
+        let window_2 = unsafe { x::Window::new(2) };
         state
-            .add_client(window_se, Vector2D::new(150, 150), Vector2D::new(100, 100))
+            .add_client_to_workspace(0, window_1, Vector2D::new(0, 0), Vector2D::new(1920, 1080))
             .unwrap();
-
-        state.set_focused(Some(window_ne));
-        let client = state
-            .select_client(WindowSelector::Closest(CardinalDirection::South))
+        state
+            .add_client_to_workspace(1, window_2, Vector2D::new(10, 20), Vector2D::new(300, 200))
             .unwrap();
-        assert_eq!(window_se, client.window);
-
-        state.set_focused(Some(window_se));
-        let client = state
-            .select_client(WindowSelector::Closest(CardinalDirection::West))
+        state
+            .set_client_class(window_2, "firefox".to_owned())
+            .unwrap();
+        state
+            .set_client_title(window_2, "Mozilla Firefox".to_owned())
+            .unwrap();
+        state
+            .set_client_instance(window_2, Some("Navigator".to_owned()))
+            .unwrap();
+        state.set_client_pid(window_2, Some(1234)).unwrap();
+        state
+            .set_client_types(window_2, vec!["normal".to_owned()])
             .unwrap();
-        assert_eq!(window_sw, client.window);
 
-        state.set_focused(Some(window_sw));
-        let client = state
-            .select_client(WindowSelector::Closest(CardinalDirection::North))
+        let windows = state.windows_info(None).unwrap();
+        assert_eq!(2, windows.len());
+        assert!(windows[0].fullscreen);
+        assert_eq!(
+            WindowInfo {
+                id: 2,
+                title: Some("Mozilla Firefox".to_owned()),
+                class: Some("firefox".to_owned()),
+                instance: Some("Navigator".to_owned()),
+                pid: Some(1234),
+                types: vec!["normal".to_owned()],
+                transient_for: None,
+                pos: Vector2D::new(10, 20),
+                size: Vector2D::new(300, 200),
+                workspace: 1,
+                floating: true,
+                fullscreen: false,
+                urgent: false,
+            },
+            windows[1]
+        );
+
+        let windows = state
+            .windows_info(Some(WorkspaceSelector::Name("web".to_owned())))
             .unwrap();
-        assert_eq!(window_nw, client.window);
+        assert_eq!(vec![2], windows.iter().map(|w| w.id).collect::<Vec<_>>());
+        assert!(matches!(
+            state.windows_info(Some(WorkspaceSelector::Index(5))),
+            Err(Error::WorkspaceNotFound)
+        ));
     }
 
     #[test]
-    fn select_client_window_selector_cycle() {
+    fn test_focused_window_info() {
         let mut state = State::default();
-        let window_1 = unsafe { x::Window::new(1) };
-        let window_2 = unsafe { x::Window::new(2) };
-        let window_3 = unsafe { x::Window::new(3) };
+        assert_eq!(None, state.focused_window_info());
 
+        let window = unsafe { x::Window::new(1) };
         state
-            .add_client(window_1, Vector2D::new(0, 0), Vector2D::new(100, 100))
+            .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
             .unwrap();
+        assert!(state
+            .set_client_title(window, "Terminal".to_owned())
+            .unwrap());
+        assert!(!state
+            .set_client_title(window, "Terminal".to_owned())
+            .unwrap());
+        state.focus_client(WindowSelector::Window(1)).unwrap();
+
+        let info = state.focused_window_info().unwrap();
+        assert_eq!(1, info.id);
+        assert_eq!(Some("Terminal".to_owned()), info.title);
+        assert_eq!(0, info.workspace);
+    }
 
+    #[test]
+    fn test_dump() {
+        let mut state = State::default();
+        let window = unsafe { x::Window::new(1) };
         state
-            .add_client(window_2, Vector2D::new(150, 0), Vector2D::new(100, 100))
+            .add_client(window, Vector2D::new(10, 20), Vector2D::new(100, 100))
             .unwrap();
-
+        state.focus_client(WindowSelector::Window(1)).unwrap();
         state
-            .add_client(window_3, Vector2D::new(0, 150), Vector2D::new(100, 100))
+            .mark_client(WindowSelector::Focused, "term".to_owned())
             .unwrap();
 
-        state.set_focused(Some(window_1));
+        let dump = state.dump();
+        assert_eq!(json!(1), dump["focused"]);
+        assert_eq!(json!({"term": 1}), dump["marks"]);
+        assert_eq!(json!("1"), dump["workspaces"][0]["name"]);
+        assert_eq!(json!([1]), dump["workspaces"][0]["focus_history"]);
+        assert_eq!(
+            json!({"x": 10, "y": 20}),
+            dump["workspaces"][0]["clients"][0]["pos"]
+        );
+        assert_eq!(json!("default"), dump["monitors"][0]["name"]);
+    }
 
-        let client = state
-            .select_client(WindowSelector::Cycle(CycleDirection::Next))
+    #[test]
+    fn test_tree() {
+        let mut state = State::default();
+        state
+            .set_monitors(vec![
+                MonitorInfo {
+                    name: "left".to_owned(),
+                    pos: Vector2D::new(0, 0),
+                    size: Vector2D::new(1920, 1080),
+                    primary: true,
+                },
+                MonitorInfo {
+                    name: "right".to_owned(),
+                    pos: Vector2D::new(1920, 0),
+                    size: Vector2D::new(1920, 1080),
+                    primary: false,
+                },
+            ])
+            .unwrap();
+        let first = unsafe { x::Window::new(1) };
+        let second = unsafe { x::Window::new(2) };
+        let third = unsafe { x::Window::new(3) };
+        for window in [first, second, third] {
+            state
+                .add_client(window, Vector2D::new(0, 0), Vector2D::new(100, 100))
+                .unwrap();
+        }
+        state
+            .focus_client(WindowSelector::Window(second.resource_id()))
             .unwrap();
 
-        assert_eq!(window_2, client.window);
+        let tree = state.tree();
 
-        let client = state
-            .select_client(WindowSelector::Cycle(CycleDirection::Prev))
+        assert_eq!(
+            vec!["left", "right"],
+            tree.iter().map(|monitor| &monitor.name).collect::<Vec<_>>()
+        );
+        assert!(tree[0].focused);
+        let workspace = &tree[0].workspaces[0];
+        assert!(workspace.visible && workspace.active);
+        assert_eq!("floating", workspace.layout);
+        assert_eq!(
+            vec![2, 3, 1],
+            workspace
+                .windows
+                .iter()
+                .map(|window| window.id)
+                .collect::<Vec<_>>()
+        );
+        assert!(tree[1].workspaces.iter().all(|workspace| !workspace.active));
+    }
+
+    #[test]
+    fn test_clients_count() {
+        let mut state = State::default();
+        state.add_workspace(Some("web".to_owned())).unwrap();
+        state
+            .add_client(
+                unsafe { x::Window::new(1) },
+                Vector2D::new(0, 0),
+                Vector2D::new(100, 100),
+            )
+            .unwrap();
+        state
+            .add_client_to_workspace(
+                1,
+                unsafe { x::Window::new(2) },
+                Vector2D::new(0, 0),
+                Vector2D::new(100, 100),
+            )
             .unwrap();
 
-        assert_eq!(window_3, client.window);
+        assert_eq!(2, state.clients_count());
     }
 }
@@ -1,7 +1,9 @@
 use std::ops;
+
+use serde::{Deserialize, Serialize};
 // TODO: generics
 
-#[derive(Debug, Copy, Clone, Default, PartialEq)]
+#[derive(Debug, Copy, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Vector2D {
     pub x: i32,
     pub y: i32,
@@ -42,6 +44,13 @@ impl Vector2D {
             y: self.y.max(other.y),
         }
     }
+
+    pub fn min(&self, other: Self) -> Self {
+        Self {
+            x: self.x.min(other.x),
+            y: self.y.min(other.y),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -71,4 +80,12 @@ mod tests {
         let v3 = v1.max(v2);
         assert_eq!(v3, Vector2D::new(3, 4));
     }
+
+    #[test]
+    fn test_vector2d_min() {
+        let v1 = Vector2D::new(1, 4);
+        let v2 = Vector2D::new(3, 2);
+        let v3 = v1.min(v2);
+        assert_eq!(v3, Vector2D::new(1, 2));
+    }
 }
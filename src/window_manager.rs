@@ -1,35 +1,99 @@
 use anyhow::{anyhow, Context, Result};
 use crossbeam::channel;
+use std::collections::HashMap;
 use std::path::PathBuf;
 use std::process;
+use std::time::{Duration, Instant};
 use std::{sync::Arc, thread};
-use xcb::{x, Xid};
+use xcb::{shape, x, xkb, Xid};
 
 use crate::atoms::Atoms;
-use crate::commands::{Command, WindowSelector, WorkspaceSelector};
+use crate::bindings::{Binding, Bindings, Press, DEFAULT_MODE};
+use crate::borders;
+use crate::client::{Request, Response};
+use crate::color::Color;
+use crate::commands::{
+    Command, MonitorSelector, Query, SnapPosition, WindowSelector, WorkspaceSelector,
+};
 use crate::config::Config;
-use crate::state::State;
+use crate::corners;
+use crate::events::{Event, Subscribers};
+use crate::geometry_cache::GeometryCache;
+use crate::keys::{self, KeyCombo, Keymap};
+use crate::launcher::{self, Launcher};
+use crate::log::{debug, info, warning};
+use crate::metrics::Metrics;
+use crate::recording::{self, Recording};
+use crate::rules::{Rule, WindowProperties};
+use crate::session_store::SessionStore;
+use crate::state::{SessionClient, StackPosition, State, Strut};
 use crate::vector::Vector2D;
-use crate::{ewmh, icccm};
+use crate::workspace_store::WorkspaceStore;
+use crate::xconn::XConn;
+use crate::{ewmh, hooks, i3_ipc, icccm, monitor, rules};
 
-pub struct WindowManager {
+/// How often the session is written to disk, if it changed.
+const SESSION_SAVE_INTERVAL: Duration = Duration::from_secs(2);
+
+pub struct WindowManager<C = xcb::Connection> {
     state: State,
-    conn: Arc<xcb::Connection>,
+    conn: Arc<C>,
     atoms: Atoms,
-    client_receiver: channel::Receiver<Command>,
+    client_receiver: channel::Receiver<Request>,
     screen_num: i32,
     config: Config,
+    /// The config file, read again on reload.
+    config_path: PathBuf,
+    /// The rules added at runtime, which take precedence over the rules of the config.
+    rules: Vec<Rule>,
+    geometry_cache: GeometryCache,
+    workspace_store: WorkspaceStore,
+    session_store: SessionStore,
+    /// Fires when the session should be written to disk.
+    session_timer: channel::Receiver<Instant>,
+    /// The clients of the session being recovered, indexed by window id.
+    recovered_clients: HashMap<u32, SessionClient>,
+    /// The names of the window type atoms met so far, as atoms do not change while the server runs.
+    window_type_names: HashMap<x::Atom, String>,
+    subscribers: Subscribers,
+    /// The recording in progress, if any.
+    recording: Option<Recording>,
+    launcher: Launcher,
+    metrics: Metrics,
+    bindings: Bindings,
+    /// Fires when the chord in progress times out.
+    chord_timer: channel::Receiver<Instant>,
+    /// The default colormap of the screen, where the colors are allocated.
+    colormap: x::Colormap,
+    /// The pixel values of the border colors of the config.
+    border_pixel: u32,
+    focused_border_pixel: u32,
+    outer_border_pixel: u32,
+    /// Whether the server is grabbed, as grabs do not nest.
+    server_grabbed: bool,
+    /// Whether the keyboard is read with XKB, which knows its groups.
+    xkb: bool,
+    /// The keyboard mapping, read again when it changes.
+    keymap: Keymap,
+    /// The socket of the i3 IPC, if it is served.
+    i3_socket: Option<PathBuf>,
 }
 
-impl WindowManager {
+impl<C: XConn> WindowManager<C> {
+    #[allow(clippy::too_many_arguments)]
     pub fn new(
-        conn: xcb::Connection,
+        conn: C,
         screen_num: i32,
-        client_receiver: channel::Receiver<Command>,
+        client_receiver: channel::Receiver<Request>,
         config: Config,
-    ) -> WindowManager {
+        config_path: PathBuf,
+        geometry_cache: GeometryCache,
+        workspace_store: WorkspaceStore,
+        session_store: SessionStore,
+        i3_socket: Option<PathBuf>,
+    ) -> Self {
         let conn = Arc::new(conn);
-        let atoms = Atoms::intern_all(&conn).unwrap();
+        let atoms = conn.intern_atoms().unwrap();
         WindowManager {
             state: State::default(),
             conn,
@@ -37,19 +101,74 @@ impl WindowManager {
             client_receiver,
             screen_num,
             config,
+            config_path,
+            rules: Vec::new(),
+            geometry_cache,
+            workspace_store,
+            session_store,
+            session_timer: channel::tick(SESSION_SAVE_INTERVAL),
+            recovered_clients: HashMap::new(),
+            window_type_names: HashMap::new(),
+            subscribers: Subscribers::default(),
+            recording: None,
+            launcher: Launcher::default(),
+            metrics: Metrics::default(),
+            bindings: Bindings::default(),
+            chord_timer: channel::never(),
+            colormap: x::Colormap::none(),
+            border_pixel: 0,
+            focused_border_pixel: 0,
+            outer_border_pixel: 0,
+            server_grabbed: false,
+            xkb: false,
+            keymap: Keymap::with_groups(0, Vec::new()),
+            i3_socket,
         }
     }
+}
 
-    pub fn run(&mut self, autostart_file_path: PathBuf) -> Result<()> {
+/// Starting and running the window manager needs the xcb connection itself,
+/// to read the screens and to wait for the events.
+impl WindowManager {
+    /// Manage the windows of the display until quitting.
+    ///
+    /// When recovering, the windows left by a crashed instance go back to their workspace and geometry.
+    pub fn run(&mut self, autostart_file_path: PathBuf, recover: bool) -> Result<()> {
         let conn = Arc::clone(&self.conn);
         let setup = conn.get_setup();
-        // TODO handle no screen?
-        let screen = setup.roots().nth(self.screen_num as usize).unwrap();
+        // Only the screen of the display name is managed, e.g. 1 for ":0.1"
+        let screen = setup.roots().nth(self.screen_num as usize).ok_or_else(|| {
+            anyhow!(
+                "Screen {} not found, the display has {} screens",
+                self.screen_num,
+                setup.roots().count()
+            )
+        })?;
         self.state.root = screen.root();
-        self.state.monitor_size = Vector2D::new(
-            screen.width_in_pixels().into(),
-            screen.height_in_pixels().into(),
-        );
+        self.colormap = screen.default_colormap();
+        self.alloc_border_colors()?;
+        self.state.wrap = self.config.wrap;
+        self.state.directional_wrap = self.config.directional_wrap;
+        self.state.set_monitor_geometry(
+            0,
+            Vector2D::new(0, 0),
+            Vector2D::new(
+                screen.width_in_pixels().into(),
+                screen.height_in_pixels().into(),
+            ),
+        )?;
+        // Without RandR or Xinerama the whole screen is a single monitor
+        let monitors = monitor::query_monitors(&conn, screen.root())?;
+        self.state.set_monitors(monitors)?;
+        // Create the workspaces of the config, or restore the ones of the previous session
+        let workspaces = if self.config.workspaces.is_empty() {
+            self.workspace_store.load()
+        } else {
+            Some(self.config.workspaces.clone())
+        };
+        if let Some(workspaces) = workspaces {
+            self.state.restore_workspaces(workspaces);
+        }
 
         if self.become_window_manager().is_err() {
             return Err(anyhow!("Another window manager is running."));
@@ -57,15 +176,19 @@ impl WindowManager {
 
         ewmh::set_supported(&conn, &self.atoms, screen.root());
 
-        // Create a child window for EWMH compliance
+        self.xkb = self.conn.use_xkb()?;
+        self.keymap = self.conn.read_keymap(self.xkb)?;
+
+        // Create a child window for EWMH compliance on the primary monitor
         // See: https://specifications.freedesktop.org/wm-spec/wm-spec-1.3.html
+        let child_pos = self.state.monitor(MonitorSelector::Primary)?.pos();
         self.state.child = conn.generate_id();
         self.conn.send_request(&x::CreateWindow {
             depth: 0,
             wid: self.state.child,
             parent: self.state.root,
-            x: 0,
-            y: 0,
+            x: child_pos.x as i16,
+            y: child_pos.y as i16,
             width: 1,
             height: 1,
             border_width: 0,
@@ -76,12 +199,43 @@ impl WindowManager {
 
         ewmh::set_wm_name(&conn, &self.atoms, self.state.child, "toniowm");
         ewmh::set_supporting_wm_check(&conn, &self.atoms, self.state.root, self.state.child);
+        if let Some(socket) = &self.i3_socket {
+            i3_ipc::set_socket_path_property(&conn, &self.atoms, self.state.root, socket);
+        }
         ewmh::set_active_window(&conn, &self.atoms, self.state.root, self.state.child);
-        ewmh::set_current_desktop(&conn, &self.atoms, self.state.root, 0);
+        ewmh::set_current_desktop(
+            &conn,
+            &self.atoms,
+            self.state.root,
+            self.state.active_workspace() as u32,
+        );
+
+        let session = recover.then(|| self.session_store.load()).flatten();
+        if let Some(session) = &session {
+            self.recovered_clients = session
+                .clients
+                .iter()
+                .map(|client| (client.window, client.clone()))
+                .collect();
+        }
+        self.with_server_grabbed(Self::adopt_windows)?;
+        self.recovered_clients.clear();
+        if let Some(id) = session.and_then(|session| session.focused) {
+            if let Ok(Some(window)) = self.state.focus_client(WindowSelector::Window(id)) {
+                self.show_and_focus_window(window)?;
+            }
+        }
 
-        process::Command::new(&autostart_file_path)
-            .spawn()
-            .with_context(|| "Failed to load toniorc")?;
+        // The autostart script is optional, the programs can be listed in the config instead
+        if autostart_file_path.exists() {
+            launcher::spawn(&mut process::Command::new(&autostart_file_path))
+                .with_context(|| "Failed to load toniorc")?;
+        }
+        for program in &self.config.autostart {
+            if let Err(e) = self.launcher.autostart(program) {
+                println!("Error: Failed to start `{}`: {}", program.command, e);
+            }
+        }
 
         self.refresh_workspaces();
 
@@ -89,119 +243,129 @@ impl WindowManager {
 
         // Spawn XCB event thread
         let (sender, receiver) = crossbeam::channel::unbounded();
+        let (error_sender, error_receiver) = crossbeam::channel::unbounded();
         let conn = Arc::clone(&self.conn);
         thread::spawn(move || loop {
-            // TODO: handle error, maybe just log?
-            let event = conn.wait_for_event().unwrap();
-            println!("Received event: {:?}", event);
-            match event {
-                xcb::Event::X(event) => sender.send(event).unwrap(),
-                xcb::Event::Unknown(_) => {}
+            let event = match conn.wait_for_event() {
+                Ok(event) => event,
+                // The errors of unchecked requests are reported here,
+                // they are expected for windows destroyed in the meantime
+                Err(xcb::Error::Protocol(err)) => {
+                    debug!("X error: {:?}", err);
+                    if error_sender.send(err).is_err() {
+                        break;
+                    }
+                    continue;
+                }
+                // The connection cannot be used anymore, let the main loop shut down
+                Err(xcb::Error::Connection(err)) => {
+                    let _ = sender.send(Err(err));
+                    break;
+                }
+            };
+            debug!("Received event: {:?}", event);
+            let sent = match event {
+                // Monitor changes are not selected yet
+                xcb::Event::RandR(_) => true,
+                xcb::Event::Unknown(_) => true,
+                event => sender.send(Ok(event)).is_ok(),
             };
+            // The main loop is gone
+            if !sent {
+                break;
+            }
         });
 
+        let result = self.event_loop(receiver, error_receiver);
+        self.shutdown();
+
+        result
+    }
+
+    /// Handle the events of the X server and the commands of the clients until quitting.
+    ///
+    /// Return an error if the connection to the X server is lost.
+    fn event_loop(
+        &mut self,
+        receiver: channel::Receiver<Result<xcb::Event, xcb::ConnError>>,
+        error_receiver: channel::Receiver<xcb::ProtocolError>,
+    ) -> Result<()> {
         loop {
             channel::select! {
-                recv(receiver) -> event => match event.unwrap() {
-                    x::Event::ButtonPress(ev) => {
-                        self.handle_button_press_event(ev)?;
-                    }
-                    x::Event::MotionNotify(ev) => {
-                        self.handle_motion_notify_event(ev)?;
-                    }
-                    x::Event::ConfigureRequest(ev) => {
-                        self.handle_configure_request_event(ev)?;
-                    }
-                    x::Event::MapRequest(ev) => {
-                        self.handle_map_request_event(ev)?;
-                    },
-                    x::Event::DestroyNotify(ev) => {
-                        self.handle_destroy_notify_event(ev);
-                    }
-                    x::Event::ClientMessage(ev) => {
-                        // This event is sent if a pager wants to switch ti antoher workspace.
-                        if ev.r#type().resource_id() == self.atoms.net_current_desktop.resource_id() {
-                            if let x::ClientMessageData::Data32([index, ..]) = ev.data() {
-                                self.activate_workspace(WorkspaceSelector::Index(index as usize))?;
-                            }
+                recv(receiver) -> event => {
+                    let event = match event {
+                        Ok(Ok(event)) => event,
+                        Ok(Err(err)) => {
+                            return Err(err).context("Lost the connection to the X server")
                         }
-                    }
-                    ev => {
-                        println!("Unhandled event: {:?}", ev);
-                    }
-                },
-                recv(self.client_receiver) -> message => match message.unwrap() {
-                    Command::Quit => {
-                        println!("Quitting");
-                        break;
-                    }
-                    Command::Focus{ selector } => {
-                        match self.state.focus_client(selector) {
-                            Ok(window) => {
-                                if let Some(window) = window {
-                                    self.focus_window(window)?;
-                                };
-                            }
-                            Err(e) => {
-                                println!("Error: {:?}", e);
-                            }
+                        Err(_) => return Err(anyhow!("The X event thread stopped")),
+                    };
+                    let result = match event {
+                        xcb::Event::X(event) => {
+                            self.metrics.count_event(&event);
+                            self.handle_event(event)
                         }
-                    }
-                    Command::Close{ selector } => {
-                        match self.state.select_client(selector) {
-                            Ok(client) => {
-                                self.delete_window(client.window())?;
-                            }
-                            // TODO: return error in result channel
-                            _ => {
-                                println!("Client not found");
-                            }
+                        xcb::Event::Shape(shape::Event::Notify(ev)) => {
+                            self.handle_shape_notify_event(ev).map(|_| true)
                         }
+                        xcb::Event::Xkb(
+                            xkb::Event::NewKeyboardNotify(_) | xkb::Event::MapNotify(_),
+                        ) => self.update_keymap().map(|_| true),
+                        _ => Ok(true),
+                    };
+                    // An event about a window which is already gone must not stop the window manager
+                    match result {
+                        Ok(true) => {}
+                        Ok(false) => break,
+                        Err(err) if is_connection_error(&err) => return Err(err),
+                        Err(err) => warning!("Failed to handle event: {:#}", err),
                     }
-                    Command::AddWorkspace{ name } => {
-                        self.state.add_workspace(name)?;
-                        self.refresh_workspaces();
-                    }
-                    Command::RenameWorkspace{ selector, name } => {
-                        self.state.rename_workspace(selector, name)?;
-                        self.refresh_workspaces();
+                }
+                recv(error_receiver) -> _ => {
+                    self.metrics.count_x_error();
+                }
+                recv(self.chord_timer) -> _ => {
+                    self.end_chord();
+                }
+                recv(self.session_timer) -> _ => {
+                    if let Err(err) = self.session_store.save(self.state.session()) {
+                        warning!("Failed to save the session: {:#}", err);
                     }
-                    Command::ActivateWorkspace{ selector } => {
-                        self.activate_workspace(selector)?;
+                }
+                recv(self.client_receiver) -> message => {
+                    let Request { command, response_sender, event_sender } = message.unwrap();
+                    self.metrics.count_command();
+                    if let Command::Quit = command {
+                        info!("Quitting");
+                        let _ = response_sender.send(Response::Ok(serde_json::Value::Null));
+                        break;
                     }
-                    Command::SetBorderWidth{ width } => {
-                        self.config.border_width = width;
-                        for (window, _) in self.state.active_workspace_clients().iter() {
-                            self.conn.send_request(&x::ConfigureWindow {
-                                window: *window,
-                                value_list: &[x::ConfigWindow::BorderWidth(self.config.border_width)],
-                            });
+                    if let Command::Subscribe { events } = command {
+                        if let Some(event_sender) = event_sender {
+                            self.subscribers.subscribe(events, event_sender);
                         }
+                        let _ = response_sender.send(Response::Ok(serde_json::Value::Null));
+                        continue;
                     }
-                    Command::SetBorderColor{ color } => {
-                        self.config.border_color = color;
-                        for (window, _) in self.state.active_workspace_clients().iter() {
-                            if Some(*window) == self.state.focused() {
-                                continue;
-                            }
-
-                            self.conn.send_request(&x::ChangeWindowAttributes {
-                                window: *window,
-                                value_list: &[
-                                    x::Cw::BorderPixel(self.config.border_color),
-                                ],
-                            });
+
+                    // Failing commands are recorded too, as they may be needed to reproduce a bug
+                    self.record_command(&command);
+
+                    // A failing command is reported to the client instead of stopping the window manager,
+                    // unless the connection to the X server is lost
+                    let response = match self.handle_command(command) {
+                        Ok(payload) => Response::Ok(payload),
+                        Err(e) if is_connection_error(&e) => {
+                            let _ = response_sender.send(Response::Error(format!("{:#}", e)));
+                            return Err(e);
                         }
-                    }
-                    Command::SetFocusedBorderColor{ color } => {
-                        self.config.focused_border_color = color;
-                        if let Some(window) = self.state.focused() {
-                            self.conn.send_request(&x::ChangeWindowAttributes {
-                                window,
-                                value_list: &[x::Cw::BorderPixel(self.config.focused_border_color)],
-                            });
+                        Err(e) => {
+                            println!("Error: {:?}", e);
+                            Response::Error(format!("{:#}", e))
                         }
-                    }
+                    };
+                    // The client may have disconnected already
+                    let _ = response_sender.send(response);
                 }
             }
 
@@ -209,309 +373,2174 @@ impl WindowManager {
         }
         Ok(())
     }
+}
 
-    /// Become the window manager.
-    /// This is done by changing the root window's event mask.
-    ///
-    /// If another window manager is already running, this will fail.
-    fn become_window_manager(&self) -> Result<()> {
-        self.conn
-            .send_and_check_request(&x::ChangeWindowAttributes {
-                window: self.state.root,
-                value_list: &[
-                    x::Cw::EventMask(
-                        x::EventMask::SUBSTRUCTURE_NOTIFY
-                            | x::EventMask::SUBSTRUCTURE_REDIRECT
-                            | x::EventMask::BUTTON_PRESS
-                            | x::EventMask::BUTTON_RELEASE,
-                    ),
-                    x::Cw::Cursor(Xid::none()),
-                ],
-            })?;
-
-        Ok(())
-    }
-
-    /// This is called when a new window is created.
-    fn handle_map_request_event(&mut self, ev: x::MapRequestEvent) -> Result<()> {
-        // Map the window
-        self.conn.send_request(&x::MapWindow {
-            window: ev.window(),
-        });
+impl<C: XConn> WindowManager<C> {
+    /// Clean up before exiting, whether quitting or after losing the connection.
+    fn shutdown(&mut self) {
+        self.launcher.stop_autostart();
+        self.session_store.remove();
 
-        if ewmh::get_wm_window_type(&self.conn, &self.atoms, ev.window())?
-            .contains(&self.atoms.net_wm_window_type_dock)
-        {
-            // Do not manage dock windows
-            return Ok(());
+        // Release the clients: the windows on hidden workspaces are shown again
+        for window in self.state.windows() {
+            self.conn.send_request(&x::MapWindow { window });
         }
-
-        // Ask the X server for the window's geometry
-        let cookie = self.conn.send_request(&x::GetGeometry {
-            drawable: x::Drawable::Window(ev.window()),
-        });
-        let reply = self.conn.wait_for_reply(cookie)?;
-
-        // Add the window to the state
-        let size = Vector2D::new(reply.width().into(), reply.height().into());
-        // Center the window
-        let pos = Vector2D::new(
-            self.state.monitor_size.x / 2 - size.x / 2,
-            self.state.monitor_size.y / 2 - size.y / 2,
-        );
-        self.state.add_client(ev.window(), pos, size)?;
-
-        // Set border width
-        self.conn.send_request(&x::ConfigureWindow {
-            window: ev.window(),
-            value_list: &[x::ConfigWindow::BorderWidth(self.config.border_width)],
-        });
-
-        // Set border color and event mask
-        self.conn.send_request(&x::ChangeWindowAttributes {
-            window: ev.window(),
-            value_list: &[
-                x::Cw::BorderPixel(self.config.border_color),
-                x::Cw::EventMask(
-                    x::EventMask::SUBSTRUCTURE_NOTIFY | x::EventMask::SUBSTRUCTURE_REDIRECT,
-                ),
-            ],
-        });
-
-        self.conn.send_request(&x::ChangeSaveSet {
-            mode: x::SetMode::Insert,
-            window: ev.window(),
-        });
-
-        // Reparent the window
-        self.conn.send_request(&x::ReparentWindow {
-            window: ev.window(),
-            parent: self.state.root,
-            x: pos.x as i16,
-            y: pos.y as i16,
-        });
-
-        // Focus the window
         self.conn.send_request(&x::SetInputFocus {
             revert_to: x::InputFocus::PointerRoot,
-            focus: ev.window(),
+            focus: self.state.root,
             time: x::CURRENT_TIME,
         });
 
-        // Add button grab settings
-        self.conn.send_request(&x::GrabButton {
-            owner_events: true,
-            grab_window: ev.window(),
-            event_mask: x::EventMask::BUTTON_PRESS | x::EventMask::BUTTON_RELEASE,
-            pointer_mode: x::GrabMode::Async,
-            keyboard_mode: x::GrabMode::Async,
-            confine_to: xcb::Xid::none(),
-            cursor: xcb::Xid::none(),
-            button: crate::config::SELECT_BUTTON,
-            modifiers: crate::config::MOD_KEY,
-        });
-
-        // Allow events
-        self.conn.send_request(&x::AllowEvents {
-            mode: x::Allow::AsyncPointer,
-            time: x::CURRENT_TIME,
+        ewmh::delete_root_properties(&self.conn, &self.atoms, self.state.root);
+        self.conn.send_request(&x::DestroyWindow {
+            window: self.state.child,
         });
+        // The connection may be lost already
+        let _ = self.conn.flush();
+    }
 
-        // Drag settings
-        self.conn.send_request(&x::GrabButton {
-            owner_events: false,
-            grab_window: ev.window(),
-            event_mask: x::EventMask::BUTTON_PRESS
-                | x::EventMask::BUTTON_RELEASE
-                | x::EventMask::BUTTON_MOTION,
-            pointer_mode: x::GrabMode::Async,
-            keyboard_mode: x::GrabMode::Async,
-            confine_to: xcb::Xid::none(),
-            cursor: xcb::Xid::none(),
-            button: crate::config::DRAG_BUTTON,
-            modifiers: crate::config::MOD_KEY,
-        });
+    /// Allocate a color in the colormap of the screen and return its pixel value.
+    fn alloc_color(&self, color: &Color) -> Result<u32> {
+        self.conn
+            .alloc_color(self.colormap, color)
+            .with_context(|| format!("Failed to allocate the color {}", color))
+    }
 
-        // Resize settings
-        self.conn.send_request(&x::GrabButton {
-            owner_events: false,
-            grab_window: ev.window(),
-            event_mask: x::EventMask::BUTTON_PRESS
-                | x::EventMask::BUTTON_RELEASE
-                | x::EventMask::BUTTON_MOTION,
-            pointer_mode: x::GrabMode::Async,
-            keyboard_mode: x::GrabMode::Async,
-            confine_to: xcb::Xid::none(),
-            cursor: xcb::Xid::none(),
-            button: crate::config::RESIZE_BUTTON,
-            modifiers: crate::config::MOD_KEY,
+    /// Release a color allocated by `alloc_color`.
+    fn free_color(&self, pixel: u32) {
+        self.conn.send_request(&x::FreeColors {
+            cmap: self.colormap,
+            plane_mask: 0,
+            pixels: &[pixel],
         });
+    }
 
-        self.state
-            .focus_client(WindowSelector::Window(ev.window().resource_id()))?;
-        self.focus_window(ev.window())?;
+    /// Allocate the border colors of the config.
+    /// A color the X server does not know, e.g. a misspelled name, is replaced by the default.
+    fn alloc_border_colors(&mut self) -> Result<()> {
+        let default = Config::default();
+        self.border_pixel = match self.alloc_color(&self.config.border_color) {
+            Ok(pixel) => pixel,
+            Err(e) => {
+                println!("Error: {:?}", e);
+                self.config.border_color = default.border_color;
+                self.alloc_color(&self.config.border_color)?
+            }
+        };
+        self.focused_border_pixel = match self.alloc_color(&self.config.focused_border_color) {
+            Ok(pixel) => pixel,
+            Err(e) => {
+                println!("Error: {:?}", e);
+                self.config.focused_border_color = default.focused_border_color;
+                self.alloc_color(&self.config.focused_border_color)?
+            }
+        };
+        self.outer_border_pixel = match self.alloc_color(&self.config.outer_border_color) {
+            Ok(pixel) => pixel,
+            Err(e) => {
+                println!("Error: {:?}", e);
+                self.config.outer_border_color = default.outer_border_color;
+                self.alloc_color(&self.config.outer_border_color)?
+            }
+        };
 
         Ok(())
     }
 
-    fn handle_button_press_event(&mut self, ev: x::ButtonPressEvent) -> Result<()> {
-        let cookie = self.conn.send_request(&x::GetGeometry {
-            drawable: x::Drawable::Window(ev.event()),
-        });
-
-        let resp = self.conn.wait_for_reply(cookie)?;
-
-        self.state.drag_start_pos = Vector2D::new(ev.root_x().into(), ev.root_y().into());
-        self.state.drag_start_frame_pos = Vector2D::new(resp.x().into(), resp.y().into());
+    /// Allocate the border colors again and apply them, after the config changed.
+    fn refresh_border_colors(&mut self) -> Result<()> {
+        let old_pixels = [
+            self.border_pixel,
+            self.focused_border_pixel,
+            self.outer_border_pixel,
+        ];
+        self.alloc_border_colors()?;
+        for pixel in old_pixels {
+            self.free_color(pixel);
+        }
+        self.refresh_borders()
+    }
 
-        if ev.detail() == x::ButtonIndex::N1 as u8 {
-            self.state
-                .focus_client(WindowSelector::Window(ev.event().resource_id()))?;
-            self.focus_window(ev.event())?;
+    /// Read the keyboard mapping again after it changed, e.g. when a layout was added,
+    /// and grab the keys of the active mode again, as their keycodes may have changed.
+    fn update_keymap(&mut self) -> Result<()> {
+        let combos = self.bindings.active_combos();
+        for combo in &combos {
+            // The keys which are gone cannot be ungrabbed
+            self.grab_keys(*combo, false).unwrap_or_default();
+        }
+        self.keymap = self.conn.read_keymap(self.xkb)?;
+        for combo in combos {
+            if let Err(e) = self.grab_keys(combo, true) {
+                warning!("Failed to grab {}: {:#}", combo, e);
+            }
         }
 
         Ok(())
     }
 
-    fn handle_motion_notify_event(&mut self, ev: x::MotionNotifyEvent) -> Result<()> {
-        let mouse_pos = Vector2D::new(ev.root_x().into(), ev.root_y().into());
-        if !ev.state().contains(crate::config::MOD_KEY_BUT) {
-            return Ok(());
-        }
+    /// Get the key combination of a key event.
+    ///
+    /// The keysym of the active group is preferred,
+    /// unless only the keysym of another group of the key is bound.
+    fn key_combo(&self, keycode: u8, state: x::KeyButMask) -> Option<KeyCombo> {
+        let modifiers = x::ModMask::from_bits_truncate(state.bits());
+        let combos = self
+            .keymap
+            .combos(keycode, modifiers, keys::state_group(state.bits()));
 
-        if ev.state().contains(crate::config::DRAG_BUTTON_MASK) {
-            let new_pos = self.state.drag_client(ev.event(), mouse_pos)?;
+        combos
+            .iter()
+            .find(|combo| self.bindings.expects(**combo))
+            .or(combos.first())
+            .copied()
+    }
 
-            self.conn.send_request(&x::ConfigureWindow {
-                window: ev.event(),
-                value_list: &[x::ConfigWindow::X(new_pos.x), x::ConfigWindow::Y(new_pos.y)],
-            });
-        } else if ev.state().contains(crate::config::RESIZE_BUTTON_MASK) {
-            let new_size = self.state.drag_resize_client(ev.event(), mouse_pos)?;
-            self.conn.send_request(&x::ConfigureWindow {
-                window: ev.event(),
-                value_list: &[
-                    x::ConfigWindow::Width(new_size.x as u32),
-                    x::ConfigWindow::Height(new_size.y as u32),
-                ],
-            });
+    /// Grab or ungrab a key combination on the root window,
+    /// so that it is reported to the window manager whatever window is focused.
+    ///
+    /// Return an error if no key of the keyboard produces the keysym.
+    fn grab_keys(&self, combo: KeyCombo, grab: bool) -> Result<()> {
+        let grabs = self.keymap.grabs(combo);
+        if grabs.is_empty() {
+            return Err(anyhow!("No key produces the keysym {:#x}", combo.keysym));
+        }
+
+        for (key, modifiers) in grabs {
+            if grab {
+                self.conn.send_request(&x::GrabKey {
+                    owner_events: true,
+                    grab_window: self.state.root,
+                    modifiers,
+                    key,
+                    pointer_mode: x::GrabMode::Async,
+                    keyboard_mode: x::GrabMode::Async,
+                });
+            } else {
+                self.conn.send_request(&x::UngrabKey {
+                    key,
+                    grab_window: self.state.root,
+                    modifiers,
+                });
+            }
         }
 
         Ok(())
     }
 
-    fn handle_configure_request_event(&self, ev: x::ConfigureRequestEvent) -> Result<()> {
-        // Do not manage dock windows
-        if !ewmh::get_wm_window_type(&self.conn, &self.atoms, ev.window())?
-            .contains(&self.atoms.net_wm_window_type_dock)
-        {
-            self.conn.send_request(&x::ConfigureWindow {
-                window: ev.window(),
-                value_list: &[
-                    x::ConfigWindow::X(ev.x() as i32),
-                    x::ConfigWindow::Y(ev.y() as i32),
-                    x::ConfigWindow::Width(ev.width() as u32),
-                    x::ConfigWindow::Height(ev.height() as u32),
-                    x::ConfigWindow::BorderWidth(self.config.border_width),
-                    x::ConfigWindow::StackMode(ev.stack_mode()),
-                ],
-            });
+    /// Get the command bound to a pressed key combination in the active mode, if any.
+    ///
+    /// An unbound Escape goes back to the default mode.
+    /// The keyboard is grabbed when a chord starts, until it is complete, broken or timed out.
+    fn bound_command(&mut self, ev: &x::KeyPressEvent) -> Result<Option<Command>> {
+        let Some(combo) = self.key_combo(ev.detail(), ev.state()) else {
+            return Ok(None);
+        };
+        // The modifiers of the next key of a chord are pressed on their own first
+        if keys::is_modifier(combo.keysym) {
+            return Ok(None);
         }
 
-        Ok(())
-    }
+        if self.bindings.is_escape(combo) {
+            self.set_mode(DEFAULT_MODE.to_owned())?;
+            return Ok(None);
+        }
 
-    fn handle_destroy_notify_event(&mut self, ev: x::DestroyNotifyEvent) {
-        if let Err(err) = self.state.remove_client(ev.window()) {
-            println!("Failed to remove client: {}", err);
+        let pending = self.bindings.is_chord_pending();
+        match self.bindings.press(combo) {
+            Press::Command(command) => {
+                if pending {
+                    self.end_chord();
+                }
+                Ok(Some(command))
+            }
+            Press::Chord => {
+                if !pending {
+                    self.start_chord()?;
+                }
+                self.chord_timer = channel::after(Duration::from_millis(self.config.chord_timeout));
+                Ok(None)
+            }
+            Press::Unbound => {
+                if pending {
+                    self.end_chord();
+                }
+                Ok(None)
+            }
         }
     }
 
-    fn focus_window(&mut self, window: x::Window) -> Result<()> {
-        // Unfocus last focused window
-        if let Some(last_focused) = self.state.last_focused() {
-            self.conn.send_request(&x::ChangeWindowAttributes {
-                window: last_focused,
-                value_list: &[x::Cw::BorderPixel(self.config.border_color)],
-            });
-        }
+    /// Get the command bound to the release of a key combination in the active mode, if any.
+    ///
+    /// The releases generated by autorepeat while the key is held are ignored.
+    fn released_command(&mut self, ev: &x::KeyReleaseEvent) -> Result<Option<Command>> {
+        let Some(combo) = self.key_combo(ev.detail(), ev.state()) else {
+            return Ok(None);
+        };
+        let Some(command) = self.bindings.release(combo) else {
+            return Ok(None);
+        };
 
-        // Set the input focus
-        self.conn.send_request(&x::SetInputFocus {
-            revert_to: x::InputFocus::PointerRoot,
-            focus: window,
-            time: x::CURRENT_TIME,
-        });
+        // Autorepeat sends a release and a press at once, so the key is still down
+        let keys = self.conn.query_keymap()?;
+        let keycode = ev.detail() as usize;
+        if keys[keycode / 8] & (1 << (keycode % 8)) != 0 {
+            return Ok(None);
+        }
 
-        // Select and focus
-        self.conn.send_request(&x::ChangeWindowAttributes {
-            window,
-            value_list: &[x::Cw::BorderPixel(self.config.focused_border_color)],
-        });
+        Ok(Some(command))
+    }
 
-        self.conn.send_request(&x::SetInputFocus {
-            revert_to: x::InputFocus::PointerRoot,
-            focus: window,
-            time: x::CURRENT_TIME,
-        });
+    /// Run a command bound to keys, logging its errors.
+    ///
+    /// Return false if the command quits the window manager.
+    fn run_bound_command(&mut self, command: Command) -> bool {
+        if let Command::Quit = command {
+            info!("Quitting");
+            return false;
+        }
 
-        // Raise the window above the others
-        self.conn.send_request(&x::ConfigureWindow {
-            window,
-            value_list: &[x::ConfigWindow::StackMode(x::StackMode::Above)],
-        });
+        self.record_command(&command);
+        if let Err(e) = self.handle_command(command) {
+            println!("Error: {:?}", e);
+        }
 
-        // Set the EWMH hint
-        ewmh::set_active_window(&self.conn, &self.atoms, self.state.root, window);
-        Ok(())
+        true
     }
 
-    fn delete_window(&self, window: x::Window) -> Result<()> {
-        // Check if the window supports the delete protocol
-        // If it doesnt, just kill it
-        let wm_protocols = icccm::get_wm_protocols(&self.conn, &self.atoms, window)?;
-        if wm_protocols.contains(&self.atoms.wm_delete_window) {
-            icccm::send_wm_delete_window(&self.conn, &self.atoms, window)?;
-        } else {
-            self.conn.send_request(&x::KillClient {
-                resource: window.resource_id(),
-            });
+    /// Grab the keyboard to read the next keys of a chord, which are not grabbed on their own.
+    fn start_chord(&mut self) -> Result<()> {
+        let status = self.conn.grab_keyboard(self.state.root)?;
+        if status != x::GrabStatus::Success {
+            self.bindings.cancel_chord();
+            return Err(anyhow!("Failed to grab the keyboard: {:?}", status));
         }
 
         Ok(())
     }
 
-    fn activate_workspace(&mut self, selector: WorkspaceSelector) -> Result<()> {
-        // Unmap all windows on the current workspace
-        for (window, _) in self.state.active_workspace_clients().iter() {
-            self.conn.send_request(&x::UnmapWindow { window: *window });
-        }
+    /// Abandon the chord in progress, if any, and release the keyboard.
+    fn end_chord(&mut self) {
+        self.bindings.cancel_chord();
+        self.chord_timer = channel::never();
+        self.conn.send_request(&x::UngrabKeyboard {
+            time: x::CURRENT_TIME,
+        });
+    }
 
-        let workspace_index = self.state.activate_workspace(selector)?;
-        ewmh::set_current_desktop(
-            &self.conn,
-            &self.atoms,
-            self.state.root,
-            workspace_index as u32,
-        );
+    /// Switch the binding mode, grabbing only the keys bound in the new mode.
+    fn set_mode(&mut self, mode: String) -> Result<()> {
+        if mode == self.bindings.mode() {
+            return Ok(());
+        }
 
-        // Map all windows on the new workspace
-        for (window, _) in self.state.active_workspace_clients().iter() {
-            self.conn.send_request(&x::MapWindow { window: *window });
+        if self.bindings.is_chord_pending() {
+            self.end_chord();
         }
+        for combo in self.bindings.active_combos() {
+            self.grab_keys(combo, false)?;
+        }
+        self.bindings.set_mode(mode);
+        for combo in self.bindings.active_combos() {
+            self.grab_keys(combo, true)?;
+        }
+
+        self.subscribers.publish(Event::ModeChanged {
+            mode: self.bindings.mode().to_owned(),
+        });
 
         Ok(())
     }
 
-    fn refresh_workspaces(&self) {
-        ewmh::set_number_of_desktops(
-            &self.conn,
-            &self.atoms,
-            self.state.root,
-            self.state.workspaces_names().len() as u32,
-        );
+    /// Append a command to the recording in progress, if any.
+    /// The recording is stopped if the command cannot be written.
+    fn record_command(&mut self, command: &Command) {
+        let Some(recording) = self.recording.as_mut() else {
+            return;
+        };
 
-        ewmh::set_desktop_names(
+        if recording::is_recorded(command) {
+            if let Err(e) = recording.record(command) {
+                println!(
+                    "Error: failed to record a command, stopping the recording: {:?}",
+                    e
+                );
+                self.recording = None;
+            }
+        }
+    }
+
+    /// Handle a command received from a client.
+    ///
+    /// Return the payload sent back to the client, which is null for most commands.
+    fn handle_command(&mut self, command: Command) -> Result<serde_json::Value> {
+        match command {
+            // Handled by the event loop
+            Command::Quit | Command::Subscribe { .. } => {}
+            Command::Focus { selector } => {
+                // Keep the pointer on the focused window when navigating with the keyboard
+                let warp_pointer = self.config.warp_pointer
+                    && matches!(
+                        selector,
+                        WindowSelector::Closest(_) | WindowSelector::Cycle(_)
+                    );
+                if let Some(window) = self.state.focus_client(selector)? {
+                    // Marked windows can live on another workspace
+                    self.show_and_focus_window(window)?;
+                    if warp_pointer {
+                        self.warp_pointer_to_focused();
+                    }
+                }
+            }
+            Command::CycleMru {
+                direction,
+                all_workspaces,
+            } => {
+                let window = self.state.cycle_mru(direction, all_workspaces)?;
+                self.show_and_focus_window(window)?;
+                if self.config.warp_pointer {
+                    self.warp_pointer_to_focused();
+                }
+            }
+            Command::EndCycleMru => {
+                self.state.end_cycle_mru();
+            }
+            Command::Close { selector } => {
+                return self.for_each_client(selector, |wm, window| wm.delete_window(window));
+            }
+            Command::Kill { selector } => {
+                return self.for_each_client(selector, |wm, window| {
+                    wm.kill_window(window);
+                    Ok(())
+                });
+            }
+            Command::Mark { selector, name } => {
+                self.state.mark_client(selector, name)?;
+            }
+            Command::Snap { selector, position } => {
+                self.snap_client(selector, position)?;
+            }
+            Command::Unsnap { selector } => {
+                let client = self.state.unsnap_client(selector)?;
+                let (window, pos, size) = (client.window(), client.pos(), client.size());
+                self.configure_window_geometry(window, pos, size);
+            }
+            Command::Undo { selector } => {
+                let client = self.state.undo_client_geometry(selector)?;
+                let (window, pos, size) = (client.window(), client.pos(), client.size());
+                self.configure_window_geometry(window, pos, size);
+            }
+            Command::Resize {
+                selector,
+                size,
+                relative,
+                center,
+            } => {
+                let client = self.state.resize_client(selector, size, relative, center)?;
+                let (window, pos, size) = (client.window(), client.pos(), client.size());
+                self.configure_window_geometry(window, pos, size);
+            }
+            Command::AddWorkspace { name, monitor } => {
+                // The workspace may already exist if it was restored from the previous session
+                let result = self.state.add_workspace_to_monitor(name, monitor);
+                self.refresh_workspaces();
+                self.layout_changed();
+                result?;
+            }
+            Command::RenameWorkspace { selector, name } => {
+                self.state.rename_workspace(selector, name)?;
+                self.refresh_workspaces();
+                self.layout_changed();
+            }
+            Command::ActivateWorkspace { selector } => {
+                self.activate_workspace(selector)?;
+                if self.config.warp_pointer {
+                    self.warp_pointer_to_focused();
+                }
+            }
+            Command::MoveWorkspaceToMonitor {
+                workspace_selector,
+                monitor_selector,
+            } => {
+                self.move_workspace_to_monitor(workspace_selector, monitor_selector)?;
+                self.layout_changed();
+            }
+            Command::SetBorderWidth { width } => {
+                self.config.border_width = width;
+                self.refresh_borders()?;
+            }
+            // The old color is freed once no border uses it anymore
+            Command::SetBorderColor { color } => {
+                let old_pixel = self.border_pixel;
+                self.border_pixel = self.alloc_color(&color)?;
+                self.config.border_color = color;
+                self.refresh_borders()?;
+                self.free_color(old_pixel);
+            }
+            Command::SetFocusedBorderColor { color } => {
+                let old_pixel = self.focused_border_pixel;
+                self.focused_border_pixel = self.alloc_color(&color)?;
+                self.config.focused_border_color = color;
+                if let Some(window) = self.state.focused() {
+                    self.draw_border(window, self.focused_border_pixel);
+                }
+                self.free_color(old_pixel);
+            }
+            Command::SetOuterBorderWidth { width } => {
+                self.config.outer_border_width = width;
+                self.refresh_borders()?;
+            }
+            Command::SetOuterBorderColor { color } => {
+                let old_pixel = self.outer_border_pixel;
+                self.outer_border_pixel = self.alloc_color(&color)?;
+                self.config.outer_border_color = color;
+                self.refresh_borders()?;
+                self.free_color(old_pixel);
+            }
+            Command::SetFocusModel { focus_model } => {
+                self.config.focus_model = focus_model;
+            }
+            Command::SetWarpPointer { enabled } => {
+                self.config.warp_pointer = enabled;
+            }
+            Command::SetEdgeSnap { enabled } => {
+                self.config.edge_snap = enabled;
+            }
+            Command::SetEdgeSnapThreshold { threshold } => {
+                self.config.edge_snap_threshold = threshold;
+            }
+            Command::SetWrap { enabled } => {
+                self.config.wrap = enabled;
+                self.state.wrap = enabled;
+            }
+            Command::SetDirectionalWrap { enabled } => {
+                self.config.directional_wrap = enabled;
+                self.state.directional_wrap = enabled;
+            }
+            Command::SetPlacementMonitor { selector } => {
+                self.config.placement_monitor = selector;
+            }
+            Command::SetPadding { selector, padding } => {
+                self.state.set_monitor_padding(selector, padding)?;
+            }
+            Command::SetPreWorkspaceSwitchHook { command } => {
+                self.config.pre_workspace_switch_hook = command;
+            }
+            Command::SetPostWorkspaceSwitchHook { command } => {
+                self.config.post_workspace_switch_hook = command;
+            }
+            Command::SetModKey { modifier } => {
+                self.config.mod_key = keys::parse_modifier(&modifier)
+                    .ok_or_else(|| anyhow!("Unknown modifier: {}", modifier))?;
+                self.regrab_buttons();
+            }
+            Command::SetSelectButton { button } => {
+                self.config.select_button = button.try_into().map_err(|e: String| anyhow!(e))?;
+                self.regrab_buttons();
+            }
+            Command::SetDragButton { button } => {
+                self.config.drag_button = button.try_into().map_err(|e: String| anyhow!(e))?;
+                self.regrab_buttons();
+            }
+            Command::SetResizeButton { button } => {
+                self.config.resize_button = button.try_into().map_err(|e: String| anyhow!(e))?;
+                self.regrab_buttons();
+            }
+            Command::SetChordTimeout { timeout } => self.config.chord_timeout = timeout,
+            Command::FocusMonitor { selector } => {
+                let visible_workspaces = self.state.visible_workspaces();
+                self.state.focus_monitor(selector)?;
+                self.update_visible_workspaces(&visible_workspaces)?;
+                self.publish_workspace_switched();
+                if self.config.warp_pointer {
+                    self.warp_pointer_to_focused();
+                }
+            }
+            Command::SwapMonitors { first, second } => {
+                self.swap_monitors(first, second)?;
+                self.layout_changed();
+            }
+            Command::Assign { rule } => {
+                rule.validate().map_err(|e| anyhow!(e))?;
+                self.rules.push(rule);
+            }
+            Command::SetTheme { name } => {
+                self.config.apply_theme(&name)?;
+                self.refresh_border_colors()?;
+            }
+            Command::Reload => {
+                self.config = Config::load(&self.config_path)?;
+                self.state.wrap = self.config.wrap;
+                self.state.directional_wrap = self.config.directional_wrap;
+                self.refresh_border_colors()?;
+                self.regrab_buttons();
+            }
+            Command::Batch { commands } => {
+                recording::check_nested_commands(&commands, "batch")?;
+                // The commands may flush their requests, e.g. to wait for a reply,
+                // but the server is grabbed so no intermediate state is rendered.
+                // The commands before a failing one stay applied.
+                let payloads = self.with_server_grabbed(|wm| {
+                    let mut payloads = Vec::new();
+                    for (index, command) in commands.into_iter().enumerate() {
+                        let payload = wm.handle_command(command).with_context(|| {
+                            format!("Command {} of the batch failed", index + 1)
+                        })?;
+                        payloads.push(payload);
+                    }
+                    Ok(payloads)
+                })?;
+                return Ok(serde_json::Value::Array(payloads));
+            }
+            Command::Exec { cmd, workspace } => {
+                // Fail early, rather than when the window is mapped
+                if let Some(workspace) = &workspace {
+                    self.state
+                        .select_workspace(WorkspaceSelector::Name(workspace.clone()))?;
+                }
+                self.launcher
+                    .exec(&cmd, workspace)
+                    .with_context(|| format!("Failed to run `{}`", cmd))?;
+            }
+            Command::Bind {
+                mode,
+                keys,
+                release,
+                command,
+            } => {
+                if matches!(*command, Command::Subscribe { .. }) {
+                    return Err(anyhow!("This command cannot be bound to keys"));
+                }
+                let sequence = keys::parse_key_sequence(&keys).map_err(|e| anyhow!(e))?;
+                if release && sequence.len() > 1 {
+                    return Err(anyhow!("Chords cannot be bound on release"));
+                }
+                // Check that the keyboard has the keys, even if the mode is not active
+                if let Some(combo) = sequence
+                    .iter()
+                    .find(|combo| self.keymap.keycodes(combo.keysym).is_empty())
+                {
+                    return Err(anyhow!("No key produces the keysym {:#x}", combo.keysym));
+                }
+                let first = sequence[0];
+                let active = mode == self.bindings.mode();
+                self.bindings.bind(Binding {
+                    mode,
+                    keys: sequence,
+                    release,
+                    command: *command,
+                });
+                // Only the first keys of a chord are grabbed, the keyboard is grabbed after them
+                if active {
+                    self.grab_keys(first, true)?;
+                }
+            }
+            Command::Unbind {
+                mode,
+                keys,
+                release,
+            } => {
+                let sequence = keys::parse_key_sequence(&keys).map_err(|e| anyhow!(e))?;
+                if self.bindings.unbind(&mode, &sequence, release).is_none() {
+                    return Err(anyhow!("{} is not bound in the {} mode", keys, mode));
+                }
+                // The first keys may still start other chords
+                if mode == self.bindings.mode()
+                    && !self.bindings.active_combos().contains(&sequence[0])
+                {
+                    self.grab_keys(sequence[0], false)?;
+                }
+            }
+            Command::Mode { name } => self.set_mode(name)?,
+            Command::RecordStart { file } => {
+                if self.recording.is_some() {
+                    return Err(anyhow!("A recording is already in progress"));
+                }
+                self.recording = Some(Recording::start(&file)?);
+            }
+            Command::RecordStop => {
+                if self.recording.take().is_none() {
+                    return Err(anyhow!("No recording is in progress"));
+                }
+            }
+            Command::Replay { file } => {
+                let commands = recording::read_commands(&file)?;
+                recording::check_nested_commands(&commands, "replay")?;
+                for (index, command) in commands.into_iter().enumerate() {
+                    // A replay is recorded as the commands it runs
+                    self.record_command(&command);
+                    self.handle_command(command)
+                        .with_context(|| format!("Command {} of the replay failed", index + 1))?;
+                }
+            }
+            Command::Query { query } => {
+                return Ok(match query {
+                    Query::Workspaces => serde_json::to_value(self.state.workspaces_info())?,
+                    Query::Windows(workspace) => {
+                        serde_json::to_value(self.state.windows_info(workspace)?)?
+                    }
+                    Query::Focused => serde_json::to_value(self.state.focused_window_info())?,
+                    Query::Tree => serde_json::to_value(self.state.tree())?,
+                    Query::Metrics => {
+                        serde_json::to_value(self.metrics.report(self.state.clients_count()))?
+                    }
+                    Query::Bindings => serde_json::to_value(self.bindings.report(&self.config))?,
+                    Query::State => self.state.dump(),
+                    Query::Monitor(selector) => {
+                        serde_json::to_value(self.state.monitor(selector)?)?
+                    }
+                });
+            }
+        }
+
+        Ok(serde_json::Value::Null)
+    }
+
+    /// Become the window manager.
+    /// This is done by changing the root window's event mask.
+    ///
+    /// If another window manager is already running, this will fail.
+    fn become_window_manager(&self) -> Result<()> {
+        self.conn
+            .send_and_check_request(&x::ChangeWindowAttributes {
+                window: self.state.root,
+                value_list: &[
+                    x::Cw::EventMask(
+                        x::EventMask::SUBSTRUCTURE_NOTIFY
+                            | x::EventMask::SUBSTRUCTURE_REDIRECT
+                            | x::EventMask::BUTTON_PRESS
+                            | x::EventMask::BUTTON_RELEASE
+                            | x::EventMask::ENTER_WINDOW,
+                    ),
+                    x::Cw::Cursor(Xid::none()),
+                ],
+            })?;
+
+        Ok(())
+    }
+
+    /// Handle an event of the X server.
+    ///
+    /// Return false if the window manager should quit.
+    fn handle_event(&mut self, event: x::Event) -> Result<bool> {
+        match event {
+            x::Event::KeyPress(ev) => {
+                if let Some(command) = self.bound_command(&ev)? {
+                    return Ok(self.run_bound_command(command));
+                }
+            }
+            x::Event::KeyRelease(ev) => {
+                if let Some(command) = self.released_command(&ev)? {
+                    return Ok(self.run_bound_command(command));
+                }
+            }
+            x::Event::ButtonPress(ev) => {
+                self.handle_button_press_event(ev)?;
+            }
+            x::Event::ButtonRelease(ev) => {
+                self.handle_button_release_event(ev)?;
+            }
+            x::Event::MotionNotify(ev) => {
+                self.handle_motion_notify_event(ev)?;
+            }
+            x::Event::ConfigureRequest(ev) => {
+                self.handle_configure_request_event(ev)?;
+            }
+            x::Event::CreateNotify(ev) => {
+                self.handle_create_notify_event(ev);
+            }
+            x::Event::ConfigureNotify(ev) => {
+                self.handle_configure_notify_event(ev)?;
+            }
+            x::Event::ResizeRequest(ev) => {
+                self.handle_resize_request_event(ev)?;
+            }
+            x::Event::MapRequest(ev) => {
+                self.handle_map_request_event(ev)?;
+            }
+            x::Event::UnmapNotify(ev) => {
+                self.handle_unmap_notify_event(ev)?;
+            }
+            x::Event::DestroyNotify(ev) => {
+                self.handle_destroy_notify_event(ev)?;
+            }
+            x::Event::EnterNotify(ev) => {
+                self.handle_enter_notify_event(ev)?;
+            }
+            x::Event::LeaveNotify(ev) => {
+                self.handle_leave_notify_event(ev);
+            }
+            x::Event::PropertyNotify(ev) => {
+                self.handle_property_notify_event(ev)?;
+            }
+            // Without XKB, the changes of the keyboard mapping are reported by the core protocol
+            x::Event::MappingNotify(ev) if ev.request() != x::Mapping::Pointer => {
+                self.update_keymap()?;
+            }
+            x::Event::ClientMessage(ev) => {
+                // This event is sent if a pager wants to switch ti antoher workspace.
+                if ev.r#type().resource_id() == self.atoms.net_current_desktop.resource_id() {
+                    if let x::ClientMessageData::Data32([index, ..]) = ev.data() {
+                        self.activate_workspace(WorkspaceSelector::Index(index as usize))?;
+                    }
+                }
+            }
+            ev => {
+                debug!("Unhandled event: {:?}", ev);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// This is called when a new window is created.
+    fn handle_map_request_event(&mut self, ev: x::MapRequestEvent) -> Result<()> {
+        self.manage_window(ev.window(), false)
+    }
+
+    /// Start managing a window.
+    ///
+    /// An adopted window existed before the window manager started:
+    /// it keeps its position and it is not focused.
+    fn manage_window(&mut self, window: x::Window, adopted: bool) -> Result<()> {
+        // Menus and tooltips place themselves and are never managed,
+        // they are not supposed to ask to be mapped anyway
+        let attributes = self.conn.get_window_attributes(window)?;
+        if attributes.override_redirect {
+            self.conn.send_request(&x::MapWindow { window });
+            return Ok(());
+        }
+
+        // The type is read once here, then kept up to date from PropertyNotify events
+        let types = ewmh::get_wm_window_type(&self.conn, &self.atoms, window)?;
+        if types.contains(&self.atoms.net_wm_window_type_dock) {
+            // Do not manage dock windows, but keep track of the space they reserve
+            self.conn.send_request(&x::ChangeWindowAttributes {
+                window,
+                value_list: &[x::Cw::EventMask(x::EventMask::PROPERTY_CHANGE)],
+            });
+            self.update_dock(window)?;
+            self.conn.send_request(&x::MapWindow { window });
+            return Ok(());
+        }
+
+        let (current_pos, size) = self.window_geometry(window)?;
+
+        // Add the window to the state
+        let depth = self.conn.visual_depth(self.screen_num, attributes.visual);
+        let (instance, class) = icccm::get_wm_class(&self.conn, window)?.unzip();
+        let title = self.window_title(window)?;
+        let types = self.window_type_names(&types)?;
+        // Dialogs open over their parent window
+        let transient_for = icccm::get_wm_transient_for(&self.conn, window)?;
+        let parent = transient_for
+            .and_then(|parent| {
+                self.state
+                    .select_client(WindowSelector::Window(parent.resource_id()))
+                    .ok()
+            })
+            .map(|parent| (parent.window(), parent.center()));
+        // A window recovered after a crash goes back where it was
+        let recovered = self
+            .recovered_clients
+            .remove(&window.resource_id())
+            .filter(|_| adopted)
+            .and_then(|client| {
+                let workspace = self
+                    .state
+                    .select_workspace(WorkspaceSelector::Name(client.workspace))
+                    .ok()?;
+                Some((workspace, client.pos, client.size))
+            });
+        // A program started on a workspace opens there, unless it is a dialog
+        let workspace = match (recovered, parent) {
+            (Some((workspace, ..)), _) => workspace,
+            (None, Some((parent, _))) => self.state.client_workspace(parent).unwrap(),
+            (None, None) => match self.startup_workspace(window)? {
+                Some(workspace) => workspace,
+                None => self.assigned_workspace(class.as_deref(), title.as_deref(), &types)?,
+            },
+        };
+        // Restore the last geometry of the application, or center the window
+        let (area_pos, area_size) = self.state.workspace_area(workspace);
+        let center = match parent {
+            Some((_, center)) => center,
+            None => Vector2D::new(area_pos.x + area_size.x / 2, area_pos.y + area_size.y / 2),
+        };
+        let (pos, size) = recovered
+            .map(|(_, pos, size)| (pos, size))
+            .or_else(|| {
+                class
+                    .as_deref()
+                    .filter(|_| parent.is_none() && !adopted)
+                    .and_then(|class| self.geometry_cache.get(class))
+            })
+            .unwrap_or_else(|| {
+                let pos = if adopted {
+                    current_pos
+                } else {
+                    Vector2D::new(center.x - size.x / 2, center.y - size.y / 2)
+                };
+                (pos, size)
+            });
+        self.state
+            .add_client_to_workspace(workspace, window, pos, size)?;
+        self.update_client_list_stacking();
+        if let Some(class) = class {
+            self.state.set_client_class(window, class)?;
+        }
+        if let Some(title) = title {
+            self.state.set_client_title(window, title)?;
+        }
+        self.state.set_client_instance(window, instance)?;
+        self.state.set_client_transient_for(window, transient_for)?;
+        let pid = ewmh::get_wm_pid(&self.conn, &self.atoms, window)?;
+        self.state.set_client_pid(window, pid)?;
+        self.state.set_client_types(window, types)?;
+        let protocols = icccm::get_wm_protocols(&self.conn, &self.atoms, window)?;
+        self.state.set_client_protocols(window, protocols)?;
+        let urgent = icccm::get_wm_hints_urgency(&self.conn, window)?;
+        self.state.set_client_urgent(window, urgent)?;
+        let size_hints = icccm::get_wm_normal_hints(&self.conn, window)?;
+        self.state.set_client_size_hints(window, size_hints)?;
+        let shaped = self.select_shape(window)?;
+        self.state.set_client_shaped(window, shaped)?;
+        self.state.set_client_depth(window, depth)?;
+        let frame_extents = self.frame_extents(window)?;
+        self.state.set_client_frame_extents(window, frame_extents)?;
+
+        // Set size and border width
+        self.conn.send_request(&x::ConfigureWindow {
+            window,
+            value_list: &[
+                x::ConfigWindow::Width(size.x as u32),
+                x::ConfigWindow::Height(size.y as u32),
+                x::ConfigWindow::BorderWidth(self.border_width(window)),
+            ],
+        });
+        self.round_corners(window);
+        self.draw_border(window, self.border_pixel);
+
+        // Set the event mask
+        self.conn.send_request(&x::ChangeWindowAttributes {
+            window,
+            value_list: &[x::Cw::EventMask(
+                x::EventMask::SUBSTRUCTURE_NOTIFY
+                    | x::EventMask::SUBSTRUCTURE_REDIRECT
+                    | x::EventMask::ENTER_WINDOW
+                    | x::EventMask::LEAVE_WINDOW
+                    | x::EventMask::PROPERTY_CHANGE
+                    | x::EventMask::RESIZE_REDIRECT,
+            )],
+        });
+
+        self.conn.send_request(&x::ChangeSaveSet {
+            mode: x::SetMode::Insert,
+            window,
+        });
+
+        let visible = self.state.visible_workspaces().contains(&workspace);
+        if adopted {
+            // Reparenting would unmap the window, which is a top level window already
+            self.conn.send_request(&x::ConfigureWindow {
+                window,
+                value_list: &[x::ConfigWindow::X(pos.x), x::ConfigWindow::Y(pos.y)],
+            });
+            // The window may be mapped, or unmapped if the previous window manager hid it
+            if !visible && attributes.map_state == x::MapState::Viewable {
+                self.state.expect_unmap(window)?;
+                self.conn.send_request(&x::UnmapWindow { window });
+            }
+        } else {
+            // Reparent the window
+            self.conn.send_request(&x::ReparentWindow {
+                window,
+                parent: self.state.root,
+                x: pos.x as i16,
+                y: pos.y as i16,
+            });
+        }
+
+        // Map the window, unless its workspace is hidden
+        if visible {
+            self.conn.send_request(&x::MapWindow { window });
+        }
+
+        self.grab_buttons(window);
+
+        self.subscribers.publish(Event::WindowMapped {
+            window: window.resource_id(),
+        });
+
+        // Focusing a window on another monitor focuses that monitor
+        if visible && !adopted {
+            self.state
+                .focus_client(WindowSelector::Window(window.resource_id()))?;
+            self.focus_window(window)?;
+        }
+
+        Ok(())
+    }
+
+    /// Manage the windows mapped before the window manager started,
+    /// e.g. when it is restarted or started after some programs.
+    ///
+    /// Override-redirect windows, such as menus, are not managed,
+    /// nor the unmapped windows, unless they are iconic.
+    fn adopt_windows(&mut self) -> Result<()> {
+        for window in self.conn.query_tree(self.state.root)? {
+            // The window may be gone already
+            let Ok(attributes) = self.conn.get_window_attributes(window) else {
+                continue;
+            };
+            if attributes.override_redirect {
+                continue;
+            }
+            // The windows of the hidden workspaces of a crashed instance are unmapped
+            if attributes.map_state != x::MapState::Viewable
+                && !self.recovered_clients.contains_key(&window.resource_id())
+                && icccm::get_wm_state(&self.conn, &self.atoms, window)?
+                    != Some(icccm::ICONIC_STATE)
+            {
+                continue;
+            }
+
+            if let Err(e) = self.manage_window(window, true) {
+                warning!("Failed to manage window {}: {:#}", window.resource_id(), e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Grab the mouse buttons used to focus, move and resize a window.
+    fn grab_buttons(&self, window: x::Window) {
+        // Select settings
+        self.conn.send_request(&x::GrabButton {
+            owner_events: true,
+            grab_window: window,
+            event_mask: x::EventMask::BUTTON_PRESS | x::EventMask::BUTTON_RELEASE,
+            pointer_mode: x::GrabMode::Async,
+            keyboard_mode: x::GrabMode::Async,
+            confine_to: xcb::Xid::none(),
+            cursor: xcb::Xid::none(),
+            button: self.config.select_button.index(),
+            modifiers: self.config.mod_key,
+        });
+
+        // Allow events
+        self.conn.send_request(&x::AllowEvents {
+            mode: x::Allow::AsyncPointer,
+            time: x::CURRENT_TIME,
+        });
+
+        // Drag settings
+        self.conn.send_request(&x::GrabButton {
+            owner_events: false,
+            grab_window: window,
+            event_mask: x::EventMask::BUTTON_PRESS
+                | x::EventMask::BUTTON_RELEASE
+                | x::EventMask::BUTTON_MOTION,
+            pointer_mode: x::GrabMode::Async,
+            keyboard_mode: x::GrabMode::Async,
+            confine_to: xcb::Xid::none(),
+            cursor: xcb::Xid::none(),
+            button: self.config.drag_button.index(),
+            modifiers: self.config.mod_key,
+        });
+
+        // Resize settings
+        self.conn.send_request(&x::GrabButton {
+            owner_events: false,
+            grab_window: window,
+            event_mask: x::EventMask::BUTTON_PRESS
+                | x::EventMask::BUTTON_RELEASE
+                | x::EventMask::BUTTON_MOTION,
+            pointer_mode: x::GrabMode::Async,
+            keyboard_mode: x::GrabMode::Async,
+            confine_to: xcb::Xid::none(),
+            cursor: xcb::Xid::none(),
+            button: self.config.resize_button.index(),
+            modifiers: self.config.mod_key,
+        });
+    }
+
+    /// Get the border width of a client.
+    /// Shaped windows and windows drawing their own decorations have no border.
+    fn border_width(&self, window: x::Window) -> u32 {
+        match self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))
+        {
+            Ok(client) if client.shaped() || client.has_frame_extents() => 0,
+            _ => self.config.border_width,
+        }
+    }
+
+    /// Check whether a window is shaped, and select its shape changes.
+    /// Without the Shape extension, every window is rectangular.
+    fn select_shape(&self, window: x::Window) -> Result<bool> {
+        if !self.shape_active() {
+            return Ok(false);
+        }
+
+        self.conn.send_request(&shape::SelectInput {
+            destination_window: window,
+            enable: true,
+        });
+
+        Ok(self.conn.bounding_shaped(window)?)
+    }
+
+    fn shape_active(&self) -> bool {
+        self.conn.has_extension(xcb::Extension::Shape)
+    }
+
+    /// Round the corners of a client, after its size or border width changed.
+    ///
+    /// The clients shaped by their application keep their own shape,
+    /// and the ones drawing their own decorations keep their shadow.
+    fn round_corners(&self, window: x::Window) {
+        if self.config.corner_radius == 0 || !self.shape_active() {
+            return;
+        }
+        let Ok(client) = self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))
+        else {
+            return;
+        };
+        if client.shaped() || client.has_frame_extents() {
+            return;
+        }
+
+        self.conn.send_request(&shape::Rectangles {
+            operation: shape::So::Set,
+            destination_kind: shape::Sk::Bounding,
+            ordering: x::ClipOrdering::YxBanded,
+            destination_window: window,
+            x_offset: 0,
+            y_offset: 0,
+            rectangles: &corners::rounded_rectangles(
+                client.size(),
+                self.config.border_width,
+                self.config.corner_radius,
+            ),
+        });
+    }
+
+    /// Make a client rectangular again, after the corners are no longer rounded.
+    fn square_corners(&self, window: x::Window) {
+        if !self.shape_active() {
+            return;
+        }
+        if let Ok(client) = self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))
+        {
+            if client.shaped() {
+                return;
+            }
+        }
+
+        self.conn.send_request(&shape::Mask {
+            operation: shape::So::Set,
+            destination_kind: shape::Sk::Bounding,
+            destination_window: window,
+            x_offset: 0,
+            y_offset: 0,
+            source_bitmap: x::Pixmap::none(),
+        });
+    }
+
+    /// Draw the border of a client, with the color of its focus state as the inner color
+    /// of a double border.
+    ///
+    /// A double border is drawn in a pixmap matching the size of the client,
+    /// see `borders::inner_border_rectangles`.
+    fn draw_border(&self, window: x::Window, pixel: u32) {
+        let border_width = self.border_width(window);
+        let client = self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()));
+        let client = match client {
+            Ok(client) if self.config.outer_border_width > 0 && border_width > 0 => client,
+            _ => {
+                self.conn.send_request(&x::ChangeWindowAttributes {
+                    window,
+                    value_list: &[x::Cw::BorderPixel(pixel)],
+                });
+                return;
+            }
+        };
+
+        let size = client.size();
+        let pixmap = self.conn.generate_id();
+        self.conn.send_request(&x::CreatePixmap {
+            depth: client.depth(),
+            pid: pixmap,
+            drawable: x::Drawable::Window(window),
+            width: (size.x + 2 * border_width as i32) as u16,
+            height: (size.y + 2 * border_width as i32) as u16,
+        });
+        let gc = self.conn.generate_id();
+        self.conn.send_request(&x::CreateGc {
+            cid: gc,
+            drawable: x::Drawable::Pixmap(pixmap),
+            value_list: &[x::Gc::Foreground(self.outer_border_pixel)],
+        });
+        self.conn.send_request(&x::PolyFillRectangle {
+            drawable: x::Drawable::Pixmap(pixmap),
+            gc,
+            rectangles: &[x::Rectangle {
+                x: 0,
+                y: 0,
+                width: u16::MAX,
+                height: u16::MAX,
+            }],
+        });
+        self.conn.send_request(&x::ChangeGc {
+            gc,
+            value_list: &[x::Gc::Foreground(pixel)],
+        });
+        self.conn.send_request(&x::PolyFillRectangle {
+            drawable: x::Drawable::Pixmap(pixmap),
+            gc,
+            rectangles: &borders::inner_border_rectangles(
+                size,
+                border_width,
+                self.config.outer_border_width,
+            ),
+        });
+
+        // The window keeps the pixmap until its border changes
+        self.conn.send_request(&x::ChangeWindowAttributes {
+            window,
+            value_list: &[x::Cw::BorderPixmap(pixmap)],
+        });
+        self.conn.send_request(&x::FreeGc { gc });
+        self.conn.send_request(&x::FreePixmap { pixmap });
+    }
+
+    /// Draw a double border again, after the size or border width of the client changed.
+    fn redraw_border(&self, window: x::Window) {
+        if self.config.outer_border_width == 0 {
+            return;
+        }
+
+        let pixel = if self.state.focused() == Some(window) {
+            self.focused_border_pixel
+        } else {
+            self.border_pixel
+        };
+        self.draw_border(window, pixel);
+    }
+
+    /// Grab the mouse buttons again on all the windows, after they were changed.
+    /// Apply the border width and colors of the config to every window.
+    ///
+    /// The windows are all updated with the server grabbed, so that they change at once.
+    fn refresh_borders(&mut self) -> Result<()> {
+        self.with_server_grabbed(|wm| {
+            let focused = wm.state.focused();
+            for window in wm.state.windows() {
+                let pixel = if Some(window) == focused {
+                    wm.focused_border_pixel
+                } else {
+                    wm.border_pixel
+                };
+                wm.conn.send_request(&x::ConfigureWindow {
+                    window,
+                    value_list: &[x::ConfigWindow::BorderWidth(wm.border_width(window))],
+                });
+                wm.draw_border(window, pixel);
+                if wm.config.corner_radius == 0 {
+                    wm.square_corners(window);
+                } else {
+                    wm.round_corners(window);
+                }
+            }
+            Ok(())
+        })
+    }
+
+    fn regrab_buttons(&self) {
+        for window in self.state.windows() {
+            self.conn.send_request(&x::UngrabButton {
+                button: x::ButtonIndex::Any,
+                grab_window: window,
+                modifiers: x::ModMask::ANY,
+            });
+            self.grab_buttons(window);
+        }
+    }
+
+    /// Get the index of the workspace the program owning a new window was started on, if any.
+    fn startup_workspace(&mut self, window: x::Window) -> Result<Option<usize>> {
+        let Some(pid) = ewmh::get_wm_pid(&self.conn, &self.atoms, window)? else {
+            return Ok(None);
+        };
+
+        // The workspace may have been renamed since the program was started
+        Ok(self.launcher.take_startup_workspace(pid).and_then(|name| {
+            self.state
+                .select_workspace(WorkspaceSelector::Name(name))
+                .ok()
+        }))
+    }
+
+    /// Get the index of the workspace a new window belongs to, given its class, title and types.
+    ///
+    /// This is the workspace assigned by a rule,
+    /// or the workspace shown on the placement monitor.
+    /// If the rule asks so, the assigned workspace is activated.
+    fn assigned_workspace(
+        &mut self,
+        class: Option<&str>,
+        title: Option<&str>,
+        types: &[String],
+    ) -> Result<usize> {
+        let properties = WindowProperties {
+            class,
+            title,
+            types,
+        };
+        let rule = match rules::matching_rule(&self.rules, &properties)
+            .or_else(|| rules::matching_rule(&self.config.rules, &properties))
+        {
+            Some(rule) => rule.clone(),
+            None => return Ok(self.placement_workspace()),
+        };
+
+        let workspace = match self
+            .state
+            .select_workspace(WorkspaceSelector::Name(rule.workspace))
+        {
+            Ok(workspace) => workspace,
+            Err(e) => {
+                println!("Error: {:?}", e);
+                return Ok(self.placement_workspace());
+            }
+        };
+        if rule.switch && workspace != self.state.active_workspace() {
+            self.activate_workspace(WorkspaceSelector::Index(workspace))?;
+        }
+
+        Ok(workspace)
+    }
+
+    /// Get the index of the workspace shown on the placement monitor.
+    /// If the monitor is not found, this is the active workspace.
+    fn placement_workspace(&self) -> usize {
+        match self.state.monitor(self.config.placement_monitor.clone()) {
+            Ok(monitor) => monitor.workspace(),
+            Err(_) => self.state.active_workspace(),
+        }
+    }
+
+    fn handle_button_press_event(&mut self, ev: x::ButtonPressEvent) -> Result<()> {
+        // The buttons are only grabbed on the clients
+        let Ok(client) = self
+            .state
+            .select_client(WindowSelector::Window(ev.event().resource_id()))
+        else {
+            return Ok(());
+        };
+        let (pos, size) = (client.pos(), client.size());
+
+        self.state.start_drag(
+            ev.event(),
+            Vector2D::new(ev.root_x().into(), ev.root_y().into()),
+            pos,
+            size,
+        );
+
+        if ev.detail() == self.config.select_button.number() {
+            self.state
+                .focus_client(WindowSelector::Window(ev.event().resource_id()))?;
+            self.focus_window(ev.event())?;
+        }
+
+        Ok(())
+    }
+
+    /// This is called when a mouse button is released, which ends the drag in progress.
+    ///
+    /// A window dragged to an edge of the work area is snapped to it.
+    fn handle_button_release_event(&mut self, ev: x::ButtonReleaseEvent) -> Result<()> {
+        let Some(window) = self.state.end_drag() else {
+            return Ok(());
+        };
+
+        let mouse_pos = Vector2D::new(ev.root_x().into(), ev.root_y().into());
+        // The window was clicked, not dragged
+        if mouse_pos == self.state.drag_start_pos {
+            return Ok(());
+        }
+
+        if ev.detail() == self.config.drag_button.number() {
+            // A window dragged to another monitor moves to the workspace shown there
+            if let Some(index) = self.state.workspace_at(mouse_pos) {
+                self.state.move_client_to_workspace(window, index)?;
+                self.update_client_list_stacking();
+            }
+
+            if self.config.edge_snap {
+                if let Some(position) = self
+                    .state
+                    .edge_snap_position(mouse_pos, self.config.edge_snap_threshold as i32)
+                {
+                    self.snap_client(WindowSelector::Window(window.resource_id()), position)?;
+                }
+            }
+        }
+
+        // Apply the final geometry once, some motion events may have been skipped
+        let client = self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))?;
+        let (pos, size) = (client.pos(), client.size());
+        self.configure_window_geometry(window, pos, size);
+        icccm::send_configure_notify(&self.conn, window, pos, size, self.border_width(window));
+
+        Ok(())
+    }
+
+    fn handle_motion_notify_event(&mut self, ev: x::MotionNotifyEvent) -> Result<()> {
+        let mouse_pos = Vector2D::new(ev.root_x().into(), ev.root_y().into());
+        if self.state.dragging() != Some(ev.event()) {
+            return Ok(());
+        }
+        if !ev.state().contains(self.config.mod_key_mask()) {
+            return Ok(());
+        }
+
+        if ev.state().contains(self.config.drag_button.mask()) {
+            let new_pos = self.state.drag_client(ev.event(), mouse_pos)?;
+
+            self.conn.send_request(&x::ConfigureWindow {
+                window: ev.event(),
+                value_list: &[x::ConfigWindow::X(new_pos.x), x::ConfigWindow::Y(new_pos.y)],
+            });
+        } else if ev.state().contains(self.config.resize_button.mask()) {
+            let (new_pos, new_size) = self.state.drag_resize_client(ev.event(), mouse_pos)?;
+            self.configure_window_geometry(ev.event(), new_pos, new_size);
+        }
+
+        Ok(())
+    }
+
+    /// This is called when a window asks to be moved, resized or restacked.
+    ///
+    /// Only the values in the mask of the request are changed.
+    /// The geometry of the clients is decided by `State::configure_request_geometry`,
+    /// other windows, e.g. docks or windows not mapped yet, are configured as requested.
+    fn handle_configure_request_event(&mut self, ev: x::ConfigureRequestEvent) -> Result<()> {
+        let mask = ev.value_mask();
+        let stack_values = || {
+            let mut values = Vec::new();
+            if mask.contains(x::ConfigWindowMask::SIBLING) {
+                values.push(x::ConfigWindow::Sibling(ev.sibling()));
+            }
+            if mask.contains(x::ConfigWindowMask::STACK_MODE) {
+                values.push(x::ConfigWindow::StackMode(ev.stack_mode()));
+            }
+            values
+        };
+
+        if self.state.client_workspace(ev.window()).is_some() {
+            let pos = Vector2D::new(ev.x().into(), ev.y().into());
+            let size = Vector2D::new(ev.width().into(), ev.height().into());
+            self.apply_client_geometry(ev.window(), mask, pos, size)?;
+
+            let values = stack_values();
+            if !values.is_empty() {
+                self.conn.send_request(&x::ConfigureWindow {
+                    window: ev.window(),
+                    value_list: &values,
+                });
+                self.restack_client_as_requested(&ev)?;
+            }
+            return Ok(());
+        }
+
+        // The values must be in the order of the mask bits
+        let mut values = Vec::new();
+        if mask.contains(x::ConfigWindowMask::X) {
+            values.push(x::ConfigWindow::X(ev.x().into()));
+        }
+        if mask.contains(x::ConfigWindowMask::Y) {
+            values.push(x::ConfigWindow::Y(ev.y().into()));
+        }
+        if mask.contains(x::ConfigWindowMask::WIDTH) {
+            values.push(x::ConfigWindow::Width(ev.width().into()));
+        }
+        if mask.contains(x::ConfigWindowMask::HEIGHT) {
+            values.push(x::ConfigWindow::Height(ev.height().into()));
+        }
+        if mask.contains(x::ConfigWindowMask::BORDER_WIDTH) {
+            values.push(x::ConfigWindow::BorderWidth(ev.border_width().into()));
+        }
+        values.extend(stack_values());
+
+        self.conn.send_request(&x::ConfigureWindow {
+            window: ev.window(),
+            value_list: &values,
+        });
+
+        Ok(())
+    }
+
+    /// Update the stacking order of a client restacked by a configure request.
+    ///
+    /// The other stack modes depend on how the windows overlap,
+    /// their outcome is known from the ConfigureNotify event that follows.
+    fn restack_client_as_requested(&mut self, ev: &x::ConfigureRequestEvent) -> Result<()> {
+        let sibling = ev
+            .value_mask()
+            .contains(x::ConfigWindowMask::SIBLING)
+            .then(|| ev.sibling());
+        let position = match (ev.stack_mode(), sibling) {
+            (x::StackMode::Above, None) => StackPosition::Top,
+            (x::StackMode::Below, None) => StackPosition::Bottom,
+            (x::StackMode::Above, Some(sibling)) => StackPosition::Above(sibling),
+            (x::StackMode::Below, Some(sibling)) => StackPosition::Below(sibling),
+            _ => return Ok(()),
+        };
+        self.state.restack_client(ev.window(), position)?;
+        self.update_client_list_stacking();
+
+        Ok(())
+    }
+
+    /// This is called when a window has been moved, resized or restacked.
+    ///
+    /// The geometry and the stacking order of the clients follow the ones of the server.
+    /// The geometry of a client being dragged is ahead of the server, so it is kept.
+    fn handle_configure_notify_event(&mut self, ev: x::ConfigureNotifyEvent) -> Result<()> {
+        let pos = Vector2D::new(ev.x().into(), ev.y().into());
+        let size = Vector2D::new(ev.width().into(), ev.height().into());
+        if self.state.client_workspace(ev.window()).is_none() {
+            // The subwindows of the clients are not followed
+            if ev.event() == self.state.root && !ev.override_redirect() {
+                self.state.window_configured(ev.window(), pos, size);
+            }
+            return Ok(());
+        }
+
+        if self.state.dragging() != Some(ev.window()) {
+            self.state.client_configured(ev.window(), pos, size)?;
+        }
+
+        let position = if ev.above_sibling().is_none() {
+            StackPosition::Bottom
+        } else {
+            StackPosition::Above(ev.above_sibling())
+        };
+        self.state.restack_client(ev.window(), position)?;
+        self.update_client_list_stacking();
+
+        Ok(())
+    }
+
+    /// Set the _NET_CLIENT_LIST_STACKING property from the stacking order of the state.
+    fn update_client_list_stacking(&self) {
+        ewmh::set_client_list_stacking(
+            &self.conn,
+            &self.atoms,
+            self.state.root,
+            &self.state.client_list_stacking(),
+        );
+    }
+
+    /// This is called when a program resizes a client with the older resize request mechanism.
+    fn handle_resize_request_event(&mut self, ev: x::ResizeRequestEvent) -> Result<()> {
+        let size = Vector2D::new(ev.width().into(), ev.height().into());
+        let mask = x::ConfigWindowMask::WIDTH | x::ConfigWindowMask::HEIGHT;
+
+        self.apply_client_geometry(ev.window(), mask, Vector2D::new(0, 0), size)
+    }
+
+    /// Apply the geometry requested by a client, as decided by the state.
+    /// Only the values in the mask are requested.
+    /// The client keeps the border width of the config.
+    fn apply_client_geometry(
+        &mut self,
+        window: x::Window,
+        mask: x::ConfigWindowMask,
+        pos: Vector2D,
+        size: Vector2D,
+    ) -> Result<()> {
+        let client = self
+            .state
+            .configure_request_geometry(window, mask, pos, size)?;
+        let (pos, size) = (client.pos(), client.size());
+
+        self.conn.send_request(&x::ConfigureWindow {
+            window,
+            value_list: &[
+                x::ConfigWindow::X(pos.x),
+                x::ConfigWindow::Y(pos.y),
+                x::ConfigWindow::Width(size.x as u32),
+                x::ConfigWindow::Height(size.y as u32),
+                x::ConfigWindow::BorderWidth(self.border_width(window)),
+            ],
+        });
+        self.round_corners(window);
+        self.redraw_border(window);
+        // The size may differ from the requested one
+        icccm::send_configure_notify(&self.conn, window, pos, size, self.border_width(window));
+
+        Ok(())
+    }
+
+    /// This is called when the pointer enters a managed window or the root window.
+    /// The focus is changed according to the focus model.
+    fn handle_enter_notify_event(&mut self, ev: x::EnterNotifyEvent) -> Result<()> {
+        // Ignore crossing events generated by grabs
+        if ev.mode() != x::NotifyMode::Normal {
+            return Ok(());
+        }
+        // The pointer is coming back from a subwindow of the client
+        if ev.detail() == x::NotifyDetail::Inferior {
+            return Ok(());
+        }
+
+        self.state.pointer_enter(ev.event());
+
+        if let Some(selector) = self
+            .state
+            .pointer_enter_focus(self.config.focus_model, ev.event())
+        {
+            match self.state.focus_client(selector)? {
+                Some(window) => self.focus_window(window)?,
+                None => self.unfocus_window(),
+            }
+        }
+
+        Ok(())
+    }
+
+    /// This is called when the pointer leaves a managed window.
+    fn handle_leave_notify_event(&mut self, ev: x::LeaveNotifyEvent) {
+        // The pointer is still inside the client when it moves to one of its subwindows
+        if ev.mode() != x::NotifyMode::Normal || ev.detail() == x::NotifyDetail::Inferior {
+            return;
+        }
+
+        self.state.pointer_leave(ev.event());
+    }
+
+    /// Handle a shape change of a managed window, e.g. a clock becoming round.
+    ///
+    /// The border is removed from shaped windows and restored on rectangular ones.
+    fn handle_shape_notify_event(&mut self, ev: shape::NotifyEvent) -> Result<()> {
+        let window = ev.affected_window();
+        // The input and clip shapes do not change what the window looks like
+        if ev.shape_kind() != shape::Sk::Bounding {
+            return Ok(());
+        }
+        let Ok(client) = self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))
+        else {
+            return Ok(());
+        };
+        // The shape rounding the corners is not the one of the application
+        if self.config.corner_radius > 0 && !client.shaped() {
+            return Ok(());
+        }
+
+        self.state.set_client_shaped(window, ev.shaped())?;
+        self.conn.send_request(&x::ConfigureWindow {
+            window,
+            value_list: &[x::ConfigWindow::BorderWidth(self.border_width(window))],
+        });
+
+        Ok(())
+    }
+
+    /// Handle a property change of a managed window.
+    ///
+    /// This keeps the title, the urgency hint and the size hints of the clients,
+    /// and the space reserved by the docks, up to date.
+    fn handle_property_notify_event(&mut self, ev: x::PropertyNotifyEvent) -> Result<()> {
+        let window = ev.window();
+        let atom = ev.atom();
+
+        if atom == self.atoms.net_wm_strut || atom == self.atoms.net_wm_strut_partial {
+            // Unmapped docks do not reserve space
+            if self.state.is_dock(window) {
+                self.update_dock(window)?;
+            }
+            return Ok(());
+        }
+
+        // The window may not be managed, e.g. a dock
+        if self.state.client_workspace(window).is_none() {
+            return Ok(());
+        }
+
+        if atom == self.atoms.net_wm_name || atom == x::ATOM_WM_NAME {
+            if let Some(title) = self.window_title(window)? {
+                if self.state.set_client_title(window, title.clone())? {
+                    self.subscribers.publish(Event::TitleChanged {
+                        window: window.resource_id(),
+                        title,
+                    });
+                }
+            }
+        } else if atom == x::ATOM_WM_CLASS {
+            let (instance, class) = icccm::get_wm_class(&self.conn, window)?.unzip();
+            if let Some(class) = class {
+                self.state.set_client_class(window, class)?;
+            }
+            self.state.set_client_instance(window, instance)?;
+        } else if atom == self.atoms.net_wm_pid {
+            let pid = ewmh::get_wm_pid(&self.conn, &self.atoms, window)?;
+            self.state.set_client_pid(window, pid)?;
+        } else if atom == self.atoms.net_wm_window_type {
+            let types = ewmh::get_wm_window_type(&self.conn, &self.atoms, window)?;
+            let types = self.window_type_names(&types)?;
+            self.state.set_client_types(window, types)?;
+        } else if atom == self.atoms.wm_protocols {
+            let protocols = icccm::get_wm_protocols(&self.conn, &self.atoms, window)?;
+            self.state.set_client_protocols(window, protocols)?;
+        } else if atom == x::ATOM_WM_TRANSIENT_FOR {
+            let transient_for = icccm::get_wm_transient_for(&self.conn, window)?;
+            self.state.set_client_transient_for(window, transient_for)?;
+        } else if atom == x::ATOM_WM_HINTS {
+            let urgent = icccm::get_wm_hints_urgency(&self.conn, window)?;
+            self.state.set_client_urgent(window, urgent)?;
+        } else if atom == x::ATOM_WM_NORMAL_HINTS {
+            let size_hints = icccm::get_wm_normal_hints(&self.conn, window)?;
+            self.state.set_client_size_hints(window, size_hints)?;
+        } else if atom == self.atoms.gtk_frame_extents {
+            let frame_extents = self.frame_extents(window)?;
+            self.state.set_client_frame_extents(window, frame_extents)?;
+            self.conn.send_request(&x::ConfigureWindow {
+                window,
+                value_list: &[x::ConfigWindow::BorderWidth(self.border_width(window))],
+            });
+            self.redraw_border(window);
+        }
+
+        Ok(())
+    }
+
+    /// Get the title of a window, preferring _NET_WM_NAME over WM_NAME.
+    fn window_title(&self, window: x::Window) -> Result<Option<String>> {
+        match ewmh::get_wm_name(&self.conn, &self.atoms, window)? {
+            Some(title) => Ok(Some(title)),
+            None => Ok(icccm::get_wm_name(&self.conn, window)?),
+        }
+    }
+
+    /// Get the invisible shadow around a window drawing its own decorations.
+    fn frame_extents(&self, window: x::Window) -> Result<Strut> {
+        let extents = match ewmh::get_gtk_frame_extents(&self.conn, &self.atoms, window)? {
+            Some([left, right, top, bottom]) => Strut {
+                left: left as i32,
+                right: right as i32,
+                top: top as i32,
+                bottom: bottom as i32,
+            },
+            None => Strut::default(),
+        };
+
+        Ok(extents)
+    }
+
+    /// This is called when a window is created.
+    /// The geometry of the top-level windows is followed until they are managed.
+    fn handle_create_notify_event(&mut self, ev: x::CreateNotifyEvent) {
+        if ev.parent() != self.state.root || ev.override_redirect() {
+            return;
+        }
+
+        self.state.window_configured(
+            ev.window(),
+            Vector2D::new(ev.x().into(), ev.y().into()),
+            Vector2D::new(ev.width().into(), ev.height().into()),
+        );
+    }
+
+    /// Get the position and size of a window which is not a client.
+    ///
+    /// The X server is only asked for the windows created before the window manager started.
+    fn window_geometry(&self, window: x::Window) -> Result<(Vector2D, Vector2D)> {
+        if let Some(geometry) = self.state.window_geometry(window) {
+            return Ok(geometry);
+        }
+
+        Ok(self.conn.get_geometry(window)?)
+    }
+
+    /// Get the names of window types, asking the X server only for the atoms not met yet.
+    fn window_type_names(&mut self, types: &[x::Atom]) -> Result<Vec<String>> {
+        for &atom in types {
+            if !self.window_type_names.contains_key(&atom) {
+                let name = self.conn.get_atom_name(atom)?;
+                self.window_type_names
+                    .insert(atom, ewmh::window_type_name(&name));
+            }
+        }
+
+        Ok(types
+            .iter()
+            .map(|atom| self.window_type_names[atom].clone())
+            .collect())
+    }
+
+    /// Update the space reserved by a dock window on the monitor it overlaps.
+    fn update_dock(&mut self, window: x::Window) -> Result<()> {
+        let (pos, size) = self.window_geometry(window)?;
+
+        let strut = match ewmh::get_wm_strut(&self.conn, &self.atoms, window)? {
+            Some([left, right, top, bottom]) => Strut {
+                left: left as i32,
+                right: right as i32,
+                top: top as i32,
+                bottom: bottom as i32,
+            },
+            None => Strut::default(),
+        };
+        self.state.set_dock(window, pos, size, strut);
+
+        Ok(())
+    }
+
+    /// This is called when a window is unmapped.
+    ///
+    /// An unmapped dock no longer reserves space.
+    /// Unmaps requested by the window manager are expected, e.g. when switching workspace.
+    /// Any other unmap of a client means it withdrew its window, so it is no longer managed.
+    fn handle_unmap_notify_event(&mut self, ev: x::UnmapNotifyEvent) -> Result<()> {
+        if self.state.remove_dock(ev.window()) {
+            return Ok(());
+        }
+        if !self.state.client_unmapped(ev.window()) {
+            return Ok(());
+        }
+
+        // The window may be mapped again later, as a new client
+        self.conn.send_request(&x::ChangeSaveSet {
+            mode: x::SetMode::Delete,
+            window: ev.window(),
+        });
+
+        self.unmanage_window(ev.window())
+    }
+
+    fn handle_destroy_notify_event(&mut self, ev: x::DestroyNotifyEvent) -> Result<()> {
+        // Visible clients have already been removed when they were unmapped
+        let result = match self.state.client_workspace(ev.window()) {
+            Some(_) => self.unmanage_window(ev.window()),
+            None => Ok(()),
+        };
+        // Unmanaging keeps the geometry of the window, in case it is mapped again
+        self.state.window_destroyed(ev.window());
+
+        result
+    }
+
+    /// Stop managing a client which has been withdrawn or destroyed.
+    fn unmanage_window(&mut self, window: x::Window) -> Result<()> {
+        // Remember the last geometry of the application
+        if let Ok(client) = self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))
+        {
+            if let Some(class) = client.class() {
+                self.geometry_cache
+                    .insert(class.to_owned(), client.pos(), client.size());
+                if let Err(err) = self.geometry_cache.save() {
+                    warning!("Failed to save the geometry cache: {}", err);
+                }
+            }
+        }
+
+        if let Err(err) = self.state.remove_client(window) {
+            println!("Failed to remove client: {}", err);
+            return Ok(());
+        }
+        self.update_client_list_stacking();
+        self.subscribers.publish(Event::WindowClosed {
+            window: window.resource_id(),
+        });
+
+        // Focus the most recently focused window, if any
+        if let Some(window) = self.state.focused() {
+            self.focus_window(window)?;
+        }
+
+        Ok(())
+    }
+
+    fn focus_window(&mut self, window: x::Window) -> Result<()> {
+        // Unfocus last focused window
+        if let Some(last_focused) = self.state.last_focused() {
+            self.draw_border(last_focused, self.border_pixel);
+        }
+
+        // Set the input focus
+        self.conn.send_request(&x::SetInputFocus {
+            revert_to: x::InputFocus::PointerRoot,
+            focus: window,
+            time: x::CURRENT_TIME,
+        });
+
+        // Select and focus
+        self.draw_border(window, self.focused_border_pixel);
+
+        self.conn.send_request(&x::SetInputFocus {
+            revert_to: x::InputFocus::PointerRoot,
+            focus: window,
+            time: x::CURRENT_TIME,
+        });
+
+        // Raise the window above the others, as the state did when it was focused
+        self.conn.send_request(&x::ConfigureWindow {
+            window,
+            value_list: &[x::ConfigWindow::StackMode(x::StackMode::Above)],
+        });
+        self.update_client_list_stacking();
+
+        // Set the EWMH hint
+        ewmh::set_active_window(&self.conn, &self.atoms, self.state.root, window);
+
+        let title = self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))
+            .ok()
+            .and_then(|client| client.title().map(str::to_owned));
+        self.subscribers.publish(Event::FocusChanged {
+            window: Some(window.resource_id()),
+            title,
+        });
+        Ok(())
+    }
+
+    /// Snap a client to a portion of the work area and apply its new geometry.
+    fn snap_client(&mut self, selector: WindowSelector, position: SnapPosition) -> Result<()> {
+        let window = self.state.select_client(selector)?.window();
+        let client = self.state.snap_client(
+            WindowSelector::Window(window.resource_id()),
+            position,
+            self.border_width(window),
+        )?;
+        let (window, pos, size) = (client.window(), client.pos(), client.size());
+
+        self.configure_window_geometry(window, pos, size);
+
+        Ok(())
+    }
+
+    /// Move and resize a window.
+    fn configure_window_geometry(&self, window: x::Window, pos: Vector2D, size: Vector2D) {
+        self.conn.send_request(&x::ConfigureWindow {
+            window,
+            value_list: &[
+                x::ConfigWindow::X(pos.x),
+                x::ConfigWindow::Y(pos.y),
+                x::ConfigWindow::Width(size.x as u32),
+                x::ConfigWindow::Height(size.y as u32),
+            ],
+        });
+        self.round_corners(window);
+        self.redraw_border(window);
+    }
+
+    /// Focus a window, activating its workspace first if it is not the active one.
+    fn show_and_focus_window(&mut self, window: x::Window) -> Result<()> {
+        if let Some(index) = self.state.client_workspace(window) {
+            if index != self.state.active_workspace() {
+                self.activate_workspace(WorkspaceSelector::Index(index))?;
+            }
+        }
+
+        self.focus_window(window)
+    }
+
+    /// Warp the pointer to the center of the focused window.
+    /// If the focused window is not on the active workspace, warp to the center of the monitor.
+    fn warp_pointer_to_focused(&self) {
+        let pos = match self.state.select_client(WindowSelector::Focused) {
+            Ok(client) => client.center(),
+            Err(_) => {
+                let (area_pos, area_size) = self.state.work_area();
+                Vector2D::new(area_pos.x + area_size.x / 2, area_pos.y + area_size.y / 2)
+            }
+        };
+
+        self.conn.send_request(&x::WarpPointer {
+            src_window: x::Window::none(),
+            dst_window: self.state.root,
+            src_x: 0,
+            src_y: 0,
+            src_width: 0,
+            src_height: 0,
+            dst_x: pos.x as i16,
+            dst_y: pos.y as i16,
+        });
+    }
+
+    /// Drop the input focus, restoring the border of the last focused window.
+    fn unfocus_window(&mut self) {
+        if let Some(last_focused) = self.state.last_focused() {
+            self.draw_border(last_focused, self.border_pixel);
+        }
+
+        self.conn.send_request(&x::SetInputFocus {
+            revert_to: x::InputFocus::PointerRoot,
+            focus: self.state.root,
+            time: x::CURRENT_TIME,
+        });
+
+        ewmh::set_active_window(&self.conn, &self.atoms, self.state.root, x::Window::none());
+
+        self.subscribers.publish(Event::FocusChanged {
+            window: None,
+            title: None,
+        });
+    }
+
+    /// Run an action on every window matched by a selector.
+    ///
+    /// A selector matching a single window fails like the action does.
+    /// Otherwise every window is tried, and the reply reports the outcome for each of them.
+    fn for_each_client(
+        &mut self,
+        selector: WindowSelector,
+        mut action: impl FnMut(&mut Self, x::Window) -> Result<()>,
+    ) -> Result<serde_json::Value> {
+        let many = selector.matches_many();
+        let windows = self.state.select_clients(selector)?;
+        if !many {
+            action(self, windows[0])?;
+            return Ok(serde_json::Value::Null);
+        }
+
+        let mut results = Vec::with_capacity(windows.len());
+        for window in windows {
+            let error = match action(self, window) {
+                Ok(()) => None,
+                Err(e) if is_connection_error(&e) => return Err(e),
+                Err(e) => Some(format!("{:#}", e)),
+            };
+            results.push(serde_json::json!({
+                "window": window.resource_id(),
+                "error": error,
+            }));
+        }
+
+        Ok(serde_json::Value::Array(results))
+    }
+
+    fn delete_window(&self, window: x::Window) -> Result<()> {
+        // Check if the window supports the delete protocol
+        // If it doesnt, just kill it
+        let supports_delete = self
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))
+            .is_ok_and(|client| client.supports_protocol(self.atoms.wm_delete_window));
+        if supports_delete {
+            icccm::send_wm_delete_window(&self.conn, &self.atoms, window)?;
+        } else {
+            self.kill_window(window);
+        }
+
+        Ok(())
+    }
+
+    /// Forcefully terminate the client owning the window.
+    /// Unlike `delete_window`, this does not ask the client politely,
+    /// so it can be used on hung applications.
+    fn kill_window(&self, window: x::Window) {
+        self.conn.send_request(&x::KillClient {
+            resource: window.resource_id(),
+        });
+    }
+
+    fn activate_workspace(&mut self, selector: WorkspaceSelector) -> Result<()> {
+        let previous = self.state.active_workspace();
+        let index = self.state.select_workspace(selector)?;
+        if let Some(hook) = &self.config.pre_workspace_switch_hook {
+            hooks::run_hook(hook, &self.workspace_switch_env(previous, index));
+        }
+
+        let visible_workspaces = self.state.visible_workspaces();
+        self.state
+            .activate_workspace(WorkspaceSelector::Index(index))?;
+        self.update_visible_workspaces(&visible_workspaces)?;
+        self.publish_workspace_switched();
+
+        if let Some(hook) = &self.config.post_workspace_switch_hook {
+            hooks::run_hook(hook, &self.workspace_switch_env(previous, index));
+        }
+
+        Ok(())
+    }
+
+    /// Get the environment of the workspace switch hooks.
+    fn workspace_switch_env(&self, previous: usize, index: usize) -> Vec<(&str, String)> {
+        let names = self.state.workspaces_names();
+
+        vec![
+            ("TONIOWM_WORKSPACE_INDEX", index.to_string()),
+            ("TONIOWM_WORKSPACE_NAME", names[index].clone()),
+            ("TONIOWM_PREVIOUS_WORKSPACE_INDEX", previous.to_string()),
+            ("TONIOWM_PREVIOUS_WORKSPACE_NAME", names[previous].clone()),
+        ]
+    }
+
+    fn move_workspace_to_monitor(
+        &mut self,
+        workspace_selector: WorkspaceSelector,
+        monitor_selector: MonitorSelector,
+    ) -> Result<()> {
+        let visible_workspaces = self.state.visible_workspaces();
+        let index = self
+            .state
+            .move_workspace_to_monitor(workspace_selector, monitor_selector)?;
+
+        self.with_server_grabbed(|wm| {
+            wm.move_workspace_clients(index);
+            wm.update_visible_workspaces(&visible_workspaces)
+        })
+    }
+
+    fn swap_monitors(&mut self, first: MonitorSelector, second: MonitorSelector) -> Result<()> {
+        let (first, second) = self.state.swap_monitors(first, second)?;
+
+        self.with_server_grabbed(|wm| {
+            wm.move_workspace_clients(first);
+            wm.move_workspace_clients(second);
+            Ok(())
+        })
+    }
+
+    /// Move the clients of a workspace to their position in the state,
+    /// e.g. after the workspace moved to another monitor.
+    fn move_workspace_clients(&self, index: usize) {
+        if let Some(clients) = self.state.workspace_clients(index) {
+            for client in clients.values() {
+                self.conn.send_request(&x::ConfigureWindow {
+                    window: client.window(),
+                    value_list: &[
+                        x::ConfigWindow::X(client.pos().x),
+                        x::ConfigWindow::Y(client.pos().y),
+                    ],
+                });
+            }
+        }
+    }
+
+    /// Map the clients of the workspaces that became visible, unmap the others,
+    /// and restore the focus of the active workspace.
+    fn update_visible_workspaces(&mut self, previous: &[usize]) -> Result<()> {
+        self.with_server_grabbed(|wm| wm.show_visible_workspaces(previous))
+    }
+
+    fn show_visible_workspaces(&mut self, previous: &[usize]) -> Result<()> {
+        let visible_workspaces = self.state.visible_workspaces();
+
+        // Unmap all windows on the hidden workspaces
+        let hidden: Vec<_> = previous
+            .iter()
+            .filter(|i| !visible_workspaces.contains(i))
+            .flat_map(|index| self.state.workspace_clients(*index))
+            .flat_map(|clients| clients.keys().copied())
+            .collect();
+        for window in hidden {
+            self.state.expect_unmap(window)?;
+            self.conn.send_request(&x::UnmapWindow { window });
+        }
+
+        // Map all windows on the shown workspaces
+        for index in visible_workspaces.iter().filter(|i| !previous.contains(i)) {
+            for window in self
+                .state
+                .workspace_clients(*index)
+                .into_iter()
+                .flat_map(|c| c.keys())
+            {
+                self.conn.send_request(&x::MapWindow { window: *window });
+            }
+        }
+
+        ewmh::set_current_desktop(
+            &self.conn,
+            &self.atoms,
+            self.state.root,
+            self.state.active_workspace() as u32,
+        );
+
+        // Restore the focus of the active workspace
+        match self.state.focused() {
+            Some(window) => self.focus_window(window)?,
+            None => self.unfocus_window(),
+        }
+
+        Ok(())
+    }
+
+    /// Run an operation changing many windows with the server grabbed,
+    /// so that the screen is not redrawn while the windows are half updated.
+    ///
+    /// The server is released even if the operation fails,
+    /// otherwise every other client would hang.
+    fn with_server_grabbed<T>(
+        &mut self,
+        operation: impl FnOnce(&mut Self) -> Result<T>,
+    ) -> Result<T> {
+        // The outermost operation releases the server
+        if self.server_grabbed {
+            return operation(self);
+        }
+
+        self.conn.send_request(&x::GrabServer {});
+        self.server_grabbed = true;
+        let result = operation(self);
+        self.server_grabbed = false;
+        self.conn.send_request(&x::UngrabServer {});
+        // Do not wait for the main loop to flush, the other clients are blocked until then
+        self.conn.flush()?;
+
+        result
+    }
+
+    /// Save the workspaces and notify the subscribers after the workspaces
+    /// were added, renamed or moved across monitors.
+    fn layout_changed(&mut self) {
+        self.save_workspaces();
+        self.subscribers.publish(Event::LayoutChanged);
+    }
+
+    /// Notify the subscribers of the active workspace.
+    fn publish_workspace_switched(&mut self) {
+        let index = self.state.active_workspace();
+        let name = self.state.workspaces_names()[index].clone();
+        self.subscribers
+            .publish(Event::WorkspaceSwitched { index, name });
+    }
+
+    /// Write the workspaces to disk, so that they are restored on the next start.
+    fn save_workspaces(&self) {
+        if let Err(err) = self.workspace_store.save(self.state.workspaces_settings()) {
+            warning!("Failed to save the workspaces: {}", err);
+        }
+    }
+
+    fn refresh_workspaces(&self) {
+        ewmh::set_number_of_desktops(
+            &self.conn,
+            &self.atoms,
+            self.state.root,
+            self.state.workspaces_names().len() as u32,
+        );
+
+        ewmh::set_desktop_names(
             &self.conn,
             &self.atoms,
             self.state.root,
@@ -519,3 +2548,173 @@ impl WindowManager {
         );
     }
 }
+
+/// Check whether an error is caused by the connection to the X server,
+/// which cannot be recovered, unlike the errors about a single request.
+pub fn is_connection_error(err: &anyhow::Error) -> bool {
+    err.chain().any(|cause| {
+        matches!(
+            cause.downcast_ref::<xcb::Error>(),
+            Some(xcb::Error::Connection(_))
+        ) || cause.downcast_ref::<xcb::ConnError>().is_some()
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use xcb::XidNew;
+
+    use crate::xconn::{MockConn, MockRequest, PropertyValue};
+
+    fn window_manager(conn: MockConn) -> WindowManager<MockConn> {
+        // The stores are not written by the handlers under test
+        let dir = std::env::temp_dir().join("toniowm-wm-test");
+        let (_, receiver) = channel::unbounded();
+        let mut wm = WindowManager::new(
+            conn,
+            0,
+            receiver,
+            Config::default(),
+            dir.join("config.json"),
+            GeometryCache::load(dir.join("geometry.json")),
+            WorkspaceStore::new(dir.join("workspaces.json")),
+            SessionStore::new(dir.join("session.json")),
+            None,
+        );
+        wm.state.root = unsafe { x::Window::new(1) };
+        wm.state
+            .set_monitor_geometry(0, Vector2D::new(0, 0), Vector2D::new(1920, 1080))
+            .unwrap();
+
+        wm
+    }
+
+    #[test]
+    fn test_handle_configure_request_event() {
+        let mut wm = window_manager(MockConn::default());
+        let client = unsafe { x::Window::new(10) };
+        wm.state
+            .add_client_to_workspace(0, client, Vector2D::new(10, 20), Vector2D::new(300, 200))
+            .unwrap();
+
+        // Only the requested values of a client change
+        let event = x::ConfigureRequestEvent::new(
+            x::StackMode::Above,
+            wm.state.root,
+            client,
+            x::Window::none(),
+            50,
+            60,
+            400,
+            500,
+            0,
+            x::ConfigWindowMask::X | x::ConfigWindowMask::WIDTH,
+        );
+        wm.handle_configure_request_event(event).unwrap();
+
+        let border_width = wm.config.border_width;
+        let requests = wm.conn.take_requests();
+        assert_eq!(
+            Some(&MockRequest::ConfigureWindow {
+                window: client,
+                values: vec![
+                    x::ConfigWindow::X(50),
+                    x::ConfigWindow::Y(20),
+                    x::ConfigWindow::Width(400),
+                    x::ConfigWindow::Height(200),
+                    x::ConfigWindow::BorderWidth(border_width),
+                ],
+            }),
+            requests.first()
+        );
+        assert!(requests.contains(&MockRequest::SendConfigureNotify {
+            window: client,
+            event_mask: x::EventMask::STRUCTURE_NOTIFY,
+            pos: Vector2D::new(50, 20),
+            size: Vector2D::new(400, 200),
+            border_width,
+        }));
+        let client = wm
+            .state
+            .select_client(WindowSelector::Window(client.resource_id()))
+            .unwrap();
+        assert_eq!(Vector2D::new(50, 20), client.pos());
+        assert_eq!(Vector2D::new(400, 200), client.size());
+
+        // The other windows are configured as requested
+        let window = unsafe { x::Window::new(11) };
+        let event = x::ConfigureRequestEvent::new(
+            x::StackMode::Above,
+            wm.state.root,
+            window,
+            x::Window::none(),
+            50,
+            60,
+            400,
+            500,
+            0,
+            x::ConfigWindowMask::HEIGHT | x::ConfigWindowMask::STACK_MODE,
+        );
+        wm.handle_configure_request_event(event).unwrap();
+
+        assert_eq!(
+            vec![MockRequest::ConfigureWindow {
+                window,
+                values: vec![
+                    x::ConfigWindow::Height(500),
+                    x::ConfigWindow::StackMode(x::StackMode::Above),
+                ],
+            }],
+            wm.conn.take_requests()
+        );
+    }
+
+    #[test]
+    fn test_handle_map_request_event() {
+        let mut conn = MockConn::default();
+        let window = unsafe { x::Window::new(10) };
+        let menu = unsafe { x::Window::new(11) };
+        conn.add_window(window, Vector2D::new(0, 0), Vector2D::new(400, 300));
+        conn.add_window(menu, Vector2D::new(0, 0), Vector2D::new(100, 200))
+            .override_redirect = true;
+        conn.set_property(
+            window,
+            x::ATOM_WM_CLASS,
+            x::ATOM_STRING,
+            PropertyValue::Bytes(b"navigator\0firefox\0".to_vec()),
+        );
+        let mut wm = window_manager(conn);
+
+        let event = x::MapRequestEvent::new(wm.state.root, window);
+        wm.handle_map_request_event(event).unwrap();
+
+        // The window is centered on the monitor, mapped and focused
+        let client = wm
+            .state
+            .select_client(WindowSelector::Window(window.resource_id()))
+            .unwrap();
+        assert_eq!(Some("firefox"), client.class());
+        assert_eq!(Vector2D::new(760, 390), client.pos());
+        assert_eq!(Vector2D::new(400, 300), client.size());
+        let requests = wm.conn.take_requests();
+        assert!(requests.contains(&MockRequest::MapWindow(window)));
+        assert!(requests.contains(&MockRequest::SetInputFocus(window)));
+
+        // An override-redirect window, e.g. a menu, is mapped without being managed
+        let event = x::MapRequestEvent::new(wm.state.root, menu);
+        wm.handle_map_request_event(event).unwrap();
+        assert!(wm
+            .state
+            .select_client(WindowSelector::Window(menu.resource_id()))
+            .is_err());
+        assert_eq!(vec![MockRequest::MapWindow(menu)], wm.conn.take_requests());
+
+        // A window destroyed before being managed is not managed
+        let gone = unsafe { x::Window::new(12) };
+        let event = x::MapRequestEvent::new(wm.state.root, gone);
+        assert!(wm.handle_map_request_event(event).is_err());
+        assert!(wm.conn.take_requests().is_empty());
+    }
+}
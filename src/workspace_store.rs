@@ -0,0 +1,35 @@
+//! Keeps the workspaces on disk, so that they survive a restart
+//! of the window manager.
+
+use std::path::PathBuf;
+
+use anyhow::Result;
+
+use crate::json_file::JsonFile;
+use crate::state::WorkspaceSettings;
+
+#[derive(Debug)]
+pub struct WorkspaceStore {
+    /// The workspaces file.
+    file: JsonFile<Vec<WorkspaceSettings>>,
+}
+
+impl WorkspaceStore {
+    pub fn new(path: PathBuf) -> Self {
+        Self {
+            file: JsonFile::new(path),
+        }
+    }
+
+    /// Read the workspaces from the file.
+    ///
+    /// Return None if the file does not exist or cannot be parsed.
+    pub fn load(&self) -> Option<Vec<WorkspaceSettings>> {
+        self.file.load()
+    }
+
+    /// Write the workspaces to the file.
+    pub fn save(&self, workspaces: Vec<WorkspaceSettings>) -> Result<()> {
+        self.file.save(&workspaces)
+    }
+}
@@ -0,0 +1,929 @@
+//! The requests sent to the X server, behind a trait so that the window manager
+//! can be tested without an X server.
+//!
+//! The replies are returned as plain data, as xcb replies cannot be built outside the library.
+
+use std::sync::Arc;
+
+use xcb::{shape, x, xkb, XidNew};
+
+use crate::atoms::Atoms;
+use crate::color::Color;
+use crate::keys::Keymap;
+use crate::vector::Vector2D;
+
+/// The value of a window property.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PropertyValue {
+    /// The elements of a property of format 8, e.g. a string.
+    Bytes(Vec<u8>),
+    /// The elements of a property of format 16 or 32, e.g. cardinals, atoms or windows.
+    Cardinals(Vec<u32>),
+}
+
+/// A window property, as returned by GetProperty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Property {
+    /// The type of the property, or ATOM_NONE if the property is not set.
+    pub r#type: x::Atom,
+    pub value: PropertyValue,
+}
+
+impl Property {
+    /// A property which is not set.
+    #[cfg(test)]
+    pub fn none() -> Self {
+        Self {
+            r#type: x::ATOM_NONE,
+            value: PropertyValue::Bytes(Vec::new()),
+        }
+    }
+
+    /// Get the value of a property of format 8, or nothing if it has another format.
+    pub fn bytes(&self) -> &[u8] {
+        match &self.value {
+            PropertyValue::Bytes(bytes) => bytes,
+            PropertyValue::Cardinals(_) => &[],
+        }
+    }
+
+    /// Get the value of a property of format 16 or 32, or nothing if it has another format.
+    pub fn cardinals(&self) -> &[u32] {
+        match &self.value {
+            PropertyValue::Bytes(_) => &[],
+            PropertyValue::Cardinals(cardinals) => cardinals,
+        }
+    }
+
+    /// Get the value of a property holding resources, e.g. atoms or windows.
+    pub fn resources<T: XidNew>(&self) -> Vec<T> {
+        self.cardinals()
+            .iter()
+            // The ids come from the X server
+            .map(|&id| unsafe { T::new(id) })
+            .collect()
+    }
+}
+
+/// The attributes of a window the window manager reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WindowAttributes {
+    /// Whether the window places itself, e.g. a menu.
+    pub override_redirect: bool,
+    pub map_state: x::MapState,
+    pub visual: x::Visualid,
+}
+
+/// A request which has no reply.
+pub trait VoidRequest: xcb::RequestWithoutReply {
+    /// The request as recorded by the mock connection.
+    #[cfg(test)]
+    fn record(&self) -> MockRequest;
+}
+
+pub trait XConn {
+    /// Send a request which has no reply.
+    /// Its errors are reported by the X server along with the events.
+    fn send_request<R: VoidRequest>(&self, request: &R);
+
+    /// Send a request which has no reply and wait for its outcome.
+    fn send_and_check_request<R: VoidRequest>(&self, request: &R) -> xcb::ProtocolResult<()>;
+
+    /// Generate the id of a new resource, e.g. a window.
+    fn generate_id<T: XidNew>(&self) -> T;
+
+    /// Send the requests waiting in the output buffer.
+    fn flush(&self) -> xcb::ConnResult<()>;
+
+    /// Check whether an extension is active on the connection.
+    fn has_extension(&self, extension: xcb::Extension) -> bool;
+
+    /// Intern the atoms the window manager uses.
+    fn intern_atoms(&self) -> xcb::Result<Atoms>;
+
+    fn get_window_attributes(&self, window: x::Window) -> xcb::Result<WindowAttributes>;
+
+    /// Get the position and the size of a window.
+    fn get_geometry(&self, window: x::Window) -> xcb::Result<(Vector2D, Vector2D)>;
+
+    /// Get a property of a window, up to `long_length` 32 bit units.
+    fn get_property(
+        &self,
+        window: x::Window,
+        property: x::Atom,
+        r#type: x::Atom,
+        long_length: u32,
+    ) -> xcb::Result<Property>;
+
+    fn get_atom_name(&self, atom: x::Atom) -> xcb::Result<String>;
+
+    /// Get the children of a window, from the bottommost to the topmost.
+    fn query_tree(&self, window: x::Window) -> xcb::Result<Vec<x::Window>>;
+
+    /// Get the state of the keys of the keyboard, one bit per keycode.
+    fn query_keymap(&self) -> xcb::Result<[u8; 32]>;
+
+    fn grab_keyboard(&self, window: x::Window) -> xcb::Result<x::GrabStatus>;
+
+    /// Allocate a color in a colormap and return its pixel value.
+    fn alloc_color(&self, colormap: x::Colormap, color: &Color) -> xcb::Result<u32>;
+
+    /// Check whether the bounding region of a window is shaped, with the Shape extension.
+    fn bounding_shaped(&self, window: x::Window) -> xcb::Result<bool>;
+
+    /// Get the depth of a visual of a screen, or the depth of the screen if it is not found.
+    fn visual_depth(&self, screen_num: i32, visual: x::Visualid) -> u8;
+
+    /// Start using XKB if the server supports it,
+    /// and select the events reporting changes of the keyboard mapping.
+    ///
+    /// The key events then report the active group, e.g. the layout switched to.
+    /// Return false if XKB is not available, the keyboard is then read with the core protocol.
+    fn use_xkb(&self) -> xcb::Result<bool>;
+
+    /// Get the keysyms of the keyboard and the modifier NumLock is mapped to.
+    ///
+    /// With XKB, the keysyms of every group are known, otherwise only the ones of the first group.
+    fn read_keymap(&self, xkb: bool) -> xcb::Result<Keymap>;
+}
+
+impl XConn for xcb::Connection {
+    fn send_request<R: VoidRequest>(&self, request: &R) {
+        xcb::Connection::send_request(self, request);
+    }
+
+    fn send_and_check_request<R: VoidRequest>(&self, request: &R) -> xcb::ProtocolResult<()> {
+        xcb::Connection::send_and_check_request(self, request)
+    }
+
+    fn generate_id<T: XidNew>(&self) -> T {
+        xcb::Connection::generate_id(self)
+    }
+
+    fn flush(&self) -> xcb::ConnResult<()> {
+        xcb::Connection::flush(self)
+    }
+
+    fn has_extension(&self, extension: xcb::Extension) -> bool {
+        self.active_extensions().any(|active| active == extension)
+    }
+
+    fn intern_atoms(&self) -> xcb::Result<Atoms> {
+        Atoms::intern_all(self)
+    }
+
+    fn get_window_attributes(&self, window: x::Window) -> xcb::Result<WindowAttributes> {
+        let cookie = xcb::Connection::send_request(self, &x::GetWindowAttributes { window });
+        let reply = self.wait_for_reply(cookie)?;
+
+        Ok(WindowAttributes {
+            override_redirect: reply.override_redirect(),
+            map_state: reply.map_state(),
+            visual: reply.visual(),
+        })
+    }
+
+    fn get_geometry(&self, window: x::Window) -> xcb::Result<(Vector2D, Vector2D)> {
+        let cookie = xcb::Connection::send_request(
+            self,
+            &x::GetGeometry {
+                drawable: x::Drawable::Window(window),
+            },
+        );
+        let reply = self.wait_for_reply(cookie)?;
+
+        Ok((
+            Vector2D::new(reply.x().into(), reply.y().into()),
+            Vector2D::new(reply.width().into(), reply.height().into()),
+        ))
+    }
+
+    fn get_property(
+        &self,
+        window: x::Window,
+        property: x::Atom,
+        r#type: x::Atom,
+        long_length: u32,
+    ) -> xcb::Result<Property> {
+        let cookie = xcb::Connection::send_request(
+            self,
+            &x::GetProperty {
+                delete: false,
+                window,
+                property,
+                r#type,
+                long_offset: 0,
+                long_length,
+            },
+        );
+        let reply = self.wait_for_reply(cookie)?;
+        let value = match reply.format() {
+            32 => PropertyValue::Cardinals(reply.value::<u32>().to_vec()),
+            16 => {
+                PropertyValue::Cardinals(reply.value::<u16>().iter().map(|&v| v.into()).collect())
+            }
+            _ => PropertyValue::Bytes(reply.value::<u8>().to_vec()),
+        };
+
+        Ok(Property {
+            r#type: reply.r#type(),
+            value,
+        })
+    }
+
+    fn get_atom_name(&self, atom: x::Atom) -> xcb::Result<String> {
+        let cookie = xcb::Connection::send_request(self, &x::GetAtomName { atom });
+        let reply = self.wait_for_reply(cookie)?;
+
+        Ok(reply.name().to_utf8().into_owned())
+    }
+
+    fn query_tree(&self, window: x::Window) -> xcb::Result<Vec<x::Window>> {
+        let cookie = xcb::Connection::send_request(self, &x::QueryTree { window });
+        let reply = self.wait_for_reply(cookie)?;
+
+        Ok(reply.children().to_vec())
+    }
+
+    fn query_keymap(&self) -> xcb::Result<[u8; 32]> {
+        let cookie = xcb::Connection::send_request(self, &x::QueryKeymap {});
+        let reply = self.wait_for_reply(cookie)?;
+
+        Ok(*reply.keys())
+    }
+
+    fn grab_keyboard(&self, window: x::Window) -> xcb::Result<x::GrabStatus> {
+        let cookie = xcb::Connection::send_request(
+            self,
+            &x::GrabKeyboard {
+                owner_events: false,
+                grab_window: window,
+                time: x::CURRENT_TIME,
+                pointer_mode: x::GrabMode::Async,
+                keyboard_mode: x::GrabMode::Async,
+            },
+        );
+
+        Ok(self.wait_for_reply(cookie)?.status())
+    }
+
+    fn alloc_color(&self, colormap: x::Colormap, color: &Color) -> xcb::Result<u32> {
+        match color {
+            Color::Rgb(r, g, b) => {
+                // The components are 16 bits
+                let cookie = xcb::Connection::send_request(
+                    self,
+                    &x::AllocColor {
+                        cmap: colormap,
+                        red: *r as u16 * 0x101,
+                        green: *g as u16 * 0x101,
+                        blue: *b as u16 * 0x101,
+                    },
+                );
+                self.wait_for_reply(cookie).map(|reply| reply.pixel())
+            }
+            Color::Named(name) => {
+                let cookie = xcb::Connection::send_request(
+                    self,
+                    &x::AllocNamedColor {
+                        cmap: colormap,
+                        name: name.as_bytes(),
+                    },
+                );
+                self.wait_for_reply(cookie).map(|reply| reply.pixel())
+            }
+        }
+    }
+
+    fn bounding_shaped(&self, window: x::Window) -> xcb::Result<bool> {
+        let cookie = xcb::Connection::send_request(
+            self,
+            &shape::QueryExtents {
+                destination_window: window,
+            },
+        );
+
+        Ok(self.wait_for_reply(cookie)?.bounding_shaped())
+    }
+
+    fn visual_depth(&self, screen_num: i32, visual: x::Visualid) -> u8 {
+        let screen = self.get_setup().roots().nth(screen_num as usize).unwrap();
+
+        screen
+            .allowed_depths()
+            .find(|depth| {
+                depth
+                    .visuals()
+                    .iter()
+                    .any(|visual_type| visual_type.visual_id() == visual)
+            })
+            .map_or(screen.root_depth(), |depth| depth.depth())
+    }
+
+    fn use_xkb(&self) -> xcb::Result<bool> {
+        if !self.has_extension(xcb::Extension::Xkb) {
+            return Ok(false);
+        }
+
+        let cookie = xcb::Connection::send_request(
+            self,
+            &xkb::UseExtension {
+                wanted_major: 1,
+                wanted_minor: 0,
+            },
+        );
+        if !self.wait_for_reply(cookie)?.supported() {
+            return Ok(false);
+        }
+
+        let events = xkb::EventType::NEW_KEYBOARD_NOTIFY | xkb::EventType::MAP_NOTIFY;
+        let map_parts = xkb::MapPart::KEY_SYMS | xkb::MapPart::MODIFIER_MAP;
+        xcb::Connection::send_request(
+            self,
+            &xkb::SelectEvents {
+                device_spec: xkb::Id::UseCoreKbd as xkb::DeviceSpec,
+                affect_which: events,
+                clear: xkb::EventType::empty(),
+                select_all: events,
+                affect_map: map_parts,
+                map: map_parts,
+                details: &[],
+            },
+        );
+
+        Ok(true)
+    }
+
+    fn read_keymap(&self, xkb: bool) -> xcb::Result<Keymap> {
+        let setup = self.get_setup();
+        let (min_keycode, max_keycode) = (setup.min_keycode(), setup.max_keycode());
+        let modifier_cookie = xcb::Connection::send_request(self, &x::GetModifierMapping {});
+        let mut keymap = if xkb {
+            let cookie = xcb::Connection::send_request(
+                self,
+                &xkb::GetMap {
+                    device_spec: xkb::Id::UseCoreKbd as xkb::DeviceSpec,
+                    full: xkb::MapPart::KEY_SYMS,
+                    partial: xkb::MapPart::empty(),
+                    first_type: 0,
+                    n_types: 0,
+                    first_key_sym: 0,
+                    n_key_syms: 0,
+                    first_key_action: 0,
+                    n_key_actions: 0,
+                    first_key_behavior: 0,
+                    n_key_behaviors: 0,
+                    virtual_mods: xkb::VMod::empty(),
+                    first_key_explicit: 0,
+                    n_key_explicit: 0,
+                    first_mod_map_key: 0,
+                    n_mod_map_keys: 0,
+                    first_v_mod_map_key: 0,
+                    n_v_mod_map_keys: 0,
+                },
+            );
+            let reply = self.wait_for_reply(cookie)?;
+            // The first keysym of each group is the one produced without modifiers
+            let keysyms = reply
+                .map()
+                .into_iter()
+                .find_map(|part| match part {
+                    xkb::GetMapReplyMap::KeySyms(maps) => Some(maps),
+                    _ => None,
+                })
+                .unwrap_or_default()
+                .iter()
+                .map(|map| {
+                    let groups = (map.group_info() & 0x0f) as usize;
+                    let width = map.width() as usize;
+                    (0..groups)
+                        .filter_map(|group| map.syms().get(group * width).copied())
+                        .collect()
+                })
+                .collect();
+            Keymap::with_groups(reply.first_key_sym(), keysyms)
+        } else {
+            let cookie = xcb::Connection::send_request(
+                self,
+                &x::GetKeyboardMapping {
+                    first_keycode: min_keycode,
+                    count: max_keycode - min_keycode + 1,
+                },
+            );
+            let reply = self.wait_for_reply(cookie)?;
+            Keymap::new(
+                min_keycode,
+                reply.keysyms_per_keycode(),
+                reply.keysyms().to_vec(),
+            )
+        };
+        let modifier_reply = self.wait_for_reply(modifier_cookie)?;
+        keymap.set_modifier_mapping(
+            modifier_reply.keycodes_per_modifier(),
+            modifier_reply.keycodes(),
+        );
+
+        Ok(keymap)
+    }
+}
+
+impl<C: XConn> XConn for Arc<C> {
+    fn send_request<R: VoidRequest>(&self, request: &R) {
+        C::send_request(self, request);
+    }
+
+    fn send_and_check_request<R: VoidRequest>(&self, request: &R) -> xcb::ProtocolResult<()> {
+        C::send_and_check_request(self, request)
+    }
+
+    fn generate_id<T: XidNew>(&self) -> T {
+        C::generate_id(self)
+    }
+
+    fn flush(&self) -> xcb::ConnResult<()> {
+        C::flush(self)
+    }
+
+    fn has_extension(&self, extension: xcb::Extension) -> bool {
+        C::has_extension(self, extension)
+    }
+
+    fn intern_atoms(&self) -> xcb::Result<Atoms> {
+        C::intern_atoms(self)
+    }
+
+    fn get_window_attributes(&self, window: x::Window) -> xcb::Result<WindowAttributes> {
+        C::get_window_attributes(self, window)
+    }
+
+    fn get_geometry(&self, window: x::Window) -> xcb::Result<(Vector2D, Vector2D)> {
+        C::get_geometry(self, window)
+    }
+
+    fn get_property(
+        &self,
+        window: x::Window,
+        property: x::Atom,
+        r#type: x::Atom,
+        long_length: u32,
+    ) -> xcb::Result<Property> {
+        C::get_property(self, window, property, r#type, long_length)
+    }
+
+    fn get_atom_name(&self, atom: x::Atom) -> xcb::Result<String> {
+        C::get_atom_name(self, atom)
+    }
+
+    fn query_tree(&self, window: x::Window) -> xcb::Result<Vec<x::Window>> {
+        C::query_tree(self, window)
+    }
+
+    fn query_keymap(&self) -> xcb::Result<[u8; 32]> {
+        C::query_keymap(self)
+    }
+
+    fn grab_keyboard(&self, window: x::Window) -> xcb::Result<x::GrabStatus> {
+        C::grab_keyboard(self, window)
+    }
+
+    fn alloc_color(&self, colormap: x::Colormap, color: &Color) -> xcb::Result<u32> {
+        C::alloc_color(self, colormap, color)
+    }
+
+    fn bounding_shaped(&self, window: x::Window) -> xcb::Result<bool> {
+        C::bounding_shaped(self, window)
+    }
+
+    fn visual_depth(&self, screen_num: i32, visual: x::Visualid) -> u8 {
+        C::visual_depth(self, screen_num, visual)
+    }
+
+    fn use_xkb(&self) -> xcb::Result<bool> {
+        C::use_xkb(self)
+    }
+
+    fn read_keymap(&self, xkb: bool) -> xcb::Result<Keymap> {
+        C::read_keymap(self, xkb)
+    }
+}
+
+impl VoidRequest for x::MapWindow {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::MapWindow(self.window)
+    }
+}
+
+impl VoidRequest for x::UnmapWindow {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::UnmapWindow(self.window)
+    }
+}
+
+impl VoidRequest for x::DestroyWindow {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::DestroyWindow(self.window)
+    }
+}
+
+impl VoidRequest for x::ConfigureWindow<'_> {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::ConfigureWindow {
+            window: self.window,
+            values: self.value_list.to_vec(),
+        }
+    }
+}
+
+impl VoidRequest for x::ChangeWindowAttributes<'_> {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::ChangeWindowAttributes {
+            window: self.window,
+            values: self.value_list.to_vec(),
+        }
+    }
+}
+
+impl VoidRequest for x::ChangeProperty<'_, u8> {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::ChangeProperty {
+            window: self.window,
+            property: self.property,
+            r#type: self.r#type,
+            value: PropertyValue::Bytes(self.data.to_vec()),
+        }
+    }
+}
+
+impl VoidRequest for x::ChangeProperty<'_, u32> {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::ChangeProperty {
+            window: self.window,
+            property: self.property,
+            r#type: self.r#type,
+            value: PropertyValue::Cardinals(self.data.to_vec()),
+        }
+    }
+}
+
+impl VoidRequest for x::ChangeProperty<'_, x::Atom> {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::ChangeProperty {
+            window: self.window,
+            property: self.property,
+            r#type: self.r#type,
+            value: PropertyValue::Cardinals(self.data.iter().map(xcb::Xid::resource_id).collect()),
+        }
+    }
+}
+
+impl VoidRequest for x::ChangeProperty<'_, x::Window> {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::ChangeProperty {
+            window: self.window,
+            property: self.property,
+            r#type: self.r#type,
+            value: PropertyValue::Cardinals(self.data.iter().map(xcb::Xid::resource_id).collect()),
+        }
+    }
+}
+
+impl VoidRequest for x::DeleteProperty {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::DeleteProperty {
+            window: self.window,
+            property: self.property,
+        }
+    }
+}
+
+impl VoidRequest for x::SetInputFocus {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::SetInputFocus(self.focus)
+    }
+}
+
+impl VoidRequest for x::KillClient {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::KillClient(self.resource)
+    }
+}
+
+impl VoidRequest for x::SendEvent<'_, x::ConfigureNotifyEvent> {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::SendConfigureNotify {
+            window: self.event.window(),
+            event_mask: self.event_mask,
+            pos: Vector2D::new(self.event.x().into(), self.event.y().into()),
+            size: Vector2D::new(self.event.width().into(), self.event.height().into()),
+            border_width: self.event.border_width().into(),
+        }
+    }
+}
+
+impl VoidRequest for x::SendEvent<'_, x::ClientMessageEvent> {
+    #[cfg(test)]
+    fn record(&self) -> MockRequest {
+        MockRequest::SendClientMessage {
+            window: self.event.window(),
+            r#type: self.event.r#type(),
+            data: match self.event.data() {
+                x::ClientMessageData::Data32(data) => data,
+                _ => [0; 5],
+            },
+        }
+    }
+}
+
+/// Implement `VoidRequest` for the requests recorded by their name only.
+macro_rules! void_requests {
+    ($($request:ident $(<$lifetime:lifetime>)? from $module:ident),* $(,)?) => {
+        $(
+            impl VoidRequest for $module::$request$(<$lifetime>)? {
+                #[cfg(test)]
+                fn record(&self) -> MockRequest {
+                    MockRequest::Other(stringify!($request))
+                }
+            }
+        )*
+    };
+}
+
+void_requests!(
+    AllowEvents from x,
+    ChangeGc<'_> from x,
+    ChangeSaveSet from x,
+    CreateGc<'_> from x,
+    CreatePixmap from x,
+    CreateWindow<'_> from x,
+    FreeColors<'_> from x,
+    FreeGc from x,
+    FreePixmap from x,
+    GrabButton from x,
+    GrabKey from x,
+    GrabServer from x,
+    PolyFillRectangle<'_> from x,
+    ReparentWindow from x,
+    UngrabButton from x,
+    UngrabKey from x,
+    UngrabKeyboard from x,
+    UngrabServer from x,
+    WarpPointer from x,
+    Mask from shape,
+    Rectangles<'_> from shape,
+    SelectInput from shape,
+);
+
+/// A request recorded by the mock connection.
+#[cfg(test)]
+#[derive(Debug, Clone, PartialEq)]
+pub enum MockRequest {
+    MapWindow(x::Window),
+    UnmapWindow(x::Window),
+    DestroyWindow(x::Window),
+    ConfigureWindow {
+        window: x::Window,
+        values: Vec<x::ConfigWindow>,
+    },
+    ChangeWindowAttributes {
+        window: x::Window,
+        values: Vec<x::Cw>,
+    },
+    ChangeProperty {
+        window: x::Window,
+        property: x::Atom,
+        r#type: x::Atom,
+        value: PropertyValue,
+    },
+    DeleteProperty {
+        window: x::Window,
+        property: x::Atom,
+    },
+    SetInputFocus(x::Window),
+    KillClient(u32),
+    /// A synthetic ConfigureNotify event sent to a window.
+    SendConfigureNotify {
+        window: x::Window,
+        event_mask: x::EventMask,
+        pos: Vector2D,
+        size: Vector2D,
+        border_width: u32,
+    },
+    /// A ClientMessage event with 32 bit data sent to a window.
+    SendClientMessage {
+        window: x::Window,
+        r#type: x::Atom,
+        data: [u32; 5],
+    },
+    /// Any other request, by name.
+    Other(&'static str),
+}
+
+/// A connection recording the requests instead of sending them,
+/// and replying with the windows and properties it was given.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MockConn {
+    /// The requests sent, in order.
+    pub requests: std::cell::RefCell<Vec<MockRequest>>,
+    /// The windows of the display, the others are reported as destroyed.
+    windows: std::collections::HashMap<x::Window, (WindowAttributes, Vector2D, Vector2D)>,
+    /// The properties which are set, the others are reported as not set.
+    properties: std::collections::HashMap<(x::Window, x::Atom), Property>,
+    last_id: std::cell::Cell<u32>,
+}
+
+#[cfg(test)]
+impl MockConn {
+    /// Add a top-level window, unmapped, to the display and return its attributes.
+    pub fn add_window(
+        &mut self,
+        window: x::Window,
+        pos: Vector2D,
+        size: Vector2D,
+    ) -> &mut WindowAttributes {
+        let attributes = WindowAttributes {
+            override_redirect: false,
+            map_state: x::MapState::Unmapped,
+            visual: 0,
+        };
+        &mut self
+            .windows
+            .entry(window)
+            .or_insert((attributes, pos, size))
+            .0
+    }
+
+    /// Set a property of a window.
+    pub fn set_property(
+        &mut self,
+        window: x::Window,
+        property: x::Atom,
+        r#type: x::Atom,
+        value: PropertyValue,
+    ) {
+        self.properties
+            .insert((window, property), Property { r#type, value });
+    }
+
+    /// Take the requests sent so far.
+    pub fn take_requests(&self) -> Vec<MockRequest> {
+        self.requests.take()
+    }
+
+    fn window(&self, window: x::Window) -> xcb::Result<&(WindowAttributes, Vector2D, Vector2D)> {
+        self.windows
+            .get(&window)
+            .ok_or(xcb::Error::Connection(xcb::ConnError::Connection))
+    }
+}
+
+#[cfg(test)]
+impl XConn for MockConn {
+    fn send_request<R: VoidRequest>(&self, request: &R) {
+        self.requests.borrow_mut().push(request.record());
+    }
+
+    fn send_and_check_request<R: VoidRequest>(&self, request: &R) -> xcb::ProtocolResult<()> {
+        self.send_request(request);
+        Ok(())
+    }
+
+    fn generate_id<T: XidNew>(&self) -> T {
+        self.last_id.set(self.last_id.get() + 1);
+        // Far from the ids of the windows added by the tests
+        unsafe { T::new(0x0100_0000 + self.last_id.get()) }
+    }
+
+    fn flush(&self) -> xcb::ConnResult<()> {
+        Ok(())
+    }
+
+    fn has_extension(&self, _extension: xcb::Extension) -> bool {
+        false
+    }
+
+    fn intern_atoms(&self) -> xcb::Result<Atoms> {
+        Ok(Atoms::numbered())
+    }
+
+    fn get_window_attributes(&self, window: x::Window) -> xcb::Result<WindowAttributes> {
+        Ok(self.window(window)?.0)
+    }
+
+    fn get_geometry(&self, window: x::Window) -> xcb::Result<(Vector2D, Vector2D)> {
+        let (_, pos, size) = self.window(window)?;
+        Ok((*pos, *size))
+    }
+
+    fn get_property(
+        &self,
+        window: x::Window,
+        property: x::Atom,
+        r#type: x::Atom,
+        _long_length: u32,
+    ) -> xcb::Result<Property> {
+        self.window(window)?;
+        // As with the X server, the value is not returned if the type does not match
+        match self.properties.get(&(window, property)) {
+            Some(found) if r#type == x::ATOM_ANY || found.r#type == r#type => Ok(found.clone()),
+            _ => Ok(Property::none()),
+        }
+    }
+
+    fn get_atom_name(&self, atom: x::Atom) -> xcb::Result<String> {
+        Ok(format!("ATOM_{}", xcb::Xid::resource_id(&atom)))
+    }
+
+    fn query_tree(&self, _window: x::Window) -> xcb::Result<Vec<x::Window>> {
+        Ok(self.windows.keys().copied().collect())
+    }
+
+    fn query_keymap(&self) -> xcb::Result<[u8; 32]> {
+        Ok([0; 32])
+    }
+
+    fn grab_keyboard(&self, _window: x::Window) -> xcb::Result<x::GrabStatus> {
+        Ok(x::GrabStatus::Success)
+    }
+
+    fn alloc_color(&self, _colormap: x::Colormap, _color: &Color) -> xcb::Result<u32> {
+        Ok(0)
+    }
+
+    fn bounding_shaped(&self, _window: x::Window) -> xcb::Result<bool> {
+        Ok(false)
+    }
+
+    fn visual_depth(&self, _screen_num: i32, _visual: x::Visualid) -> u8 {
+        24
+    }
+
+    fn use_xkb(&self) -> xcb::Result<bool> {
+        Ok(false)
+    }
+
+    fn read_keymap(&self, _xkb: bool) -> xcb::Result<Keymap> {
+        Ok(Keymap::with_groups(0, Vec::new()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_mock_conn() {
+        let mut conn = MockConn::default();
+        let window = unsafe { x::Window::new(42) };
+        let property = unsafe { x::Atom::new(300) };
+        conn.add_window(window, Vector2D::new(10, 20), Vector2D::new(300, 200));
+        conn.set_property(
+            window,
+            property,
+            x::ATOM_CARDINAL,
+            PropertyValue::Cardinals(vec![7]),
+        );
+
+        conn.send_request(&x::MapWindow { window });
+        conn.send_request(&x::GrabServer {});
+        assert_eq!(
+            vec![
+                MockRequest::MapWindow(window),
+                MockRequest::Other("GrabServer")
+            ],
+            conn.take_requests()
+        );
+        assert!(conn.take_requests().is_empty());
+
+        assert_eq!(
+            (Vector2D::new(10, 20), Vector2D::new(300, 200)),
+            conn.get_geometry(window).unwrap()
+        );
+        let value = conn
+            .get_property(window, property, x::ATOM_CARDINAL, 1)
+            .unwrap();
+        assert_eq!(&[7], value.cardinals());
+        // The type does not match
+        let value = conn
+            .get_property(window, property, x::ATOM_STRING, 1)
+            .unwrap();
+        assert_eq!(Property::none(), value);
+        // The window is gone
+        assert!(conn
+            .get_window_attributes(unsafe { x::Window::new(43) })
+            .is_err());
+    }
+}
@@ -0,0 +1,268 @@
+//! End-to-end tests of the window manager on a headless X server.
+//!
+//! Each test starts Xvfb on its own display, runs the window manager on it,
+//! opens windows with xcb and drives the window manager through `toniowm client`.
+//! They are skipped unless TONIOWM_INTEGRATION is set, e.g.
+//! `TONIOWM_INTEGRATION=1 cargo test --test integration`.
+//! TONIOWM_INTEGRATION_DISPLAY sets the number of the first display, 99 by default.
+
+use std::env;
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::process::{self, Child, Command, Stdio};
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::thread;
+use std::time::{Duration, Instant};
+
+use serde_json::Value;
+use xcb::{x, Xid};
+
+/// How long to wait for the X server, the window manager or a window.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The offset of the display of the next test, as tests run in parallel.
+static NEXT_DISPLAY: AtomicU32 = AtomicU32::new(0);
+
+/// An X server with the window manager running on it.
+struct Harness {
+    dir: PathBuf,
+    display: String,
+    xvfb: Child,
+    wm: Child,
+    /// The connections of the windows, each window being a client of its own,
+    /// so that killing it does not affect the others.
+    clients: Vec<xcb::Connection>,
+}
+
+impl Harness {
+    /// Start an X server and the window manager.
+    ///
+    /// Return None if the integration tests are not enabled.
+    fn start() -> Option<Self> {
+        if env::var_os("TONIOWM_INTEGRATION").is_none() {
+            eprintln!("Skipped, set TONIOWM_INTEGRATION to run the integration tests");
+            return None;
+        }
+
+        let first_display = env::var("TONIOWM_INTEGRATION_DISPLAY")
+            .ok()
+            .and_then(|number| number.parse().ok())
+            .unwrap_or(99);
+        let number = first_display + NEXT_DISPLAY.fetch_add(1, Ordering::SeqCst);
+        let display = format!(":{}", number);
+        let dir = env::temp_dir().join(format!("toniowm-integration-{}-{}", process::id(), number));
+        fs::create_dir_all(&dir).unwrap();
+        // The default config, without the user's
+        fs::write(dir.join("config.json"), "{}").unwrap();
+
+        // Xvfb writes the number of its display to stdout once it accepts connections,
+        // and exits without writing it if the display is already in use
+        let mut xvfb = Command::new("Xvfb")
+            .args([&display, "-screen", "0", "1280x800x24", "-nolisten", "tcp"])
+            .args(["-displayfd", "1"])
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .spawn()
+            .expect("Failed to start Xvfb");
+        let mut ready = String::new();
+        BufReader::new(xvfb.stdout.take().unwrap())
+            .read_line(&mut ready)
+            .unwrap();
+        assert_eq!(
+            number.to_string(),
+            ready.trim(),
+            "Xvfb failed to start on {}, is the display in use?",
+            display
+        );
+
+        let wm = toniowm(&dir, &display)
+            .arg("start")
+            .arg("--display")
+            .arg(&display)
+            .arg("--autostart")
+            .arg(dir.join("toniorc"))
+            .stdout(Stdio::null())
+            .spawn()
+            .expect("Failed to start the window manager");
+        let harness = Self {
+            dir,
+            display,
+            xvfb,
+            wm,
+            clients: Vec::new(),
+        };
+        wait_for("the window manager", || {
+            harness.dir.join("toniowm.sock").exists()
+        });
+
+        Some(harness)
+    }
+
+    /// Send a command to the window manager and return its output as JSON, or null if there is none.
+    ///
+    /// Panic if the command fails.
+    fn client(&self, args: &[&str]) -> Value {
+        let output = toniowm(&self.dir, &self.display)
+            .arg("client")
+            .args(args)
+            .output()
+            .unwrap();
+        assert!(
+            output.status.success(),
+            "toniowm client {} failed: {}",
+            args.join(" "),
+            String::from_utf8_lossy(&output.stderr)
+        );
+
+        let stdout = String::from_utf8(output.stdout).unwrap();
+        if stdout.trim().is_empty() {
+            return Value::Null;
+        }
+        serde_json::from_str(&stdout).unwrap()
+    }
+
+    /// Get the ids of the windows of a workspace, or of every workspace.
+    fn windows(&self, workspace: Option<&str>) -> Vec<u64> {
+        let mut args = vec!["query", "windows"];
+        if let Some(workspace) = workspace {
+            args.extend(["--name", workspace]);
+        }
+
+        self.client(&args)
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|window| window["id"].as_u64().unwrap())
+            .collect()
+    }
+
+    /// Get the id of the focused window.
+    fn focused(&self) -> Option<u64> {
+        self.client(&["query", "focused"])["id"].as_u64()
+    }
+
+    /// Open a window of an application and wait until it is managed.
+    fn open_window(&mut self, class: &str) -> u64 {
+        let (conn, screen_num) = xcb::Connection::connect(Some(&self.display)).unwrap();
+        let screen = conn.get_setup().roots().nth(screen_num as usize).unwrap();
+        let window = conn.generate_id();
+        conn.send_request(&x::CreateWindow {
+            depth: x::COPY_FROM_PARENT as u8,
+            wid: window,
+            parent: screen.root(),
+            x: 0,
+            y: 0,
+            width: 200,
+            height: 100,
+            border_width: 0,
+            class: x::WindowClass::InputOutput,
+            visual: screen.root_visual(),
+            value_list: &[],
+        });
+        conn.send_request(&x::ChangeProperty {
+            mode: x::PropMode::Replace,
+            window,
+            property: x::ATOM_WM_CLASS,
+            r#type: x::ATOM_STRING,
+            data: format!("{}\0{}\0", class, class).as_bytes(),
+        });
+        conn.send_request(&x::MapWindow { window });
+        conn.flush().unwrap();
+
+        let id = window.resource_id() as u64;
+        self.clients.push(conn);
+        wait_for("the window to be managed", || {
+            self.windows(None).contains(&id)
+        });
+
+        id
+    }
+}
+
+impl Drop for Harness {
+    fn drop(&mut self) {
+        let _ = self.wm.kill();
+        let _ = self.wm.wait();
+        let _ = self.xvfb.kill();
+        let _ = self.xvfb.wait();
+        let _ = fs::remove_dir_all(&self.dir);
+    }
+}
+
+/// A toniowm command using the files of a test only.
+fn toniowm(dir: &Path, display: &str) -> Command {
+    let mut command = Command::new(env!("CARGO_BIN_EXE_toniowm"));
+    command
+        .env("HOME", dir)
+        .env("DISPLAY", display)
+        .env("TONIOWM_SOCKET", dir.join("toniowm.sock"))
+        .env("TONIOWM_CONFIG", dir.join("config.json"));
+    command
+}
+
+/// Wait until a condition holds, or panic after the timeout.
+fn wait_for(what: &str, mut condition: impl FnMut() -> bool) {
+    let start = Instant::now();
+    while !condition() {
+        assert!(start.elapsed() < TIMEOUT, "Timed out waiting for {}", what);
+        thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn test_new_windows_are_focused() {
+    let Some(mut harness) = Harness::start() else {
+        return;
+    };
+
+    let first = harness.open_window("first");
+    assert_eq!(Some(first), harness.focused());
+    let second = harness.open_window("second");
+    assert_eq!(Some(second), harness.focused());
+
+    harness.client(&["focus", "--window", &first.to_string()]);
+    assert_eq!(Some(first), harness.focused());
+    harness.client(&["focus", "--class", "sec*"]);
+    assert_eq!(Some(second), harness.focused());
+}
+
+#[test]
+fn test_workspaces() {
+    let Some(mut harness) = Harness::start() else {
+        return;
+    };
+
+    let first = harness.open_window("first");
+    harness.client(&["add-workspace", "--name", "web"]);
+    harness.client(&["activate-workspace", "--name", "web"]);
+    assert_eq!(None, harness.focused());
+
+    let browser = harness.open_window("browser");
+    assert_eq!(vec![browser], harness.windows(Some("web")));
+    assert_eq!(vec![first, browser], harness.windows(None));
+
+    // The focus of each workspace is remembered
+    harness.client(&["activate-workspace", "--index", "0"]);
+    assert_eq!(Some(first), harness.focused());
+    let workspaces = harness.client(&["query", "workspaces"]);
+    assert_eq!(Value::Bool(true), workspaces[0]["active"]);
+    assert_eq!(Value::from(1), workspaces[1]["clients"]);
+}
+
+#[test]
+fn test_closed_windows_are_unmanaged() {
+    let Some(mut harness) = Harness::start() else {
+        return;
+    };
+
+    let first = harness.open_window("first");
+    let second = harness.open_window("second");
+
+    // The windows do not support WM_DELETE_WINDOW, so their client is killed
+    harness.client(&["close", "--window", &second.to_string()]);
+    wait_for("the window to be unmanaged", || {
+        harness.windows(None) == vec![first]
+    });
+    assert_eq!(Some(first), harness.focused());
+}